@@ -0,0 +1,63 @@
+// build.rs
+// Rebuild when migrations change, since `sqlx::migrate!()` embeds them at compile time.
+// Also captures the git commit, branch, build time and rustc version for `build_info`.
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    // Images built without the .git directory can pass the commit and branch in
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-env-changed=GIT_BRANCH");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // Rebuild on commit or checkout; paths that don't exist would force a rebuild every time
+    for path in [".git/HEAD", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    if let Some(head_ref) = git(&["symbolic-ref", "-q", "HEAD"]) {
+        let ref_path = format!(".git/{}", head_ref);
+        if Path::new(&ref_path).exists() {
+            println!("cargo:rerun-if-changed={}", ref_path);
+        }
+    }
+
+    let sha = env_value("GIT_SHA").or_else(|| git(&["rev-parse", "HEAD"])).unwrap_or_else(|| "unknown".to_string());
+    let branch = env_value("GIT_BRANCH").or_else(|| git(&["rev-parse", "--abbrev-ref", "HEAD"])).unwrap_or_else(|| "unknown".to_string());
+
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible
+    let timestamp = env_value("SOURCE_DATE_EPOCH")
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default());
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=BUILD_GIT_SHA={}", sha);
+    println!("cargo:rustc-env=BUILD_GIT_BRANCH={}", branch);
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", timestamp);
+    println!("cargo:rustc-env=BUILD_RUSTC_VERSION={}", rustc_version);
+}
+
+fn env_value(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|value| value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+/// Output of a git command, or `None` outside a repository or without git installed
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok().map(|output| output.trim().to_string()).filter(|output| !output.is_empty())
+}
@@ -0,0 +1,23 @@
+-- Base catalogue and stock tables. IF NOT EXISTS lets this run against databases
+-- that were set up by hand before migrations existed.
+CREATE TABLE IF NOT EXISTS goods (
+    goods_id SERIAL PRIMARY KEY,
+    material_code TEXT NOT NULL UNIQUE,
+    goods_name TEXT NOT NULL,
+    description TEXT[],
+    price NUMERIC NOT NULL CHECK (price >= 0),
+    volumn_l NUMERIC NOT NULL,
+    mass_g NUMERIC NOT NULL,
+    mass_base SMALLINT NOT NULL DEFAULT 0,
+    volumn_base SMALLINT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS inventory (
+    item_id SERIAL PRIMARY KEY,
+    goods_id INTEGER NOT NULL REFERENCES goods (goods_id),
+    quantity INTEGER NOT NULL CHECK (quantity >= 0),
+    expired_date TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS inventory_goods_id_idx ON inventory (goods_id);
+CREATE INDEX IF NOT EXISTS inventory_expired_date_idx ON inventory (expired_date);
@@ -0,0 +1,24 @@
+-- Quantity history. item_id has no foreign key so history outlives deleted items.
+CREATE TABLE IF NOT EXISTS stock_movements (
+    movement_id BIGSERIAL PRIMARY KEY,
+    item_id INTEGER NOT NULL,
+    delta INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    resulting_quantity INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS stock_movements_item_id_created_at_idx ON stock_movements (item_id, created_at);
+CREATE INDEX IF NOT EXISTS stock_movements_created_at_idx ON stock_movements (created_at);
+
+-- Stock held for pending orders; released along with the item it reserves
+CREATE TABLE IF NOT EXISTS reservations (
+    reservation_id SERIAL PRIMARY KEY,
+    item_id INTEGER NOT NULL REFERENCES inventory (item_id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    reference TEXT,
+    expires_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS reservations_item_id_idx ON reservations (item_id);
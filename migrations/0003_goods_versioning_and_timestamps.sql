@@ -0,0 +1,11 @@
+ALTER TABLE goods ADD COLUMN IF NOT EXISTS reorder_point INTEGER CHECK (reorder_point >= 0);
+ALTER TABLE goods ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE goods ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE goods ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+ALTER TABLE inventory ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE inventory ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- Sync feeds page through rows in (updated_at, id) order
+CREATE INDEX IF NOT EXISTS goods_updated_at_idx ON goods (updated_at, goods_id);
+CREATE INDEX IF NOT EXISTS inventory_updated_at_idx ON inventory (updated_at, item_id);
@@ -0,0 +1,15 @@
+-- Goods categories, nested through parent_id
+CREATE TABLE IF NOT EXISTS categories (
+    category_id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    parent_id INTEGER REFERENCES categories (category_id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (parent_id <> category_id)
+);
+
+CREATE INDEX IF NOT EXISTS categories_parent_id_idx ON categories (parent_id);
+
+ALTER TABLE goods ADD COLUMN IF NOT EXISTS category_id INTEGER REFERENCES categories (category_id);
+
+CREATE INDEX IF NOT EXISTS goods_category_id_idx ON goods (category_id);
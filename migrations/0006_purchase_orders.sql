@@ -0,0 +1,19 @@
+-- Purchase orders; lines track how much of each ordered quantity has been received
+CREATE TABLE IF NOT EXISTS purchase_orders (
+    purchase_order_id SERIAL PRIMARY KEY,
+    reference TEXT,
+    status TEXT NOT NULL DEFAULT 'draft' CHECK (status IN ('draft', 'ordered', 'received')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS purchase_order_lines (
+    line_id SERIAL PRIMARY KEY,
+    purchase_order_id INTEGER NOT NULL REFERENCES purchase_orders (purchase_order_id) ON DELETE CASCADE,
+    goods_id INTEGER NOT NULL REFERENCES goods (goods_id),
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    received_quantity INTEGER NOT NULL DEFAULT 0 CHECK (received_quantity >= 0 AND received_quantity <= quantity)
+);
+
+CREATE INDEX IF NOT EXISTS purchase_order_lines_purchase_order_id_idx ON purchase_order_lines (purchase_order_id);
+CREATE INDEX IF NOT EXISTS purchase_order_lines_goods_id_idx ON purchase_order_lines (goods_id);
@@ -0,0 +1,4 @@
+-- Scanner barcodes (EAN-8, EAN-13 or Code128); several goods may have none
+ALTER TABLE goods ADD COLUMN IF NOT EXISTS barcode TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS goods_barcode_key ON goods (barcode);
@@ -0,0 +1,4 @@
+-- Free-form lowercase labels used for filtering
+ALTER TABLE goods ADD COLUMN IF NOT EXISTS tags TEXT[];
+
+CREATE INDEX IF NOT EXISTS goods_tags_idx ON goods USING GIN (tags);
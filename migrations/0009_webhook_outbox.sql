@@ -0,0 +1,18 @@
+-- Inventory events awaiting webhook delivery, written in the same transaction as the change.
+-- delivered_to lists the endpoints that already accepted the event; failed_at marks events
+-- that ran out of attempts and were dead-lettered.
+CREATE TABLE IF NOT EXISTS webhook_outbox (
+    event_id BIGSERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    delivered_to TEXT[] NOT NULL DEFAULT '{}',
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    delivered_at TIMESTAMPTZ,
+    failed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS webhook_outbox_pending_idx ON webhook_outbox (next_attempt_at)
+    WHERE delivered_at IS NULL AND failed_at IS NULL;
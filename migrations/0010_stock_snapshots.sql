@@ -0,0 +1,13 @@
+-- Total stock per goods per day, for historical reports. One row per (snapshot_date, goods_id);
+-- re-running a day's snapshot overwrites it. goods_id has no foreign key so history outlives
+-- deleted goods.
+CREATE TABLE IF NOT EXISTS stock_snapshots (
+    snapshot_date DATE NOT NULL,
+    goods_id INTEGER NOT NULL,
+    total_quantity BIGINT NOT NULL,
+    total_value NUMERIC NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (snapshot_date, goods_id)
+);
+
+CREATE INDEX IF NOT EXISTS stock_snapshots_goods_id_date_idx ON stock_snapshots (goods_id, snapshot_date);
@@ -0,0 +1,19 @@
+-- Expiry alerts. One row per item, expiry and crossed threshold, so each threshold fires once
+-- per item; acknowledged alerts are not refreshed. item_id has no foreign key so alerts
+-- outlive deleted items.
+CREATE TABLE IF NOT EXISTS alerts (
+    alert_id SERIAL PRIMARY KEY,
+    item_id INTEGER NOT NULL,
+    goods_id INTEGER NOT NULL,
+    goods_name TEXT NOT NULL,
+    quantity INTEGER NOT NULL,
+    expired_date TIMESTAMPTZ NOT NULL,
+    threshold_days INTEGER NOT NULL,
+    days_remaining INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    acknowledged_at TIMESTAMPTZ,
+    UNIQUE (item_id, expired_date, threshold_days)
+);
+
+CREATE INDEX IF NOT EXISTS alerts_unacknowledged_idx ON alerts (created_at) WHERE acknowledged_at IS NULL;
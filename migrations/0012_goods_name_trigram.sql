@@ -0,0 +1,12 @@
+-- Trigram index on goods names for fuzzy search (similarity ranking) and for the default
+-- ILIKE '%...%' name filter. pg_trgm may be missing or not installable by this role; the
+-- migration then leaves it out and `fuzzy=true` searches answer 400 instead.
+DO $$
+BEGIN
+    CREATE EXTENSION IF NOT EXISTS pg_trgm;
+    CREATE INDEX IF NOT EXISTS goods_goods_name_trgm_idx ON goods USING gin (goods_name gin_trgm_ops);
+EXCEPTION
+    WHEN insufficient_privilege OR undefined_file THEN
+        RAISE NOTICE 'pg_trgm is not available (%); fuzzy goods search is disabled', SQLERRM;
+END
+$$;
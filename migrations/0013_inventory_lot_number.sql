@@ -0,0 +1,4 @@
+-- Supplier lot/batch number per inventory item, for recall lookups; older rows have none
+ALTER TABLE inventory ADD COLUMN IF NOT EXISTS lot_number TEXT;
+
+CREATE INDEX IF NOT EXISTS inventory_lot_number_idx ON inventory (lot_number);
@@ -0,0 +1,2 @@
+-- Archive flag for goods hidden from catalogs without deleting them or their inventory
+ALTER TABLE goods ADD COLUMN IF NOT EXISTS is_active BOOLEAN NOT NULL DEFAULT TRUE;
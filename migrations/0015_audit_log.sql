@@ -0,0 +1,20 @@
+-- Audit trail of mutating requests. A row is written before the handler runs and completed
+-- with the status and affected ids afterwards; rows whose request failed are removed, so a
+-- row left without a status_code is a request whose outcome was not recorded.
+CREATE TABLE IF NOT EXISTS audit_log (
+    audit_id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    api_key_label TEXT,
+    client_ip TEXT,
+    role TEXT NOT NULL,
+    request_id TEXT,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    target_table TEXT NOT NULL,
+    affected_ids INTEGER[] NOT NULL DEFAULT '{}',
+    request_body JSONB,
+    status_code INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_created_at_idx ON audit_log (created_at);
+CREATE INDEX IF NOT EXISTS audit_log_target_table_idx ON audit_log (target_table, created_at);
@@ -0,0 +1,3 @@
+-- Negative quantities (backorders) are allowed or refused by `inventory.allow_negative_stock`,
+-- which the stock-changing queries enforce, so the column check no longer applies
+ALTER TABLE inventory DROP CONSTRAINT IF EXISTS inventory_quantity_check;
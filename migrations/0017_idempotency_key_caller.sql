@@ -0,0 +1,5 @@
+-- Idempotency keys are scoped to the caller as well as the path, so a client reusing
+-- another client's key is not served that client's stored response
+ALTER TABLE idempotency_keys ADD COLUMN IF NOT EXISTS caller TEXT NOT NULL DEFAULT '';
+ALTER TABLE idempotency_keys DROP CONSTRAINT IF EXISTS idempotency_keys_pkey;
+ALTER TABLE idempotency_keys ADD PRIMARY KEY (idempotency_key, request_path, caller);
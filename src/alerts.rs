@@ -0,0 +1,101 @@
+// src/alerts.rs
+//
+// Daily expiry check. In-stock items expiring within the widest configured threshold are
+// matched to the tightest threshold they have crossed and recorded in the `alerts` table
+// (see `AlertsTable::record`). Newly raised alerts are also POSTed to `alerts.webhook_url`
+// when one is configured. A failed run is logged and retried a few minutes later.
+use crate::config::AlertsConfig;
+use crate::tables::{Alert, AlertsTable, NewAlert};
+use crate::utils::datetime::{days_until_expiration, until_next_time_of_day};
+use crate::utils::logging::log_database_error;
+use crate::webhooks::{signature, SIGNATURE_HEADER};
+use chrono::{Days, Utc};
+use reqwest::header::CONTENT_TYPE;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Delay before retrying a failed check
+const RETRY_DELAY: Duration = Duration::from_secs(300);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Raise and refresh alerts for items expiring within the configured thresholds; returns the new alerts
+pub async fn check_expiring(table: &AlertsTable, thresholds_days: &[u32]) -> Result<Vec<Alert>, sqlx::Error> {
+    let Some(&widest) = thresholds_days.iter().max() else {
+        return Ok(Vec::new());
+    };
+
+    let items = table.expiring_items(Utc::now() + Days::new(widest as u64)).await?;
+    let alerts: Vec<NewAlert> = items
+        .into_iter()
+        .filter_map(|item| {
+            let days_remaining = days_until_expiration(&item.expired_date);
+            let threshold = thresholds_days.iter().filter(|&&days| days_remaining <= days as i64).min()?;
+            Some(NewAlert {
+                item,
+                threshold_days: *threshold as i32,
+                days_remaining: days_remaining as i32,
+            })
+        })
+        .collect();
+
+    table.record(&alerts).await
+}
+
+/// Start the daily expiry check; does nothing unless it is enabled
+pub fn spawn_expiry_alerts(table: AlertsTable, config: AlertsConfig, webhook_secret: Option<String>) -> anyhow::Result<()> {
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let run_at = config.run_at_time()?;
+    let client = reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build()?;
+    info!("Checking for expiring inventory daily at {} UTC (thresholds {:?} days)", config.run_at, config.thresholds_days);
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(until_next_time_of_day(run_at)).await;
+
+            let raised = loop {
+                match check_expiring(&table, &config.thresholds_days).await {
+                    Ok(raised) => break raised,
+                    Err(e) => {
+                        log_database_error("check expiring inventory", &e);
+                        tokio::time::sleep(RETRY_DELAY).await;
+                    }
+                }
+            };
+
+            if raised.is_empty() {
+                continue;
+            }
+            info!("Raised {} expiry alerts", raised.len());
+
+            if let Some(url) = &config.webhook_url {
+                post_alerts(&client, url, webhook_secret.as_deref(), &raised).await;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Send newly raised alerts as a JSON array. The alerts stay listed at `GET /alerts`, so a
+/// failed delivery is only logged.
+async fn post_alerts(client: &reqwest::Client, url: &str, secret: Option<&str>, alerts: &[Alert]) {
+    let body = match serde_json::to_string(alerts) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Failed to serialize expiry alerts: {}", e);
+            return;
+        }
+    };
+
+    let mut request = client.post(url).header(CONTENT_TYPE, "application/json");
+    if let Some(secret) = secret {
+        request = request.header(SIGNATURE_HEADER, signature(secret, body.as_bytes()));
+    }
+
+    if let Err(e) = request.body(body).send().await.and_then(|response| response.error_for_status()) {
+        warn!("Failed to deliver {} expiry alerts to {}: {}", alerts.len(), url, e);
+    }
+}
@@ -0,0 +1,164 @@
+// src/audit.rs
+//
+// Audit log of mutating requests. Each request runs with its entry pending, and the goods
+// and inventory tables write it on the transaction of the change, so a committed change
+// always has its entry. Other successful requests are recorded here afterwards with the ids
+// found in the response, and fail if the entry cannot be stored. Failed requests leave none.
+use crate::auth::{ApiKeyLabel, Role};
+use crate::error::ApiError;
+use crate::request_id::current_request_id;
+use crate::server::AppState;
+use crate::tables::{AuditLogTable, NewAuditEntry};
+use crate::utils::logging::log_database_error;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+use std::net::SocketAddr;
+use tracing::{error, warn};
+
+/// Tables accepted by `GET /audit?table=`
+pub const AUDITED_TABLES: &[&str] = &["goods", "inventory", "categories", "purchase_orders", "reservations", "alerts", "stock_snapshots"];
+
+/// Request body fields stored as `[REDACTED]`, matched case-insensitively anywhere in the name
+const SENSITIVE_FIELDS: &[&str] = &["password", "secret", "token", "api_key", "apikey", "authorization", "credential"];
+
+const REDACTED: &str = "[REDACTED]";
+
+pub async fn audit_mutations(State(state): State<AppState>, request: Request, next: Next) -> Result<Response, ApiError> {
+    if !state.config.server.audit_log_enabled || matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(next.run(request).await);
+    }
+
+    let (parts, body) = request.into_parts();
+
+    // The body is already capped by the route's body limit
+    let body = to_bytes(body, usize::MAX).await.map_err(|e| {
+        warn!("Failed to read request body: {}", e);
+        ApiError::PayloadTooLarge("Request body exceeds the size limit for this endpoint".to_string())
+    })?;
+
+    let target_table = target_table(parts.uri.path());
+    let entry = NewAuditEntry {
+        api_key_label: parts.extensions.get::<ApiKeyLabel>().map(|label| label.0.clone()),
+        client_ip: parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip().to_string()),
+        role: parts.extensions.get::<Role>().copied().unwrap_or(Role::Admin).as_str().to_string(),
+        request_id: current_request_id(),
+        method: parts.method.to_string(),
+        path: parts.uri.path_and_query().map_or_else(|| parts.uri.path().to_string(), |path_and_query| path_and_query.to_string()),
+        target_table: target_table.clone(),
+        request_body: serde_json::from_slice::<Value>(&body).ok().map(redact),
+    };
+
+    let (response, recorded) =
+        AuditLogTable::with_pending_entry(entry.clone(), next.run(Request::from_parts(parts, Body::from(body)))).await;
+    if recorded || !response.status().is_success() {
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX).await.map_err(|e| {
+        warn!("Failed to buffer response body: {}", e);
+        ApiError::Internal("Failed to buffer response body".to_string())
+    })?;
+
+    let affected_ids = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .map(|response| affected_ids(&response, &target_table))
+        .unwrap_or_default();
+    state.database.audit_log_table.record(&entry, parts.status, &affected_ids).await.map_err(|e| {
+        log_database_error("write audit entry", &e);
+        error!("{} {} succeeded but its audit entry could not be written", entry.method, entry.path);
+        ApiError::database(e, "audit log")
+    })?;
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Table a request path writes to, e.g. `purchase_orders` for `/purchase-orders/7/receive`
+fn target_table(path: &str) -> String {
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    match (segments.next(), segments.next()) {
+        (Some("admin"), Some("purge-expired")) => "inventory".to_string(),
+        (Some("admin"), Some("snapshot")) => "stock_snapshots".to_string(),
+        (Some(resource), _) => resource.replace('-', "_"),
+        (None, _) => String::new(),
+    }
+}
+
+/// Key holding a row id of `table` in response bodies
+fn id_field(table: &str) -> Option<&'static str> {
+    match table {
+        "goods" => Some("goods_id"),
+        "inventory" => Some("item_id"),
+        "categories" => Some("category_id"),
+        "purchase_orders" => Some("purchase_order_id"),
+        "reservations" => Some("reservation_id"),
+        "alerts" => Some("alert_id"),
+        "stock_snapshots" => Some("snapshot_id"),
+        _ => None,
+    }
+}
+
+/// Ids of `table` in the `data` of a response: every `<id>` or `*_<id>` field, and the
+/// `deleted` lists of the by-ids deletes
+fn affected_ids(response: &Value, table: &str) -> Vec<i32> {
+    let Some(id_field) = id_field(table) else {
+        return Vec::new();
+    };
+    let suffix = format!("_{}", id_field);
+
+    let mut ids = Vec::new();
+    let mut pending: Vec<&Value> = response.get("data").into_iter().collect();
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Object(fields) => {
+                for (name, field) in fields {
+                    if name == id_field || name.ends_with(&suffix) {
+                        ids.extend(field.as_i64().and_then(|id| i32::try_from(id).ok()));
+                    } else if name == "deleted" && let Some(deleted) = field.as_array() {
+                        for item in deleted {
+                            match item.as_i64() {
+                                Some(id) => ids.extend(i32::try_from(id).ok()),
+                                None => pending.push(item),
+                            }
+                        }
+                    } else {
+                        pending.push(field);
+                    }
+                }
+            }
+            Value::Array(items) => pending.extend(items),
+            _ => {}
+        }
+    }
+
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+/// Replace the values of sensitive fields, at any depth
+fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, field)| {
+                    let lowercase = name.to_lowercase();
+                    if SENSITIVE_FIELDS.iter().any(|sensitive| lowercase.contains(sensitive)) {
+                        (name, Value::String(REDACTED.to_string()))
+                    } else {
+                        (name, redact(field))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        other => other,
+    }
+}
@@ -0,0 +1,178 @@
+// src/auth.rs
+//
+// API key authentication. Each configured key carries a role; the middleware resolves it
+// and stores it in the request extensions for handlers that need finer checks. With no
+// keys configured, authentication is disabled and every request runs as `Admin`.
+use crate::error::ApiError;
+use crate::server::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{header, Method},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Header carrying the API key; `Authorization: Bearer <key>` is accepted as well
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Paths served without a key so health probes, version checks and API docs keep working
+const PUBLIC_PATHS: &[&str] = &["/", "/health", "/version", "/openapi.json"];
+const PUBLIC_PREFIXES: &[&str] = &["/docs"];
+
+/// Access level of an API key; each role includes the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Role {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input.trim().to_lowercase().as_str() {
+            "read" => Ok(Role::Read),
+            "write" => Ok(Role::Write),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("Invalid role '{}'. Allowed values: read, write, admin", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Read => "read",
+            Role::Write => "write",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// Fail with 403 unless this role is at least `required`
+    pub fn require(self, required: Role, action: &str) -> Result<(), ApiError> {
+        if self >= required {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden(format!(
+                "{} requires the '{}' role, but the API key has '{}'",
+                action,
+                required.as_str(),
+                self.as_str()
+            )))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub key: String,
+    pub role: Role,
+    /// Name recorded in the audit log for requests made with this key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Label of the key that authenticated the request, stored in the request extensions
+#[derive(Debug, Clone)]
+pub struct ApiKeyLabel(pub String);
+
+/// Parse `API_KEYS`-style input: comma-separated `key:role` pairs
+pub fn parse_api_keys(input: &str) -> Result<Vec<ApiKey>, String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (key, role) = entry
+                .rsplit_once(':')
+                .ok_or_else(|| format!("Invalid API key entry '{}'. Expected key:role", entry))?;
+            if key.is_empty() {
+                return Err("API key must not be empty".to_string());
+            }
+            Ok(ApiKey {
+                key: key.to_string(),
+                role: Role::parse(role)?,
+                label: None,
+            })
+        })
+        .collect()
+}
+
+/// Resolve the caller's role and reject writes from read-only keys
+pub async fn require_api_key(State(state): State<AppState>, mut request: Request, next: Next) -> Result<Response, ApiError> {
+    let api_keys = &state.config.auth.api_keys;
+    let path = request.uri().path();
+
+    let (role, label) = if api_keys.is_empty() {
+        (Role::Admin, None)
+    } else if PUBLIC_PATHS.contains(&path) || PUBLIC_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+        return Ok(next.run(request).await);
+    } else {
+        let provided = request_api_key(&request).ok_or_else(|| {
+            warn!("Missing API key for {} {}", request.method(), path);
+            ApiError::Unauthorized("API key required".to_string())
+        })?;
+
+        api_keys
+            .iter()
+            .find(|api_key| api_key.key == provided)
+            .map(|api_key| (api_key.role, api_key.label.clone()))
+            .ok_or_else(|| {
+                warn!("Invalid API key for {} {}", request.method(), path);
+                ApiError::Unauthorized("Invalid API key".to_string())
+            })?
+    };
+
+    if !matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        role.require(Role::Write, &format!("{} {}", request.method(), path)).inspect_err(|e| warn!("{}", e))?;
+    }
+
+    request.extensions_mut().insert(role);
+    if let Some(label) = label {
+        request.extensions_mut().insert(ApiKeyLabel(label));
+    }
+    Ok(next.run(request).await)
+}
+
+pub(crate) fn request_api_key(request: &Request) -> Option<&str> {
+    let headers = request.headers();
+
+    if let Some(key) = headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()) {
+        return Some(key);
+    }
+
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_key_role_pairs() {
+        let keys = parse_api_keys(" reader:read, ops:key:ADMIN ,").unwrap();
+
+        assert_eq!(keys.len(), 2);
+        assert_eq!((keys[0].key.as_str(), keys[0].role), ("reader", Role::Read));
+        // The role follows the last colon, so keys may contain colons
+        assert_eq!((keys[1].key.as_str(), keys[1].role), ("ops:key", Role::Admin));
+    }
+
+    #[test]
+    fn rejects_entries_without_a_valid_role() {
+        assert!(parse_api_keys("reader").is_err());
+        assert!(parse_api_keys(":read").is_err());
+        assert!(parse_api_keys("reader:owner").is_err());
+    }
+
+    #[test]
+    fn each_role_includes_the_ones_before_it() {
+        assert!(Role::Admin.require(Role::Write, "Deleting goods").is_ok());
+        assert!(Role::Write.require(Role::Write, "Deleting goods").is_ok());
+        assert!(matches!(Role::Read.require(Role::Write, "Deleting goods"), Err(ApiError::Forbidden(_))));
+        assert!(matches!(Role::Write.require(Role::Admin, "Wildcard delete"), Err(ApiError::Forbidden(_))));
+    }
+}
@@ -0,0 +1,68 @@
+// src/build_info.rs
+//
+// What was built, captured by build.rs at compile time: served at `GET /version`, summarized
+// in `GET /` and sent with every response as `X-Api-Version`.
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::LazyLock;
+
+pub static API_VERSION_HEADER: HeaderName = HeaderName::from_static("x-api-version");
+
+pub const PACKAGE_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Full commit hash, or "unknown" when built outside a git checkout without `GIT_SHA`
+pub const GIT_SHA: &str = env!("BUILD_GIT_SHA");
+pub const GIT_BRANCH: &str = env!("BUILD_GIT_BRANCH");
+pub const RUSTC_VERSION: &str = env!("BUILD_RUSTC_VERSION");
+/// Seconds since the Unix epoch
+const BUILD_TIMESTAMP: &str = env!("BUILD_TIMESTAMP");
+
+/// Length of the abbreviated commit hash
+const SHORT_SHA_LEN: usize = 7;
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BuildInfo {
+    /// Package version with the short commit as build metadata, e.g. `0.1.0+1a2b3c4`
+    pub version: String,
+    pub package_version: String,
+    pub git_sha: String,
+    pub git_short_sha: String,
+    pub git_branch: String,
+    pub build_timestamp: Option<DateTime<Utc>>,
+    pub rustc_version: String,
+}
+
+static BUILD_INFO: LazyLock<BuildInfo> = LazyLock::new(|| BuildInfo {
+    version: format!("{}+{}", PACKAGE_VERSION, short_sha()),
+    package_version: PACKAGE_VERSION.to_string(),
+    git_sha: GIT_SHA.to_string(),
+    git_short_sha: short_sha().to_string(),
+    git_branch: GIT_BRANCH.to_string(),
+    build_timestamp: BUILD_TIMESTAMP.parse().ok().and_then(|secs| DateTime::from_timestamp(secs, 0)),
+    rustc_version: RUSTC_VERSION.to_string(),
+});
+
+pub fn build_info() -> &'static BuildInfo {
+    &BUILD_INFO
+}
+
+pub fn short_sha() -> &'static str {
+    GIT_SHA.get(..SHORT_SHA_LEN).unwrap_or(GIT_SHA)
+}
+
+/// Tag every response with the running version
+pub async fn add_version_header(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+
+    if let Ok(value) = HeaderValue::from_str(&build_info().version) {
+        response.headers_mut().insert(API_VERSION_HEADER.clone(), value);
+    }
+
+    response
+}
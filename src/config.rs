@@ -1,81 +1,908 @@
 // src/config.rs
+use crate::auth::{parse_api_keys, ApiKey};
+use crate::tables::{ExpiredPurgeMode, DEFAULT_FUZZY_THRESHOLD};
+use crate::utils::datetime::parse_time_of_day;
+use crate::utils::pagination::PageLimits;
+use crate::utils::string_utils::{sanitize_for_log, Normalization};
 use anyhow::Result;
+use chrono::NaiveTime;
+use clap::Parser;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgConnectOptions;
 use std::env;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::Level;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
     pub database_url: String,
+    /// Read-only replica for GET endpoints; reads use the primary when unset
+    pub read_replica_url: Option<String>,
     pub max_connections: u32,
+    /// Apply embedded migrations at startup; disable where the app user lacks DDL rights
+    pub run_migrations: bool,
+    /// Startup connection attempts before giving up
+    pub connect_max_attempts: u32,
+    /// Upper bound on the total time spent waiting between startup attempts
+    pub connect_max_wait_secs: u64,
+    pub acquire_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub max_lifetime_secs: u64,
+    /// Server-side limit on search and export queries; 0 disables it
+    pub statement_timeout_ms: u64,
+    /// In-process goods lookup cache; disable when several API instances share a database
+    pub goods_cache_enabled: bool,
+    pub goods_cache_capacity: u64,
+    pub goods_cache_ttl_secs: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    pub max_batch_size: usize,
+    pub max_body_bytes: usize,
+    pub max_batch_body_bytes: usize,
+    pub idempotency_ttl_secs: u64,
+    /// Default cap on rows a filter-based update or delete may touch
+    pub max_affected_rows: u32,
+    /// Answer with the pre-envelope response bodies unless the client sends `Accept-Version: 2`
+    pub legacy_envelope: bool,
+    /// gzip/brotli response compression, negotiated through `Accept-Encoding`
+    pub compression_enabled: bool,
+    /// Responses with a known size below this are sent uncompressed
+    pub compression_min_bytes: u16,
+    /// Page size when a request sets `page` but not `per_page`, and for always-paginated listings
+    pub default_page_size: u32,
+    /// Larger `per_page` values are clamped to this, with a warning in the response meta
+    pub max_page_size: u32,
+    /// Rows returned by a search without `page`/`per_page`; admins may lift it with `?unbounded=true`
+    pub max_unpaginated_results: u32,
+    /// Similarity (0-1] a goods name must exceed to match a `fuzzy=true` search
+    pub fuzzy_search_threshold: f32,
+    /// Uppercase material codes on write and in searches, so `ch-100` and `CH-100` are one good
+    pub uppercase_material_codes: bool,
+    /// Serve every route under this prefix, e.g. "/api"; served at the root when unset
+    pub base_path: Option<String>,
+    /// Record successful mutating requests in `audit_log`; turn off for tests backed by the fakes
+    pub audit_log_enabled: bool,
+    /// Start in maintenance mode, rejecting writes; `POST /admin/maintenance` switches it at runtime
+    pub read_only: bool,
+    /// `Retry-After` sent with writes rejected in maintenance mode
+    pub maintenance_retry_after_secs: u64,
+    /// Requests without a response by then get 504
+    pub request_timeout_secs: u64,
+    /// Timeout for the exports and the batch and import endpoints, which handle whole tables or files
+    pub long_request_timeout_secs: u64,
+    /// Requests handled at once; more are refused with 503 instead of queueing
+    pub max_in_flight_requests: usize,
+    /// Serve HTTPS with this certificate; plain HTTP when unset
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        let page_limits = PageLimits::default();
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 3000,
+            max_batch_size: 500,
+            max_body_bytes: 1048576,
+            max_batch_body_bytes: 16777216,
+            idempotency_ttl_secs: 86400,
+            max_affected_rows: 1000,
+            legacy_envelope: false,
+            compression_enabled: true,
+            compression_min_bytes: 1024,
+            default_page_size: page_limits.default_per_page,
+            max_page_size: page_limits.max_per_page,
+            max_unpaginated_results: 10000,
+            fuzzy_search_threshold: DEFAULT_FUZZY_THRESHOLD,
+            uppercase_material_codes: true,
+            base_path: None,
+            audit_log_enabled: true,
+            read_only: false,
+            maintenance_retry_after_secs: 60,
+            request_timeout_secs: 30,
+            long_request_timeout_secs: 600,
+            max_in_flight_requests: 512,
+            tls: None,
+        }
+    }
+}
+
+/// PEM certificate chain and private key for serving HTTPS without a reverse proxy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    /// How often the files are checked for a rotated certificate; SIGHUP reloads immediately
+    pub reload_interval_secs: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            cert_path: String::new(),
+            key_path: String::new(),
+            reload_interval_secs: 300,
+        }
+    }
+}
+
+/// API keys accepted by the server; authentication is disabled when empty
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    pub api_keys: Vec<ApiKey>,
+}
+
+/// Endpoints notified of inventory changes; delivery is disabled when `urls` is empty
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub urls: Vec<String>,
+    /// Key for the `X-Webhook-Signature` HMAC; requests are unsigned when unset
+    pub secret: Option<String>,
+    /// Delivery attempts before an event is dead-lettered
+    pub max_attempts: u32,
+    pub timeout_secs: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            secret: None,
+            max_attempts: 10,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.urls.is_empty()
+    }
+}
+
+/// Scheduled zeroing or deletion of inventory that expired more than `retention_days` ago
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExpiredPurgeConfig {
+    pub enabled: bool,
+    pub retention_days: u32,
+    pub mode: ExpiredPurgeMode,
+    /// Time between runs when `run_at` is unset
+    pub interval_secs: u64,
+    /// Run once a day at this UTC time ("HH:MM") instead of every `interval_secs`
+    pub run_at: Option<String>,
+}
+
+impl Default for ExpiredPurgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retention_days: 90,
+            mode: ExpiredPurgeMode::Zero,
+            interval_secs: 86400,
+            run_at: None,
+        }
+    }
+}
+
+impl ExpiredPurgeConfig {
+    /// `run_at` as a time of day
+    pub fn run_at_time(&self) -> Result<Option<NaiveTime>> {
+        self.run_at
+            .as_deref()
+            .map(|run_at| parse_time_of_day(run_at).map_err(|e| anyhow::anyhow!("Invalid expired purge run_at: {}", e)))
+            .transpose()
+    }
+}
+
+/// Daily per-goods stock snapshot for `GET /reports/stock-history`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StockSnapshotConfig {
+    pub enabled: bool,
+    /// UTC time of day ("HH:MM") at which the day's snapshot is taken
+    pub run_at: String,
+}
+
+impl Default for StockSnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            run_at: "23:55".to_string(),
+        }
+    }
+}
+
+impl StockSnapshotConfig {
+    /// `run_at` as a time of day
+    pub fn run_at_time(&self) -> Result<NaiveTime> {
+        parse_time_of_day(&self.run_at).map_err(|e| anyhow::anyhow!("Invalid stock snapshot run_at: {}", e))
+    }
+}
+
+/// Daily check for inventory expiring within any of `thresholds_days`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    pub enabled: bool,
+    pub thresholds_days: Vec<u32>,
+    /// UTC time of day ("HH:MM") at which the check runs
+    pub run_at: String,
+    /// Newly raised alerts are also POSTed here, signed like the inventory webhooks
+    pub webhook_url: Option<String>,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            thresholds_days: vec![30, 7, 1],
+            run_at: "07:00".to_string(),
+            webhook_url: None,
+        }
+    }
+}
+
+impl AlertsConfig {
+    /// `run_at` as a time of day
+    pub fn run_at_time(&self) -> Result<NaiveTime> {
+        parse_time_of_day(&self.run_at).map_err(|e| anyhow::anyhow!("Invalid alerts run_at: {}", e))
+    }
+}
+
+/// Inventory rules shared by validation and the stock-changing queries
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InventoryConfig {
+    /// Let quantities go below zero (backorders) on create, update, adjust, decrement, consume
+    /// and reservation commits; refused everywhere when false
+    pub allow_negative_stock: bool,
+}
+
+/// Input rules beyond the built-in field checks
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidationConfig {
+    /// Regex every material_code must match, e.g. `^[A-Z]{2,4}-\d{3,6}$`; anchor it with `^`
+    /// and `$` to match the whole code. Any code is accepted when unset.
+    pub material_code_pattern: Option<String>,
+    /// `material_code_pattern`, compiled once when the configuration is loaded
+    #[serde(skip)]
+    material_code_regex: Option<Regex>,
+    /// Copied from `inventory.allow_negative_stock` when the configuration is loaded
+    #[serde(skip)]
+    allow_negative_stock: bool,
+}
+
+impl ValidationConfig {
+    /// Check a (normalized) material code against the configured pattern
+    pub fn check_material_code(&self, material_code: &str, field_name: &str) -> Result<(), String> {
+        match (&self.material_code_regex, &self.material_code_pattern) {
+            (Some(regex), Some(pattern)) if !regex.is_match(material_code) => Err(format!(
+                "Invalid {} '{}': expected a code matching {}",
+                field_name, material_code, pattern
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reject a negative quantity unless negative stock is allowed
+    pub fn check_quantity(&self, quantity: i32, field_name: &str) -> Result<(), String> {
+        if quantity < 0 && !self.allow_negative_stock {
+            return Err(format!("{} cannot be negative", field_name));
+        }
+
+        Ok(())
+    }
+}
+
+/// Command-line flags. Settings given here override the environment, which overrides
+/// config.yaml, which overrides the built-in defaults.
+#[derive(Debug, Clone, Default, Parser)]
+#[command(version, about = "OneChill Dev API server")]
+pub struct Cli {
+    /// Address to listen on [overrides HOST and server.host]
+    #[arg(long)]
+    pub host: Option<String>,
+    /// Port to listen on [overrides PORT and server.port]
+    #[arg(long)]
+    pub port: Option<u16>,
+    /// Primary database connection URL [overrides DATABASE_URL and the DB_* variables]
+    #[arg(long)]
+    pub database_url: Option<String>,
+    /// error, warn, info, debug or trace [overrides LOG_LEVEL; default info]
+    #[arg(long)]
+    pub log_level: Option<Level>,
+    /// Apply migrations and exit, for init containers
+    #[arg(long, conflicts_with_all = ["print_config", "check"])]
+    pub migrate_only: bool,
+    /// Print the effective configuration as YAML, with credentials masked, and exit
+    #[arg(long, conflicts_with = "check")]
+    pub print_config: bool,
+    /// Validate the configuration and database connectivity, then exit; fails with a nonzero status
+    #[arg(long)]
+    pub check: bool,
+}
+
+impl Cli {
+    /// `--log-level`, then LOG_LEVEL, then info
+    pub fn log_level(&self) -> Result<Level> {
+        if let Some(level) = self.log_level {
+            return Ok(level);
+        }
+        match env::var("LOG_LEVEL") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("LOG_LEVEL: must be error, warn, info, debug or trace (got {:?})", value)),
+            Err(_) => Ok(Level::INFO),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub database: DatabaseConfig,
     pub server: ServerConfig,
+    pub auth: AuthConfig,
+    pub webhooks: WebhookConfig,
+    pub expired_purge: ExpiredPurgeConfig,
+    pub stock_snapshot: StockSnapshotConfig,
+    pub alerts: AlertsConfig,
+    pub inventory: InventoryConfig,
+    pub validation: ValidationConfig,
 }
 
 impl AppConfig {
-    pub fn load() -> Result<Self> {
-        // Load environment variables from .env file if it exists
-        if let Err(_) = dotenvy::dotenv() {
-            // .env file doesn't exist, continue with environment variables
-            tracing::info!("No .env file found, using environment variables");
-        } else {
-            tracing::info!("Loaded environment variables from .env file");
-        }
+    /// Build the configuration from, in increasing precedence, the built-in defaults,
+    /// config.yaml, environment variables and the command line
+    pub fn load(cli: &Cli) -> Result<Self> {
+        // Invalid values are collected and reported together once everything has been read
+        let mut problems = ConfigProblems::default();
+
+        // Every section and field of config.yaml is optional
+        let file = match std::fs::read_to_string(CONFIG_FILE) {
+            Ok(config_content) => serde_yaml::from_str::<ConfigFile>(&config_content).unwrap_or_else(|e| {
+                problems.push(CONFIG_FILE, e);
+                ConfigFile::default()
+            }),
+            Err(_) => ConfigFile::default(),
+        };
 
         // Load database config from environment variables
-        let database_url = env::var("DATABASE_URL")
-            .map_err(|_| anyhow::anyhow!("DATABASE_URL environment variable is required"))?;
-        
-        let max_connections = env::var("DB_MAX_CONNECTIONS")
-            .unwrap_or_else(|_| "20".to_string())
-            .parse::<u32>()?;
+        let database_url = match (&cli.database_url, env::var("DATABASE_URL")) {
+            (Some(url), _) => url.clone(),
+            (None, Ok(url)) => url,
+            (None, Err(_)) => database_url_from_components(&mut problems).unwrap_or_default(),
+        };
+
+        let read_replica_url = env::var("DATABASE_READ_REPLICA_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty());
+
+        let max_connections = problems.env("DB_MAX_CONNECTIONS", 20u32);
+        let run_migrations = problems.env_bool("RUN_MIGRATIONS", true);
+
+        // Startup retries while the database comes up
+        let connect_max_attempts = problems.env("DB_CONNECT_MAX_ATTEMPTS", 10u32);
+        let connect_max_wait_secs = problems.env("DB_CONNECT_MAX_WAIT_SECS", 60u64);
+
+        // Pool timeouts
+        let acquire_timeout_secs = problems.env("DB_ACQUIRE_TIMEOUT_SECS", 10u64);
+        let idle_timeout_secs = problems.env("DB_IDLE_TIMEOUT_SECS", 600u64);
+        let max_lifetime_secs = problems.env("DB_MAX_LIFETIME_SECS", 1800u64);
+        let statement_timeout_ms = problems.env("DB_STATEMENT_TIMEOUT_MS", 30000u64);
+
+        let goods_cache_enabled = problems.env_bool("GOODS_CACHE_ENABLED", true);
+        let goods_cache_capacity = problems.env("GOODS_CACHE_CAPACITY", 10000u64);
+        let goods_cache_ttl_secs = problems.env("GOODS_CACHE_TTL_SECS", 300u64);
 
         let database_config = DatabaseConfig {
             database_url,
+            read_replica_url,
             max_connections,
+            run_migrations,
+            connect_max_attempts: connect_max_attempts.max(1),
+            connect_max_wait_secs,
+            acquire_timeout_secs,
+            idle_timeout_secs,
+            max_lifetime_secs,
+            statement_timeout_ms,
+            goods_cache_enabled,
+            goods_cache_capacity,
+            goods_cache_ttl_secs,
         };
 
-        // Try to load server config from config.yaml first
-        let server_config = if let Ok(config_content) = std::fs::read_to_string("config.yaml") {
-            let yaml_config: ServerConfigYaml = serde_yaml::from_str(&config_content)?;
-            ServerConfig {
-                host: yaml_config.server.host,
-                port: yaml_config.server.port,
+        let mut server_config = file.server;
+        problems.override_env("HOST", &mut server_config.host);
+        problems.override_env("PORT", &mut server_config.port);
+        // Maximum number of entries accepted by the batch endpoints
+        problems.override_env("MAX_BATCH_SIZE", &mut server_config.max_batch_size);
+        // Request body limits in bytes; batch and import endpoints get the larger one
+        problems.override_env("MAX_BODY_BYTES", &mut server_config.max_body_bytes);
+        problems.override_env("MAX_BATCH_BODY_BYTES", &mut server_config.max_batch_body_bytes);
+        // How long a stored Idempotency-Key response is replayed
+        problems.override_env("IDEMPOTENCY_TTL_SECS", &mut server_config.idempotency_ttl_secs);
+        // Rows a filter-based PUT or DELETE may touch unless the request passes max_affected
+        problems.override_env("MAX_AFFECTED_ROWS", &mut server_config.max_affected_rows);
+        // Keep the old response bodies as the default while clients migrate
+        problems.override_env_bool("LEGACY_RESPONSE_ENVELOPE", &mut server_config.legacy_envelope);
+        // Response compression; streamed exports have no known size and are always compressed
+        problems.override_env_bool("COMPRESSION_ENABLED", &mut server_config.compression_enabled);
+        problems.override_env("COMPRESSION_MIN_BYTES", &mut server_config.compression_min_bytes);
+        // Page size default and cap for paginated listings
+        problems.override_env("DEFAULT_PAGE_SIZE", &mut server_config.default_page_size);
+        problems.override_env("MAX_PAGE_SIZE", &mut server_config.max_page_size);
+        problems.override_env("MAX_UNPAGINATED_RESULTS", &mut server_config.max_unpaginated_results);
+        problems.override_env("FUZZY_SEARCH_THRESHOLD", &mut server_config.fuzzy_search_threshold);
+        problems.override_env_bool("UPPERCASE_MATERIAL_CODES", &mut server_config.uppercase_material_codes);
+        problems.override_env_bool("AUDIT_LOG_ENABLED", &mut server_config.audit_log_enabled);
+        // Maintenance mode at startup, e.g. while migrations run
+        problems.override_env_bool("READ_ONLY", &mut server_config.read_only);
+        problems.override_env("MAINTENANCE_RETRY_AFTER_SECS", &mut server_config.maintenance_retry_after_secs);
+        // Timeouts and the in-flight cap, so a stuck database can't pile up requests indefinitely
+        problems.override_env("REQUEST_TIMEOUT_SECS", &mut server_config.request_timeout_secs);
+        problems.override_env("LONG_REQUEST_TIMEOUT_SECS", &mut server_config.long_request_timeout_secs);
+        problems.override_env("MAX_IN_FLIGHT_REQUESTS", &mut server_config.max_in_flight_requests);
+        // Either path turns TLS on; a missing counterpart is reported by validate
+        if env::var("TLS_CERT_PATH").is_ok() || env::var("TLS_KEY_PATH").is_ok() {
+            let tls_config = server_config.tls.get_or_insert_with(TlsConfig::default);
+            problems.override_env("TLS_CERT_PATH", &mut tls_config.cert_path);
+            problems.override_env("TLS_KEY_PATH", &mut tls_config.key_path);
+        }
+        if let Some(tls_config) = &mut server_config.tls {
+            problems.override_env("TLS_RELOAD_INTERVAL_SECS", &mut tls_config.reload_interval_secs);
+        }
+        if let Ok(base_path) = env::var("BASE_PATH") {
+            server_config.base_path = Some(base_path).filter(|base_path| !base_path.is_empty());
+        }
+        if let Some(host) = &cli.host {
+            server_config.host = host.clone();
+        }
+        if let Some(port) = cli.port {
+            server_config.port = port;
+        }
+
+        // API keys as comma-separated key:role pairs, e.g. "abc123:read,def456:admin"
+        let mut auth_config = file.auth;
+        if let Ok(value) = env::var("API_KEYS") {
+            match parse_api_keys(&value) {
+                Ok(api_keys) => auth_config.api_keys = api_keys,
+                Err(e) => problems.push("API_KEYS", e),
+            }
+        }
+
+        // Webhook endpoints as a comma-separated list of URLs
+        let mut webhook_config = file.webhooks;
+        if let Ok(value) = env::var("WEBHOOK_URLS") {
+            webhook_config.urls = value.split(',').map(str::trim).filter(|url| !url.is_empty()).map(String::from).collect();
+        }
+        if let Ok(secret) = env::var("WEBHOOK_SECRET") {
+            webhook_config.secret = Some(secret).filter(|secret| !secret.is_empty());
+        }
+        problems.override_env("WEBHOOK_MAX_ATTEMPTS", &mut webhook_config.max_attempts);
+        problems.override_env("WEBHOOK_TIMEOUT_SECS", &mut webhook_config.timeout_secs);
+
+        // Clean-up of long-expired stock, off unless enabled
+        let mut expired_purge_config = file.expired_purge;
+        problems.override_env_bool("EXPIRED_PURGE_ENABLED", &mut expired_purge_config.enabled);
+        problems.override_env("EXPIRED_PURGE_RETENTION_DAYS", &mut expired_purge_config.retention_days);
+        if let Ok(mode) = env::var("EXPIRED_PURGE_MODE") {
+            match ExpiredPurgeMode::parse(&mode) {
+                Ok(mode) => expired_purge_config.mode = mode,
+                Err(e) => problems.push("EXPIRED_PURGE_MODE", e),
             }
-        } else {
-            // Fallback to environment variables for server config
-            ServerConfig {
-                host: env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
-                port: env::var("PORT")
-                    .unwrap_or_else(|_| "3000".to_string())
-                    .parse()?,
+        }
+        problems.override_env("EXPIRED_PURGE_INTERVAL_SECS", &mut expired_purge_config.interval_secs);
+        if let Ok(run_at) = env::var("EXPIRED_PURGE_RUN_AT") {
+            expired_purge_config.run_at = Some(run_at).filter(|run_at| !run_at.is_empty());
+        }
+
+        // Daily stock snapshot, on by default
+        let mut stock_snapshot_config = file.stock_snapshot;
+        problems.override_env_bool("STOCK_SNAPSHOT_ENABLED", &mut stock_snapshot_config.enabled);
+        problems.override_env("STOCK_SNAPSHOT_RUN_AT", &mut stock_snapshot_config.run_at);
+
+        // Expiry alerts; thresholds as comma-separated days, e.g. "30,7,1"
+        let mut alerts_config = file.alerts;
+        problems.override_env_bool("ALERTS_ENABLED", &mut alerts_config.enabled);
+        if let Ok(value) = env::var("ALERT_THRESHOLDS_DAYS") {
+            match value.split(',').map(|days| days.trim().parse::<u32>()).collect::<Result<Vec<u32>, _>>() {
+                Ok(thresholds_days) => alerts_config.thresholds_days = thresholds_days,
+                Err(_) => problems.push("ALERT_THRESHOLDS_DAYS", format!("must be comma-separated day counts (got {:?})", value)),
             }
-        };
+        }
+        problems.override_env("ALERTS_RUN_AT", &mut alerts_config.run_at);
+        if let Ok(url) = env::var("ALERTS_WEBHOOK_URL") {
+            alerts_config.webhook_url = Some(url).filter(|url| !url.is_empty());
+        }
+
+        // Backorders: whether quantities may go below zero
+        let mut inventory_config = file.inventory;
+        problems.override_env_bool("ALLOW_NEGATIVE_STOCK", &mut inventory_config.allow_negative_stock);
+
+        // Required material_code format, as a regex; an empty value turns the check off
+        let mut validation_config = file.validation;
+        validation_config.allow_negative_stock = inventory_config.allow_negative_stock;
+        if let Ok(pattern) = env::var("MATERIAL_CODE_PATTERN") {
+            validation_config.material_code_pattern = Some(pattern).filter(|pattern| !pattern.is_empty());
+        }
+        if let Some(pattern) = &validation_config.material_code_pattern {
+            match Regex::new(pattern) {
+                Ok(regex) => validation_config.material_code_regex = Some(regex),
+                Err(e) => problems.push(
+                    source("MATERIAL_CODE_PATTERN", "validation.material_code_pattern"),
+                    format!("invalid regex: {}", e),
+                ),
+            }
+        }
 
-        Ok(AppConfig {
+        let mut config = AppConfig {
             database: database_config,
             server: server_config,
+            auth: auth_config,
+            webhooks: webhook_config,
+            expired_purge: expired_purge_config,
+            stock_snapshot: stock_snapshot_config,
+            alerts: alerts_config,
+            inventory: inventory_config,
+            validation: validation_config,
+        };
+        config.validate(cli, &mut problems);
+        problems.into_result()?;
+
+        if config.auth.api_keys.is_empty() {
+            tracing::warn!("No API keys configured, authentication is disabled");
+        }
+        if config.webhooks.is_enabled() && config.webhooks.secret.is_none() {
+            tracing::warn!("Webhooks configured without a secret, deliveries will not be signed");
+        }
+
+        // Widest threshold first, without repeats
+        config.alerts.thresholds_days.sort_unstable_by(|a, b| b.cmp(a));
+        config.alerts.thresholds_days.dedup();
+
+        Ok(config)
+    }
+
+    /// Check values that parsed but cannot work, naming the setting each problem came from.
+    /// The defaults are valid, so a bad value was set by the highest-precedence source present.
+    fn validate(&self, cli: &Cli, problems: &mut ConfigProblems) {
+        let server = &self.server;
+        if server.port == 0 {
+            let source = if cli.port.is_some() { "--port".to_string() } else { source("PORT", "server.port") };
+            problems.push(source, "must be between 1 and 65535");
+        }
+        if !is_valid_host(&server.host) {
+            let source = if cli.host.is_some() { "--host".to_string() } else { source("HOST", "server.host") };
+            problems.push(source, format!("{:?} is not an IP address or hostname", server.host));
+        }
+        if server.default_page_size == 0 || server.default_page_size > server.max_page_size {
+            problems.push(
+                source("DEFAULT_PAGE_SIZE", "server.default_page_size"),
+                format!("must be between 1 and max_page_size ({}), got {}", server.max_page_size, server.default_page_size),
+            );
+        }
+        if server.max_unpaginated_results == 0 {
+            problems.push(source("MAX_UNPAGINATED_RESULTS", "server.max_unpaginated_results"), "must be greater than 0");
+        }
+        if !(server.fuzzy_search_threshold > 0.0 && server.fuzzy_search_threshold <= 1.0) {
+            problems.push(
+                source("FUZZY_SEARCH_THRESHOLD", "server.fuzzy_search_threshold"),
+                format!("must be greater than 0 and at most 1, got {}", server.fuzzy_search_threshold),
+            );
+        }
+
+        if server.request_timeout_secs == 0 {
+            problems.push(source("REQUEST_TIMEOUT_SECS", "server.request_timeout_secs"), "must be greater than 0");
+        }
+        if server.long_request_timeout_secs == 0 {
+            problems.push(source("LONG_REQUEST_TIMEOUT_SECS", "server.long_request_timeout_secs"), "must be greater than 0");
+        }
+        if server.max_in_flight_requests == 0 {
+            problems.push(source("MAX_IN_FLIGHT_REQUESTS", "server.max_in_flight_requests"), "must be greater than 0");
+        }
+
+        if let Some(tls) = &server.tls {
+            if tls.cert_path.trim().is_empty() {
+                problems.push(source("TLS_CERT_PATH", "server.tls.cert_path"), "is required when TLS is configured");
+            }
+            if tls.key_path.trim().is_empty() {
+                problems.push(source("TLS_KEY_PATH", "server.tls.key_path"), "is required when TLS is configured");
+            }
+            if tls.reload_interval_secs == 0 {
+                problems.push(source("TLS_RELOAD_INTERVAL_SECS", "server.tls.reload_interval_secs"), "must be at least 1");
+            }
+        }
+
+        if let Some(base_path) = &server.base_path
+            && (!base_path.starts_with('/') || base_path.ends_with('/') || base_path.contains(['{', '}', '*']))
+        {
+            problems.push(
+                source("BASE_PATH", "server.base_path"),
+                format!("must start with '/', not end with '/' and contain no route parameters, got {:?}", base_path),
+            );
+        }
+
+        let database = &self.database;
+        if database.max_connections == 0 {
+            problems.push("DB_MAX_CONNECTIONS", "must be greater than 0");
+        }
+        if !database.database_url.is_empty()
+            && let Err(e) = PgConnectOptions::from_str(&database.database_url)
+        {
+            let source = if cli.database_url.is_some() {
+                "--database-url"
+            } else if env::var("DATABASE_URL").is_ok() {
+                "DATABASE_URL"
+            } else {
+                "DB_HOST/DB_PORT/DB_USER/DB_NAME"
+            };
+            problems.push(source, format!("invalid database URL: {}", e));
+        }
+        if let Some(url) = &database.read_replica_url
+            && let Err(e) = PgConnectOptions::from_str(url)
+        {
+            problems.push("DATABASE_READ_REPLICA_URL", format!("invalid database URL: {}", e));
+        }
+
+        let webhooks = &self.webhooks;
+        for url in &webhooks.urls {
+            if let Err(e) = validate_http_url(url) {
+                problems.push(source("WEBHOOK_URLS", "webhooks.urls"), e);
+            }
+        }
+        if webhooks.max_attempts == 0 {
+            problems.push(source("WEBHOOK_MAX_ATTEMPTS", "webhooks.max_attempts"), "must be at least 1");
+        }
+        if webhooks.timeout_secs == 0 {
+            problems.push(source("WEBHOOK_TIMEOUT_SECS", "webhooks.timeout_secs"), "must be at least 1");
+        }
+
+        let expired_purge = &self.expired_purge;
+        if let Err(e) = expired_purge.run_at_time() {
+            problems.push(source("EXPIRED_PURGE_RUN_AT", "expired_purge.run_at"), e);
+        }
+        if expired_purge.interval_secs == 0 {
+            problems.push(source("EXPIRED_PURGE_INTERVAL_SECS", "expired_purge.interval_secs"), "must be at least 1");
+        }
+
+        if let Err(e) = self.stock_snapshot.run_at_time() {
+            problems.push(source("STOCK_SNAPSHOT_RUN_AT", "stock_snapshot.run_at"), e);
+        }
+
+        let alerts = &self.alerts;
+        if let Err(e) = alerts.run_at_time() {
+            problems.push(source("ALERTS_RUN_AT", "alerts.run_at"), e);
+        }
+        if let Some(url) = &alerts.webhook_url
+            && let Err(e) = validate_http_url(url)
+        {
+            problems.push(source("ALERTS_WEBHOOK_URL", "alerts.webhook_url"), e);
+        }
+        if alerts.enabled && alerts.thresholds_days.is_empty() {
+            problems.push(source("ALERT_THRESHOLDS_DAYS", "alerts.thresholds_days"), "alerts are enabled but no thresholds are configured");
+        }
+    }
+
+    /// Log the effective configuration with credentials masked
+    pub fn log_effective(&self) {
+        match serde_json::to_string(self) {
+            Ok(json) => tracing::info!("Effective configuration: {}", sanitize_for_log(&json)),
+            Err(e) => tracing::warn!("Failed to serialize configuration for logging: {}", e),
+        }
+    }
+
+    /// The effective configuration as YAML with credentials masked, for `--print-config`
+    pub fn to_redacted_yaml(&self) -> Result<String> {
+        Ok(sanitize_for_log(&serde_yaml::to_string(self)?))
+    }
+}
+
+/// Configuration file read from the working directory
+const CONFIG_FILE: &str = "config.yaml";
+
+/// Problems found while loading the configuration, each prefixed with the env var or yaml path
+/// that caused it
+#[derive(Debug, Default)]
+struct ConfigProblems(Vec<String>);
+
+impl ConfigProblems {
+    fn push(&mut self, source: impl std::fmt::Display, problem: impl std::fmt::Display) {
+        self.0.push(format!("{}: {}", source, problem));
+    }
+
+    /// Environment variable `name` parsed as `T`, or `default` when unset
+    fn env<T>(&mut self, name: &str, mut default: T) -> T
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.override_env(name, &mut default);
+        default
+    }
+
+    fn env_bool(&mut self, name: &str, mut default: bool) -> bool {
+        self.override_env_bool(name, &mut default);
+        default
+    }
+
+    /// Replace `target` with environment variable `name` when it is set. An unparsable value is
+    /// recorded and leaves `target` alone, so that loading can go on to find further problems.
+    fn override_env<T>(&mut self, name: &str, target: &mut T)
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        if let Ok(value) = env::var(name) {
+            match value.trim().parse() {
+                Ok(parsed) => *target = parsed,
+                Err(e) => self.push(name, format!("{} (got {:?})", e, value)),
+            }
+        }
+    }
+
+    fn override_env_bool(&mut self, name: &str, target: &mut bool) {
+        if let Ok(value) = env::var(name) {
+            match value.trim().parse() {
+                Ok(parsed) => *target = parsed,
+                Err(_) => self.push(name, format!("must be true or false (got {:?})", value)),
+            }
+        }
+    }
+
+    fn into_result(self) -> Result<()> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        for problem in &self.0 {
+            tracing::error!("Invalid configuration: {}", problem);
+        }
+        Err(anyhow::anyhow!(
+            "Invalid configuration ({} problems):\n  - {}",
+            self.0.len(),
+            self.0.join("\n  - ")
+        ))
+    }
+}
+
+/// Name of a setting for a problem report: its env var when set, otherwise its config.yaml path
+fn source(env_var: &str, yaml_path: &str) -> String {
+    if env::var(env_var).is_ok() {
+        env_var.to_string()
+    } else {
+        format!("{} {}", CONFIG_FILE, yaml_path)
+    }
+}
+
+/// An IP address, or a hostname made of dot-separated alphanumeric/hyphen labels
+fn is_valid_host(host: &str) -> bool {
+    if host.parse::<IpAddr>().is_ok() {
+        return true;
+    }
+
+    !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
         })
+}
+
+fn validate_http_url(url: &str) -> std::result::Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid URL {:?}: {}", url, e))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(()),
+        scheme => Err(format!("URL {:?} must use http or https, not {}", url, scheme)),
+    }
+}
+
+impl ServerConfig {
+    pub fn page_limits(&self) -> PageLimits {
+        PageLimits {
+            default_per_page: self.default_page_size,
+            max_per_page: self.max_page_size,
+        }
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        Normalization {
+            uppercase_material_code: self.uppercase_material_codes,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn statement_timeout(&self) -> Option<Duration> {
+        (self.statement_timeout_ms > 0).then(|| Duration::from_millis(self.statement_timeout_ms))
+    }
+}
+
+/// Characters left unescaped in URL user info and path segments
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// Compose a connection URL from DB_HOST, DB_PORT, DB_USER, DB_PASSWORD, DB_NAME and DB_SSLMODE,
+/// used when DATABASE_URL is not set. Missing variables are recorded in `problems`.
+fn database_url_from_components(problems: &mut ConfigProblems) -> Option<String> {
+    let mut required = |name: &str| {
+        let value = env::var(name).ok();
+        if value.is_none() {
+            problems.push(name, "required when DATABASE_URL is not set");
+        }
+        value
+    };
+
+    let host = required("DB_HOST");
+    let user = required("DB_USER");
+    let dbname = required("DB_NAME");
+    let port = problems.env("DB_PORT", 5432u16);
+    let (host, user, dbname) = (host?, user?, dbname?);
+
+    let credentials = match env::var("DB_PASSWORD") {
+        Ok(password) => format!(
+            "{}:{}",
+            utf8_percent_encode(&user, URL_COMPONENT),
+            utf8_percent_encode(&password, URL_COMPONENT)
+        ),
+        Err(_) => utf8_percent_encode(&user, URL_COMPONENT).to_string(),
+    };
+
+    let mut url = format!(
+        "postgres://{}@{}:{}/{}",
+        credentials,
+        host,
+        port,
+        utf8_percent_encode(&dbname, URL_COMPONENT)
+    );
+
+    if let Ok(sslmode) = env::var("DB_SSLMODE") {
+        url.push_str(&format!("?sslmode={}", utf8_percent_encode(&sslmode, URL_COMPONENT)));
     }
+
+    Some(url)
 }
 
-// Helper struct for parsing YAML server config
-#[derive(Debug, Deserialize)]
-struct ServerConfigYaml {
-    server: ServerConfigInner,
+/// config.yaml; every section and field is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+    server: ServerConfig,
+    auth: AuthConfig,
+    webhooks: WebhookConfig,
+    expired_purge: ExpiredPurgeConfig,
+    stock_snapshot: StockSnapshotConfig,
+    alerts: AlertsConfig,
+    inventory: InventoryConfig,
+    validation: ValidationConfig,
 }
 
-#[derive(Debug, Deserialize)]
-struct ServerConfigInner {
-    host: String,
-    port: u16,
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_quantities_are_refused_unless_allowed() {
+        let refusing = ValidationConfig::default();
+        assert_eq!(refusing.check_quantity(-1, "quantity"), Err("quantity cannot be negative".to_string()));
+        assert_eq!(refusing.check_quantity(0, "quantity"), Ok(()));
+
+        let allowing = ValidationConfig { allow_negative_stock: true, ..ValidationConfig::default() };
+        assert_eq!(allowing.check_quantity(-1, "quantity"), Ok(()));
+    }
+}
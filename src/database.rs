@@ -1,73 +1,332 @@
 // src/database.rs
 use crate::config::DatabaseConfig;
-use crate::tables::{GoodsTable, InventoryTable};
+use crate::tables::{
+    AlertsTable, AuditLogTable, CategoriesTable, GoodsCache, GoodsCacheConfig, GoodsTable, IdempotencyTable, InventoryTable, PurchaseOrdersTable,
+    ReservationsTable, StockMovementsTable, StockSnapshotsTable, SyncTable, WebhookOutboxTable,
+};
 use anyhow::Result;
-use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::time::Duration;
-use tracing::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    migrate::Migrator,
+    postgres::{PgConnectOptions, PgPoolOptions},
+    PgPool,
+};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// Backoff between startup connection attempts, doubling up to the cap
+const INITIAL_CONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_CONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// Schema migrations from `migrations/`, embedded at compile time
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Tables whose absence makes `/health` report the service as unhealthy
+const HEALTH_CHECKED_TABLES: &[&str] = &["goods", "inventory"];
+
+/// `SELECT 1` slower than this marks the database as degraded
+const DEGRADED_LATENCY_MS: f64 = 500.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PoolStats {
+    /// Open connections, idle or in use
+    pub size: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    pub max_connections: u32,
+}
+
+impl PoolStats {
+    fn of(pool: &PgPool) -> Self {
+        let size = pool.size();
+        let idle = pool.num_idle() as u32;
+        Self {
+            size,
+            idle,
+            in_use: size.saturating_sub(idle),
+            max_connections: pool.options().get_max_connections(),
+        }
+    }
+
+    /// Every connection is open and busy, so new queries wait for one
+    pub fn is_exhausted(&self) -> bool {
+        self.idle == 0 && self.size >= self.max_connections
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TableHealth {
+    pub table: String,
+    pub exists: bool,
+    /// Planner estimate; unset until the table has been analyzed
+    pub approximate_rows: Option<i64>,
+}
+
+/// Result of `Database::health_check`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DatabaseHealth {
+    pub connected: bool,
+    /// Round trip of `SELECT 1`
+    pub latency_ms: Option<f64>,
+    pub pool: PoolStats,
+    pub tables: Vec<TableHealth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DatabaseHealth {
+    /// Connected and every checked table is present
+    pub fn is_usable(&self) -> bool {
+        self.connected && self.error.is_none() && self.tables.iter().all(|table| table.exists)
+    }
+
+    /// Usable, but slow or out of free connections
+    pub fn is_degraded(&self) -> bool {
+        self.pool.is_exhausted() || self.latency_ms.is_some_and(|latency_ms| latency_ms > DEGRADED_LATENCY_MS)
+    }
+}
 
 #[derive(Clone)]
 pub struct Database {
     pub pool: PgPool,
+    /// Pool for read-only queries; the primary pool when no replica is configured
+    pub read_pool: PgPool,
+    has_read_replica: bool,
     pub goods_table: GoodsTable,
     pub inventory_table: InventoryTable,
+    pub stock_movements_table: StockMovementsTable,
+    pub stock_snapshots_table: StockSnapshotsTable,
+    pub reservations_table: ReservationsTable,
+    pub idempotency_table: IdempotencyTable,
+    pub sync_table: SyncTable,
+    pub categories_table: CategoriesTable,
+    pub purchase_orders_table: PurchaseOrdersTable,
+    pub alerts_table: AlertsTable,
+    pub webhook_outbox_table: WebhookOutboxTable,
+    pub audit_log_table: AuditLogTable,
 }
 
 impl Database {
-    pub async fn new(config: DatabaseConfig) -> Result<Self> {
-        info!("Connecting to database...");
-        
-        // Use the database_url directly from config
-        let database_url = &config.database_url;
+    /// `webhook_events` turns on writing inventory events to the webhook outbox;
+    /// `allow_negative_stock` lets stock changes take quantities below zero
+    pub async fn new(config: DatabaseConfig, webhook_events: bool, allow_negative_stock: bool) -> Result<Self> {
+        let pool = Self::connect(&config).await?;
+
+        if config.run_migrations {
+            Self::run_migrations(&pool).await?;
+        } else {
+            info!("Migrations disabled, verifying table access");
+            for table in ["goods", "inventory", "stock_movements", "reservations", "idempotency_keys", "sync_tombstones", "categories", "purchase_orders", "purchase_order_lines", "webhook_outbox", "stock_snapshots", "alerts", "audit_log"] {
+                crate::utils::database::verify_table_access(&pool, table).await?;
+            }
+            info!("Table access verified");
+        }
+
+        let read_pool = match &config.read_replica_url {
+            Some(url) => {
+                info!("Read replica configured, opening read pool");
+                Self::connect_to(&config, url).await?
+            }
+            None => pool.clone(),
+        };
 
-        // Create connection pool with proper configuration
+        let has_read_replica = config.read_replica_url.is_some();
+        Ok(Self::from_pools(&config, pool, read_pool, has_read_replica, webhook_events, allow_negative_stock))
+    }
+
+    /// Pools that connect on first use: nothing is verified or migrated. For tests whose
+    /// goods and inventory come from the fakes in `testing` and that never reach the database.
+    pub fn connect_lazy(config: &DatabaseConfig) -> Result<Self> {
+        let pool = PgPoolOptions::new().connect_lazy(&config.database_url)?;
+        Ok(Self::from_pools(config, pool.clone(), pool, false, false, false))
+    }
+
+    fn from_pools(config: &DatabaseConfig, pool: PgPool, read_pool: PgPool, has_read_replica: bool, webhook_events: bool, allow_negative_stock: bool) -> Self {
+        if !config.goods_cache_enabled {
+            info!("Goods cache disabled");
+        }
+        let goods_cache = GoodsCache::new(GoodsCacheConfig {
+            enabled: config.goods_cache_enabled,
+            capacity: config.goods_cache_capacity,
+            ttl: Duration::from_secs(config.goods_cache_ttl_secs),
+        });
+        let goods_table = GoodsTable::new(pool.clone(), read_pool.clone(), config.statement_timeout(), goods_cache);
+
+        let inventory_table = InventoryTable::new(pool.clone(), read_pool.clone(), config.statement_timeout(), goods_table.clone())
+            .with_webhook_events(webhook_events)
+            .with_negative_stock(allow_negative_stock);
+
+        Self {
+            purchase_orders_table: PurchaseOrdersTable::new(pool.clone(), inventory_table.clone()),
+            inventory_table,
+            goods_table,
+            stock_movements_table: StockMovementsTable::new(pool.clone()),
+            stock_snapshots_table: StockSnapshotsTable::new(pool.clone()),
+            reservations_table: ReservationsTable::new(pool.clone())
+                .with_negative_stock(allow_negative_stock)
+                .with_webhook_events(webhook_events),
+            idempotency_table: IdempotencyTable::new(pool.clone()),
+            sync_table: SyncTable::new(pool.clone()),
+            categories_table: CategoriesTable::new(pool.clone()),
+            alerts_table: AlertsTable::new(pool.clone()),
+            webhook_outbox_table: WebhookOutboxTable::new(pool.clone()),
+            audit_log_table: AuditLogTable::new(pool.clone()),
+            has_read_replica,
+            read_pool,
+            pool,
+        }
+    }
+
+    /// Open and verify the primary connection pool
+    pub async fn connect(config: &DatabaseConfig) -> Result<PgPool> {
+        Self::connect_to(config, &config.database_url).await
+    }
+
+    /// Open and verify a connection pool for `database_url` using the pool settings in `config`
+    async fn connect_to(config: &DatabaseConfig, database_url: &str) -> Result<PgPool> {
+        let connect_options = PgConnectOptions::from_str(database_url).map_err(|e| {
+            error!("Invalid database URL: {}", e);
+            e
+        })?;
+
+        // Log the target without the password
+        info!(
+            "Connecting to database {}@{}:{}/{}...",
+            connect_options.get_username(),
+            connect_options.get_host(),
+            connect_options.get_port(),
+            connect_options.get_database().unwrap_or_default()
+        );
+
+        // Retry with exponential backoff so the API can start alongside the database
+        let max_wait = Duration::from_secs(config.connect_max_wait_secs);
+        let mut waited = Duration::ZERO;
+        let mut delay = INITIAL_CONNECT_DELAY;
+        let mut attempt = 1;
+
+        let pool = loop {
+            match Self::try_connect(config, connect_options.clone()).await {
+                Ok(pool) => break pool,
+                Err(e) if attempt < config.connect_max_attempts && waited + delay <= max_wait => {
+                    warn!(
+                        "Database connection attempt {}/{} failed: {}. Retrying in {:?}",
+                        attempt, config.connect_max_attempts, e, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    waited += delay;
+                    delay = (delay * 2).min(MAX_CONNECT_DELAY);
+                    attempt += 1;
+                }
+                Err(e) => {
+                    error!("Failed to connect to database after {} attempts: {}", attempt, e);
+                    return Err(e.into());
+                }
+            }
+        };
+
+        info!("Database connection pool created with {} max connections", config.max_connections);
+        info!("Database connection verified");
+
+        Ok(pool)
+    }
+
+    /// One connection attempt: build the pool and probe it with `SELECT 1`
+    async fn try_connect(config: &DatabaseConfig, connect_options: PgConnectOptions) -> Result<PgPool, sqlx::Error> {
         let pool = PgPoolOptions::new()
             .max_connections(config.max_connections)
-            .acquire_timeout(Duration::from_secs(10))
-            .idle_timeout(Duration::from_secs(600))
-            .max_lifetime(Duration::from_secs(1800))
-            .connect(database_url)
-            .await
-            .map_err(|e| {
-                error!("Failed to connect to database: {}", e);
-                e
-            })?;
+            .acquire_timeout(Duration::from_secs(config.acquire_timeout_secs))
+            .idle_timeout(Duration::from_secs(config.idle_timeout_secs))
+            .max_lifetime(Duration::from_secs(config.max_lifetime_secs))
+            .connect_with(connect_options)
+            .await?;
 
-        info!("Database connection pool created with {} max connections", config.max_connections);
+        sqlx::query("SELECT 1").execute(&pool).await?;
 
-        // Test the connection
-        sqlx::query("SELECT 1")
-            .execute(&pool)
-            .await
-            .map_err(|e| {
-                error!("Database health check failed: {}", e);
-                e
-            })?;
+        Ok(pool)
+    }
 
-        info!("Database connection verified");
+    /// Apply pending migrations and log every applied version
+    pub async fn run_migrations(pool: &PgPool) -> Result<()> {
+        info!("Running database migrations...");
+        MIGRATOR.run(pool).await.map_err(|e| {
+            error!("Database migration failed: {}", e);
+            e
+        })?;
 
-        // Initialize tables
-        let goods_table = GoodsTable::new(pool.clone());
-        let inventory_table = InventoryTable::new(pool.clone());
-        
-        // Verify table access instead of trying to create tables
-        crate::utils::database::verify_table_access(&pool, "goods").await?;
-        info!("Goods table access verified");
+        let applied = sqlx::query_as::<_, (i64, String)>(
+            "SELECT version, description FROM _sqlx_migrations WHERE success ORDER BY version"
+        )
+        .fetch_all(pool)
+        .await?;
 
-        crate::utils::database::verify_table_access(&pool, "inventory").await?;
-        info!("Inventory table access verified");
+        for (version, description) in &applied {
+            info!("Migration {} applied: {}", version, description);
+        }
+        info!("Database schema up to date ({} migrations applied)", applied.len());
 
-        Ok(Self {
-            pool,
-            goods_table,
-            inventory_table,
-        })
+        Ok(())
     }
 
-    pub async fn health_check(&self) -> Result<(), sqlx::Error> {
-        sqlx::query("SELECT 1")
-            .execute(&self.pool)
-            .await
-            .map(|_| ())
+    /// Probe the primary: pool usage, a timed `SELECT 1`, and the presence and approximate
+    /// size of the core tables. Failures are reported in the result rather than returned.
+    pub async fn health_check(&self) -> DatabaseHealth {
+        let pool = PoolStats::of(&self.pool);
+
+        let started = Instant::now();
+        if let Err(e) = sqlx::query("SELECT 1").execute(&self.pool).await {
+            return DatabaseHealth { connected: false, latency_ms: None, pool, tables: Vec::new(), error: Some(e.to_string()) };
+        }
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        // reltuples is the planner's estimate: cheap, and -1 until the table was first analyzed
+        let found = sqlx::query_as::<_, (String, Option<i64>)>(
+            r#"
+            SELECT t.table_name::text, c.reltuples::int8
+            FROM information_schema.tables t
+            LEFT JOIN pg_class c ON c.oid = (quote_ident(t.table_schema) || '.' || quote_ident(t.table_name))::regclass
+            WHERE t.table_schema = current_schema() AND t.table_name = ANY($1)
+            "#
+        )
+        .bind(HEALTH_CHECKED_TABLES)
+        .fetch_all(&self.pool)
+        .await;
+
+        let (tables, error) = match found {
+            Ok(found) => {
+                let tables = HEALTH_CHECKED_TABLES
+                    .iter()
+                    .map(|&table| {
+                        let row = found.iter().find(|(name, _)| name == table);
+                        TableHealth {
+                            table: table.to_string(),
+                            exists: row.is_some(),
+                            approximate_rows: row.and_then(|(_, rows)| *rows).filter(|rows| *rows >= 0),
+                        }
+                    })
+                    .collect();
+                (tables, None)
+            }
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+
+        DatabaseHealth { connected: true, latency_ms: Some(latency_ms), pool, tables, error }
+    }
+
+    /// Probe the read replica; `None` when reads go to the primary
+    pub async fn replica_health_check(&self) -> Option<Result<(), sqlx::Error>> {
+        if !self.has_read_replica {
+            return None;
+        }
+
+        Some(
+            sqlx::query("SELECT 1")
+                .execute(&self.read_pool)
+                .await
+                .map(|_| ())
+        )
     }
 }
\ No newline at end of file
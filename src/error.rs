@@ -0,0 +1,548 @@
+// src/error.rs
+use crate::extract::is_json_content_type;
+use crate::response::error_response;
+use crate::tables::{BatchItemError, TableError};
+use crate::utils::response::{format_database_error, unique_violation};
+use axum::{
+    BoxError,
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::any::Any;
+use thiserror::Error;
+
+/// Crate-wide API error, rendered in the response envelope with a machine-readable code
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    Validation(String),
+
+    #[error("{0}")]
+    MalformedJson(String),
+
+    #[error("{}", invalid_field_message(.field.as_deref(), .message))]
+    InvalidField {
+        field: Option<String>,
+        message: String,
+    },
+
+    #[error("Batch rejected: {} invalid entries", .0.len())]
+    InvalidBatch(Vec<BatchItemError>),
+
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    MethodNotAllowed(String),
+
+    #[error("{0}")]
+    NotAcceptable(String),
+
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+
+    #[error("{0}")]
+    PayloadTooLarge(String),
+
+    #[error("{0}")]
+    IdempotencyKeyReused(String),
+
+    #[error("{message}")]
+    Conflict {
+        message: String,
+        details: Option<serde_json::Value>,
+    },
+
+    #[error("{0}")]
+    ForeignKeyViolation(String),
+
+    #[error("{0}")]
+    Database(String),
+
+    #[error("{0}")]
+    Timeout(String),
+
+    #[error("{0}")]
+    RequestTimeout(String),
+
+    #[error("{0}")]
+    Overloaded(String),
+
+    /// Writes are switched off; sent with `Retry-After`
+    #[error("{message}")]
+    Maintenance {
+        message: String,
+        retry_after_secs: u64,
+    },
+
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn conflict(message: &str) -> Self {
+        ApiError::Conflict {
+            message: message.to_string(),
+            details: None,
+        }
+    }
+
+    /// Classify a database error, using `operation` to build the user-facing message
+    pub fn database(error: sqlx::Error, operation: &str) -> Self {
+        let message = format_database_error(&error, operation);
+
+        match &error {
+            sqlx::Error::RowNotFound => ApiError::NotFound(message),
+            sqlx::Error::Database(db_err) => match db_err.code().as_deref() {
+                Some("23503") => ApiError::ForeignKeyViolation(message),
+                Some("23505") => ApiError::Conflict {
+                    message,
+                    details: unique_violation(db_err.as_ref()).and_then(|violation| serde_json::to_value(violation).ok()),
+                },
+                Some("23514") => ApiError::Validation(message),
+                // query_canceled: the statement timeout fired
+                Some("57014") => ApiError::Timeout(message),
+                _ => ApiError::Database(message),
+            },
+            _ => ApiError::Internal(message),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiError::MalformedJson(_) => StatusCode::BAD_REQUEST,
+            ApiError::InvalidField { .. } => StatusCode::BAD_REQUEST,
+            ApiError::InvalidBatch(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            ApiError::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::IdempotencyKeyReused(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Conflict { .. } => StatusCode::CONFLICT,
+            ApiError::ForeignKeyViolation(_) => StatusCode::CONFLICT,
+            ApiError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::RequestTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::Overloaded(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Maintenance { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Validation(_) => "VALIDATION_ERROR",
+            ApiError::MalformedJson(_) => "MALFORMED_JSON",
+            ApiError::InvalidField { .. } => "VALIDATION_ERROR",
+            ApiError::InvalidBatch(_) => "VALIDATION_ERROR",
+            ApiError::Unauthorized(_) => "UNAUTHORIZED",
+            ApiError::Forbidden(_) => "FORBIDDEN",
+            ApiError::NotFound(_) => "NOT_FOUND",
+            ApiError::MethodNotAllowed(_) => "METHOD_NOT_ALLOWED",
+            ApiError::NotAcceptable(_) => "NOT_ACCEPTABLE",
+            ApiError::UnsupportedMediaType(_) => "UNSUPPORTED_MEDIA_TYPE",
+            ApiError::PayloadTooLarge(_) => "PAYLOAD_TOO_LARGE",
+            ApiError::IdempotencyKeyReused(_) => "IDEMPOTENCY_KEY_REUSED",
+            ApiError::Conflict { .. } => "CONFLICT",
+            ApiError::ForeignKeyViolation(_) => "FOREIGN_KEY_VIOLATION",
+            ApiError::Database(_) => "DATABASE_ERROR",
+            ApiError::Timeout(_) => "QUERY_TIMEOUT",
+            ApiError::RequestTimeout(_) => "REQUEST_TIMEOUT",
+            ApiError::Overloaded(_) => "OVERLOADED",
+            ApiError::Maintenance { .. } => "READ_ONLY_MODE",
+            ApiError::Internal(_) => "INTERNAL_ERROR",
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(error: sqlx::Error) -> Self {
+        ApiError::database(error, "database operation")
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(error: serde_json::Error) -> Self {
+        ApiError::Internal(format!("Failed to serialize response: {}", error))
+    }
+}
+
+impl From<TableError> for ApiError {
+    fn from(error: TableError) -> Self {
+        match error {
+            TableError::BlockedByInventory { ref goods } => ApiError::Conflict {
+                message: error.to_string(),
+                details: serde_json::to_value(goods).ok(),
+            },
+            TableError::InsufficientStock { item_id, current_quantity, delta } => ApiError::Conflict {
+                message: error.to_string(),
+                details: Some(serde_json::json!({
+                    "item_id": item_id,
+                    "current_quantity": current_quantity,
+                    "delta": delta,
+                })),
+            },
+            TableError::InsufficientGoodsStock { goods_id, requested, available } => ApiError::Conflict {
+                message: error.to_string(),
+                details: Some(serde_json::json!({
+                    "goods_id": goods_id,
+                    "requested": requested,
+                    "available": available,
+                })),
+            },
+            TableError::InsufficientAvailableStock { item_id, requested, available } => ApiError::Conflict {
+                message: error.to_string(),
+                details: Some(serde_json::json!({
+                    "item_id": item_id,
+                    "requested": requested,
+                    "available": available,
+                })),
+            },
+            TableError::DuplicateInventory { item_id } => ApiError::Conflict {
+                message: error.to_string(),
+                details: Some(serde_json::json!({ "item_id": item_id })),
+            },
+            TableError::InactiveGoods { goods_id } => ApiError::Conflict {
+                message: error.to_string(),
+                details: Some(serde_json::json!({ "goods_id": goods_id })),
+            },
+            TableError::CategoryInUse { category_id, goods_count, subcategory_count } => ApiError::Conflict {
+                message: error.to_string(),
+                details: Some(serde_json::json!({
+                    "category_id": category_id,
+                    "goods_count": goods_count,
+                    "subcategory_count": subcategory_count,
+                })),
+            },
+            TableError::CategoryCycle { .. } => ApiError::Validation(error.to_string()),
+            TableError::PurchaseOrderStatus { purchase_order_id, ref status, .. } => ApiError::Conflict {
+                message: error.to_string(),
+                details: Some(serde_json::json!({
+                    "purchase_order_id": purchase_order_id,
+                    "status": status,
+                })),
+            },
+            TableError::DuplicateBarcode { ref barcode, goods_id } => ApiError::Conflict {
+                message: error.to_string(),
+                details: Some(serde_json::json!({
+                    "barcode": barcode,
+                    "goods_id": goods_id,
+                })),
+            },
+            TableError::TooManyAffected { matched, max_affected } => ApiError::Conflict {
+                message: error.to_string(),
+                details: Some(serde_json::json!({
+                    "matched": matched,
+                    "max_affected": max_affected,
+                })),
+            },
+            TableError::StaleVersion { ref goods } => ApiError::Conflict {
+                message: error.to_string(),
+                details: serde_json::to_value(goods).ok(),
+            },
+            TableError::InvalidBatch { errors } => ApiError::InvalidBatch(errors),
+            TableError::Database(e) => e.into(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let code = self.code();
+
+        match self {
+            ApiError::Conflict { message, details } => error_response(status, code, &message, details),
+            ApiError::Maintenance { message, retry_after_secs } => {
+                let mut response = error_response(status, code, &message, None);
+                response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+                response
+            }
+            ApiError::InvalidField { ref field, ref message } => {
+                let details = serde_json::json!({ "field": field, "message": message });
+                error_response(status, code, &self.to_string(), Some(details))
+            }
+            ApiError::InvalidBatch(ref errors) => {
+                error_response(status, code, &self.to_string(), serde_json::to_value(errors).ok())
+            }
+            other => error_response(status, code, &other.to_string(), None),
+        }
+    }
+}
+
+fn invalid_field_message(field: Option<&str>, message: &str) -> String {
+    match field {
+        Some(field) => format!("Invalid request body at `{}`: {}", field, message),
+        None => format!("Invalid request body: {}", message),
+    }
+}
+
+/// Response for a handler that panicked, for `CatchPanicLayer`. The panic message is logged but
+/// never sent; the client only gets the request_id to quote.
+pub fn panic_as_json(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload");
+    tracing::error!("Handler panicked: {}", message);
+
+    ApiError::Internal("Internal server error".to_string()).into_response()
+}
+
+/// Replace the plain-text 413 produced by body limits and extractors with the JSON error body
+pub async fn payload_too_large_as_json(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE || is_json_content_type(response.headers()) {
+        return response;
+    }
+
+    tracing::warn!("Request body rejected: exceeds size limit");
+    ApiError::PayloadTooLarge("Request body exceeds the size limit for this endpoint".to_string()).into_response()
+}
+
+/// Replace the empty 504 produced by the request timeout layers with the JSON error body
+pub async fn gateway_timeout_as_json(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+
+    if response.status() != StatusCode::GATEWAY_TIMEOUT || is_json_content_type(response.headers()) {
+        return response;
+    }
+
+    tracing::warn!("Request timed out");
+    ApiError::RequestTimeout("The request did not complete in time".to_string()).into_response()
+}
+
+/// Response for requests shed by the in-flight limit, for `HandleErrorLayer`
+pub async fn overloaded_as_json(error: BoxError) -> ApiError {
+    tracing::warn!("Request rejected: {}", error);
+    ApiError::Overloaded("The server is handling too many requests; retry later".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_id::propagate_request_id;
+    use axum::{body::{to_bytes, Body}, routing::get, Router};
+    use tower::ServiceExt;
+    use tower_http::catch_panic::CatchPanicLayer;
+
+    async fn render(error: ApiError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn each_variant_renders_its_status_and_code() {
+        let cases = [
+            (ApiError::Validation("bad".to_string()), StatusCode::BAD_REQUEST, "VALIDATION_ERROR"),
+            (ApiError::NotFound("gone".to_string()), StatusCode::NOT_FOUND, "NOT_FOUND"),
+            (ApiError::conflict("taken"), StatusCode::CONFLICT, "CONFLICT"),
+            (ApiError::ForeignKeyViolation("missing goods".to_string()), StatusCode::CONFLICT, "FOREIGN_KEY_VIOLATION"),
+            (ApiError::Database("broken".to_string()), StatusCode::INTERNAL_SERVER_ERROR, "DATABASE_ERROR"),
+            (ApiError::Internal("oops".to_string()), StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR"),
+        ];
+
+        for (error, status, code) in cases {
+            let message = error.to_string();
+            let (actual_status, body) = render(error).await;
+
+            assert_eq!(actual_status, status, "{}", code);
+            assert_eq!(body["success"], false);
+            assert_eq!(body["error"]["code"], code);
+            assert_eq!(body["error"]["message"], message);
+        }
+    }
+
+    #[tokio::test]
+    async fn row_not_found_is_not_found() {
+        let (status, body) = render(sqlx::Error::RowNotFound.into()).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"]["code"], "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn non_database_sqlx_errors_are_internal() {
+        let (status, body) = render(sqlx::Error::PoolTimedOut.into()).await;
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"]["code"], "INTERNAL_ERROR");
+    }
+
+    #[tokio::test]
+    async fn table_conflicts_carry_their_details() {
+        let (status, body) = render(TableError::TooManyAffected { matched: 12, max_affected: 10 }.into()).await;
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"]["code"], "CONFLICT");
+        assert_eq!(body["error"]["details"], serde_json::json!([{ "matched": 12, "max_affected": 10 }]));
+    }
+
+    #[tokio::test]
+    async fn panicking_handler_answers_500_without_the_panic_message() {
+        async fn panics() -> &'static str {
+            panic!("secret connection string")
+        }
+
+        // Layered as in `Server::create_router`
+        let router = Router::new()
+            .route("/panic", get(panics))
+            .layer(CatchPanicLayer::custom(panic_as_json))
+            .layer(axum::middleware::from_fn(propagate_request_id));
+        let request = Request::builder().uri("/panic").header("x-request-id", "panic-1").body(Body::empty()).unwrap();
+
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"]["code"], "INTERNAL_ERROR");
+        assert_eq!(body["meta"]["request_id"], "panic-1");
+        assert!(!body.to_string().contains("secret"), "{}", body);
+    }
+
+    /// A unique violation as the driver reports it, without PostgreSQL's detail line
+    #[derive(Debug)]
+    struct UniqueViolationError {
+        constraint: &'static str,
+    }
+
+    impl std::fmt::Display for UniqueViolationError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "duplicate key value violates unique constraint \"{}\"", self.constraint)
+        }
+    }
+
+    impl std::error::Error for UniqueViolationError {}
+
+    impl sqlx::error::DatabaseError for UniqueViolationError {
+        fn message(&self) -> &str {
+            "duplicate key value violates unique constraint"
+        }
+
+        fn code(&self) -> Option<std::borrow::Cow<'_, str>> {
+            Some("23505".into())
+        }
+
+        fn constraint(&self) -> Option<&str> {
+            Some(self.constraint)
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> sqlx::error::ErrorKind {
+            sqlx::error::ErrorKind::UniqueViolation
+        }
+    }
+
+    fn unique_violation(constraint: &'static str) -> sqlx::Error {
+        sqlx::Error::Database(Box::new(UniqueViolationError { constraint }))
+    }
+
+    #[tokio::test]
+    async fn known_unique_constraint_is_a_conflict_naming_the_field() {
+        for (constraint, field) in [("goods_material_code_key", "material_code"), ("goods_barcode_key", "barcode")] {
+            let (status, body) = render(ApiError::database(unique_violation(constraint), "create goods")).await;
+
+            assert_eq!(status, StatusCode::CONFLICT);
+            assert_eq!(body["error"]["code"], "CONFLICT");
+            assert_eq!(body["error"]["message"], format!("A record with this {} already exists", field));
+            assert_eq!(body["error"]["details"], serde_json::json!([{ "field": field }]));
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_unique_constraint_is_a_conflict_without_details() {
+        let (status, body) = render(ApiError::database(unique_violation("some_other_key"), "create goods")).await;
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"]["message"], "Record already exists");
+        assert_eq!(body["error"]["details"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn request_past_the_timeout_is_a_json_504() {
+        async fn sleeps() -> &'static str {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            "too late"
+        }
+
+        // Layered as in `Server::create_router`
+        let router = Router::new()
+            .route("/slow", get(sleeps))
+            .layer(tower_http::timeout::TimeoutLayer::with_status_code(StatusCode::GATEWAY_TIMEOUT, std::time::Duration::from_millis(10)))
+            .layer(axum::middleware::from_fn(gateway_timeout_as_json));
+
+        let response = router.oneshot(Request::builder().uri("/slow").body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body["error"]["code"], "REQUEST_TIMEOUT");
+    }
+
+    #[tokio::test]
+    async fn requests_past_the_in_flight_limit_are_shed_with_a_json_503() {
+        let entered = std::sync::Arc::new(tokio::sync::Notify::new());
+        let release = std::sync::Arc::new(tokio::sync::Notify::new());
+        let handler = {
+            let (entered, release) = (entered.clone(), release.clone());
+            move || async move {
+                entered.notify_one();
+                release.notified().await;
+                "done"
+            }
+        };
+
+        // Layered as in `Server::create_router`, with room for one request
+        let router = Router::new().route("/busy", get(handler)).layer(
+            tower::ServiceBuilder::new()
+                .layer(axum::error_handling::HandleErrorLayer::new(overloaded_as_json))
+                .layer(tower::load_shed::LoadShedLayer::new())
+                .layer(tower::limit::GlobalConcurrencyLimitLayer::new(1)),
+        );
+        let request = || Request::builder().uri("/busy").body(Body::empty()).unwrap();
+
+        let first = tokio::spawn(router.clone().oneshot(request()));
+        entered.notified().await;
+
+        let response = router.clone().oneshot(request()).await.unwrap();
+        let status = response.status();
+        let body: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["error"]["code"], "OVERLOADED");
+
+        // The request holding the slot is not disturbed, and frees it when done
+        release.notify_one();
+        assert_eq!(first.await.unwrap().unwrap().status(), StatusCode::OK);
+        let handler_done = router.oneshot(request());
+        release.notify_one();
+        assert_eq!(handler_done.await.unwrap().status(), StatusCode::OK);
+    }
+}
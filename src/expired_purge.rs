@@ -0,0 +1,55 @@
+// src/expired_purge.rs
+//
+// Scheduled clean-up of long-expired inventory. Items whose `expired_date` lies more than
+// `retention_days` in the past are zeroed or deleted (see `ExpiredPurgeConfig`). A failed
+// run, e.g. while the database is down, is logged and retried on the next scheduled run.
+use crate::config::ExpiredPurgeConfig;
+use crate::tables::InventoryTable;
+use crate::utils::datetime::until_next_time_of_day;
+use crate::utils::logging::log_database_error;
+use chrono::{DateTime, Days, NaiveTime, Utc};
+use std::time::Duration;
+use tracing::info;
+
+/// Items that expired before this instant are purged
+pub fn purge_cutoff(retention_days: u32) -> DateTime<Utc> {
+    Utc::now() - Days::new(retention_days as u64)
+}
+
+/// Time until the next run: the next `run_at` of the day when set, otherwise `interval_secs`
+fn next_delay(config: &ExpiredPurgeConfig, run_at: Option<NaiveTime>) -> Duration {
+    run_at.map_or(Duration::from_secs(config.interval_secs), until_next_time_of_day)
+}
+
+/// Start the scheduled purge; does nothing unless it is enabled
+pub fn spawn_expired_purge(table: InventoryTable, config: ExpiredPurgeConfig) -> anyhow::Result<()> {
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let run_at = config.run_at_time()?;
+    info!(
+        "Purging inventory expired more than {} days ago ({:?}) {}",
+        config.retention_days,
+        config.mode,
+        config.run_at.as_deref().map_or_else(|| format!("every {}s", config.interval_secs), |run_at| format!("daily at {} UTC", run_at))
+    );
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(next_delay(&config, run_at)).await;
+
+            let cutoff = purge_cutoff(config.retention_days);
+            match table.purge_expired(cutoff, config.mode, false).await {
+                Ok(item_ids) if item_ids.is_empty() => {}
+                Ok(item_ids) => info!(
+                    "Expired purge ({:?}) affected {} items expired before {}: {:?}",
+                    config.mode, item_ids.len(), cutoff, item_ids
+                ),
+                Err(e) => log_database_error("purge expired inventory", &e),
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+// src/export.rs
+use crate::tables::{Good, GoodWithStock, InventoryItemWithGoods, ValuationRow};
+use crate::utils::logging::log_database_error;
+use axum::{
+    body::{Body, Bytes},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, Stream, StreamExt};
+use serde::Serialize;
+
+/// A row type that can be written as one CSV record
+pub trait CsvRecord {
+    const HEADER: &'static [&'static str];
+
+    fn record(&self) -> Vec<String>;
+}
+
+impl CsvRecord for Good {
+    const HEADER: &'static [&'static str] = &[
+        "goods_id", "material_code", "goods_name", "description", "price",
+        "volumn_l", "mass_g", "mass_base", "volumn_base", "reorder_point", "category_id", "barcode", "tags",
+        "is_active",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.goods_id.to_string(),
+            self.material_code.clone(),
+            self.goods_name.clone(),
+            join_list(&self.description),
+            self.price.to_string(),
+            self.volumn_l.to_string(),
+            self.mass_g.to_string(),
+            self.mass_base.to_string(),
+            self.volumn_base.to_string(),
+            optional(self.reorder_point),
+            optional(self.category_id),
+            optional(self.barcode.as_deref()),
+            join_list(&self.tags),
+            self.is_active.to_string(),
+        ]
+    }
+}
+
+impl CsvRecord for GoodWithStock {
+    const HEADER: &'static [&'static str] = &[
+        "goods_id", "material_code", "goods_name", "description", "price",
+        "volumn_l", "mass_g", "mass_base", "volumn_base", "reorder_point", "category_id", "barcode", "tags",
+        "is_active", "total_quantity", "lot_count", "earliest_expired_date",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        let mut record = self.good.record();
+        record.extend([
+            self.total_quantity.to_string(),
+            self.lot_count.to_string(),
+            format_date(&self.earliest_expired_date),
+        ]);
+        record
+    }
+}
+
+impl CsvRecord for InventoryItemWithGoods {
+    const HEADER: &'static [&'static str] = &[
+        "item_id", "goods_id", "material_code", "goods_name", "description", "price",
+        "volumn_l", "mass_g", "mass_base", "volumn_base", "quantity", "expired_date", "lot_number",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.item_id.to_string(),
+            self.goods_id.to_string(),
+            self.material_code.clone(),
+            self.goods_name.clone(),
+            join_list(&self.description),
+            self.price.to_string(),
+            self.volumn_l.to_string(),
+            self.mass_g.to_string(),
+            self.mass_base.to_string(),
+            self.volumn_base.to_string(),
+            self.quantity.to_string(),
+            format_date(&self.expired_date),
+            self.lot_number.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+impl CsvRecord for ValuationRow {
+    const HEADER: &'static [&'static str] = &["group_key", "group_label", "total_quantity", "total_value"];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            optional(self.group_key),
+            self.group_label.clone(),
+            self.total_quantity.to_string(),
+            self.total_value.to_string(),
+        ]
+    }
+}
+
+/// Stream rows out as a `text/csv` attachment named `<name>-<timestamp>.csv`.
+/// A database error mid-stream is logged and ends the body early.
+pub fn csv_response<T, S>(name: &str, rows: S) -> Response
+where
+    T: CsvRecord + Send + 'static,
+    S: Stream<Item = Result<T, sqlx::Error>> + Send + 'static,
+{
+    let header = stream::once(async { encode_record(T::HEADER) });
+    let records = rows.map(|row| match row {
+        Ok(item) => encode_record(item.record()),
+        Err(e) => {
+            log_database_error("csv export", &e);
+            Err(std::io::Error::other(e))
+        }
+    });
+
+    let filename = format!("{}-{}.csv", name, Utc::now().format("%Y%m%dT%H%M%SZ"));
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(header.chain(records)),
+    )
+        .into_response()
+}
+
+/// Stream rows out as newline-delimited JSON, one object per line.
+/// Rows come from a bounded channel, so a slow client slows the database read instead of buffering.
+pub fn ndjson_response<T, S>(rows: S) -> Response
+where
+    T: Serialize + Send + 'static,
+    S: Stream<Item = Result<T, sqlx::Error>> + Send + 'static,
+{
+    let lines = rows.map(|row| match row {
+        Ok(item) => {
+            let mut line = serde_json::to_vec(&item).map_err(std::io::Error::other)?;
+            line.push(b'\n');
+            Ok(Bytes::from(line))
+        }
+        Err(e) => {
+            log_database_error("ndjson export", &e);
+            Err(std::io::Error::other(e))
+        }
+    });
+
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response()
+}
+
+fn encode_record<I: AsRef<[u8]>>(fields: impl IntoIterator<Item = I>) -> Result<Bytes, std::io::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(fields)?;
+    writer.into_inner().map(Bytes::from).map_err(|e| e.into_error())
+}
+
+fn join_list(items: &Option<Vec<String>>) -> String {
+    items.as_ref().map(|items| items.join("|")).unwrap_or_default()
+}
+
+fn format_date(date: &Option<DateTime<Utc>>) -> String {
+    date.map(|date| date.to_rfc3339()).unwrap_or_default()
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
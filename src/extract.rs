@@ -0,0 +1,185 @@
+// src/extract.rs
+//
+// Extractors whose rejections use the standard error envelope instead of axum's plain text.
+// `JsonBody` replaces `Json` on the write endpoints: 415 without a JSON content type,
+// MALFORMED_JSON for a body that does not parse, and a VALIDATION_ERROR naming the offending
+// field when a value has the wrong type. `ApiQuery` replaces `Query` for typed query strings.
+use crate::error::ApiError;
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, OptionalFromRequest, Query, Request},
+    http::{header, request::Parts, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+use serde_json::error::Category;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// Query string deserialized into `T`. Values go through a string map first, so a repeated key
+/// keeps its last value and an empty value is `Some("")`, as with `Query<HashMap<String, String>>`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApiQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let invalid = |error: String| {
+            warn!("Rejected query string: {}", error);
+            ApiError::Validation(format!("Invalid query parameters: {}", error))
+        };
+
+        let Query(params) = Query::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| invalid(rejection.body_text()))?;
+        let params: serde_json::Map<String, serde_json::Value> = params
+            .into_iter()
+            .map(|(key, value)| (key, serde_json::Value::String(value)))
+            .collect();
+
+        serde_json::from_value(serde_json::Value::Object(params))
+            .map(ApiQuery)
+            .map_err(|e| invalid(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonBody<T>(pub T);
+
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json_content_type(req.headers()) {
+            return Err(unsupported_media_type().into_response());
+        }
+
+        // A body over the size limit is turned into a JSON 413 by `payload_too_large_as_json`
+        let bytes = Bytes::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+        parse(&bytes).map(JsonBody).map_err(IntoResponse::into_response)
+    }
+}
+
+/// `Option<JsonBody<T>>` is `None` when the request has no content type at all, for endpoints
+/// whose body is optional
+impl<T, S> OptionalFromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        if !req.headers().contains_key(header::CONTENT_TYPE) {
+            return Ok(None);
+        }
+        <Self as FromRequest<S>>::from_request(req, state).await.map(Some)
+    }
+}
+
+fn unsupported_media_type() -> ApiError {
+    ApiError::UnsupportedMediaType("Expected a request body with Content-Type: application/json".to_string())
+}
+
+/// `application/json`, with or without parameters, or any `application/*+json` type
+pub(crate) fn is_json_content_type(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    essence
+        .strip_prefix("application/")
+        .is_some_and(|subtype| subtype == "json" || subtype.ends_with("+json"))
+}
+
+fn parse<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ApiError> {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(body_error)?;
+    deserializer
+        .end()
+        .map_err(|e| ApiError::MalformedJson(format!("Malformed JSON: {}", e)))?;
+    Ok(value)
+}
+
+fn body_error(error: serde_path_to_error::Error<serde_json::Error>) -> ApiError {
+    let path = error.path().to_string();
+    let inner = error.into_inner();
+
+    if matches!(inner.classify(), Category::Syntax | Category::Eof | Category::Io) {
+        return ApiError::MalformedJson(format!("Malformed JSON: {}", inner));
+    }
+
+    // serde_json appends the position to data errors; the field path is more useful here
+    let full = inner.to_string();
+    let location = format!(" at line {} column {}", inner.line(), inner.column());
+    let message = full.strip_suffix(&location).unwrap_or(&full).to_string();
+
+    // `.` is the document root
+    let mut field = (path != ".").then_some(path);
+
+    // A missing or unknown field is reported against its parent; name the field itself
+    let named = message
+        .strip_prefix("missing field `")
+        .or_else(|| message.strip_prefix("unknown field `"))
+        .and_then(|rest| rest.split('`').next());
+    if let Some(name) = named {
+        field = Some(match field {
+            Some(parent) if parent == name || parent.ends_with(&format!(".{}", name)) => parent,
+            Some(parent) => format!("{}.{}", parent, name),
+            None => name.to_string(),
+        });
+    }
+
+    ApiError::InvalidField { field, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::GoodsQueryParams;
+    use serde::Deserialize;
+
+    async fn query<T: DeserializeOwned>(uri: &str) -> Result<T, ApiError> {
+        let (mut parts, _) = Request::builder().uri(uri).body(()).unwrap().into_parts();
+        ApiQuery::<T>::from_request_parts(&mut parts, &()).await.map(|ApiQuery(params)| params)
+    }
+
+    #[tokio::test]
+    async fn repeated_key_keeps_its_last_value() {
+        let params: GoodsQueryParams = query("/goods?material_code=CH-100&material_code=CH-200").await.unwrap();
+
+        assert_eq!(params.material_code.as_deref(), Some("CH-200"));
+    }
+
+    #[tokio::test]
+    async fn empty_value_is_present_and_missing_key_is_absent() {
+        let params: GoodsQueryParams = query("/goods?goods_name=&page=2").await.unwrap();
+
+        assert_eq!(params.goods_name.as_deref(), Some(""));
+        assert_eq!(params.page.as_deref(), Some("2"));
+        assert_eq!(params.material_code, None);
+    }
+
+    #[tokio::test]
+    async fn values_that_do_not_deserialize_are_validation_errors() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Required {
+            name: String,
+        }
+
+        let error = query::<Required>("/goods?page=2").await.unwrap_err();
+
+        assert!(matches!(&error, ApiError::Validation(message) if message.starts_with("Invalid query parameters")), "{:?}", error);
+    }
+}
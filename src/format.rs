@@ -0,0 +1,137 @@
+// src/format.rs
+//
+// Output formats for the GET search endpoints. `?format=` (for browsers, which cannot set
+// `Accept`) takes precedence over the `Accept` header; JSON is the default.
+use crate::error::ApiError;
+use crate::export::{csv_response, CsvRecord};
+use crate::response::{paginated_response, select_fields_each, success_response, truncated_response};
+use crate::utils::pagination::PaginatedResponse;
+use axum::{
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    response::Response,
+};
+use futures_util::stream;
+use serde::Serialize;
+
+/// Set on a CSV cut off at the row cap, to the cap
+pub static TRUNCATED_LIMIT_HEADER: HeaderName = HeaderName::from_static("x-truncated-limit");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    const ALL: [OutputFormat; 2] = [OutputFormat::Json, OutputFormat::Csv];
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "application/json",
+            OutputFormat::Csv => "text/csv",
+        }
+    }
+
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err("Invalid format. Allowed values: json, csv".to_string()),
+        }
+    }
+
+    /// The format for one media range of an `Accept` header; wildcards fall back to JSON
+    fn from_media_range(range: &str) -> Option<Self> {
+        match range {
+            "*/*" | "application/*" => Some(OutputFormat::Json),
+            "text/*" => Some(OutputFormat::Csv),
+            _ => Self::ALL.into_iter().find(|format| format.media_type() == range),
+        }
+    }
+
+    /// Pick the format from `?format=`, then `Accept` (highest q first), then JSON
+    pub fn negotiate(headers: &HeaderMap, format: Option<&str>) -> Result<Self, ApiError> {
+        if let Some(format) = format {
+            return Self::parse(format).map_err(|error| ApiError::Validation(format!("Invalid query parameters: {}", error)));
+        }
+
+        let Some(accept) = headers.get(header::ACCEPT) else {
+            return Ok(OutputFormat::Json);
+        };
+        let accept = accept.to_str().map_err(|_| Self::not_acceptable())?;
+        if accept.trim().is_empty() {
+            return Ok(OutputFormat::Json);
+        }
+
+        let mut ranges: Vec<(String, f32)> = accept
+            .split(',')
+            .map(|range| {
+                let mut parts = range.split(';');
+                let media_type = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (media_type, quality)
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect();
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges
+            .iter()
+            .find_map(|(media_type, _)| Self::from_media_range(media_type))
+            .ok_or_else(Self::not_acceptable)
+    }
+
+    fn not_acceptable() -> ApiError {
+        let supported: Vec<&str> = Self::ALL.into_iter().map(Self::media_type).collect();
+        ApiError::NotAcceptable(format!("Unsupported Accept header. Supported types: {}", supported.join(", ")))
+    }
+}
+
+/// Render search results; JSON keeps `fields` and the envelope, CSV is a `<name>-<timestamp>.csv` attachment.
+/// `truncated_at` is the row cap when `rows` were cut off at it.
+pub fn render_rows<T>(
+    format: OutputFormat,
+    name: &str,
+    rows: Vec<T>,
+    fields: Option<&[String]>,
+    truncated_at: Option<u32>,
+    message: &str,
+) -> Result<Response, ApiError>
+where
+    T: CsvRecord + Serialize + Send + 'static,
+{
+    match (format, truncated_at) {
+        (OutputFormat::Json, None) => Ok(success_response(select_fields_each(&rows, fields)?, message)),
+        (OutputFormat::Json, Some(limit)) => Ok(truncated_response(select_fields_each(&rows, fields)?, limit, message)),
+        (OutputFormat::Csv, None) => Ok(csv_rows(name, rows)),
+        (OutputFormat::Csv, Some(limit)) => {
+            let mut response = csv_rows(name, rows);
+            response.headers_mut().insert(TRUNCATED_LIMIT_HEADER.clone(), HeaderValue::from(limit));
+            Ok(response)
+        }
+    }
+}
+
+/// `render_rows` for one page; the CSV holds the page's rows only
+pub fn render_page<T>(format: OutputFormat, name: &str, page: PaginatedResponse<T>, fields: Option<&[String]>, message: &str) -> Result<Response, ApiError>
+where
+    T: CsvRecord + Serialize + Send + 'static,
+{
+    match format {
+        OutputFormat::Json => {
+            let data = select_fields_each(&page.data, fields)?;
+            Ok(paginated_response(page.with_data(data), message))
+        }
+        OutputFormat::Csv => Ok(csv_rows(name, page.data)),
+    }
+}
+
+fn csv_rows<T>(name: &str, rows: Vec<T>) -> Response
+where
+    T: CsvRecord + Send + 'static,
+{
+    csv_response(name, stream::iter(rows.into_iter().map(Ok::<_, sqlx::Error>)))
+}
@@ -0,0 +1,213 @@
+// src/idempotency.rs
+//
+// `Idempotency-Key` support for create endpoints. The first request with a key runs
+// normally and its response is stored; repeats with the same body get the stored
+// response back without running the handler, repeats with a different body get 422.
+// Keys belong to the caller, so two clients picking the same key never see each other's
+// responses.
+use crate::auth::{request_api_key, ApiKeyLabel};
+use crate::error::ApiError;
+use crate::server::AppState;
+use crate::tables::{IdempotencyKey, IdempotencyTable};
+use crate::utils::logging::log_database_error;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::{info, warn};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// How often expired keys are purged
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+pub async fn idempotent(State(state): State<AppState>, request: Request, next: Next) -> Result<Response, ApiError> {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH)
+        .ok_or_else(|| {
+            ApiError::Validation(format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_IDEMPOTENCY_KEY_LENGTH
+            ))
+        })?
+        .to_string();
+
+    let key = IdempotencyKey {
+        key,
+        request_path: request.uri().path().to_string(),
+        caller: caller(&request),
+    };
+    let (parts, body) = request.into_parts();
+
+    // The body is already capped by the route's body limit
+    let body = to_bytes(body, usize::MAX).await.map_err(|e| {
+        warn!("Failed to read request body: {}", e);
+        ApiError::PayloadTooLarge("Request body exceeds the size limit for this endpoint".to_string())
+    })?;
+
+    // The query string is part of the request (e.g. `on_conflict`), so it is hashed too
+    let path_and_query = parts.uri.path_and_query().map_or(key.request_path.as_str(), |path_and_query| path_and_query.as_str());
+    let request_hash = hex::encode(Sha256::new().chain_update(parts.method.as_str()).chain_update(path_and_query).chain_update(&body).finalize());
+
+    let table = &state.database.idempotency_table;
+    let ttl = Duration::from_secs(state.config.server.idempotency_ttl_secs);
+    // A claim still in progress after the request timeout belongs to a request that is gone
+    let stale_after = Duration::from_secs(state.config.server.request_timeout_secs);
+
+    let existing = table.claim(&key, &request_hash, ttl, stale_after).await.map_err(|e| {
+        log_database_error("claim idempotency key", &e);
+        ApiError::database(e, "idempotency key lookup")
+    })?;
+
+    if let Some(record) = existing {
+        if record.request_hash != request_hash {
+            warn!("Idempotency-Key {} reused with a different request body", key.key);
+            return Err(ApiError::IdempotencyKeyReused(
+                "Idempotency-Key was already used with a different request body".to_string(),
+            ));
+        }
+
+        let (Some(status_code), Some(response_body)) = (record.status_code, record.response_body) else {
+            warn!("Idempotency-Key {} is still being processed", key.key);
+            return Err(ApiError::conflict("A request with this Idempotency-Key is still being processed"));
+        };
+
+        info!("Replaying stored response for Idempotency-Key {}", key.key);
+        let mut response = Response::new(Body::from(response_body));
+        *response.status_mut() = StatusCode::from_u16(status_code as u16).unwrap_or(StatusCode::OK);
+        response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        response.headers_mut().insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+        return Ok(response);
+    }
+
+    let claim = Claim { table: table.clone(), key: Some(key) };
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    // Server errors are not stored so the client can retry with the same key
+    if response.status().is_server_error() {
+        claim.release().await;
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX).await.map_err(|e| {
+        warn!("Failed to buffer response body: {}", e);
+        ApiError::Internal("Failed to buffer response body".to_string())
+    })?;
+
+    // If the response can't be stored, free the key rather than leave it stuck in progress
+    claim.complete(parts.status.as_u16(), &String::from_utf8_lossy(&body)).await;
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Who sent the request: the API key's label, else a fingerprint of the key itself
+fn caller(request: &Request) -> String {
+    if let Some(ApiKeyLabel(label)) = request.extensions().get::<ApiKeyLabel>() {
+        return format!("label:{}", label);
+    }
+    request_api_key(request)
+        .map(|api_key| format!("key:{}", hex::encode(&Sha256::digest(api_key)[..16])))
+        .unwrap_or_default()
+}
+
+/// A claimed key. Dropping it before the response is stored, as a panic or the route
+/// timeout does to the request future, releases the key so a retry is not refused as
+/// still in progress until the claim goes stale.
+struct Claim {
+    table: IdempotencyTable,
+    key: Option<IdempotencyKey>,
+}
+
+impl Claim {
+    async fn complete(mut self, status_code: u16, response_body: &str) {
+        let Some(key) = self.key.take() else { return };
+        if let Err(e) = self.table.complete(&key, status_code, response_body).await {
+            log_database_error("store idempotent response", &e);
+            release(&self.table, &key).await;
+        }
+    }
+
+    async fn release(mut self) {
+        if let Some(key) = self.key.take() {
+            release(&self.table, &key).await;
+        }
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let table = self.table.clone();
+            tokio::spawn(async move { release(&table, &key).await });
+        }
+    }
+}
+
+async fn release(table: &IdempotencyTable, key: &IdempotencyKey) {
+    if let Err(e) = table.release(key).await {
+        log_database_error("release idempotency key", &e);
+    }
+}
+
+/// Periodically delete expired idempotency keys
+pub fn spawn_cleanup(table: IdempotencyTable) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match table.delete_expired().await {
+                Ok(0) => {}
+                Ok(deleted) => info!("Deleted {} expired idempotency keys", deleted),
+                Err(e) => log_database_error("delete expired idempotency keys", &e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::API_KEY_HEADER;
+
+    fn request(api_key: Option<&str>, label: Option<&str>) -> Request {
+        let mut request = Request::builder().uri("/goods");
+        if let Some(api_key) = api_key {
+            request = request.header(API_KEY_HEADER, api_key);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        if let Some(label) = label {
+            request.extensions_mut().insert(ApiKeyLabel(label.to_string()));
+        }
+        request
+    }
+
+    #[test]
+    fn caller_is_the_key_label_else_a_fingerprint_of_the_key() {
+        assert_eq!(caller(&request(Some("secret-a"), Some("shop"))), "label:shop");
+
+        let (first, second) = (caller(&request(Some("secret-a"), None)), caller(&request(Some("secret-b"), None)));
+        assert!(first.starts_with("key:") && !first.contains("secret"), "{}", first);
+        assert_ne!(first, second);
+        assert_eq!(first, caller(&request(Some("secret-a"), None)));
+    }
+
+    #[test]
+    fn caller_is_empty_without_authentication() {
+        assert_eq!(caller(&request(None, None)), "");
+    }
+}
@@ -0,0 +1,28 @@
+// src/lib.rs
+//
+// The API as a library: the binary in main.rs is a thin wrapper around `Server`, and tests or
+// other tools can build an `AppState` and mount `Server::create_router` in their own axum app.
+mod alerts;
+mod audit;
+mod auth;
+pub mod build_info;
+pub mod config;
+pub mod database;
+mod error;
+mod expired_purge;
+mod export;
+mod format;
+mod idempotency;
+mod maintenance;
+mod extract;
+pub mod request;
+mod request_id;
+pub mod response;
+pub mod server;
+mod stock_snapshots;
+pub mod tables;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod tls;
+pub mod utils;
+mod webhooks;
@@ -1,34 +1,60 @@
 // src/main.rs
-mod config;
-mod database;
-mod request;
-mod response;
-mod server;
-mod tables;
-mod utils;
-
 use anyhow::Result;
-use config::AppConfig;
-use database::Database;
-use server::Server;
-use tracing::{info, Level};
-use tracing_subscriber;
+use clap::Parser;
+use onechilli_dev_api::config::{AppConfig, Cli};
+use onechilli_dev_api::database::Database;
+use onechilli_dev_api::server::Server;
+use tracing::info;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
+    let cli = Cli::parse();
+
+    // Read .env before logging starts, so that it can set LOG_LEVEL
+    let env_file_loaded = dotenvy::dotenv().is_ok();
+
+    // Initialize tracing; --print-config keeps stdout for the YAML
+    let writer = if cli.print_config { BoxMakeWriter::new(std::io::stderr) } else { BoxMakeWriter::new(std::io::stdout) };
     tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
+        .with_max_level(cli.log_level()?)
+        .with_writer(writer)
         .init();
 
     info!("Starting OneChill Dev API server...");
+    if env_file_loaded {
+        info!("Loaded environment variables from .env file");
+    } else {
+        info!("No .env file found, using environment variables");
+    }
 
     // Load configuration
-    let config = AppConfig::load()?;
+    let config = AppConfig::load(&cli)?;
     info!("Configuration loaded successfully");
 
+    if cli.print_config {
+        print!("{}", config.to_redacted_yaml()?);
+        return Ok(());
+    }
+    config.log_effective();
+
+    // Apply migrations and exit, for init containers
+    if cli.migrate_only {
+        let pool = Database::connect(&config.database).await?;
+        Database::run_migrations(&pool).await?;
+        info!("Migrations complete, exiting (--migrate-only)");
+        return Ok(());
+    }
+
+    // Validate configuration and database connectivity, for CI smoke tests
+    if cli.check {
+        Database::connect(&config.database).await?.close().await;
+        info!("Configuration and database connectivity OK, exiting (--check)");
+        return Ok(());
+    }
+
     // Initialize database
-    let database = Database::new(config.database.clone()).await?;
+    let database = Database::new(config.database.clone(), config.webhooks.is_enabled(), config.inventory.allow_negative_stock).await?;
     info!("Database connection established");
 
     // Create and run server
@@ -0,0 +1,40 @@
+// src/maintenance.rs
+//
+// Maintenance mode: while on, writes are rejected with 503 and `Retry-After` and reads keep
+// working, e.g. during database migrations. It starts from `server.read_only` and is switched
+// at runtime through `POST /admin/maintenance`; the runtime setting is not persisted.
+use crate::error::ApiError;
+use crate::server::AppState;
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use tracing::warn;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MaintenanceMode {
+    /// Reject POST/PUT/PATCH/DELETE with 503 while true
+    pub read_only: bool,
+}
+
+/// Stays writable so maintenance mode can be switched off again
+pub const MAINTENANCE_PATH: &str = "/admin/maintenance";
+
+pub async fn reject_writes_in_maintenance(State(state): State<AppState>, request: Request, next: Next) -> Result<Response, ApiError> {
+    let is_write = !matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+
+    if is_write && state.read_only.load(Ordering::Relaxed) && request.uri().path() != MAINTENANCE_PATH {
+        warn!("Rejected {} {} in maintenance mode", request.method(), request.uri().path());
+        return Err(ApiError::Maintenance {
+            message: "The API is in read-only maintenance mode; retry later".to_string(),
+            retry_after_secs: state.config.server.maintenance_retry_after_secs,
+        });
+    }
+
+    Ok(next.run(request).await)
+}
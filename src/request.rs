@@ -1,18 +1,39 @@
 // src/request.rs
 use crate::tables::{
-    GoodsSearchParams, CreateGoodRequest, UpdateGoodRequest,
-    InventorySearchParams, CreateInventoryRequest, UpdateInventoryRequest
+    GoodsConflictMode, InventoryDuplicateMode, GoodsSearchParams, GoodsSortColumn, CreateGoodRequest, UpdateGoodRequest,
+    InventorySearchParams, InventorySortColumn, InventorySummaryParams, SummarySortColumn, CreateInventoryRequest, UpdateInventoryRequest,
+    AdjustInventoryRequest, QuantityChangeRequest, ConsumeInventoryRequest, CreateReservationRequest, BatchItemError, MovementSearchParams,
+    SyncCursor, SyncParams, CreateCategoryRequest, UpdateCategoryRequest, CreatePurchaseOrderRequest,
+    ReceivePurchaseOrderRequest, PurchaseOrderStatus, BulkWriteOptions, StockHistoryParams, AlertSearchParams, ValuationGroupBy,
+    ValuationParams, AuditSearchParams,
 };
+use crate::audit::AUDITED_TABLES;
+use crate::config::ValidationConfig;
+use crate::utils::pagination::{PageCursor, PaginationParams};
+use crate::utils::sorting::SortOrder;
+use crate::utils::string_utils::{closest_match, MatchMode, Normalization};
 use crate::utils::validation::*;
-use axum::extract::Query;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
 pub struct GoodsQueryParams {
     pub goods_id: Option<String>,
+    /// Comma-separated goods ids, e.g. `12,45,78`
+    pub goods_ids: Option<String>,
     pub material_code: Option<String>,
     pub goods_name: Option<String>,
+    pub description_contains: Option<String>,
+    /// Exact barcode
+    pub barcode: Option<String>,
+    /// Single tag the goods must carry
+    pub tag: Option<String>,
+    /// Comma-separated tags; matches goods carrying any of them
+    pub tags_any: Option<String>,
+    /// Comma-separated tags; matches goods carrying all of them
+    pub tags_all: Option<String>,
     pub price: Option<String>,
     pub volumn_l: Option<String>,
     pub mass_g: Option<String>,
@@ -22,23 +43,76 @@ pub struct GoodsQueryParams {
     pub max_mass_g: Option<String>,
     pub min_price: Option<String>,
     pub max_price: Option<String>,
+    pub min_updated_at: Option<String>,
+    pub max_updated_at: Option<String>,
+    pub category_id: Option<String>,
+    /// Case-insensitive exact category name
+    pub category_name: Option<String>,
+    /// Also match goods in descendant categories of category_id / category_name
+    pub include_subcategories: Option<String>,
+    /// `true`: goods with at least one inventory row holding stock; `false`: goods with none
+    pub in_stock: Option<String>,
+    /// `true` to also match archived goods, which are left out by default
+    pub include_inactive: Option<String>,
+    /// `exact`, `prefix` or `contains` (default) for material_code and goods_name
+    pub match_mode: Option<String>,
+    /// Match material_code and goods_name byte for byte, case included; replaces match_mode
+    pub literal: Option<String>,
+
+    // Pagination params
+    pub page: Option<String>,
+    pub per_page: Option<String>,
+    /// `next_cursor` from the previous page; continues after it instead of at `page`
+    pub cursor: Option<String>,
+
+    // Sorting params
+    pub sort_by: Option<String>,
+    pub sort_order: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
 pub struct InventoryQueryParams {
     // Inventory specific params
     pub item_id: Option<String>,
+    /// Comma-separated item ids, e.g. `3,8,21`
+    pub item_ids: Option<String>,
     pub quantity: Option<String>,
     pub min_quantity: Option<String>,
+    /// `-1` finds items below zero, when negative stock is allowed
     pub max_quantity: Option<String>,
     pub expired_date: Option<String>,
     pub min_expired_date: Option<String>,
     pub max_expired_date: Option<String>,
+    pub expiring_within_days: Option<String>,
+    pub expired: Option<String>,
+    pub has_expired_date: Option<String>,
+    /// Exact supplier lot number, e.g. `L2024-091` for a recall lookup
+    pub lot_number: Option<String>,
+    /// Case-insensitive substring of the lot number
+    pub lot_number_contains: Option<String>,
+    /// `true`: quantity > 0; `false`: quantity = 0
+    pub in_stock: Option<String>,
+    pub include_reserved: Option<String>,
+    pub min_updated_at: Option<String>,
+    pub max_updated_at: Option<String>,
     
     // Goods params (inherited)
     pub goods_id: Option<String>,
+    /// Comma-separated goods ids, e.g. `12,45,78`
+    pub goods_ids: Option<String>,
     pub material_code: Option<String>,
     pub goods_name: Option<String>,
+    pub description_contains: Option<String>,
+    /// Exact barcode
+    pub barcode: Option<String>,
+    /// Single tag the goods must carry
+    pub tag: Option<String>,
+    /// Comma-separated tags; matches goods carrying any of them
+    pub tags_any: Option<String>,
+    /// Comma-separated tags; matches goods carrying all of them
+    pub tags_all: Option<String>,
     pub price: Option<String>,
     pub volumn_l: Option<String>,
     pub mass_g: Option<String>,
@@ -48,16 +122,62 @@ pub struct InventoryQueryParams {
     pub max_mass_g: Option<String>,
     pub min_price: Option<String>,
     pub max_price: Option<String>,
+    pub category_id: Option<String>,
+    /// Case-insensitive exact category name
+    pub category_name: Option<String>,
+    /// Also match goods in descendant categories of category_id / category_name
+    pub include_subcategories: Option<String>,
+    /// `true` to also match items of archived goods, which are left out by default
+    pub include_inactive: Option<String>,
+    /// `exact`, `prefix` or `contains` (default) for material_code and goods_name
+    pub match_mode: Option<String>,
+    /// Match material_code and goods_name byte for byte, case included; replaces match_mode
+    pub literal: Option<String>,
+
+    // Pagination params
+    pub page: Option<String>,
+    pub per_page: Option<String>,
+    /// `next_cursor` from the previous page; continues after it instead of at `page`
+    pub cursor: Option<String>,
+
+    // Sorting params
+    pub sort_by: Option<String>,
+    pub sort_order: Option<String>,
 }
 
 impl GoodsQueryParams {
-    pub fn validate_and_parse(self) -> Result<GoodsSearchParams, String> {
+    /// Apply the write-side normalization to the string filters, so stored values keep matching
+    pub fn normalize(&mut self, normalization: Normalization) {
+        normalize_optional(&mut self.material_code, |code| normalization.material_code(code));
+        normalize_optional(&mut self.goods_name, |name| normalization.goods_name(name));
+        normalize_optional(&mut self.description_contains, |text| normalization.text(text));
+        normalize_optional(&mut self.barcode, |barcode| normalization.text(barcode));
+    }
+
+    pub fn validate_and_parse(mut self) -> Result<GoodsSearchParams, String> {
+        // The wildcard skips every other filter, so one next to it would silently be ignored
+        let goods_name_wildcard = self.goods_name.take_if(|name| name == "*");
+        let material_code_wildcard = self.material_code.take_if(|code| code == "*");
+        if (goods_name_wildcard.is_some() || material_code_wildcard.is_some()) && self.has_any_params() {
+            return Err(WILDCARD_WITH_FILTERS.to_string());
+        }
+        self.goods_name = self.goods_name.or(goods_name_wildcard);
+        self.material_code = self.material_code.or(material_code_wildcard);
+
         let mut search_params = GoodsSearchParams::new();
 
+        if self.goods_id.is_some() && self.goods_ids.is_some() {
+            return Err("goods_id and goods_ids cannot be combined".to_string());
+        }
+
         if let Some(goods_id_str) = self.goods_id {
             search_params.goods_id = Some(parse_safe_integer(&goods_id_str, "goods_id")?);
         }
 
+        if let Some(goods_ids_str) = self.goods_ids {
+            search_params.goods_ids = Some(parse_id_list(&goods_ids_str, "goods_ids")?);
+        }
+
         if let Some(material_code) = self.material_code {
             validate_safe_string(&material_code, "material_code")?;
             search_params.material_code = Some(material_code);
@@ -68,6 +188,29 @@ impl GoodsQueryParams {
             search_params.goods_name = Some(goods_name);
         }
 
+        if let Some(description_contains) = self.description_contains {
+            validate_safe_string(&description_contains, "description_contains")?;
+            search_params.description_contains = Some(description_contains);
+        }
+
+        if let Some(barcode) = self.barcode {
+            validate_barcode(&barcode, "barcode")?;
+            search_params.barcode = Some(barcode);
+        }
+
+        if let Some(tag) = self.tag {
+            validate_safe_string(tag.trim(), "tag")?;
+            search_params.tag = Some(tag.trim().to_lowercase());
+        }
+
+        if let Some(tags_any_str) = self.tags_any {
+            search_params.tags_any = Some(parse_tag_list(&tags_any_str, "tags_any")?);
+        }
+
+        if let Some(tags_all_str) = self.tags_all {
+            search_params.tags_all = Some(parse_tag_list(&tags_all_str, "tags_all")?);
+        }
+
         if let Some(price_str) = self.price {
             search_params.price = Some(parse_safe_decimal(&price_str, "price")?);
         }
@@ -104,13 +247,84 @@ impl GoodsQueryParams {
             search_params.max_price = Some(parse_safe_decimal(&max_price_str, "max_price")?);
         }
 
+        if let Some(min_updated_at_str) = self.min_updated_at {
+            search_params.min_updated_at = Some(parse_safe_datetime(&min_updated_at_str, "min_updated_at")?);
+        }
+
+        if let Some(max_updated_at_str) = self.max_updated_at {
+            search_params.max_updated_at = Some(parse_safe_datetime(&max_updated_at_str, "max_updated_at")?);
+        }
+
+        if let Some(category_id_str) = self.category_id {
+            search_params.category_id = Some(parse_safe_integer(&category_id_str, "category_id")?);
+        }
+
+        if let Some(category_name) = self.category_name {
+            validate_safe_string(&category_name, "category_name")?;
+            search_params.category_name = Some(category_name);
+        }
+
+        if let Some(include_subcategories_str) = self.include_subcategories {
+            search_params.include_subcategories = parse_safe_bool(&include_subcategories_str, "include_subcategories")?;
+        }
+
+        if let Some(in_stock_str) = self.in_stock {
+            search_params.in_stock = Some(parse_safe_bool(&in_stock_str, "in_stock")?);
+        }
+
+        if let Some(include_inactive_str) = self.include_inactive {
+            search_params.include_inactive = parse_safe_bool(&include_inactive_str, "include_inactive")?;
+        }
+
+        let literal = match self.literal {
+            Some(literal_str) => parse_safe_bool(&literal_str, "literal")?,
+            None => false,
+        };
+
+        if let Some(match_mode_str) = self.match_mode {
+            if literal {
+                return Err("literal=true cannot be combined with match_mode".to_string());
+            }
+            search_params.match_mode = MatchMode::parse(&match_mode_str)?;
+        }
+
+        if literal {
+            search_params.match_mode = MatchMode::Literal;
+        }
+
+        search_params.pagination = parse_pagination(self.page, self.per_page)?;
+
+        if let Some(sort_by_str) = self.sort_by {
+            search_params.sort_by = Some(GoodsSortColumn::parse(&sort_by_str)?);
+        }
+
+        if let Some(sort_order_str) = self.sort_order {
+            search_params.sort_order = Some(SortOrder::parse(&sort_order_str)?);
+        }
+
+        if let Some(cursor_str) = self.cursor {
+            let cursor = parse_cursor(&cursor_str, &mut search_params.pagination)?;
+            search_params.cursor = Some(search_params.keyset_from(&cursor)?);
+        }
+
+        validate_range(search_params.min_volumn_l.as_ref(), search_params.max_volumn_l.as_ref(), "min_volumn_l", "max_volumn_l")?;
+        validate_range(search_params.min_mass_g.as_ref(), search_params.max_mass_g.as_ref(), "min_mass_g", "max_mass_g")?;
+        validate_range(search_params.min_price.as_ref(), search_params.max_price.as_ref(), "min_price", "max_price")?;
+        validate_range(search_params.min_updated_at.as_ref(), search_params.max_updated_at.as_ref(), "min_updated_at", "max_updated_at")?;
+
         Ok(search_params)
     }
 
     pub fn has_any_params(&self) -> bool {
         self.goods_id.is_some()
+            || self.goods_ids.is_some()
             || self.material_code.is_some()
             || self.goods_name.is_some()
+            || self.description_contains.is_some()
+            || self.barcode.is_some()
+            || self.tag.is_some()
+            || self.tags_any.is_some()
+            || self.tags_all.is_some()
             || self.price.is_some()
             || self.volumn_l.is_some()
             || self.mass_g.is_some()
@@ -120,18 +334,50 @@ impl GoodsQueryParams {
             || self.max_mass_g.is_some()
             || self.min_price.is_some()
             || self.max_price.is_some()
+            || self.min_updated_at.is_some()
+            || self.max_updated_at.is_some()
+            || self.category_id.is_some()
+            || self.category_name.is_some()
+            || self.in_stock.is_some()
     }
 }
 
 impl InventoryQueryParams {
-    pub fn validate_and_parse(self) -> Result<InventorySearchParams, String> {
+    /// Apply the write-side normalization to the goods string filters
+    pub fn normalize(&mut self, normalization: Normalization) {
+        normalize_optional(&mut self.material_code, |code| normalization.material_code(code));
+        normalize_optional(&mut self.goods_name, |name| normalization.goods_name(name));
+        normalize_optional(&mut self.description_contains, |text| normalization.text(text));
+        normalize_optional(&mut self.barcode, |barcode| normalization.text(barcode));
+        normalize_optional(&mut self.lot_number, |lot_number| normalization.text(lot_number));
+        normalize_optional(&mut self.lot_number_contains, |term| normalization.text(term));
+    }
+
+    pub fn validate_and_parse(mut self) -> Result<InventorySearchParams, String> {
+        // The wildcard skips every other filter, so one next to it would silently be ignored
+        let goods_name_wildcard = self.goods_name.take_if(|name| name == "*");
+        let material_code_wildcard = self.material_code.take_if(|code| code == "*");
+        if (goods_name_wildcard.is_some() || material_code_wildcard.is_some()) && self.has_any_params() {
+            return Err(WILDCARD_WITH_FILTERS.to_string());
+        }
+        self.goods_name = self.goods_name.or(goods_name_wildcard);
+        self.material_code = self.material_code.or(material_code_wildcard);
+
         let mut search_params = InventorySearchParams::new();
 
         // Parse inventory-specific params
+        if self.item_id.is_some() && self.item_ids.is_some() {
+            return Err("item_id and item_ids cannot be combined".to_string());
+        }
+
         if let Some(item_id_str) = self.item_id {
             search_params.item_id = Some(parse_safe_integer(&item_id_str, "item_id")?);
         }
 
+        if let Some(item_ids_str) = self.item_ids {
+            search_params.item_ids = Some(parse_id_list(&item_ids_str, "item_ids")?);
+        }
+
         if let Some(quantity_str) = self.quantity {
             search_params.quantity = Some(parse_safe_integer(&quantity_str, "quantity")?);
         }
@@ -156,11 +402,67 @@ impl InventoryQueryParams {
             search_params.max_expired_date = Some(parse_safe_datetime(&max_expired_date_str, "max_expired_date")?);
         }
 
+        if let Some(expiring_within_days_str) = self.expiring_within_days {
+            let days = parse_safe_integer(&expiring_within_days_str, "expiring_within_days")?;
+            if !(0..=36500).contains(&days) {
+                return Err("expiring_within_days must be between 0 and 36500".to_string());
+            }
+            search_params.expiring_within_days = Some(days);
+        }
+
+        if let Some(expired_str) = self.expired {
+            search_params.expired = Some(parse_safe_bool(&expired_str, "expired")?);
+        }
+
+        if let Some(has_expired_date_str) = self.has_expired_date {
+            let has_expired_date = parse_safe_bool(&has_expired_date_str, "has_expired_date")?;
+            if !has_expired_date
+                && (search_params.expired_date.is_some()
+                    || search_params.min_expired_date.is_some()
+                    || search_params.max_expired_date.is_some())
+            {
+                return Err("has_expired_date=false cannot be combined with expired_date, min_expired_date or max_expired_date".to_string());
+            }
+            search_params.has_expired_date = Some(has_expired_date);
+        }
+
+        if let Some(lot_number) = self.lot_number {
+            validate_lot_number(&lot_number, "lot_number")?;
+            search_params.lot_number = Some(lot_number);
+        }
+
+        if let Some(lot_number_contains) = self.lot_number_contains {
+            validate_safe_string(&lot_number_contains, "lot_number_contains")?;
+            search_params.lot_number_contains = Some(lot_number_contains);
+        }
+
+        if let Some(in_stock_str) = self.in_stock {
+            search_params.in_stock = Some(parse_safe_bool(&in_stock_str, "in_stock")?);
+        }
+
+        if let Some(include_reserved_str) = self.include_reserved {
+            search_params.include_reserved = parse_safe_bool(&include_reserved_str, "include_reserved")?;
+        }
+
+        if let Some(min_updated_at_str) = self.min_updated_at {
+            search_params.min_updated_at = Some(parse_safe_datetime(&min_updated_at_str, "min_updated_at")?);
+        }
+
+        if let Some(max_updated_at_str) = self.max_updated_at {
+            search_params.max_updated_at = Some(parse_safe_datetime(&max_updated_at_str, "max_updated_at")?);
+        }
+
         // Parse goods params using existing validation
         let goods_query_params = GoodsQueryParams {
             goods_id: self.goods_id,
+            goods_ids: self.goods_ids,
             material_code: self.material_code,
             goods_name: self.goods_name,
+            description_contains: self.description_contains,
+            barcode: self.barcode,
+            tag: self.tag,
+            tags_any: self.tags_any,
+            tags_all: self.tags_all,
             price: self.price,
             volumn_l: self.volumn_l,
             mass_g: self.mass_g,
@@ -170,24 +472,106 @@ impl InventoryQueryParams {
             max_mass_g: self.max_mass_g,
             min_price: self.min_price,
             max_price: self.max_price,
+            min_updated_at: None,
+            max_updated_at: None,
+            category_id: self.category_id,
+            category_name: self.category_name,
+            include_subcategories: self.include_subcategories,
+            in_stock: None,
+            include_inactive: self.include_inactive,
+            match_mode: self.match_mode,
+            literal: self.literal,
+            page: None,
+            per_page: None,
+            cursor: None,
+            sort_by: None,
+            sort_order: None,
         };
 
         search_params.goods_params = goods_query_params.validate_and_parse()?;
+        search_params.pagination = parse_pagination(self.page, self.per_page)?;
+
+        if let Some(sort_by_str) = self.sort_by {
+            search_params.sort_by = Some(InventorySortColumn::parse(&sort_by_str)?);
+        }
+
+        if let Some(sort_order_str) = self.sort_order {
+            search_params.sort_order = Some(SortOrder::parse(&sort_order_str)?);
+        }
+
+        if let Some(cursor_str) = self.cursor {
+            let cursor = parse_cursor(&cursor_str, &mut search_params.pagination)?;
+            search_params.cursor = Some(search_params.keyset_from(&cursor)?);
+        }
+
+        validate_range(search_params.min_quantity.as_ref(), search_params.max_quantity.as_ref(), "min_quantity", "max_quantity")?;
+        validate_range(search_params.min_expired_date.as_ref(), search_params.max_expired_date.as_ref(), "min_expired_date", "max_expired_date")?;
+        validate_range(search_params.min_updated_at.as_ref(), search_params.max_updated_at.as_ref(), "min_updated_at", "max_updated_at")?;
 
         Ok(search_params)
     }
 
+    /// Parse the filters for the per-goods summary, which has its own sort columns
+    pub fn validate_and_parse_summary(mut self) -> Result<InventorySummaryParams, String> {
+        let sort_by = self.sort_by.take();
+        let sort_order = self.sort_order.take();
+
+        let mut summary_params = InventorySummaryParams {
+            search: self.validate_and_parse()?,
+            sort_by: None,
+            sort_order: None,
+        };
+
+        if let Some(sort_by_str) = sort_by {
+            summary_params.sort_by = Some(SummarySortColumn::parse(&sort_by_str)?);
+        }
+
+        if let Some(sort_order_str) = sort_order {
+            summary_params.sort_order = Some(SortOrder::parse(&sort_order_str)?);
+        }
+
+        Ok(summary_params)
+    }
+
+    /// Parse the filters for the valuation report, which returns every group unsorted by the client
+    pub fn validate_and_parse_valuation(self, group_by: ValuationGroupBy, exclude_expired: bool) -> Result<ValuationParams, String> {
+        if self.page.is_some() || self.per_page.is_some() || self.cursor.is_some() || self.sort_by.is_some() || self.sort_order.is_some() {
+            return Err("The valuation report is not paginated or sortable; page, per_page, cursor, sort_by and sort_order are not accepted".to_string());
+        }
+
+        Ok(ValuationParams {
+            search: self.validate_and_parse()?,
+            group_by,
+            exclude_expired,
+        })
+    }
+
     pub fn has_any_params(&self) -> bool {
         self.item_id.is_some()
+            || self.item_ids.is_some()
             || self.quantity.is_some()
             || self.min_quantity.is_some()
             || self.max_quantity.is_some()
             || self.expired_date.is_some()
             || self.min_expired_date.is_some()
             || self.max_expired_date.is_some()
+            || self.expiring_within_days.is_some()
+            || self.expired.is_some()
+            || self.has_expired_date.is_some()
+            || self.lot_number.is_some()
+            || self.lot_number_contains.is_some()
+            || self.in_stock.is_some()
+            || self.min_updated_at.is_some()
+            || self.max_updated_at.is_some()
             || self.goods_id.is_some()
+            || self.goods_ids.is_some()
             || self.material_code.is_some()
             || self.goods_name.is_some()
+            || self.description_contains.is_some()
+            || self.barcode.is_some()
+            || self.tag.is_some()
+            || self.tags_any.is_some()
+            || self.tags_all.is_some()
             || self.price.is_some()
             || self.volumn_l.is_some()
             || self.mass_g.is_some()
@@ -197,12 +581,252 @@ impl InventoryQueryParams {
             || self.max_mass_g.is_some()
             || self.min_price.is_some()
             || self.max_price.is_some()
+            || self.category_id.is_some()
+            || self.category_name.is_some()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct MovementQueryParams {
+    pub item_id: Option<String>,
+    pub min_created_at: Option<String>,
+    pub max_created_at: Option<String>,
+    pub page: Option<String>,
+    pub per_page: Option<String>,
+}
+
+impl MovementQueryParams {
+    pub fn validate_and_parse(self) -> Result<MovementSearchParams, String> {
+        let mut search_params = MovementSearchParams::default();
+
+        if let Some(item_id_str) = self.item_id {
+            search_params.item_id = Some(parse_safe_integer(&item_id_str, "item_id")?);
+        }
+
+        if let Some(min_created_at_str) = self.min_created_at {
+            search_params.min_created_at = Some(parse_safe_datetime(&min_created_at_str, "min_created_at")?);
+        }
+
+        if let Some(max_created_at_str) = self.max_created_at {
+            search_params.max_created_at = Some(parse_safe_datetime(&max_created_at_str, "max_created_at")?);
+        }
+
+        search_params.pagination = parse_pagination(self.page, self.per_page)?;
+
+        Ok(search_params)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct StockHistoryQueryParams {
+    pub goods_id: Option<String>,
+    /// First snapshot date, `YYYY-MM-DD`
+    pub from: Option<String>,
+    /// Last snapshot date, `YYYY-MM-DD`
+    pub to: Option<String>,
+    pub page: Option<String>,
+    pub per_page: Option<String>,
+}
+
+impl StockHistoryQueryParams {
+    pub fn validate_and_parse(self) -> Result<StockHistoryParams, String> {
+        let mut search_params = StockHistoryParams::default();
+
+        if let Some(goods_id_str) = self.goods_id {
+            search_params.goods_id = Some(parse_safe_integer(&goods_id_str, "goods_id")?);
+        }
+
+        if let Some(from_str) = self.from {
+            search_params.from = Some(parse_safe_date(&from_str, "from")?);
+        }
+
+        if let Some(to_str) = self.to {
+            search_params.to = Some(parse_safe_date(&to_str, "to")?);
+        }
+
+        validate_range(search_params.from.as_ref(), search_params.to.as_ref(), "from", "to")?;
+
+        search_params.pagination = parse_pagination(self.page, self.per_page)?;
+
+        Ok(search_params)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct AlertQueryParams {
+    /// `false` for open alerts only, `true` for acknowledged ones only
+    pub acknowledged: Option<String>,
+    pub page: Option<String>,
+    pub per_page: Option<String>,
+}
+
+impl AlertQueryParams {
+    pub fn validate_and_parse(self) -> Result<AlertSearchParams, String> {
+        let mut search_params = AlertSearchParams::default();
+
+        if let Some(acknowledged_str) = self.acknowledged {
+            search_params.acknowledged = Some(parse_safe_bool(&acknowledged_str, "acknowledged")?);
+        }
+
+        search_params.pagination = parse_pagination(self.page, self.per_page)?;
+
+        Ok(search_params)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct AuditQueryParams {
+    /// Table written to, e.g. `goods` or `purchase_orders`
+    pub table: Option<String>,
+    /// RFC3339 timestamp; entries recorded at or after it
+    pub from: Option<String>,
+    /// RFC3339 timestamp; entries recorded at or before it
+    pub to: Option<String>,
+    pub page: Option<String>,
+    pub per_page: Option<String>,
+}
+
+impl AuditQueryParams {
+    pub fn validate_and_parse(self) -> Result<AuditSearchParams, String> {
+        let mut search_params = AuditSearchParams::default();
+
+        if let Some(table) = self.table {
+            let table = table.trim().to_lowercase();
+            if !AUDITED_TABLES.contains(&table.as_str()) {
+                return Err(format!("Invalid table '{}'. Allowed values: {}", table, AUDITED_TABLES.join(", ")));
+            }
+            search_params.target_table = Some(table);
+        }
+
+        if let Some(from_str) = self.from {
+            search_params.from = Some(parse_safe_datetime(&from_str, "from")?);
+        }
+
+        if let Some(to_str) = self.to {
+            search_params.to = Some(parse_safe_datetime(&to_str, "to")?);
+        }
+
+        validate_range(search_params.from.as_ref(), search_params.to.as_ref(), "from", "to")?;
+
+        search_params.pagination = parse_pagination(self.page, self.per_page)?;
+
+        Ok(search_params)
+    }
+}
+
+/// `*` matches every row, see `GoodsSearchParams::is_get_all`
+const WILDCARD_WITH_FILTERS: &str = "goods_name=* and material_code=* match everything and cannot be combined with other filters";
+
+/// Default and maximum number of changes returned by one sync request
+const DEFAULT_SYNC_LIMIT: i64 = 100;
+const MAX_SYNC_LIMIT: i64 = 1000;
+
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct SyncQueryParams {
+    /// RFC3339 timestamp; returns changes made after it
+    pub since: Option<String>,
+    /// `next_cursor` from the previous response
+    pub cursor: Option<String>,
+    pub limit: Option<String>,
+}
+
+impl SyncQueryParams {
+    /// With neither `since` nor `cursor`, the feed starts from the beginning
+    pub fn validate_and_parse(self) -> Result<SyncParams, String> {
+        let cursor = match (self.since, self.cursor) {
+            (Some(_), Some(_)) => return Err("Use either since or cursor, not both".to_string()),
+            (Some(since_str), None) => SyncCursor::since(parse_safe_datetime(&since_str, "since")?),
+            (None, Some(cursor_str)) => SyncCursor::decode(&cursor_str)?,
+            (None, None) => SyncCursor::since(chrono::DateTime::UNIX_EPOCH),
+        };
+
+        let limit = match self.limit {
+            Some(limit_str) => {
+                let limit = parse_safe_integer(&limit_str, "limit")? as i64;
+                if !(1..=MAX_SYNC_LIMIT).contains(&limit) {
+                    return Err(format!("limit must be between 1 and {}", MAX_SYNC_LIMIT));
+                }
+                limit
+            }
+            None => DEFAULT_SYNC_LIMIT,
+        };
+
+        Ok(SyncParams { cursor, limit })
+    }
+}
+
+/// Parse optional page/per_page strings into pagination params
+fn parse_pagination(page: Option<String>, per_page: Option<String>) -> Result<Option<PaginationParams>, String> {
+    if page.is_none() && per_page.is_none() {
+        return Ok(None);
+    }
+
+    let mut pagination = PaginationParams::new();
+
+    if let Some(page_str) = page {
+        let page = parse_safe_integer(&page_str, "page")?;
+        if page < 1 {
+            return Err("page must be at least 1".to_string());
+        }
+        pagination.page = Some(page as u32);
+    }
+
+    if let Some(per_page_str) = per_page {
+        let per_page = parse_safe_integer(&per_page_str, "per_page")?;
+        if per_page < 1 {
+            return Err("per_page must be at least 1".to_string());
+        }
+        pagination.per_page = Some(per_page as u32);
+    }
+
+    Ok(Some(pagination))
+}
+
+/// Decode a `cursor`, which takes the place of `page`; a cursor alone pages at the default size
+fn parse_cursor(cursor: &str, pagination: &mut Option<PaginationParams>) -> Result<PageCursor, String> {
+    if pagination.as_ref().is_some_and(|pagination| pagination.page.is_some()) {
+        return Err("Use either page or cursor, not both".to_string());
+    }
+
+    pagination.get_or_insert_with(PaginationParams::new);
+    PageCursor::decode(cursor)
+}
+
+fn normalize_optional(value: &mut Option<String>, normalize: impl Fn(&str) -> String) {
+    if let Some(value) = value {
+        *value = normalize(value);
+    }
+}
+
+fn normalize_description(description: &mut Option<Vec<String>>, normalization: Normalization) {
+    for line in description.iter_mut().flatten() {
+        *line = normalization.text(line);
     }
 }
 
 impl CreateGoodRequest {
-    pub fn validate(&self) -> Result<(), String> {
+    /// Trim string fields, collapse spaces in goods_name and canonicalize material_code; run
+    /// before `validate` so near-duplicates such as `" ch-100 "` and `"CH-100"` are one good
+    pub fn normalize(&mut self, normalization: Normalization) {
+        self.material_code = normalization.material_code(&self.material_code);
+        self.goods_name = normalization.goods_name(&self.goods_name);
+        normalize_description(&mut self.description, normalization);
+        normalize_optional(&mut self.barcode, |barcode| normalization.text(barcode));
+    }
+
+    pub fn validate(&self, rules: &ValidationConfig) -> Result<(), String> {
         validate_safe_string(&self.material_code, "material_code")?;
+        rules.check_material_code(&self.material_code, "material_code")?;
         validate_safe_string(&self.goods_name, "goods_name")?;
 
         if let Some(desc) = &self.description {
@@ -220,13 +844,34 @@ impl CreateGoodRequest {
         if self.mass_g <= rust_decimal::Decimal::ZERO {
             return Err("Mass must be positive".to_string());
         }
+        if let Some(reorder_point) = self.reorder_point
+            && reorder_point < 0 {
+            return Err("Reorder point cannot be negative".to_string());
+        }
+        if let Some(category_id) = self.category_id
+            && category_id < 1 {
+            return Err("category_id must be a positive integer".to_string());
+        }
+        if let Some(barcode) = &self.barcode {
+            validate_barcode(barcode, "barcode")?;
+        }
+        if let Some(tags) = &self.tags {
+            validate_tags(tags, "tags")?;
+        }
 
         Ok(())
     }
 }
 
 impl UpdateGoodRequest {
-    pub fn validate(&self) -> Result<(), String> {
+    pub fn normalize(&mut self, normalization: Normalization) {
+        normalize_optional(&mut self.material_code, |code| normalization.material_code(code));
+        normalize_optional(&mut self.goods_name, |name| normalization.goods_name(name));
+        normalize_description(&mut self.description, normalization);
+        normalize_optional(&mut self.barcode, |barcode| normalization.text(barcode));
+    }
+
+    pub fn validate(&self, rules: &ValidationConfig) -> Result<(), String> {
         if self.material_code.is_none() 
             && self.goods_name.is_none() 
             && self.description.is_none() 
@@ -234,12 +879,18 @@ impl UpdateGoodRequest {
             && self.volumn_l.is_none() 
             && self.mass_g.is_none() 
             && self.mass_base.is_none() 
-            && self.volumn_base.is_none() {
+            && self.volumn_base.is_none()
+            && self.reorder_point.is_none()
+            && self.category_id.is_none()
+            && self.barcode.is_none()
+            && self.tags.is_none()
+            && self.is_active.is_none() {
             return Err("At least one field must be provided for update".to_string());
         }
 
         if let Some(material_code) = &self.material_code {
             validate_safe_string(material_code, "material_code")?;
+            rules.check_material_code(material_code, "material_code")?;
         }
 
         if let Some(goods_name) = &self.goods_name {
@@ -252,81 +903,238 @@ impl UpdateGoodRequest {
             }
         }
 
-        if let Some(price) = self.price {
-            if price < rust_decimal::Decimal::ZERO {
-                return Err("Price cannot be negative".to_string());
-            }
+        if let Some(price) = self.price
+            && price < rust_decimal::Decimal::ZERO {
+            return Err("Price cannot be negative".to_string());
         }
-        if let Some(volumn_l) = self.volumn_l {
-            if volumn_l <= rust_decimal::Decimal::ZERO {
-                return Err("Volume must be positive".to_string());
-            }
+        if let Some(volumn_l) = self.volumn_l
+            && volumn_l <= rust_decimal::Decimal::ZERO {
+            return Err("Volume must be positive".to_string());
         }
-        if let Some(mass_g) = self.mass_g {
-            if mass_g <= rust_decimal::Decimal::ZERO {
-                return Err("Mass must be positive".to_string());
-            }
+        if let Some(mass_g) = self.mass_g
+            && mass_g <= rust_decimal::Decimal::ZERO {
+            return Err("Mass must be positive".to_string());
+        }
+        if let Some(reorder_point) = self.reorder_point
+            && reorder_point < 0 {
+            return Err("Reorder point cannot be negative".to_string());
+        }
+        if let Some(category_id) = self.category_id
+            && category_id < 1 {
+            return Err("category_id must be a positive integer".to_string());
+        }
+        if let Some(barcode) = &self.barcode {
+            validate_barcode(barcode, "barcode")?;
+        }
+        if let Some(tags) = &self.tags {
+            validate_tags(tags, "tags")?;
+        }
+        if let Some(expected_version) = self.expected_version
+            && expected_version < 0 {
+            return Err("expected_version cannot be negative".to_string());
         }
 
         Ok(())
     }
 }
 
-impl CreateInventoryRequest {
+impl CreateCategoryRequest {
     pub fn validate(&self) -> Result<(), String> {
-        // Validate that we have some way to identify or create goods
-        if self.goods_id.is_none() && self.material_code.is_none() {
-            // If no goods_id or material_code, we need complete goods information
-            if self.goods_name.is_none() || self.price.is_none() || 
-               self.volumn_l.is_none() || self.mass_g.is_none() {
-                return Err("Either goods_id, material_code, or complete goods information (goods_name, price, volumn_l, mass_g) is required".to_string());
-            }
+        if self.name.trim().is_empty() {
+            return Err("Category name cannot be empty".to_string());
         }
+        validate_safe_string(&self.name, "name")?;
 
-        // Validate quantity
-        if self.quantity < 0 {
-            return Err("Quantity cannot be negative".to_string());
+        if let Some(parent_id) = self.parent_id
+            && parent_id < 1 {
+            return Err("parent_id must be a positive integer".to_string());
         }
 
-        // Validate strings if provided
-        if let Some(material_code) = &self.material_code {
-            validate_safe_string(material_code, "material_code")?;
-        }
+        Ok(())
+    }
+}
 
-        if let Some(goods_name) = &self.goods_name {
-            validate_safe_string(goods_name, "goods_name")?;
+impl UpdateCategoryRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_none() && self.parent_id.is_none() {
+            return Err("At least one field must be provided for update".to_string());
         }
 
-        if let Some(desc) = &self.description {
-            for (i, item) in desc.iter().enumerate() {
-                validate_safe_string(item, &format!("description[{}]", i))?;
+        if let Some(name) = &self.name {
+            if name.trim().is_empty() {
+                return Err("Category name cannot be empty".to_string());
             }
+            validate_safe_string(name, "name")?;
         }
 
-        // Validate numeric values if provided
-        if let Some(price) = self.price {
-            if price < rust_decimal::Decimal::ZERO {
-                return Err("Price cannot be negative".to_string());
-            }
-        }
-        if let Some(volumn_l) = self.volumn_l {
-            if volumn_l <= rust_decimal::Decimal::ZERO {
-                return Err("Volume must be positive".to_string());
-            }
-        }
-        if let Some(mass_g) = self.mass_g {
-            if mass_g <= rust_decimal::Decimal::ZERO {
-                return Err("Mass must be positive".to_string());
-            }
+        if let Some(parent_id) = self.parent_id
+            && parent_id < 1 {
+            return Err("parent_id must be a positive integer".to_string());
         }
 
         Ok(())
     }
 }
 
-impl UpdateInventoryRequest {
+/// Body of `DELETE /goods/by-ids` and `DELETE /inventory/by-ids`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteByIdsRequest {
+    pub ids: Vec<i32>,
+}
+
+/// Most ids accepted by one delete-by-ids request
+pub const MAX_DELETE_IDS: usize = 500;
+
+impl DeleteByIdsRequest {
     pub fn validate(&self) -> Result<(), String> {
-        // Check if at least one field is provided
+        if self.ids.is_empty() {
+            return Err("ids cannot be empty".to_string());
+        }
+        if self.ids.len() > MAX_DELETE_IDS {
+            return Err(format!("ids accepts at most {} ids", MAX_DELETE_IDS));
+        }
+        if let Some(i) = self.ids.iter().position(|id| *id < 1) {
+            return Err(format!("ids[{}] must be a positive integer", i));
+        }
+        Ok(())
+    }
+}
+
+/// Most lines accepted on one purchase order
+pub const MAX_PURCHASE_ORDER_LINES: usize = 500;
+
+impl CreatePurchaseOrderRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        self.initial_status()?;
+
+        if let Some(reference) = &self.reference {
+            validate_safe_string(reference, "reference")?;
+        }
+
+        if self.lines.is_empty() {
+            return Err("A purchase order needs at least one line".to_string());
+        }
+        if self.lines.len() > MAX_PURCHASE_ORDER_LINES {
+            return Err(format!("A purchase order accepts at most {} lines", MAX_PURCHASE_ORDER_LINES));
+        }
+
+        for (i, line) in self.lines.iter().enumerate() {
+            if line.goods_id < 1 {
+                return Err(format!("lines[{}].goods_id must be a positive integer", i));
+            }
+            if line.quantity < 1 {
+                return Err(format!("lines[{}].quantity must be positive", i));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Orders are created as `draft` unless `status` says `ordered`
+    pub fn initial_status(&self) -> Result<PurchaseOrderStatus, String> {
+        match self.status.as_deref() {
+            None => Ok(PurchaseOrderStatus::Draft),
+            Some(status) => match PurchaseOrderStatus::parse(status)? {
+                PurchaseOrderStatus::Received => Err("A purchase order cannot be created as received".to_string()),
+                status => Ok(status),
+            },
+        }
+    }
+}
+
+impl ReceivePurchaseOrderRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        let Some(lines) = &self.lines else {
+            return Ok(());
+        };
+
+        if lines.is_empty() {
+            return Err("lines cannot be empty; omit it to receive everything outstanding".to_string());
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            if line.quantity < 1 {
+                return Err(format!("lines[{}].quantity must be positive", i));
+            }
+            if lines[..i].iter().any(|earlier| earlier.line_id == line.line_id) {
+                return Err(format!("lines[{}]: line {} is listed more than once", i, line.line_id));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CreateInventoryRequest {
+    pub fn normalize(&mut self, normalization: Normalization) {
+        normalize_optional(&mut self.material_code, |code| normalization.material_code(code));
+        normalize_optional(&mut self.goods_name, |name| normalization.goods_name(name));
+        normalize_description(&mut self.description, normalization);
+        normalize_optional(&mut self.lot_number, |lot_number| normalization.text(lot_number));
+    }
+
+    pub fn validate(&self, rules: &ValidationConfig) -> Result<(), String> {
+        // Validate that we have some way to identify or create goods
+        if self.goods_id.is_none() && self.material_code.is_none() {
+            // If no goods_id or material_code, we need complete goods information
+            if self.goods_name.is_none() || self.price.is_none() || 
+               self.volumn_l.is_none() || self.mass_g.is_none() {
+                return Err("Either goods_id, material_code, or complete goods information (goods_name, price, volumn_l, mass_g) is required".to_string());
+            }
+        }
+
+        // Validate quantity
+        rules.check_quantity(self.quantity, "Quantity")?;
+
+        // Validate strings if provided
+        if let Some(material_code) = &self.material_code {
+            validate_safe_string(material_code, "material_code")?;
+            rules.check_material_code(material_code, "material_code")?;
+        }
+
+        if let Some(goods_name) = &self.goods_name {
+            validate_safe_string(goods_name, "goods_name")?;
+        }
+
+        if let Some(desc) = &self.description {
+            for (i, item) in desc.iter().enumerate() {
+                validate_safe_string(item, &format!("description[{}]", i))?;
+            }
+        }
+
+        // Validate numeric values if provided
+        if let Some(price) = self.price
+            && price < rust_decimal::Decimal::ZERO {
+            return Err("Price cannot be negative".to_string());
+        }
+        if let Some(volumn_l) = self.volumn_l
+            && volumn_l <= rust_decimal::Decimal::ZERO {
+            return Err("Volume must be positive".to_string());
+        }
+        if let Some(mass_g) = self.mass_g
+            && mass_g <= rust_decimal::Decimal::ZERO {
+            return Err("Mass must be positive".to_string());
+        }
+
+        if let Some(lot_number) = &self.lot_number {
+            validate_lot_number(lot_number, "lot_number")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UpdateInventoryRequest {
+    pub fn normalize(&mut self, normalization: Normalization) {
+        normalize_optional(&mut self.material_code, |code| normalization.material_code(code));
+        normalize_optional(&mut self.goods_name, |name| normalization.goods_name(name));
+        normalize_description(&mut self.description, normalization);
+        normalize_optional(&mut self.lot_number, |lot_number| normalization.text(lot_number));
+    }
+
+    pub fn validate(&self, rules: &ValidationConfig) -> Result<(), String> {
+        // Check if at least one field is provided
         if self.material_code.is_none() 
             && self.goods_name.is_none() 
             && self.description.is_none() 
@@ -336,13 +1144,15 @@ impl UpdateInventoryRequest {
             && self.mass_base.is_none() 
             && self.volumn_base.is_none()
             && self.quantity.is_none()
-            && self.expired_date.is_none() {
+            && self.expired_date.is_none()
+            && self.lot_number.is_none() {
             return Err("At least one field must be provided for update".to_string());
         }
 
         // Validate goods fields if provided
         if let Some(material_code) = &self.material_code {
             validate_safe_string(material_code, "material_code")?;
+            rules.check_material_code(material_code, "material_code")?;
         }
 
         if let Some(goods_name) = &self.goods_name {
@@ -355,77 +1165,710 @@ impl UpdateInventoryRequest {
             }
         }
 
-        if let Some(price) = self.price {
-            if price < rust_decimal::Decimal::ZERO {
-                return Err("Price cannot be negative".to_string());
-            }
+        if let Some(price) = self.price
+            && price < rust_decimal::Decimal::ZERO {
+            return Err("Price cannot be negative".to_string());
         }
-        if let Some(volumn_l) = self.volumn_l {
-            if volumn_l <= rust_decimal::Decimal::ZERO {
-                return Err("Volume must be positive".to_string());
-            }
+        if let Some(volumn_l) = self.volumn_l
+            && volumn_l <= rust_decimal::Decimal::ZERO {
+            return Err("Volume must be positive".to_string());
         }
-        if let Some(mass_g) = self.mass_g {
-            if mass_g <= rust_decimal::Decimal::ZERO {
-                return Err("Mass must be positive".to_string());
-            }
+        if let Some(mass_g) = self.mass_g
+            && mass_g <= rust_decimal::Decimal::ZERO {
+            return Err("Mass must be positive".to_string());
         }
 
         // Validate inventory fields if provided
         if let Some(quantity) = self.quantity {
-            if quantity < 0 {
-                return Err("Quantity cannot be negative".to_string());
-            }
+            rules.check_quantity(quantity, "Quantity")?;
+        }
+
+        if let Some(lot_number) = &self.lot_number {
+            validate_lot_number(lot_number, "lot_number")?;
         }
 
         Ok(())
     }
 }
 
-pub fn extract_goods_query_params(query: Query<HashMap<String, String>>) -> GoodsQueryParams {
-    let params = query.0;
-    
-    GoodsQueryParams {
-        goods_id: params.get("goods_id").cloned(),
-        material_code: params.get("material_code").cloned(),
-        goods_name: params.get("goods_name").cloned(),
-        price: params.get("price").cloned(),
-        volumn_l: params.get("volumn_l").cloned(),
-        mass_g: params.get("mass_g").cloned(),
-        min_volumn_l: params.get("min_volumn_l").cloned(),
-        max_volumn_l: params.get("max_volumn_l").cloned(),
-        min_mass_g: params.get("min_mass_g").cloned(),
-        max_mass_g: params.get("max_mass_g").cloned(),
-        min_price: params.get("min_price").cloned(),
-        max_price: params.get("max_price").cloned(),
-    }
-}
-
-pub fn extract_inventory_query_params(query: Query<HashMap<String, String>>) -> InventoryQueryParams {
-    let params = query.0;
-    
-    InventoryQueryParams {
-        // Inventory specific params
-        item_id: params.get("item_id").cloned(),
-        quantity: params.get("quantity").cloned(),
-        min_quantity: params.get("min_quantity").cloned(),
-        max_quantity: params.get("max_quantity").cloned(),
-        expired_date: params.get("expired_date").cloned(),
-        min_expired_date: params.get("min_expired_date").cloned(),
-        max_expired_date: params.get("max_expired_date").cloned(),
-        
-        // Goods params
-        goods_id: params.get("goods_id").cloned(),
-        material_code: params.get("material_code").cloned(),
-        goods_name: params.get("goods_name").cloned(),
-        price: params.get("price").cloned(),
-        volumn_l: params.get("volumn_l").cloned(),
-        mass_g: params.get("mass_g").cloned(),
-        min_volumn_l: params.get("min_volumn_l").cloned(),
-        max_volumn_l: params.get("max_volumn_l").cloned(),
-        min_mass_g: params.get("min_mass_g").cloned(),
-        max_mass_g: params.get("max_mass_g").cloned(),
-        min_price: params.get("min_price").cloned(),
-        max_price: params.get("max_price").cloned(),
+impl AdjustInventoryRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.delta == 0 {
+            return Err("delta cannot be zero".to_string());
+        }
+        validate_safe_string(&self.reason, "reason")?;
+
+        Ok(())
+    }
+}
+
+impl QuantityChangeRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.quantity <= 0 {
+            return Err("Quantity must be positive".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl ConsumeInventoryRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.goods_id.is_none() && self.material_code.is_none() {
+            return Err("Either goods_id or material_code is required".to_string());
+        }
+        if let Some(material_code) = &self.material_code {
+            validate_safe_string(material_code, "material_code")?;
+        }
+        if self.quantity <= 0 {
+            return Err("Quantity must be positive".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl CreateReservationRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.quantity <= 0 {
+            return Err("Quantity must be positive".to_string());
+        }
+        if let Some(reference) = &self.reference {
+            validate_safe_string(reference, "reference")?;
+        }
+        if let Some(expires_at) = &self.expires_at
+            && crate::utils::datetime::is_expired(expires_at) {
+            return Err("expires_at must be in the future".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// How a batch endpoint treats invalid entries (`?on_error=abort|skip`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchErrorMode {
+    /// Reject the whole batch if any entry is invalid
+    Abort,
+    /// Process the valid entries and report the invalid ones by index
+    Skip,
+}
+
+impl BatchErrorMode {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "abort" => Ok(BatchErrorMode::Abort),
+            "skip" => Ok(BatchErrorMode::Skip),
+            _ => Err("Invalid on_error. Allowed values: abort, skip".to_string()),
+        }
+    }
+}
+
+/// Valid batch entries paired with their position in the payload, plus per-position errors
+pub type ValidatedBatch<T> = (Vec<(usize, T)>, Vec<BatchItemError>);
+
+pub fn validate_batch_size(len: usize, max_batch_size: usize) -> Result<(), String> {
+    if len == 0 {
+        return Err("Batch must contain at least one entry".to_string());
+    }
+    if len > max_batch_size {
+        return Err(format!("Batch contains {} entries, maximum is {}", len, max_batch_size));
+    }
+
+    Ok(())
+}
+
+/// Validate every goods entry up front, splitting the batch into valid entries
+/// (paired with their payload index) and per-index errors.
+/// A material_code repeated within the payload is reported against its later occurrences.
+pub fn validate_goods_batch(requests: Vec<CreateGoodRequest>, normalization: Normalization, rules: &ValidationConfig) -> ValidatedBatch<CreateGoodRequest> {
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    for (index, mut request) in requests.into_iter().enumerate() {
+        request.normalize(normalization);
+        if let Err(error) = request.validate(rules) {
+            errors.push(BatchItemError::invalid(index, error));
+            continue;
+        }
+
+        if let Some(first_index) = seen.get(&request.material_code) {
+            errors.push(BatchItemError::conflict(
+                index,
+                format!("Duplicate material_code '{}' (first seen at index {})", request.material_code, first_index),
+            ));
+            continue;
+        }
+
+        seen.insert(request.material_code.clone(), index);
+        valid.push((index, request));
+    }
+
+    (valid, errors)
+}
+
+/// Validate every inventory entry up front, splitting the batch into valid entries
+/// (paired with their payload index) and per-index errors.
+/// Batch entries must reference existing goods; creating goods inline is only supported by POST /inventory.
+pub fn validate_inventory_batch(requests: Vec<CreateInventoryRequest>, normalization: Normalization, rules: &ValidationConfig) -> ValidatedBatch<CreateInventoryRequest> {
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+
+    for (index, mut request) in requests.into_iter().enumerate() {
+        request.normalize(normalization);
+        if request.goods_id.is_none() && request.material_code.is_none() {
+            errors.push(BatchItemError::invalid(index, "Batch entries must reference goods by goods_id or material_code"));
+            continue;
+        }
+
+        match request.validate(rules) {
+            Ok(()) => valid.push((index, request)),
+            Err(error) => errors.push(BatchItemError::invalid(index, error)),
+        }
+    }
+
+    (valid, errors)
+}
+
+/// Parse an inventory receipt CSV (`material_code, quantity, expired_date, lot_number` columns) into
+/// batch entries keyed by CSV line number, collecting unparseable rows as per-line errors.
+/// Fails outright only when the header is missing a required column.
+pub fn parse_inventory_csv(body: &str, normalization: Normalization, rules: &ValidationConfig) -> Result<ValidatedBatch<CreateInventoryRequest>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body.as_bytes());
+
+    let headers = reader.headers().map_err(|e| format!("Invalid CSV header: {}", e))?.clone();
+    let column = |name: &str| {
+        headers.iter()
+            .position(|header| header == name)
+            .ok_or_else(|| format!("CSV is missing the {} column", name))
+    };
+    let material_code_column = column("material_code")?;
+    let quantity_column = column("quantity")?;
+    let expired_date_column = column("expired_date").ok();
+    let lot_number_column = column("lot_number").ok();
+
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|position| position.line() as usize).unwrap_or(0);
+                errors.push(BatchItemError::invalid(line, format!("Invalid CSV row: {}", e)));
+                continue;
+            }
+        };
+        let line = record.position().map(|position| position.line() as usize).unwrap_or(0);
+
+        let parsed = (|| -> Result<CreateInventoryRequest, String> {
+            let material_code = record.get(material_code_column).unwrap_or_default().to_string();
+            let quantity = parse_safe_integer(record.get(quantity_column).unwrap_or_default(), "quantity")?;
+            let expired_date = match expired_date_column.and_then(|column| record.get(column)) {
+                Some(cell) if !cell.is_empty() => Some(crate::utils::datetime::parse_flexible_date(cell)?),
+                _ => None,
+            };
+            let lot_number = lot_number_column
+                .and_then(|column| record.get(column))
+                .filter(|cell| !cell.is_empty())
+                .map(str::to_string);
+
+            let mut request = CreateInventoryRequest {
+                goods_id: None,
+                material_code: Some(material_code),
+                goods_name: None,
+                description: None,
+                price: None,
+                volumn_l: None,
+                mass_g: None,
+                mass_base: None,
+                volumn_base: None,
+                quantity,
+                expired_date,
+                lot_number,
+            };
+            request.normalize(normalization);
+            request.validate(rules)?;
+
+            Ok(request)
+        })();
+
+        match parsed {
+            Ok(request) => entries.push((line, request)),
+            Err(error) => errors.push(BatchItemError::invalid(line, error)),
+        }
+    }
+
+    Ok((entries, errors))
+}
+
+/// Filters of a search, update or delete together with the endpoint's options. serde hands
+/// `unknown` the keys the plain structs among them did not take, so it must stay last.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct FilterQuery<F, O = ()> {
+    #[serde(flatten)]
+    pub filters: F,
+    #[serde(flatten)]
+    pub options: O,
+    #[serde(flatten)]
+    pub unknown: UnknownParams,
+}
+
+impl<F: Default + Serialize, O: Default + Serialize> FilterQuery<F, O> {
+    /// Reject keys this query does not declare, naming the closest known key for each, unless
+    /// the client opted out with `?lenient=true`. Without this a typo such as `goods_nam` is
+    /// silently dropped and the request fails for lack of filters.
+    pub fn reject_unknown_params(&self) -> Result<(), String> {
+        if let Some(value) = &self.unknown.lenient && parse_safe_bool(value, "lenient")? {
+            return Ok(());
+        }
+
+        // Every key serde accepts, read off an empty query. A flattened struct that flattens
+        // another one passes all its keys on to `unknown`, so those are filtered out here.
+        let empty = serde_json::to_value(Self::default()).unwrap_or_default();
+        let accepted: Vec<&str> = empty.as_object().into_iter().flat_map(|fields| fields.keys()).map(String::as_str).collect();
+        let unknown: Vec<&str> = self.unknown.keys.keys().map(String::as_str).filter(|key| !accepted.contains(key)).collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+
+        let described: Vec<String> = unknown
+            .iter()
+            .map(|key| match closest_match(key, &accepted) {
+                Some(suggestion) => format!("'{}' (did you mean {}?)", key, suggestion),
+                None => format!("'{}'", key),
+            })
+            .collect();
+        Err(format!(
+            "Unknown query parameters: {}. Add lenient=true to ignore unknown parameters",
+            described.join(", ")
+        ))
+    }
+}
+
+/// Query keys left over by the other fields of a `FilterQuery`, and the `lenient` opt-out
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct UnknownParams {
+    lenient: Option<String>,
+    #[serde(flatten, skip_serializing)]
+    keys: BTreeMap<String, String>,
+}
+
+/// Options of the goods and inventory searches
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SearchOptions {
+    /// `json` or `csv`; see `OutputFormat::negotiate`
+    pub format: Option<String>,
+    /// Comma-separated fields to return
+    pub fields: Option<String>,
+    pub unbounded: Option<String>,
+    pub fuzzy: Option<String>,
+}
+
+impl SearchOptions {
+    /// `None` returns every field
+    pub fn fields(&self, allowed: &[&str]) -> Result<Option<Vec<String>>, String> {
+        parse_fields(self.fields.as_deref(), allowed)
+    }
+
+    /// Return every match without pagination (defaults to false)
+    pub fn unbounded(&self) -> Result<bool, String> {
+        parse_flag(self.unbounded.as_deref(), "unbounded", false)
+    }
+
+    /// Rank goods_name by similarity (defaults to false)
+    pub fn fuzzy(&self) -> Result<bool, String> {
+        parse_flag(self.fuzzy.as_deref(), "fuzzy", false)
+    }
+}
+
+/// Options of the goods search: `SearchOptions` plus `include_stock`
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct GoodsSearchOptions {
+    #[serde(flatten)]
+    pub search: SearchOptions,
+    #[serde(flatten)]
+    pub stock: StockOptions,
+}
+
+/// `?include_stock=` for goods lookups
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct StockOptions {
+    pub include_stock: Option<String>,
+}
+
+impl StockOptions {
+    /// Defaults to false
+    pub fn include_stock(&self) -> Result<bool, String> {
+        parse_flag(self.include_stock.as_deref(), "include_stock", false)
+    }
+}
+
+/// Options of the filter-based update and delete endpoints
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BulkWriteQueryOptions {
+    pub max_affected: Option<String>,
+    pub dry_run: Option<String>,
+    /// Must be true for a wildcard delete
+    pub confirm: Option<String>,
+}
+
+impl BulkWriteQueryOptions {
+    /// `max_affected` defaults to the server's cap
+    pub fn parse(&self, default_max_affected: u32) -> Result<BulkWriteOptions, String> {
+        let max_affected = match &self.max_affected {
+            Some(value) => {
+                let max_affected = parse_safe_integer(value, "max_affected")?;
+                if max_affected < 1 {
+                    return Err("max_affected must be at least 1".to_string());
+                }
+                i64::from(max_affected)
+            }
+            None => i64::from(default_max_affected),
+        };
+
+        Ok(BulkWriteOptions { max_affected, dry_run: parse_flag(self.dry_run.as_deref(), "dry_run", false)? })
+    }
+
+    pub fn confirm(&self) -> Result<bool, String> {
+        parse_flag(self.confirm.as_deref(), "confirm", false)
+    }
+}
+
+/// Options of the valuation report
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ValuationOptions {
+    /// `json` or `csv`; see `OutputFormat::negotiate`
+    pub format: Option<String>,
+    pub group_by: Option<String>,
+    pub exclude_expired: Option<String>,
+}
+
+impl ValuationOptions {
+    /// Defaults to goods
+    pub fn group_by(&self) -> Result<ValuationGroupBy, String> {
+        self.group_by.as_deref().map_or(Ok(ValuationGroupBy::default()), ValuationGroupBy::parse)
+    }
+
+    /// Defaults to false
+    pub fn exclude_expired(&self) -> Result<bool, String> {
+        parse_flag(self.exclude_expired.as_deref(), "exclude_expired", false)
+    }
+}
+
+/// `?on_conflict=return_existing|error|update` for goods creation
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct GoodsCreateOptions {
+    pub on_conflict: Option<String>,
+}
+
+impl GoodsCreateOptions {
+    pub fn on_conflict(&self) -> Result<GoodsConflictMode, String> {
+        self.on_conflict.as_deref().map_or(Ok(GoodsConflictMode::default()), GoodsConflictMode::parse)
+    }
+}
+
+/// `?on_error=abort|skip` for goods batches
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct GoodsBatchOptions {
+    pub on_error: Option<String>,
+}
+
+impl GoodsBatchOptions {
+    pub fn on_error(&self) -> Result<BatchErrorMode, String> {
+        parse_batch_error_mode(self.on_error.as_deref())
+    }
+}
+
+/// Options of inventory creation
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct InventoryCreateOptions {
+    pub on_duplicate: Option<String>,
+    pub allow_inactive: Option<String>,
+}
+
+impl InventoryCreateOptions {
+    pub fn on_duplicate(&self) -> Result<InventoryDuplicateMode, String> {
+        self.on_duplicate.as_deref().map_or(Ok(InventoryDuplicateMode::default()), InventoryDuplicateMode::parse)
+    }
+
+    /// Record inventory of archived goods (defaults to false)
+    pub fn allow_inactive(&self) -> Result<bool, String> {
+        parse_flag(self.allow_inactive.as_deref(), "allow_inactive", false)
+    }
+}
+
+/// Options of inventory batches
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct InventoryBatchOptions {
+    pub on_error: Option<String>,
+    pub allow_inactive: Option<String>,
+}
+
+impl InventoryBatchOptions {
+    pub fn on_error(&self) -> Result<BatchErrorMode, String> {
+        parse_batch_error_mode(self.on_error.as_deref())
+    }
+
+    /// Record inventory of archived goods (defaults to false)
+    pub fn allow_inactive(&self) -> Result<bool, String> {
+        parse_flag(self.allow_inactive.as_deref(), "allow_inactive", false)
+    }
+}
+
+/// Options of inventory imports
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ImportOptions {
+    pub strict: Option<String>,
+    pub allow_inactive: Option<String>,
+}
+
+impl ImportOptions {
+    /// Reject the whole file if any row fails (defaults to false)
+    pub fn strict(&self) -> Result<bool, String> {
+        parse_flag(self.strict.as_deref(), "strict", false)
+    }
+
+    /// Record inventory of archived goods (defaults to false)
+    pub fn allow_inactive(&self) -> Result<bool, String> {
+        parse_flag(self.allow_inactive.as_deref(), "allow_inactive", false)
+    }
+}
+
+/// `?dry_run=` for the maintenance endpoints
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct DryRunOptions {
+    pub dry_run: Option<String>,
+}
+
+impl DryRunOptions {
+    pub fn dry_run(&self) -> Result<bool, String> {
+        parse_flag(self.dry_run.as_deref(), "dry_run", false)
+    }
+}
+
+/// `?fields=a,b,c` for single-item lookups
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct FieldsOptions {
+    pub fields: Option<String>,
+}
+
+impl FieldsOptions {
+    /// `None` returns every field
+    pub fn fields(&self, allowed: &[&str]) -> Result<Option<Vec<String>>, String> {
+        parse_fields(self.fields.as_deref(), allowed)
+    }
+}
+
+/// `?include_zero_reorder=` for the low-stock report
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LowStockOptions {
+    pub include_zero_reorder: Option<String>,
+}
+
+impl LowStockOptions {
+    /// Defaults to true
+    pub fn include_zero_reorder(&self) -> Result<bool, String> {
+        parse_flag(self.include_zero_reorder.as_deref(), "include_zero_reorder", true)
+    }
+}
+
+/// `?verbose=` for `/health`
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HealthOptions {
+    pub verbose: Option<String>,
+}
+
+impl HealthOptions {
+    /// Defaults to false
+    pub fn verbose(&self) -> Result<bool, String> {
+        parse_flag(self.verbose.as_deref(), "verbose", false)
+    }
+}
+
+fn parse_flag(value: Option<&str>, field_name: &str, default: bool) -> Result<bool, String> {
+    value.map_or(Ok(default), |value| parse_safe_bool(value, field_name))
+}
+
+fn parse_batch_error_mode(value: Option<&str>) -> Result<BatchErrorMode, String> {
+    value.map_or(Ok(BatchErrorMode::Abort), BatchErrorMode::parse)
+}
+
+/// Parse `a,b,c`, rejecting names outside `allowed`
+fn parse_fields(value: Option<&str>, allowed: &[&str]) -> Result<Option<Vec<String>>, String> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    let fields: Vec<String> = value
+        .split(',')
+        .map(|field| field.trim().to_string())
+        .filter(|field| !field.is_empty())
+        .collect();
+
+    if fields.is_empty() {
+        return Err(format!("fields cannot be empty. Allowed values: {}", allowed.join(", ")));
+    }
+
+    if let Some(unknown) = fields.iter().find(|field| !allowed.contains(&field.as_str())) {
+        return Err(format!("Unknown field '{}'. Allowed values: {}", unknown, allowed.join(", ")));
+    }
+
+    Ok(Some(fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Query;
+    use chrono::{DateTime, Utc};
+
+    fn goods_query(query: &str) -> GoodsQueryParams {
+        Query::try_from_uri(&format!("/goods?{}", query).parse().unwrap()).unwrap().0
+    }
+
+    fn inventory_query(query: &str) -> InventoryQueryParams {
+        Query::try_from_uri(&format!("/inventory?{}", query).parse().unwrap()).unwrap().0
+    }
+
+    fn timestamp(input: &str) -> DateTime<Utc> {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_updated_at_bounds() {
+        let params = goods_query("min_updated_at=2024-01-01T00:00:00Z&max_updated_at=2024-02-01T12:30:00Z").validate_and_parse().unwrap();
+        assert_eq!(params.min_updated_at, Some(timestamp("2024-01-01T00:00:00Z")));
+        assert_eq!(params.max_updated_at, Some(timestamp("2024-02-01T12:30:00Z")));
+
+        let params = inventory_query("min_updated_at=2024-01-01T00:00:00Z").validate_and_parse().unwrap();
+        assert_eq!(params.min_updated_at, Some(timestamp("2024-01-01T00:00:00Z")));
+        // Inventory filters on the item's own timestamp, not the good's
+        assert_eq!(params.goods_params.min_updated_at, None);
+    }
+
+    #[test]
+    fn rejects_malformed_updated_at() {
+        assert!(goods_query("min_updated_at=yesterday").validate_and_parse().is_err());
+        assert!(inventory_query("max_updated_at=2024-13-01T00:00:00Z").validate_and_parse().is_err());
+    }
+
+    #[test]
+    fn match_mode_defaults_to_contains() {
+        assert_eq!(goods_query("material_code=CH-100").validate_and_parse().unwrap().match_mode, MatchMode::Contains);
+        assert_eq!(goods_query("material_code=CH-100&match_mode=exact").validate_and_parse().unwrap().match_mode, MatchMode::Exact);
+        assert_eq!(inventory_query("material_code=CH-100&match_mode=prefix").validate_and_parse().unwrap().goods_params.match_mode, MatchMode::Prefix);
+    }
+
+    #[test]
+    fn rejects_unknown_match_mode_and_literal_with_match_mode() {
+        assert!(goods_query("material_code=CH-100&match_mode=fuzzy").validate_and_parse().is_err());
+        assert!(goods_query("material_code=CH-100&literal=true&match_mode=exact").validate_and_parse().is_err());
+        assert_eq!(goods_query("material_code=CH-100&literal=true").validate_and_parse().unwrap().match_mode, MatchMode::Literal);
+    }
+
+    #[test]
+    fn wildcard_with_exact_match_mode_still_gets_all() {
+        let params = goods_query("material_code=*&match_mode=exact").validate_and_parse().unwrap();
+        assert!(params.is_get_all());
+    }
+
+    #[test]
+    fn rejects_wildcard_combined_with_other_filters() {
+        for query in ["goods_name=*&category_id=3", "material_code=*&in_stock=true", "goods_name=*&material_code=CH"] {
+            assert_eq!(goods_query(query).validate_and_parse().unwrap_err(), WILDCARD_WITH_FILTERS);
+        }
+        for query in ["goods_name=*&expired=true", "goods_name=*&lot_number=L1", "material_code=*&min_updated_at=2024-01-01T00:00:00Z"] {
+            assert_eq!(inventory_query(query).validate_and_parse().unwrap_err(), WILDCARD_WITH_FILTERS);
+        }
+
+        // Modifiers and paging are not filters
+        assert!(goods_query("goods_name=*&material_code=*&include_inactive=true&page=2").validate_and_parse().unwrap().is_get_all());
+        assert!(inventory_query("goods_name=*&include_reserved=true&sort_by=quantity").validate_and_parse().unwrap().is_get_all());
+    }
+
+    #[test]
+    fn rejects_each_goods_range_where_min_exceeds_max() {
+        for (min_field, max_field, min, max) in [
+            ("min_price", "max_price", "100", "10"),
+            ("min_mass_g", "max_mass_g", "500", "250"),
+            ("min_volumn_l", "max_volumn_l", "2.5", "0.5"),
+            ("min_updated_at", "max_updated_at", "2024-02-01T00:00:00Z", "2024-01-01T00:00:00Z"),
+        ] {
+            let error = goods_query(&format!("{}={}&{}={}", min_field, min, max_field, max)).validate_and_parse().unwrap_err();
+            assert_eq!(error, format!("{} cannot be greater than {}", min_field, max_field));
+        }
+    }
+
+    #[test]
+    fn rejects_each_inventory_range_where_min_exceeds_max() {
+        for (min_field, max_field, min, max) in [
+            ("min_quantity", "max_quantity", "20", "5"),
+            ("min_expired_date", "max_expired_date", "2025-06-01T00:00:00Z", "2025-01-01T00:00:00Z"),
+            ("min_price", "max_price", "100", "10"),
+        ] {
+            let error = inventory_query(&format!("{}={}&{}={}", min_field, min, max_field, max)).validate_and_parse().unwrap_err();
+            assert_eq!(error, format!("{} cannot be greater than {}", min_field, max_field));
+        }
+    }
+
+    #[test]
+    fn allows_equal_and_one_sided_ranges() {
+        assert!(goods_query("min_price=10&max_price=10&min_mass_g=250&max_mass_g=250").validate_and_parse().is_ok());
+        assert!(goods_query("min_price=100").validate_and_parse().is_ok());
+        assert!(inventory_query("min_quantity=5&max_quantity=5").validate_and_parse().is_ok());
+        assert!(inventory_query("min_expired_date=2025-01-01T00:00:00Z&max_expired_date=2025-01-01T00:00:00Z").validate_and_parse().is_ok());
+    }
+
+    #[test]
+    fn parses_negative_quantity_bounds() {
+        let params = inventory_query("max_quantity=-1").validate_and_parse().unwrap();
+        assert_eq!(params.max_quantity, Some(-1));
+
+        let params = inventory_query("min_quantity=-10&max_quantity=-1").validate_and_parse().unwrap();
+        assert_eq!((params.min_quantity, params.max_quantity), (Some(-10), Some(-1)));
+        assert!(inventory_query("min_quantity=-1&max_quantity=-10").validate_and_parse().is_err());
+    }
+
+    fn filter_query<F: serde::de::DeserializeOwned, O: serde::de::DeserializeOwned>(query: &str) -> FilterQuery<F, O> {
+        Query::try_from_uri(&format!("/goods?{}", query).parse().unwrap()).unwrap().0
+    }
+
+    #[test]
+    fn unknown_params_are_listed_with_suggestions() {
+        let query = filter_query::<GoodsQueryParams, ()>("goods_nam=milk&colour=red&page=1");
+        let error = query.reject_unknown_params().unwrap_err();
+
+        assert_eq!(
+            error,
+            "Unknown query parameters: 'colour', 'goods_nam' (did you mean goods_name?). Add lenient=true to ignore unknown parameters"
+        );
+        assert_eq!(query.filters.page.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn known_params_options_and_lenient_requests_pass() {
+        assert!(filter_query::<GoodsQueryParams, ()>("goods_name=milk&page=1").reject_unknown_params().is_ok());
+
+        let query = filter_query::<GoodsQueryParams, BulkWriteQueryOptions>("goods_name=milk&confirm=true");
+        assert!(query.reject_unknown_params().is_ok());
+        assert_eq!(query.options.confirm(), Ok(true));
+
+        assert!(filter_query::<GoodsQueryParams, ()>("goods_nam=milk&lenient=true").reject_unknown_params().is_ok());
+        assert!(filter_query::<GoodsQueryParams, ()>("goods_nam=milk&lenient=false").reject_unknown_params().is_err());
+    }
+
+    #[test]
+    fn options_are_only_known_where_declared() {
+        let query = filter_query::<GoodsQueryParams, GoodsSearchOptions>("goods_name=milk&fields=goods_id&include_stock=true");
+        assert!(query.reject_unknown_params().is_ok());
+        assert_eq!(query.options.stock.include_stock(), Ok(true));
+        assert_eq!(query.options.search.fields(&["goods_id"]), Ok(Some(vec!["goods_id".to_string()])));
+
+        let error = filter_query::<InventoryQueryParams, SearchOptions>("goods_name=milk&include_stock=true").reject_unknown_params().unwrap_err();
+        assert!(error.starts_with("Unknown query parameters: 'include_stock'"), "{}", error);
     }
 }
@@ -0,0 +1,80 @@
+// src/request_id.rs
+//
+// Request id propagation. The id comes from the client's `X-Request-Id` header or is
+// generated, then wraps the request in a tracing span (so every log line carries it),
+// is echoed in the response header and ends up in `ErrorResponse` bodies.
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest client-supplied id we accept; anything else is replaced with a generated one
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The id of the request being handled, if called from within one
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+pub async fn propagate_request_id(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(request).instrument(span))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+    }
+
+    response
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_ids_made_of_safe_characters() {
+        assert!(is_valid_request_id("3f2b9c1e-7a4d-4e8f-9b0a-1c2d3e4f5a6b"));
+        assert!(is_valid_request_id("trace_01.span:02"));
+        assert!(is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LENGTH)));
+    }
+
+    #[test]
+    fn rejects_empty_long_or_unsafe_ids() {
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LENGTH + 1)));
+        assert!(!is_valid_request_id("id with spaces"));
+        assert!(!is_valid_request_id("id\r\nx-injected: 1"));
+    }
+}
@@ -1,35 +1,197 @@
 // src/response.rs
+//
+// Response envelopes. Every JSON endpoint except /health answers with
+// `{ success, data, error, meta }`; the pre-envelope shapes are still produced for clients that
+// send `Accept-Version: 1` (or for everyone when `legacy_envelope` is configured) for one release.
+use crate::database::DatabaseHealth;
+use crate::request_id::current_request_id;
+use crate::utils::pagination::PaginatedResponse;
+use crate::utils::response::details_list;
 use axum::{
-    http::StatusCode,
+    extract::{Request, State},
+    http::{HeaderName, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+pub static ACCEPT_VERSION_HEADER: HeaderName = HeaderName::from_static("accept-version");
+
+/// Response body layout, chosen per request by `negotiate_envelope`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeVersion {
+    /// `{ success, message, data, timestamp }`, errors as `{ success, code, error, details, request_id, timestamp }`
+    V1,
+    /// `{ success, data, error, meta }`
+    V2,
+}
+
+impl EnvelopeVersion {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input.trim() {
+            "1" => Ok(EnvelopeVersion::V1),
+            "2" => Ok(EnvelopeVersion::V2),
+            _ => Err("Invalid Accept-Version. Allowed values: 1, 2".to_string()),
+        }
+    }
+}
+
+tokio::task_local! {
+    static ENVELOPE_VERSION: EnvelopeVersion;
+}
+
+/// The envelope of the request being handled; V2 outside of one
+pub fn current_envelope_version() -> EnvelopeVersion {
+    ENVELOPE_VERSION.try_with(|version| *version).unwrap_or(EnvelopeVersion::V2)
+}
+
+/// Pick the envelope from `Accept-Version`, falling back to the configured default
+pub async fn negotiate_envelope(State(default): State<EnvelopeVersion>, request: Request, next: Next) -> Response {
+    let requested = request
+        .headers()
+        .get(&ACCEPT_VERSION_HEADER)
+        .map(|value| value.to_str().map_err(|_| "Invalid Accept-Version".to_string()).and_then(EnvelopeVersion::parse));
+
+    match requested {
+        Some(Err(error)) => {
+            tracing::warn!("{}", error);
+            ENVELOPE_VERSION
+                .scope(default, async { error_response(StatusCode::BAD_REQUEST, "VALIDATION_ERROR", &error, None) })
+                .await
+        }
+        Some(Ok(version)) => ENVELOPE_VERSION.scope(version, next.run(request)).await,
+        None => ENVELOPE_VERSION.scope(default, next.run(request)).await,
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ApiResponse<T> {
     pub success: bool,
-    pub message: String,
     pub data: Option<T>,
-    pub timestamp: DateTime<Utc>,
+    pub error: Option<ErrorBody>,
+    pub meta: ResponseMeta,
 }
 
+/// Error envelope: `data` is always null and `error` always set
 #[derive(Debug, Serialize, Deserialize)]
-pub struct HealthResponse {
-    pub status: String,
-    pub database_connected: bool,
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorResponse {
+    pub success: bool,
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub data: Option<serde_json::Value>,
+    pub error: Option<ErrorBody>,
+    pub meta: ResponseMeta,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorBody {
+    /// Machine-readable, e.g. VALIDATION_ERROR, NOT_FOUND, CONFLICT
+    pub code: String,
+    pub message: String,
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Object>))]
+    pub details: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ResponseMeta {
+    /// Set on paginated listings
+    pub pagination: Option<PaginationMeta>,
+    pub request_id: Option<String>,
+    /// Set when a listing without pagination stopped at the `limit` row cap
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PaginationMeta {
+    pub page: u32,
+    pub per_page: u32,
+    pub total_count: Option<u64>,
+    pub total_pages: Option<u32>,
+    /// Pass back as `cursor` for the next page; unset on the last page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Set when the requested `per_page` exceeded the maximum and was clamped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// Success body of the V1 envelope
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LegacyApiResponse<T> {
+    pub success: bool,
+    pub message: String,
+    pub data: Option<T>,
     pub timestamp: DateTime<Utc>,
 }
 
+/// Error body of the V1 envelope
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ErrorResponse {
+pub struct LegacyErrorResponse {
     pub success: bool,
+    pub code: String,
     pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HealthResponse {
+    pub status: HealthStatus,
+    pub database_connected: bool,
+    /// Read replica status; omitted when reads go to the primary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replica_connected: Option<bool>,
+    /// Maintenance mode: writes are rejected, reads are served
+    pub read_only: bool,
+    /// Pool usage, query latency and table checks; only with `?verbose=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<DatabaseHealth>,
     pub timestamp: DateTime<Utc>,
 }
 
+/// `degraded` still answers 200: requests are served, but slowly or queued for a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
 impl<T> ApiResponse<T>
+where
+    T: Serialize,
+{
+    pub fn success(data: T, pagination: Option<PaginationMeta>) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+            meta: ResponseMeta {
+                pagination,
+                request_id: current_request_id(),
+                truncated: false,
+                limit: None,
+            },
+        }
+    }
+}
+
+impl<T> LegacyApiResponse<T>
 where
     T: Serialize,
 {
@@ -44,60 +206,68 @@ where
 }
 
 impl ErrorResponse {
-    pub fn new(error: &str) -> Self {
+    pub fn new(code: &str, message: &str, details: Option<serde_json::Value>) -> Self {
         Self {
             success: false,
-            error: error.to_string(),
-            timestamp: Utc::now(),
+            data: None,
+            error: Some(ErrorBody {
+                code: code.to_string(),
+                message: message.to_string(),
+                details: details_list(details),
+            }),
+            meta: ResponseMeta {
+                pagination: None,
+                request_id: current_request_id(),
+                truncated: false,
+                limit: None,
+            },
         }
     }
+}
 
-    pub fn bad_request(error: &str) -> Response {
-        let error_response = ErrorResponse::new(error);
-        (StatusCode::BAD_REQUEST, Json(error_response)).into_response()
-    }
-
-    pub fn internal_server_error(error: &str) -> Response {
-        let error_response = ErrorResponse::new(error);
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)).into_response()
+impl LegacyErrorResponse {
+    pub fn new(code: &str, error: &str, details: Option<serde_json::Value>) -> Self {
+        Self {
+            success: false,
+            code: code.to_string(),
+            error: error.to_string(),
+            details,
+            request_id: current_request_id(),
+            timestamp: Utc::now(),
+        }
     }
 }
 
 impl HealthResponse {
-    pub fn new(database_connected: bool) -> Self {
-        let status = if database_connected {
-            "healthy".to_string()
+    pub fn new(database: DatabaseHealth, replica_connected: Option<bool>, read_only: bool, verbose: bool) -> Self {
+        // Reads fail when the replica is down, so both pools must be up
+        let status = if !database.is_usable() || replica_connected == Some(false) {
+            HealthStatus::Unhealthy
+        } else if database.is_degraded() {
+            HealthStatus::Degraded
         } else {
-            "database_disconnected".to_string()
+            HealthStatus::Healthy
         };
 
         Self {
             status,
-            database_connected,
+            database_connected: database.connected,
+            replica_connected,
+            read_only,
+            database: verbose.then_some(database),
             timestamp: Utc::now(),
         }
     }
-}
 
-// Implement IntoResponse for our custom types
-impl<T> IntoResponse for ApiResponse<T>
-where
-    T: Serialize,
-{
-    fn into_response(self) -> Response {
-        let status = if self.success {
-            StatusCode::OK
-        } else {
-            StatusCode::BAD_REQUEST
-        };
-        
-        (status, Json(self)).into_response()
+    pub fn is_healthy(&self) -> bool {
+        self.status != HealthStatus::Unhealthy
     }
 }
 
+// Implement IntoResponse for our custom types
 impl IntoResponse for HealthResponse {
     fn into_response(self) -> Response {
-        let status = if self.database_connected {
+        let status = if self.is_healthy() {
             StatusCode::OK
         } else {
             StatusCode::SERVICE_UNAVAILABLE
@@ -107,17 +277,113 @@ impl IntoResponse for HealthResponse {
     }
 }
 
-impl IntoResponse for ErrorResponse {
-    fn into_response(self) -> Response {
-        (StatusCode::BAD_REQUEST, Json(self)).into_response()
+// Helper functions for common responses
+/// 200 with `data`; `message` is only sent in the V1 envelope
+pub fn success_response<T: Serialize>(data: T, message: &str) -> Response {
+    match current_envelope_version() {
+        EnvelopeVersion::V1 => Json(LegacyApiResponse::success(data, message)).into_response(),
+        EnvelopeVersion::V2 => Json(ApiResponse::success(data, None)).into_response(),
     }
 }
 
-// Helper functions for common responses
-pub fn success_response<T: Serialize>(data: T, message: &str) -> Response {
-    ApiResponse::success(data, message).into_response()
+/// `success_response` for a listing cut off at `limit` rows; V2 flags it in `meta.truncated`
+/// and `meta.limit`, V1 in `message`
+pub fn truncated_response<T: Serialize>(data: T, limit: u32, message: &str) -> Response {
+    match current_envelope_version() {
+        EnvelopeVersion::V1 => {
+            let message = format!("{} (truncated to {} rows)", message, limit);
+            Json(LegacyApiResponse::success(data, &message)).into_response()
+        }
+        EnvelopeVersion::V2 => {
+            let mut response = ApiResponse::success(data, None);
+            response.meta.truncated = true;
+            response.meta.limit = Some(limit);
+            Json(response).into_response()
+        }
+    }
+}
+
+/// 200 with one page of items; V2 moves the page metadata, including a clamped page size
+/// warning, into `meta.pagination`
+pub fn paginated_response<T: Serialize>(page: PaginatedResponse<T>, message: &str) -> Response {
+    match current_envelope_version() {
+        EnvelopeVersion::V1 => Json(LegacyApiResponse::success(page, message)).into_response(),
+        EnvelopeVersion::V2 => {
+            let pagination = PaginationMeta {
+                page: page.page,
+                per_page: page.per_page,
+                total_count: page.total_count,
+                total_pages: page.total_pages,
+                next_cursor: page.next_cursor,
+                warning: page.warning,
+            };
+            Json(ApiResponse::success(page.data, Some(pagination))).into_response()
+        }
+    }
+}
+
+/// Error body in the envelope of the current request
+pub fn error_response(status: StatusCode, code: &str, message: &str, details: Option<serde_json::Value>) -> Response {
+    match current_envelope_version() {
+        EnvelopeVersion::V1 => (status, Json(LegacyErrorResponse::new(code, message, details))).into_response(),
+        EnvelopeVersion::V2 => (status, Json(ErrorResponse::new(code, message, details))).into_response(),
+    }
+}
+
+/// Serialize `item`, keeping only `fields` when given
+pub fn select_fields<T: Serialize>(item: &T, fields: Option<&[String]>) -> serde_json::Result<serde_json::Value> {
+    let mut value = serde_json::to_value(item)?;
+
+    if let (Some(fields), serde_json::Value::Object(object)) = (fields, &mut value) {
+        object.retain(|key, _| fields.iter().any(|field| field == key));
+    }
+
+    Ok(value)
 }
 
-pub fn health_response(database_connected: bool) -> Response {
-    HealthResponse::new(database_connected).into_response()
+/// `select_fields` over every item of a list
+pub fn select_fields_each<T: Serialize>(items: &[T], fields: Option<&[String]>) -> serde_json::Result<Vec<serde_json::Value>> {
+    items.iter().map(|item| select_fields(item, fields)).collect()
+}
+
+pub fn health_response(database: DatabaseHealth, replica_connected: Option<bool>, read_only: bool, verbose: bool) -> Response {
+    HealthResponse::new(database, replica_connected, read_only, verbose).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::pagination::{PageLimits, PaginationParams};
+    use axum::body::to_bytes;
+
+    async fn body_json(response: Response) -> serde_json::Value {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn clamped_page_size_is_reported_in_the_pagination_meta() {
+        let limits = PageLimits { default_per_page: 50, max_per_page: 100 };
+        let params = PaginationParams::with_limits(Some(2), Some(500), limits);
+        let page = PaginatedResponse::new(vec![1, 2, 3], &params, Some(103));
+
+        let body = body_json(paginated_response(page, "Goods search")).await;
+
+        let pagination = &body["meta"]["pagination"];
+        assert_eq!(pagination["per_page"], 100);
+        assert_eq!(pagination["total_pages"], 2);
+        assert_eq!(pagination["warning"], "per_page 500 exceeds the maximum of 100; 100 items per page were returned");
+        assert!(body["data"].is_array());
+    }
+
+    #[tokio::test]
+    async fn page_size_within_the_limit_has_no_warning() {
+        let params = PaginationParams::with_limits(None, Some(100), PageLimits { default_per_page: 50, max_per_page: 100 });
+        let page = PaginatedResponse::new(Vec::<i32>::new(), &params, Some(0));
+
+        let body = body_json(paginated_response(page, "Goods search")).await;
+
+        assert_eq!(body["meta"]["pagination"]["per_page"], 100);
+        assert!(body["meta"]["pagination"].get("warning").is_none());
+    }
 }
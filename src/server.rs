@@ -1,24 +1,103 @@
 // src/server.rs
+use crate::alerts::spawn_expiry_alerts;
+use crate::audit::audit_mutations;
+use crate::auth::{require_api_key, Role};
+use crate::build_info::{add_version_header, build_info, short_sha, PACKAGE_VERSION};
 use crate::config::AppConfig;
 use crate::database::Database;
-use crate::request::{extract_goods_query_params, extract_inventory_query_params};
-use crate::response::{ErrorResponse, success_response, health_response};
-use crate::tables::{CreateGoodRequest, UpdateGoodRequest, CreateInventoryRequest, UpdateInventoryRequest};
-use crate::utils::{logging::*, response::*};
+use crate::error::{gateway_timeout_as_json, overloaded_as_json, panic_as_json, payload_too_large_as_json, ApiError};
+use crate::expired_purge::{purge_cutoff, spawn_expired_purge};
+use crate::export::{csv_response, ndjson_response};
+use crate::format::{render_page, render_rows, OutputFormat};
+use crate::idempotency::{idempotent, spawn_cleanup};
+use crate::maintenance::{reject_writes_in_maintenance, MaintenanceMode};
+use crate::extract::{ApiQuery, JsonBody};
+use crate::request::{
+    parse_inventory_csv, validate_batch_size, validate_goods_batch, validate_inventory_batch, AlertQueryParams, AuditQueryParams, BatchErrorMode,
+    BulkWriteQueryOptions, DeleteByIdsRequest, DryRunOptions, FieldsOptions, FilterQuery, GoodsBatchOptions, GoodsCreateOptions, GoodsQueryParams,
+    GoodsSearchOptions, HealthOptions, ImportOptions, InventoryBatchOptions, InventoryCreateOptions, InventoryQueryParams, LowStockOptions,
+    MovementQueryParams, SearchOptions, StockHistoryQueryParams, StockOptions, SyncQueryParams, ValuationOptions,
+};
+use crate::request_id::propagate_request_id;
+use crate::response::{error_response, health_response, negotiate_envelope, paginated_response, select_fields, success_response, EnvelopeVersion};
+use crate::stock_snapshots::spawn_stock_snapshots;
+use crate::tls::TlsListener;
+use crate::tables::{
+    CloneGoodRequest, CreateGoodRequest, UpdateGoodRequest, UpsertGoodRequest, GoodsConflictMode, CreateInventoryRequest, InventoryInsertOutcome, UpdateInventoryRequest, AdjustInventoryRequest, QuantityChangeRequest,
+    ConsumeInventoryRequest, CreateReservationRequest, CreateCategoryRequest, UpdateCategoryRequest, CreatePurchaseOrderRequest,
+    ReceivePurchaseOrderRequest, TableError, SyncEntity, SyncPage, Good, GoodWithStock, GoodsSearchParams, InventoryItemWithGoods, ExpiredPurgeResult, GoodsRepository,
+    InventoryRepository,
+};
+use crate::utils::{logging::*, response::*, validation::{parse_safe_integer, validate_barcode}};
+use crate::webhooks::spawn_delivery;
 use axum::{
-    extract::{Query, State},
+    error_handling::HandleErrorLayer,
+    extract::{DefaultBodyLimit, Path, State},
+    http::{HeaderMap, Method, StatusCode, Uri},
+    middleware,
     response::Response,
     routing::{get, post, put, delete},
-    Json, Router,
+    serve::ListenerExt,
+    Extension, Router,
 };
+use chrono::Utc;
 use std::collections::HashMap;
-use tower::ServiceBuilder;
-use tower_http::cors::CorsLayer;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::{limit::GlobalConcurrencyLimitLayer, load_shed::LoadShedLayer, ServiceBuilder};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    compression::{predicate::{NotForContentType, Predicate, SizeAbove}, CompressionLayer},
+    cors::CorsLayer,
+    limit::RequestBodyLimitLayer,
+    timeout::TimeoutLayer,
+};
 use tracing::{info, warn};
 
+#[cfg(feature = "openapi")]
+mod openapi;
+
+// Types referenced only by the OpenAPI annotations on the handlers below
+#[cfg(feature = "openapi")]
+use crate::{
+    build_info::BuildInfo,
+    response::{ApiResponse, ErrorResponse, HealthResponse},
+    tables::{
+        Alert, AuditEntry, Category, ConsumeResult, GoodsBatchResult, GoodsCacheStats, GoodsDeleteByIdsResult, InventoryBatchResult, InventoryDeleteByIdsResult, InventoryStats, InventorySummary,
+        GoodsMergeResult, LowStockGoods, MergedInventoryGroup, ValuationReport, PurchaseOrder, PurchaseOrderReceipt, Reservation, SnapshotSummary, StockSnapshot, TagCount, DuplicateMaterialCode, StockMovement, SavedGood,
+    },
+};
+
 #[derive(Clone)]
 pub struct AppState {
     pub database: Database,
+    pub config: Arc<AppConfig>,
+    /// Goods and inventory as used by the handlers; the database tables unless replaced
+    pub goods: Arc<dyn GoodsRepository>,
+    pub inventory: Arc<dyn InventoryRepository>,
+    /// Maintenance mode, starting from `server.read_only`
+    pub read_only: Arc<AtomicBool>,
+}
+
+impl AppState {
+    pub fn new(database: Database, config: Arc<AppConfig>) -> Self {
+        Self {
+            goods: Arc::new(database.goods_table.clone()),
+            inventory: Arc::new(database.inventory_table.clone()),
+            read_only: Arc::new(AtomicBool::new(config.server.read_only)),
+            database,
+            config,
+        }
+    }
+
+    /// Serve goods and inventory from other implementations, e.g. the fakes in `testing`
+    pub fn with_repositories(mut self, goods: Arc<dyn GoodsRepository>, inventory: Arc<dyn InventoryRepository>) -> Self {
+        self.goods = goods;
+        self.inventory = inventory;
+        self
+    }
 }
 
 pub struct Server {
@@ -35,416 +114,3355 @@ impl Server {
         let host = self.config.server.host.clone();
         let port = self.config.server.port;
         
-        let app_state = AppState {
-            database: self.database,
-        };
+        let app_state = AppState::new(self.database, Arc::new(self.config));
 
-        let app = Self::create_router(app_state);
+        spawn_cleanup(app_state.database.idempotency_table.clone());
+        spawn_expired_purge(app_state.database.inventory_table.clone(), app_state.config.expired_purge.clone())?;
+        spawn_stock_snapshots(app_state.database.stock_snapshots_table.clone(), app_state.config.stock_snapshot.clone())?;
+        spawn_expiry_alerts(
+            app_state.database.alerts_table.clone(),
+            app_state.config.alerts.clone(),
+            app_state.config.webhooks.secret.clone(),
+        )?;
+        if app_state.config.webhooks.is_enabled() {
+            spawn_delivery(app_state.database.webhook_outbox_table.clone(), app_state.config.webhooks.clone())?;
+        }
 
-        let listener = tokio::net::TcpListener::bind(format!("{}:{}", host, port)).await?;
+        let base_path = app_state.config.server.base_path.clone();
+        let tls = app_state.config.server.tls.clone();
+        let app = match &base_path {
+            Some(base_path) => Router::new().nest(base_path, Self::create_router(app_state)).fallback(route_not_found),
+            None => Self::create_router(app_state),
+        };
 
-        info!("Server running on {}:{}", host, port);
+        let listener = tokio::net::TcpListener::bind(format!("{}:{}", host, port)).await?;
 
-        axum::serve(listener, app).await?;
+        // The peer address is recorded in the audit log
+        let app = app.into_make_service_with_connect_info::<SocketAddr>();
+        match &tls {
+            Some(tls) => {
+                let listener = TlsListener::new(listener, tls)?;
+                info!("Server running on https://{}:{}{}", host, port, base_path.as_deref().unwrap_or_default());
+                // axum provides `ConnectInfo<SocketAddr>` for custom listeners only through `tap_io`
+                axum::serve(listener.tap_io(|_| {}), app).await?;
+            }
+            None => {
+                info!("Server running on http://{}:{}{}", host, port, base_path.as_deref().unwrap_or_default());
+                axum::serve(listener, app).await?;
+            }
+        }
 
         Ok(())
     }
 
-    fn create_router(state: AppState) -> Router {
-        Router::new()
+    /// All routes and middleware, with `state` applied. Embedders can mount the result in their
+    /// own app, e.g. with `Router::nest`; `run` does so for `server.base_path`.
+    pub fn create_router(state: AppState) -> Router {
+        let server_config = &state.config.server;
+        let envelope_version = if server_config.legacy_envelope {
+            EnvelopeVersion::V1
+        } else {
+            EnvelopeVersion::V2
+        };
+
+        let request_timeout = Duration::from_secs(server_config.request_timeout_secs);
+        let long_request_timeout = Duration::from_secs(server_config.long_request_timeout_secs);
+
+        // Batch and import endpoints take whole arrays or files, so they get a higher body limit
+        // and the long timeout
+        let batch_routes = Router::new()
+            .route("/goods/batch", post(create_goods_batch))
+            .route("/inventory/batch", post(create_inventory_batch))
+            .route("/inventory/import", post(import_inventory_csv))
+            .route_layer(middleware::from_fn_with_state(state.clone(), audit_mutations))
+            .layer(RequestBodyLimitLayer::new(server_config.max_batch_body_bytes))
+            .layer(TimeoutLayer::with_status_code(StatusCode::GATEWAY_TIMEOUT, long_request_timeout));
+
+        // Exports cover whole tables, so they get the long timeout too
+        let export_routes = Router::new()
+            .route("/goods/export.csv", get(export_goods_csv))
+            .route("/goods/export.ndjson", get(export_goods_ndjson))
+            .route("/inventory/export.csv", get(export_inventory_csv))
+            .route("/inventory/export.ndjson", get(export_inventory_ndjson))
+            .layer(TimeoutLayer::with_status_code(StatusCode::GATEWAY_TIMEOUT, long_request_timeout));
+
+        let router = Router::new()
             .route("/", get(api_health))
             .route("/health", get(database_health))
+            .route("/version", get(get_version))
             // Goods routes
             .route("/goods", get(get_goods))
-            .route("/goods", post(create_goods))
+            .route("/goods", post(create_goods).layer(middleware::from_fn_with_state(state.clone(), idempotent)))
             .route("/goods", put(update_goods))
             .route("/goods", delete(delete_goods))
+            .route("/goods/by-ids", delete(delete_goods_by_ids))
+            // The router allows one parameter name per segment, so the upsert reads its
+            // material_code from the `{goods_id}` segment
+            .route("/goods/{goods_id}", put(upsert_goods).patch(patch_goods))
+            .route("/goods/{goods_id}/archive", post(archive_goods))
+            .route("/goods/{goods_id}/unarchive", post(unarchive_goods))
+            .route("/goods/{goods_id}/merge-into/{target_id}", post(merge_goods))
+            .route("/goods/{goods_id}/clone", post(clone_goods))
+            .route("/goods/by-barcode/{barcode}", get(get_goods_by_barcode))
+            .route("/goods/tags", get(get_goods_tags))
+            .route("/goods/duplicates", get(get_goods_duplicates))
             // Inventory routes
             .route("/inventory", get(get_inventory))
-            .route("/inventory", post(create_inventory))
+            .route("/inventory", post(create_inventory).layer(middleware::from_fn_with_state(state.clone(), idempotent)))
             .route("/inventory", put(update_inventory))
             .route("/inventory", delete(delete_inventory))
+            .route("/inventory/by-ids", delete(delete_inventory_by_ids))
+            .route("/inventory/consume", post(consume_inventory))
+            .route("/inventory/merge-duplicates", post(merge_duplicate_inventory))
+            .route("/inventory/stats", get(get_inventory_stats))
+            .route("/inventory/summary", get(get_inventory_summary))
+            .route("/inventory/{item_id}", get(get_inventory_item).patch(patch_inventory_item))
+            .route("/inventory/{item_id}/adjust", post(adjust_inventory))
+            .route("/inventory/{item_id}/decrement", post(decrement_inventory))
+            .route("/inventory/{item_id}/increment", post(increment_inventory))
+            .route("/inventory/{item_id}/movements", get(get_inventory_item_movements))
+            // Stock movement routes
+            .route("/movements", get(get_movements))
+            // Sync routes
+            .route("/sync/goods", get(sync_goods))
+            .route("/sync/inventory", get(sync_inventory))
+            // Report routes
+            .route("/reports/low-stock", get(get_low_stock_report))
+            .route("/reports/valuation", get(get_valuation_report))
+            .route("/reports/goods-cache", get(get_goods_cache_stats))
+            .route("/reports/stock-history", get(get_stock_history))
+            // Category routes
+            .route("/categories", get(get_categories))
+            .route("/categories", post(create_category))
+            .route("/categories/{category_id}", get(get_category))
+            .route("/categories/{category_id}", put(update_category))
+            .route("/categories/{category_id}", delete(delete_category))
+            // Purchase order routes
+            .route("/purchase-orders", post(create_purchase_order))
+            .route("/purchase-orders/{purchase_order_id}", get(get_purchase_order))
+            .route("/purchase-orders/{purchase_order_id}/order", post(place_purchase_order))
+            .route("/purchase-orders/{purchase_order_id}/receive", post(receive_purchase_order))
+            // Reservation routes
+            .route("/reservations", post(create_reservation))
+            .route("/reservations/{reservation_id}", delete(release_reservation))
+            .route("/reservations/{reservation_id}/commit", post(commit_reservation))
+            // Alert routes
+            .route("/alerts", get(get_alerts))
+            .route("/alerts/{alert_id}/acknowledge", post(acknowledge_alert))
+            // Audit routes
+            .route("/audit", get(get_audit_log))
+            // Admin routes
+            .route("/admin/purge-expired", post(purge_expired_inventory))
+            .route("/admin/snapshot", post(take_stock_snapshot))
+            .route("/admin/maintenance", post(set_maintenance_mode))
+            // Inside the body limit, so the audited body is capped; unmatched routes are not audited
+            .route_layer(middleware::from_fn_with_state(state.clone(), audit_mutations))
+            .layer(RequestBodyLimitLayer::new(server_config.max_body_bytes))
+            .layer(TimeoutLayer::with_status_code(StatusCode::GATEWAY_TIMEOUT, request_timeout))
+            .merge(batch_routes)
+            .merge(export_routes)
+            // The per-route limits above replace axum's built-in extractor limit
+            .layer(DefaultBodyLimit::disable());
+
+        // API documentation: spec at /openapi.json, Swagger UI at /docs
+        #[cfg(feature = "openapi")]
+        let router = router.merge(openapi::docs_router());
+
+        // Unknown paths and methods get the JSON envelope instead of axum's plain-text errors
+        let router = router.fallback(route_not_found).method_not_allowed_fallback(method_not_allowed);
+
+        // Compress JSON and the streamed exports; tiny bodies are not worth it
+        let router = if server_config.compression_enabled {
+            let predicate = SizeAbove::new(server_config.compression_min_bytes)
+                .and(NotForContentType::IMAGES)
+                .and(NotForContentType::SSE);
+            router.layer(CompressionLayer::new().gzip(true).br(true).compress_when(predicate))
+        } else {
+            router
+        };
+
+        router
             .layer(
                 ServiceBuilder::new()
+                    .layer(middleware::from_fn(propagate_request_id))
+                    .layer(middleware::from_fn(add_version_header))
+                    .layer(middleware::from_fn_with_state(envelope_version, negotiate_envelope))
+                    // Inside the request id and envelope scopes, so the 500 carries both
+                    .layer(CatchPanicLayer::custom(panic_as_json))
+                    .layer(middleware::from_fn(payload_too_large_as_json))
+                    .layer(middleware::from_fn(gateway_timeout_as_json))
+                    // Refuse requests past the in-flight limit with 503 instead of queueing them
+                    .layer(HandleErrorLayer::new(overloaded_as_json))
+                    .layer(LoadShedLayer::new())
+                    .layer(GlobalConcurrencyLimitLayer::new(server_config.max_in_flight_requests))
                     .layer(CorsLayer::permissive())
+                    .layer(middleware::from_fn_with_state(state.clone(), require_api_key))
+                    // After authentication, so unauthenticated writes still get 401
+                    .layer(middleware::from_fn_with_state(state.clone(), reject_writes_in_maintenance))
             )
             .with_state(state)
     }
 }
 
+/// Top-level paths listed by the 404 for an unknown route
+const TOP_LEVEL_ROUTES: &[&str] = &[
+    "/", "/health", "/version", "/goods", "/inventory", "/movements", "/sync", "/reports", "/categories",
+    "/purchase-orders", "/reservations", "/alerts", "/audit", "/admin",
+    #[cfg(feature = "openapi")]
+    "/docs",
+    #[cfg(feature = "openapi")]
+    "/openapi.json",
+];
+
+// Fallback: JSON 404 for paths no route matches
+async fn route_not_found(method: Method, uri: Uri) -> Response {
+    warn!("No route for {} {}", method, uri.path());
+    error_response(
+        StatusCode::NOT_FOUND,
+        "NOT_FOUND",
+        &format!("No route for {} {}", method, uri.path()),
+        Some(serde_json::json!({ "available_routes": TOP_LEVEL_ROUTES })),
+    )
+}
+
+// Fallback: JSON 405 for a known path without a handler for the method
+async fn method_not_allowed(method: Method, uri: Uri) -> ApiError {
+    warn!("Method {} not allowed for {}", method, uri.path());
+    ApiError::MethodNotAllowed(format!("Method {} is not allowed for {}", method, uri.path()))
+}
+
 // Route: GET / - API health check
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/",
+    tag = "health",
+    responses(
+        (status = 200, description = "API is working")
+    )
+))]
 async fn api_health() -> Response {
     info!("API health check requested");
     success_response(
         serde_json::json!({
             "status": "working",
             "service": "Rust API",
-            "version": "1.0.0"
+            "version": PACKAGE_VERSION,
+            "git_sha": short_sha()
         }),
         "API is working correctly"
     )
 }
 
+// Route: GET /version - Build details of the running server
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/version",
+    tag = "health",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<BuildInfo>)
+    )
+))]
+async fn get_version() -> Response {
+    info!("Version requested");
+    success_response(build_info(), "Build information retrieved successfully")
+}
+
 // Route: GET /health - Database health check
-async fn database_health(State(state): State<AppState>) -> Response {
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    params(("verbose" = Option<bool>, Query, description = "Include pool usage, query latency and table checks (default false)")),
+    responses(
+        (status = 200, description = "Healthy, or degraded: slow queries or no free pool connections", body = HealthResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 503, description = "Unhealthy: database or read replica unreachable, or a core table missing", body = HealthResponse)
+    )
+))]
+async fn database_health(
+    State(state): State<AppState>,
+    ApiQuery(options): ApiQuery<HealthOptions>,
+) -> Result<Response, ApiError> {
     info!("Database health check requested");
-    
-    match state.database.health_check().await {
+
+    let verbose = options.verbose().map_err(|parse_error| {
+        log_validation_error("health check", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    let (primary, replica) = tokio::join!(
+        state.database.health_check(),
+        state.database.replica_health_check()
+    );
+
+    match &primary.error {
+        None if primary.is_usable() => info!("Database health check passed"),
+        None => warn!("Database health check failed: missing tables {:?}", primary.tables.iter().filter(|table| !table.exists).map(|table| &table.table).collect::<Vec<_>>()),
+        Some(error) => warn!("Database health check failed: {}", error),
+    }
+
+    let replica_connected = replica.map(|result| match result {
         Ok(_) => {
-            info!("Database health check passed");
-            health_response(true)
+            info!("Read replica health check passed");
+            true
         }
         Err(e) => {
-            log_database_error("health check", &e);
-            health_response(false)
+            log_database_error("read replica health check", &e);
+            false
         }
-    }
+    });
+
+    Ok(health_response(primary, replica_connected, state.read_only.load(Ordering::Relaxed), verbose))
 }
 
 // GOODS ROUTES
 
 // Route: POST /goods - Create new goods
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/goods",
+    tag = "goods",
+    params(
+        ("on_conflict" = Option<String>, Query, description = "When the material_code exists: return_existing (default) returns it with created=false, error returns 409, update overwrites it"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replay the stored response when a request is retried with the same key")
+    ),
+    request_body = CreateGoodRequest,
+    responses(
+        (status = 200, description = "The good, with created=false when the material_code already existed", body = ApiResponse<SavedGood>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 409, description = "Conflict", body = ErrorResponse),
+        (status = 422, description = "Idempotency-Key reused with a different body", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
 async fn create_goods(
     State(state): State<AppState>,
-    Json(request): Json<CreateGoodRequest>,
-) -> Response {
+    ApiQuery(options): ApiQuery<GoodsCreateOptions>,
+    JsonBody(mut request): JsonBody<CreateGoodRequest>,
+) -> Result<Response, ApiError> {
     log_request_params("create goods", &request);
 
     // Validate request
-    match request.validate() {
-        Ok(_) => {}
-        Err(validation_error) => {
-            log_validation_error("create goods", &validation_error);
-            return ErrorResponse::bad_request(&validation_error);
+    let on_conflict = options.on_conflict().map_err(|parse_error| {
+        log_validation_error("create goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    request.normalize(state.config.server.normalization());
+    request.validate(&state.config.validation).map_err(|validation_error| {
+        log_validation_error("create goods", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    // Insert goods; a unique violation (on_conflict=error, or a concurrent insert) maps to 409
+    let saved = state.goods.insert(request, on_conflict).await.map_err(|e| match e {
+        TableError::Database(e) => {
+            log_database_error("create goods", &e);
+            ApiError::database(e, "goods creation")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    match (saved.created, on_conflict) {
+        (true, _) => {
+            log_success("create goods (new)", &saved, 1);
+            Ok(success_response(saved, &format_success_message("Goods creation", 1)))
+        }
+        (false, GoodsConflictMode::Update) => {
+            log_success("create goods (updated existing)", &saved, 1);
+            Ok(success_response(saved, "Goods with the same material_code already existed and was updated."))
+        }
+        (false, _) => {
+            log_success("create goods (existing found)", &saved, 1);
+            Ok(success_response(
+                saved,
+                "Goods with the same material_code already exists. Returning existing goods unchanged."
+            ))
+        }
+    }
+}
+
+// Route: PUT /goods/{material_code} - Create or fully replace the good with this material code
+#[cfg_attr(feature = "openapi", utoipa::path(
+    put,
+    path = "/goods/{material_code}",
+    tag = "goods",
+    params(("material_code" = String, Path, description = "Material code of the good to create or replace")),
+    request_body = UpsertGoodRequest,
+    responses(
+        (status = 200, description = "The good, with created=true when it was inserted", body = ApiResponse<SavedGood>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn upsert_goods(
+    State(state): State<AppState>,
+    Path(material_code): Path<String>,
+    JsonBody(request): JsonBody<UpsertGoodRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("upsert goods", &request);
+
+    // Validate request
+    let mut request = request.into_create_request(material_code);
+    request.normalize(state.config.server.normalization());
+    request.validate(&state.config.validation).map_err(|validation_error| {
+        log_validation_error("upsert goods", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    // Insert or replace goods
+    let result = state.goods.upsert_by_material_code(request).await.map_err(|e| match e {
+        TableError::Database(e) => {
+            log_database_error("upsert goods", &e);
+            ApiError::database(e, "goods upsert")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
         }
+    })?;
+
+    log_success("upsert goods", &result, 1);
+    let message = if result.created { "Goods creation" } else { "Goods update" };
+    Ok(success_response(result, &format_success_message(message, 1)))
+}
+
+// Route: GET /goods/by-barcode/{barcode} - Look up the good for a scanned barcode
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/goods/by-barcode/{barcode}",
+    tag = "goods",
+    params(
+        ("barcode" = String, Path, description = "EAN-8, EAN-13 or Code128 barcode"),
+        ("include_stock" = Option<bool>, Query, description = "Add the good's total_quantity, lot_count and earliest_expired_date over its inventory rows")
+    ),
+    responses(
+        (status = 200, description = "Success; a GoodWithStock with include_stock=true", body = ApiResponse<Good>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "No goods with this barcode", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_goods_by_barcode(
+    State(state): State<AppState>,
+    Path(barcode): Path<String>,
+    ApiQuery(options): ApiQuery<StockOptions>,
+) -> Result<Response, ApiError> {
+    log_request_params("get goods by barcode", &barcode);
+
+    // Validate path parameter
+    validate_barcode(&barcode, "barcode").map_err(|validation_error| {
+        log_validation_error("get goods by barcode", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+    let include_stock = options.include_stock().map_err(|parse_error| {
+        log_validation_error("get goods by barcode", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    // The totals are not cached, so a lookup with stock always searches
+    if include_stock {
+        // A direct lookup, so archived goods are found as well
+        let search_params = GoodsSearchParams { barcode: Some(barcode.clone()), include_inactive: true, ..GoodsSearchParams::new() };
+        let goods = state.goods.search_with_stock(search_params).await.map_err(|e| {
+            log_database_error("get goods by barcode", &e);
+            ApiError::database(e, "goods lookup")
+        })?;
+        return barcode_lookup_response(goods.into_iter().next(), &barcode);
     }
 
-    // Insert goods
-    match state.database.goods_table.insert(request).await {
-        Ok(goods) => {
-            log_success("create goods", &goods, 1);
-            success_response(goods, &format_success_message("Goods creation", 1))
+    let good = state.goods.get_by_barcode(&barcode).await.map_err(|e| {
+        log_database_error("get goods by barcode", &e);
+        ApiError::database(e, "goods lookup")
+    })?;
+    barcode_lookup_response(good, &barcode)
+}
+
+fn barcode_lookup_response<T: serde::Serialize + std::fmt::Debug>(good: Option<T>, barcode: &str) -> Result<Response, ApiError> {
+    match good {
+        Some(good) => {
+            log_success("get goods by barcode", &good, 1);
+            Ok(success_response(good, &format_success_message("Goods lookup", 1)))
         }
-        Err(e) => {
-            log_database_error("create goods", &e);
-            ErrorResponse::internal_server_error(&format_database_error(&e, "goods creation"))
+        None => {
+            warn!("No goods with barcode {}", barcode);
+            Err(ApiError::NotFound(format!("No goods with barcode {}", barcode)))
+        }
+    }
+}
+
+// Route: GET /goods/tags - Distinct goods tags with usage counts
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/goods/tags",
+    tag = "goods",
+    responses(
+        (status = 200, description = "Tags, most used first", body = ApiResponse<Vec<TagCount>>),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_goods_tags(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let tags = state.goods.tag_counts().await.map_err(|e| {
+        log_database_error("get goods tags", &e);
+        ApiError::database(e, "goods tag lookup")
+    })?;
+
+    let count = tags.len();
+    log_success("get goods tags", &tags, count);
+    Ok(success_response(tags, &format_success_message("Goods tag lookup", count)))
+}
+
+// Route: GET /goods/duplicates - Goods whose material codes collide once normalized
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/goods/duplicates",
+    tag = "goods",
+    responses(
+        (status = 200, description = "Groups of goods sharing a normalized material code", body = ApiResponse<Vec<DuplicateMaterialCode>>),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_goods_duplicates(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let groups = state.goods.duplicate_material_codes(state.config.server.normalization()).await.map_err(|e| {
+        log_database_error("get goods duplicates", &e);
+        ApiError::database(e, "duplicate goods lookup")
+    })?;
+
+    let count = groups.len();
+    log_success("get goods duplicates", &groups, count);
+    Ok(success_response(groups, &format_success_message("Duplicate goods lookup", count)))
+}
+
+// Route: PATCH /goods/{goods_id} - Update a single good by id
+#[cfg_attr(feature = "openapi", utoipa::path(
+    patch,
+    path = "/goods/{goods_id}",
+    tag = "goods",
+    params(("goods_id" = i32, Path, description = "Goods id")),
+    request_body = UpdateGoodRequest,
+    responses(
+        (status = 200, description = "Updated good", body = ApiResponse<Good>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Stale expected_version or duplicate barcode", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn patch_goods(
+    State(state): State<AppState>,
+    Path(goods_id): Path<String>,
+    JsonBody(mut request): JsonBody<UpdateGoodRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("patch goods", &(&goods_id, &request));
+
+    // Validate path parameter
+    let goods_id = parse_safe_integer(&goods_id, "goods_id").map_err(|parse_error| {
+        log_validation_error("patch goods", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    request.normalize(state.config.server.normalization());
+    request.validate(&state.config.validation).map_err(|validation_error| {
+        log_validation_error("patch goods", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    let good = state.goods.update_by_id(goods_id, request).await.map_err(|e| match e {
+        TableError::Database(e) => {
+            log_database_error("patch goods", &e);
+            ApiError::database(e, "goods update")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    match good {
+        Some(good) => {
+            log_success("patch goods", &good, 1);
+            Ok(success_response(good, &format_success_message("Goods update", 1)))
+        }
+        None => {
+            warn!("Goods {} not found", goods_id);
+            Err(ApiError::NotFound(format!("Goods {} not found", goods_id)))
+        }
+    }
+}
+
+// Route: POST /goods/{goods_id}/archive - Hide a good from searches without deleting it
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/goods/{goods_id}/archive",
+    tag = "goods",
+    params(("goods_id" = i32, Path, description = "Goods id")),
+    responses(
+        (status = 200, description = "Archived good", body = ApiResponse<Good>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn archive_goods(State(state): State<AppState>, Path(goods_id): Path<String>) -> Result<Response, ApiError> {
+    set_goods_active(&state, &goods_id, false, "archive goods").await
+}
+
+// Route: POST /goods/{goods_id}/unarchive - Return an archived good to searches
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/goods/{goods_id}/unarchive",
+    tag = "goods",
+    params(("goods_id" = i32, Path, description = "Goods id")),
+    responses(
+        (status = 200, description = "Restored good", body = ApiResponse<Good>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn unarchive_goods(State(state): State<AppState>, Path(goods_id): Path<String>) -> Result<Response, ApiError> {
+    set_goods_active(&state, &goods_id, true, "unarchive goods").await
+}
+
+/// Set a good's `is_active` flag; an update of that one field
+async fn set_goods_active(state: &AppState, goods_id: &str, is_active: bool, operation: &str) -> Result<Response, ApiError> {
+    log_request_params(operation, &goods_id);
+
+    // Validate path parameter
+    let goods_id = parse_safe_integer(goods_id, "goods_id").map_err(|parse_error| {
+        log_validation_error(operation, &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    let request = UpdateGoodRequest { is_active: Some(is_active), ..UpdateGoodRequest::default() };
+    let good = state.goods.update_by_id(goods_id, request).await.map_err(|e| match e {
+        TableError::Database(e) => {
+            log_database_error(operation, &e);
+            ApiError::database(e, "goods update")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    match good {
+        Some(good) => {
+            log_success(operation, &good, 1);
+            Ok(success_response(good, &format_success_message("Goods update", 1)))
+        }
+        None => {
+            warn!("Goods {} not found", goods_id);
+            Err(ApiError::NotFound(format!("Goods {} not found", goods_id)))
+        }
+    }
+}
+
+// Route: POST /goods/{goods_id}/clone - Create a good from an existing one with a few fields changed
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/goods/{goods_id}/clone",
+    tag = "goods",
+    params(("goods_id" = i32, Path, description = "Good to copy")),
+    request_body = CloneGoodRequest,
+    responses(
+        (status = 200, description = "The new good", body = ApiResponse<Good>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "The material_code or barcode is already taken", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn clone_goods(
+    State(state): State<AppState>,
+    Path(goods_id): Path<String>,
+    JsonBody(request): JsonBody<CloneGoodRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("clone goods", &(&goods_id, &request));
+
+    // Validate path parameter
+    let goods_id = parse_safe_integer(&goods_id, "goods_id").map_err(|parse_error| {
+        log_validation_error("clone goods", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    let source = state.goods.get_by_id(goods_id).await.map_err(|e| {
+        log_database_error("clone goods", &e);
+        ApiError::database(e, "goods lookup")
+    })?;
+    let Some(source) = source else {
+        warn!("Goods {} not found", goods_id);
+        return Err(ApiError::NotFound(format!("Goods {} not found", goods_id)));
+    };
+
+    // The copy is validated like any new good
+    let mut request = request.into_create_request(source);
+    request.normalize(state.config.server.normalization());
+    request.validate(&state.config.validation).map_err(|validation_error| {
+        log_validation_error("clone goods", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    // An existing material_code is a unique violation, reported as 409
+    let saved = state.goods.insert(request, GoodsConflictMode::Error).await.map_err(|e| match e {
+        TableError::Database(e) => {
+            log_database_error("clone goods", &e);
+            ApiError::database(e, "goods creation")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
         }
+    })?;
+
+    log_success("clone goods", &saved.good, 1);
+    Ok(success_response(saved.good, &format_success_message("Goods clone", 1)))
+}
+
+// Route: POST /goods/{goods_id}/merge-into/{target_id} - Fold a duplicate good into another
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/goods/{goods_id}/merge-into/{target_id}",
+    tag = "goods",
+    params(
+        ("goods_id" = i32, Path, description = "Duplicate good, deleted by the merge"),
+        ("target_id" = i32, Path, description = "Good that takes over the source's inventory")
+    ),
+    responses(
+        (status = 200, description = "The merged target with the moved and merged inventory items", body = ApiResponse<GoodsMergeResult>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Requires the admin role", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn merge_goods(
+    State(state): State<AppState>,
+    Extension(role): Extension<Role>,
+    Path((source_id, target_id)): Path<(String, String)>,
+) -> Result<Response, ApiError> {
+    role.require(Role::Admin, "Merging goods").inspect_err(|e| warn!("{}", e))?;
+    log_request_params("merge goods", &(&source_id, &target_id));
+
+    // Validate path parameters
+    let (source_id, target_id) = parse_safe_integer(&source_id, "goods_id")
+        .and_then(|source_id| Ok((source_id, parse_safe_integer(&target_id, "target_id")?)))
+        .and_then(|(source_id, target_id)| {
+            if source_id == target_id {
+                return Err("Cannot merge a good into itself".to_string());
+            }
+            Ok((source_id, target_id))
+        })
+        .map_err(|validation_error| {
+            log_validation_error("merge goods", &validation_error);
+            ApiError::Validation(validation_error)
+        })?;
+
+    let result = state.inventory.merge_goods(source_id, target_id).await.map_err(|e| {
+        log_database_error("merge goods", &e);
+        ApiError::database(e, "goods merge")
+    })?;
+
+    match result {
+        Some(result) => {
+            let count = result.moved_item_ids.len() + result.merged_items.len();
+            log_success("merge goods", &result, count);
+            let message = format!(
+                "Merged goods {} into {}: moved {} inventory items, merged {} into existing items",
+                source_id, target_id, result.moved_item_ids.len(), result.merged_items.len()
+            );
+            Ok(success_response(result, &message))
+        }
+        None => {
+            warn!("Goods {} or {} not found", source_id, target_id);
+            Err(ApiError::NotFound(format!("Goods {} or {} not found", source_id, target_id)))
+        }
+    }
+}
+
+// Route: POST /goods/batch - Create many goods in one transaction
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/goods/batch",
+    tag = "goods",
+    params(("on_error" = Option<String>, Query, description = "abort (default) rejects the batch on any invalid entry; skip processes the valid ones")),
+    request_body = Vec<CreateGoodRequest>,
+    responses(
+        (status = 200, description = "Success; results has one entry per payload index. Also lists every target in results (id, status ok|failed, error_code, message) with partial set when only some failed", body = ApiResponse<GoodsBatchResult>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn create_goods_batch(
+    State(state): State<AppState>,
+    ApiQuery(options): ApiQuery<GoodsBatchOptions>,
+    JsonBody(requests): JsonBody<Vec<CreateGoodRequest>>,
+) -> Result<Response, ApiError> {
+    log_request_params("create goods batch", &requests.len());
+
+    // Validate batch size and error mode
+    let error_mode = validate_batch_size(requests.len(), state.config.server.max_batch_size)
+        .and_then(|_| options.on_error())
+        .map_err(|validation_error| {
+            log_validation_error("create goods batch", &validation_error);
+            ApiError::Validation(validation_error)
+        })?;
+
+    // Validate every entry before touching the database
+    let (entries, errors) = validate_goods_batch(requests, state.config.server.normalization(), &state.config.validation);
+    if !errors.is_empty() && error_mode == BatchErrorMode::Abort {
+        log_validation_error("create goods batch", &format!("{} invalid entries", errors.len()));
+        return Err(ApiError::InvalidBatch(errors));
+    }
+
+    // Insert the valid entries
+    let indexes: HashMap<String, usize> = entries.iter().map(|(index, request)| (request.material_code.clone(), *index)).collect();
+    let mut result = state.goods.insert_batch(entries).await.map_err(|e| {
+        log_database_error("create goods batch", &e);
+        ApiError::database(e, "batch goods creation")
+    })?;
+    result.errors = errors;
+
+    let created_count = result.created.len();
+    log_success("create goods batch", &result, created_count);
+    let results = result.target_results(&indexes);
+    Ok(success_response(bulk_response(result, results), &format_success_message("Batch goods creation", created_count)))
+}
+
+// Route: GET /goods/export.csv - Stream goods search results as CSV
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/goods/export.csv",
+    tag = "goods",
+    params(GoodsQueryParams),
+    responses(
+        (status = 200, description = "CSV attachment", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid request", body = ErrorResponse)
+    )
+))]
+async fn export_goods_csv(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<FilterQuery<GoodsQueryParams>>,
+) -> Result<Response, ApiError> {
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("export goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
+    log_request_params("export goods", &query_params);
+
+    // Check if no parameters provided
+    if !query_params.has_any_params() {
+        let error = "Query parameters required. Use goods_name=* or material_code=* to export all goods, or specify the same search criteria as GET /goods";
+        log_validation_error("export goods", error);
+        return Err(ApiError::Validation(error.to_string()));
+    }
+
+    // Validate and parse query parameters
+    let search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("export goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    info!("Streaming goods CSV export");
+    Ok(csv_response("goods", state.goods.search_stream(&search_params)))
+}
+
+// Route: GET /goods/export.ndjson - Stream goods search results as NDJSON
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/goods/export.ndjson",
+    tag = "goods",
+    params(GoodsQueryParams),
+    responses(
+        (status = 200, description = "One Good JSON object per line", content_type = "application/x-ndjson", body = String),
+        (status = 400, description = "Invalid request", body = ErrorResponse)
+    )
+))]
+async fn export_goods_ndjson(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<FilterQuery<GoodsQueryParams>>,
+) -> Result<Response, ApiError> {
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("export goods ndjson", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
+    log_request_params("export goods ndjson", &query_params);
+
+    // Check if no parameters provided
+    if !query_params.has_any_params() {
+        let error = "Query parameters required. Use goods_name=* or material_code=* to export all goods, or specify the same search criteria as GET /goods";
+        log_validation_error("export goods ndjson", error);
+        return Err(ApiError::Validation(error.to_string()));
     }
+
+    // Validate and parse query parameters
+    let search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("export goods ndjson", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    info!("Streaming goods NDJSON export");
+    Ok(ndjson_response(state.goods.search_stream(&search_params)))
 }
 
 // Route: PUT /goods - Update goods with query parameters
+#[cfg_attr(feature = "openapi", utoipa::path(
+    put,
+    path = "/goods",
+    tag = "goods",
+    params(
+        GoodsQueryParams,
+        ("max_affected" = Option<i32>, Query, description = "Abort with 409 when the filter matches more rows; defaults to the server cap"),
+        ("dry_run" = Option<bool>, Query, description = "Report the rows that would be updated without changing anything")
+    ),
+    request_body = UpdateGoodRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Vec<Good>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "No goods match the filter", body = ErrorResponse),
+        (status = 409, description = "Conflict", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
 async fn update_goods(
     State(state): State<AppState>,
-    query: Query<HashMap<String, String>>,
-    Json(request): Json<UpdateGoodRequest>,
-) -> Response {
-    let query_params = extract_goods_query_params(query);
+    ApiQuery(query): ApiQuery<FilterQuery<GoodsQueryParams, BulkWriteQueryOptions>>,
+    JsonBody(mut request): JsonBody<UpdateGoodRequest>,
+) -> Result<Response, ApiError> {
+    let options = query.options.parse(state.config.server.max_affected_rows).map_err(|parse_error| {
+        log_validation_error("update goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("update goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
     log_request_params("update goods", &(&query_params, &request));
 
     // Validate update request
-    match request.validate() {
-        Ok(_) => {}
-        Err(validation_error) => {
-            log_validation_error("update goods", &validation_error);
-            return ErrorResponse::bad_request(&validation_error);
-        }
-    }
+    request.normalize(state.config.server.normalization());
+    request.validate(&state.config.validation).map_err(|validation_error| {
+        log_validation_error("update goods", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
 
     // Check if no parameters provided
     if !query_params.has_any_params() {
         let error = "Query parameters required to specify which goods to update";
         log_validation_error("update goods", error);
-        return ErrorResponse::bad_request(error);
+        return Err(ApiError::Validation(error.to_string()));
     }
 
     // Validate and parse query parameters
-    let search_params = match query_params.validate_and_parse() {
-        Ok(params) => params,
-        Err(parse_error) => {
-            log_validation_error("update goods", &parse_error);
-            return ErrorResponse::bad_request(&format!("Invalid query parameters: {}", parse_error));
-        }
-    };
+    let search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("update goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
 
     // Perform database update
-    match state.database.goods_table.update(search_params, request).await {
-        Ok(updated_goods) => {
-            if updated_goods.is_empty() {
-                warn!("No goods found to update");
-                return ErrorResponse::bad_request("No goods found to update");
-            }
-            let count = updated_goods.len();
-            log_success("update goods", &updated_goods, count);
-            success_response(updated_goods, &format_success_message("Goods update", count))
-        }
-        Err(e) => {
+    let updated_goods = state.goods.update(search_params, request, options).await.map_err(|e| match e {
+        TableError::Database(e) => {
             log_database_error("update goods", &e);
-            ErrorResponse::internal_server_error(&format_database_error(&e, "goods update"))
+            ApiError::database(e, "goods update")
         }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    if updated_goods.is_empty() {
+        warn!("No goods found to update");
+        return Err(ApiError::NotFound("No goods found to update".to_string()));
     }
+
+    let count = updated_goods.len();
+    log_success("update goods", &updated_goods, count);
+    let message = if options.dry_run {
+        format!("Dry run: {} goods would be updated", count)
+    } else {
+        format_success_message("Goods update", count)
+    };
+    Ok(success_response(updated_goods, &message))
 }
 
 // Route: DELETE /goods - Delete goods with query parameters
+#[cfg_attr(feature = "openapi", utoipa::path(
+    delete,
+    path = "/goods",
+    tag = "goods",
+    params(
+        GoodsQueryParams,
+        ("max_affected" = Option<i32>, Query, description = "Abort with 409 when the filter matches more rows; defaults to the server cap"),
+        ("dry_run" = Option<bool>, Query, description = "Report the ids that would be deleted without changing anything"),
+        ("confirm" = Option<bool>, Query, description = "Must be true for a wildcard (*) delete")
+    ),
+    responses(
+        (status = 200, description = "Deleted goods ids", body = ApiResponse<Vec<i32>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "No goods match the filter", body = ErrorResponse),
+        (status = 409, description = "Goods still have inventory items, or the filter matches more than max_affected", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
 async fn delete_goods(
     State(state): State<AppState>,
-    query: Query<HashMap<String, String>>,
-) -> Response {
-    let query_params = extract_goods_query_params(query);
+    Extension(role): Extension<Role>,
+    ApiQuery(query): ApiQuery<FilterQuery<GoodsQueryParams, BulkWriteQueryOptions>>,
+) -> Result<Response, ApiError> {
+    let (options, confirm) = query.options.parse(state.config.server.max_affected_rows)
+        .and_then(|options| query.options.confirm().map(|confirm| (options, confirm)))
+        .map_err(|parse_error| {
+            log_validation_error("delete goods", &parse_error);
+            ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+        })?;
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("delete goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
     log_request_params("delete goods", &query_params);
 
     // Check if no parameters provided
     if !query_params.has_any_params() {
         let error = "Query parameters required to specify which goods to delete";
         log_validation_error("delete goods", error);
-        return ErrorResponse::bad_request(error);
+        return Err(ApiError::Validation(error.to_string()));
     }
 
     // Validate and parse query parameters
-    let search_params = match query_params.validate_and_parse() {
-        Ok(params) => params,
-        Err(parse_error) => {
-            log_validation_error("delete goods", &parse_error);
-            return ErrorResponse::bad_request(&format!("Invalid query parameters: {}", parse_error));
+    let search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("delete goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    // Wildcard deletes wipe the whole table
+    if search_params.is_get_all() {
+        role.require(Role::Admin, "Wildcard delete").inspect_err(|e| warn!("{}", e))?;
+
+        if !confirm && !options.dry_run {
+            let error = "Wildcard delete requires confirm=true";
+            log_validation_error("delete goods", error);
+            return Err(ApiError::Validation(error.to_string()));
         }
-    };
+    }
 
     // Perform database deletion
-    match state.database.goods_table.delete(search_params).await {
-        Ok(deleted_ids) => {
-            if deleted_ids.is_empty() {
-                warn!("No goods found to delete");
-                return ErrorResponse::bad_request("No goods found to delete");
-            }
-            let count = deleted_ids.len();
-            log_success("delete goods", &deleted_ids, count);
-            success_response(deleted_ids, &format_success_message("Goods deletion", count))
-        }
-        Err(e) => {
+    let deleted_ids = state.goods.delete(search_params, options).await.map_err(|e| match e {
+        TableError::Database(e) => {
             log_database_error("delete goods", &e);
-            // Check if it's a foreign key constraint violation
-            if let sqlx::Error::Database(db_err) = &e {
-                if db_err.code() == Some(std::borrow::Cow::Borrowed("23503")) {
-                    return ErrorResponse::bad_request(&crate::utils::response::format_database_error(&e, "goods deletion"));
-                }
-            }
-            ErrorResponse::internal_server_error(&format_database_error(&e, "goods deletion"))
+            ApiError::database(e, "goods deletion")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
         }
+    })?;
+
+    if deleted_ids.is_empty() {
+        warn!("No goods found to delete");
+        return Err(ApiError::NotFound("No goods found to delete".to_string()));
     }
+
+    let count = deleted_ids.len();
+    log_success("delete goods", &deleted_ids, count);
+    let message = if options.dry_run {
+        format!("Dry run: {} goods would be deleted", count)
+    } else {
+        format_success_message("Goods deletion", count)
+    };
+    Ok(success_response(deleted_ids, &message))
+}
+
+// Route: DELETE /goods/by-ids - Delete exactly the listed goods
+#[cfg_attr(feature = "openapi", utoipa::path(
+    delete,
+    path = "/goods/by-ids",
+    tag = "goods",
+    request_body = DeleteByIdsRequest,
+    responses(
+        (status = 200, description = "Deleted and missing ids; goods still referenced by inventory are kept and listed as blocked. Also lists every target in results (id, status ok|failed, error_code, message) with partial set when only some failed", body = ApiResponse<GoodsDeleteByIdsResult>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn delete_goods_by_ids(
+    State(state): State<AppState>,
+    JsonBody(request): JsonBody<DeleteByIdsRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("delete goods by ids", &request);
+
+    request.validate().map_err(|validation_error| {
+        log_validation_error("delete goods by ids", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    let result = state.goods.delete_by_ids(&request.ids).await.map_err(|e| {
+        log_database_error("delete goods by ids", &e);
+        ApiError::database(e, "goods deletion")
+    })?;
+
+    let count = result.deleted.len();
+    log_success("delete goods by ids", &result, count);
+    let results = result.target_results();
+    Ok(success_response(bulk_response(result, results), &format_success_message("Goods deletion", count)))
 }
 
 // Route: GET /goods - Get goods with query parameters
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/goods",
+    tag = "goods",
+    params(
+        GoodsQueryParams,
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return"),
+        ("format" = Option<String>, Query, description = "json (default) or csv; overrides the Accept header"),
+        ("unbounded" = Option<bool>, Query, description = "Admin only: return every match of a search without pagination instead of stopping at the server's row cap"),
+        ("fuzzy" = Option<bool>, Query, description = "Rank goods_name by trigram similarity, most similar first, with the score in each row's similarity; 400 when the database lacks pg_trgm"),
+        ("include_stock" = Option<bool>, Query, description = "Add each good's total_quantity, lot_count and earliest_expired_date over its inventory rows (zeros and null without any)")
+    ),
+    responses(
+        (status = 200, description = "Matching goods (GoodWithStock with include_stock=true) as JSON, or CSV with Accept: text/csv; with page/per_page or cursor, meta.pagination carries the page metadata and next_cursor. Without pagination, results stop at the server's row cap, flagged by meta.truncated and meta.limit (the X-Truncated-Limit header for CSV)", body = ApiResponse<Vec<Good>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "unbounded=true requires the admin role", body = ErrorResponse),
+        (status = 406, description = "Accept lists no supported type", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
 async fn get_goods(
     State(state): State<AppState>,
-    query: Query<HashMap<String, String>>,
-) -> Response {
-    let query_params = extract_goods_query_params(query);
+    Extension(role): Extension<Role>,
+    headers: HeaderMap,
+    ApiQuery(query): ApiQuery<FilterQuery<GoodsQueryParams, GoodsSearchOptions>>,
+) -> Result<Response, ApiError> {
+    let format = OutputFormat::negotiate(&headers, query.options.search.format.as_deref()).inspect_err(|e| warn!("{}", e))?;
+    let parse_options = || -> Result<_, String> {
+        let include_stock = query.options.stock.include_stock()?;
+        let known_fields = if include_stock { GoodWithStock::FIELDS } else { Good::FIELDS };
+        let search = &query.options.search;
+        Ok((search.fields(known_fields)?, search.unbounded()?, search.fuzzy()?, include_stock))
+    };
+    let (fields, unbounded, fuzzy, include_stock) = parse_options().map_err(|parse_error| {
+        log_validation_error("search goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    if unbounded {
+        role.require(Role::Admin, "Unbounded search").inspect_err(|e| warn!("{}", e))?;
+    }
+
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("search goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
     log_request_params("search goods", &query_params);
 
     // Check if no parameters provided
     if !query_params.has_any_params() {
         let error = "Query parameters required. Use goods_name=* or material_code=* to get all goods, or specify search criteria like goods_id, material_code, goods_name, price, volumn_l, mass_g, min_volumn_l, max_volumn_l, min_mass_g, max_mass_g, min_price, max_price";
         log_validation_error("search goods", error);
-        return ErrorResponse::bad_request(error);
+        return Err(ApiError::Validation(error.to_string()));
     }
 
     // Validate and parse query parameters
-    let search_params = match query_params.validate_and_parse() {
-        Ok(params) => params,
-        Err(parse_error) => {
+    let mut search_params = query_params
+        .validate_and_parse()
+        .and_then(|mut search_params| {
+            if fuzzy {
+                search_params.rank_by_similarity(state.config.server.fuzzy_search_threshold)?;
+            }
+            Ok(search_params)
+        })
+        .map_err(|parse_error| {
             log_validation_error("search goods", &parse_error);
-            return ErrorResponse::bad_request(&format!("Invalid query parameters: {}", parse_error));
-        }
-    };
+            ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+        })?;
+    search_params.pagination = search_params.pagination.map(|pagination| pagination.limited(state.config.server.page_limits()));
+
+    if include_stock {
+        return search_goods_with_stock(&state, format, search_params, fields.as_deref(), unbounded, fuzzy).await;
+    }
+
+    // Paginated requests return the page plus total count metadata
+    if search_params.pagination.is_some() {
+        let page = state.goods.search_paginated(search_params).await.map_err(|e| {
+            log_database_error("search goods", &e);
+            search_error(e, "goods search", fuzzy)
+        })?;
+
+        let count = page.data.len();
+        log_success("search goods", &page, count);
+        return render_page(format, "goods", page, fields.as_deref(), &format_success_message("Goods search", count));
+    }
+
+    // Without pagination, stop at the row cap unless an admin opted out; one extra row tells
+    // whether anything was cut off
+    let max_results = state.config.server.max_unpaginated_results;
+    if !unbounded {
+        search_params.limit = Some(max_results.saturating_add(1));
+    }
 
     // Perform database search
-    match state.database.goods_table.search(search_params).await {
-        Ok(goods) => {
-            let count = goods.len();
-            log_success("search goods", &goods, count);
-            success_response(goods, &format_success_message("Goods search", count))
-        }
-        Err(e) => {
+    let mut goods = state.goods.search(search_params).await.map_err(|e| {
+        log_database_error("search goods", &e);
+        search_error(e, "goods search", fuzzy)
+    })?;
+    let truncated_at = truncate_results(&mut goods, unbounded, max_results, "search goods");
+
+    let count = goods.len();
+    log_success("search goods", &goods, count);
+    render_rows(format, "goods", goods, fields.as_deref(), truncated_at, &format_success_message("Goods search", count))
+}
+
+/// The rest of `get_goods` for `include_stock=true`: the same search with inventory totals
+async fn search_goods_with_stock(
+    state: &AppState,
+    format: OutputFormat,
+    mut search_params: GoodsSearchParams,
+    fields: Option<&[String]>,
+    unbounded: bool,
+    fuzzy: bool,
+) -> Result<Response, ApiError> {
+    if search_params.pagination.is_some() {
+        let page = state.goods.search_with_stock_paginated(search_params).await.map_err(|e| {
             log_database_error("search goods", &e);
-            ErrorResponse::internal_server_error(&format_database_error(&e, "goods search"))
-        }
+            search_error(e, "goods search", fuzzy)
+        })?;
+
+        let count = page.data.len();
+        log_success("search goods", &page, count);
+        return render_page(format, "goods", page, fields, &format_success_message("Goods search", count));
+    }
+
+    let max_results = state.config.server.max_unpaginated_results;
+    if !unbounded {
+        search_params.limit = Some(max_results.saturating_add(1));
+    }
+
+    let mut goods = state.goods.search_with_stock(search_params).await.map_err(|e| {
+        log_database_error("search goods", &e);
+        search_error(e, "goods search", fuzzy)
+    })?;
+    let truncated_at = truncate_results(&mut goods, unbounded, max_results, "search goods");
+
+    let count = goods.len();
+    log_success("search goods", &goods, count);
+    render_rows(format, "goods", goods, fields, truncated_at, &format_success_message("Goods search", count))
+}
+
+/// `ApiError::database`, except that a fuzzy search on a database without pg_trgm is the
+/// client's 400 rather than a server error
+fn search_error(error: sqlx::Error, operation: &str, fuzzy: bool) -> ApiError {
+    // undefined_function: similarity() comes with pg_trgm
+    let missing_similarity = matches!(&error, sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("42883"));
+    if fuzzy && missing_similarity {
+        return ApiError::Validation("fuzzy search not available: the pg_trgm extension is not installed".to_string());
+    }
+
+    ApiError::database(error, operation)
+}
+
+/// Cut an unpaginated search down to `max_results` rows; returns the cap when rows were dropped
+fn truncate_results<T>(rows: &mut Vec<T>, unbounded: bool, max_results: u32, operation: &str) -> Option<u32> {
+    if unbounded || rows.len() <= max_results as usize {
+        return None;
     }
+
+    warn!("{}: truncated to {} rows; paginate or pass unbounded=true to get the rest", operation, max_results);
+    rows.truncate(max_results as usize);
+    Some(max_results)
 }
 
 // INVENTORY ROUTES
 
 // Route: GET /inventory - Get inventory with query parameters
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/inventory",
+    tag = "inventory",
+    params(
+        InventoryQueryParams,
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return"),
+        ("format" = Option<String>, Query, description = "json (default) or csv; overrides the Accept header"),
+        ("unbounded" = Option<bool>, Query, description = "Admin only: return every match of a search without pagination instead of stopping at the server's row cap"),
+        ("fuzzy" = Option<bool>, Query, description = "Rank goods_name by trigram similarity, most similar first, with the score in each row's similarity; 400 when the database lacks pg_trgm")
+    ),
+    responses(
+        (status = 200, description = "Matching items as JSON, or CSV with Accept: text/csv; with page/per_page or cursor, meta.pagination carries the page metadata and next_cursor. Without pagination, results stop at the server's row cap, flagged by meta.truncated and meta.limit (the X-Truncated-Limit header for CSV)", body = ApiResponse<Vec<InventoryItemWithGoods>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "unbounded=true requires the admin role", body = ErrorResponse),
+        (status = 406, description = "Accept lists no supported type", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
 async fn get_inventory(
     State(state): State<AppState>,
-    query: Query<HashMap<String, String>>,
-) -> Response {
-    let query_params = extract_inventory_query_params(query);
+    Extension(role): Extension<Role>,
+    headers: HeaderMap,
+    ApiQuery(query): ApiQuery<FilterQuery<InventoryQueryParams, SearchOptions>>,
+) -> Result<Response, ApiError> {
+    let format = OutputFormat::negotiate(&headers, query.options.format.as_deref()).inspect_err(|e| warn!("{}", e))?;
+    let parse_options = || -> Result<_, String> {
+        let search = &query.options;
+        Ok((search.fields(InventoryItemWithGoods::FIELDS)?, search.unbounded()?, search.fuzzy()?))
+    };
+    let (fields, unbounded, fuzzy) = parse_options().map_err(|parse_error| {
+        log_validation_error("search inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    if unbounded {
+        role.require(Role::Admin, "Unbounded search").inspect_err(|e| warn!("{}", e))?;
+    }
+
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("search inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
     log_request_params("search inventory", &query_params);
 
     // Check if no parameters provided
     if !query_params.has_any_params() {
-        let error = "Query parameters required. Use goods_name=* or material_code=* to get all inventory, or specify search criteria like item_id, goods_id, material_code, goods_name, quantity, min_quantity, max_quantity, expired_date, min_expired_date, max_expired_date, and all goods search parameters";
+        let error = "Query parameters required. Use goods_name=* or material_code=* to get all inventory, or specify search criteria like item_id, goods_id, material_code, goods_name, quantity, min_quantity, max_quantity, expired_date, min_expired_date, max_expired_date, lot_number, and all goods search parameters";
         log_validation_error("search inventory", error);
-        return ErrorResponse::bad_request(error);
+        return Err(ApiError::Validation(error.to_string()));
     }
 
     // Validate and parse query parameters
-    let search_params = match query_params.validate_and_parse() {
-        Ok(params) => params,
-        Err(parse_error) => {
+    let mut search_params = query_params
+        .validate_and_parse()
+        .and_then(|mut search_params| {
+            if fuzzy {
+                search_params.rank_by_similarity(state.config.server.fuzzy_search_threshold)?;
+            }
+            Ok(search_params)
+        })
+        .map_err(|parse_error| {
             log_validation_error("search inventory", &parse_error);
-            return ErrorResponse::bad_request(&format!("Invalid query parameters: {}", parse_error));
-        }
-    };
+            ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+        })?;
+    search_params.pagination = search_params.pagination.map(|pagination| pagination.limited(state.config.server.page_limits()));
 
-    // Perform database search
-    match state.database.inventory_table.search(search_params).await {
-        Ok(inventory) => {
-            let count = inventory.len();
-            log_success("search inventory", &inventory, count);
-            success_response(inventory, &format_success_message("Inventory search", count))
-        }
-        Err(e) => {
+    // Paginated requests return the page plus total count metadata
+    if search_params.pagination.is_some() {
+        let page = state.inventory.search_paginated(search_params).await.map_err(|e| {
             log_database_error("search inventory", &e);
-            ErrorResponse::internal_server_error(&format_database_error(&e, "inventory search"))
-        }
+            search_error(e, "inventory search", fuzzy)
+        })?;
+
+        let count = page.data.len();
+        log_success("search inventory", &page, count);
+        return render_page(format, "inventory", page, fields.as_deref(), &format_success_message("Inventory search", count));
     }
-}
 
-// Route: POST /inventory - Create new inventory item
-async fn create_inventory(
-    State(state): State<AppState>,
-    Json(request): Json<CreateInventoryRequest>,
-) -> Response {
-    log_request_params("create inventory", &request);
+    // Without pagination, stop at the row cap unless an admin opted out; one extra row tells
+    // whether anything was cut off
+    let max_results = state.config.server.max_unpaginated_results;
+    if !unbounded {
+        search_params.limit = Some(max_results.saturating_add(1));
+    }
+
+    // Perform database search
+    let mut inventory = state.inventory.search(search_params).await.map_err(|e| {
+        log_database_error("search inventory", &e);
+        search_error(e, "inventory search", fuzzy)
+    })?;
+    let truncated_at = truncate_results(&mut inventory, unbounded, max_results, "search inventory");
+
+    let count = inventory.len();
+    log_success("search inventory", &inventory, count);
+    render_rows(format, "inventory", inventory, fields.as_deref(), truncated_at, &format_success_message("Inventory search", count))
+}
+
+// Route: GET /inventory/export.csv - Stream inventory search results as CSV
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/inventory/export.csv",
+    tag = "inventory",
+    params(InventoryQueryParams),
+    responses(
+        (status = 200, description = "CSV attachment", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid request", body = ErrorResponse)
+    )
+))]
+async fn export_inventory_csv(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<FilterQuery<InventoryQueryParams>>,
+) -> Result<Response, ApiError> {
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("export inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
+    log_request_params("export inventory", &query_params);
+
+    // Check if no parameters provided
+    if !query_params.has_any_params() {
+        let error = "Query parameters required. Use goods_name=* or material_code=* to export all inventory, or specify the same search criteria as GET /inventory";
+        log_validation_error("export inventory", error);
+        return Err(ApiError::Validation(error.to_string()));
+    }
+
+    // Validate and parse query parameters
+    let search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("export inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    info!("Streaming inventory CSV export");
+    Ok(csv_response("inventory", state.inventory.search_stream(&search_params)))
+}
+
+// Route: GET /inventory/export.ndjson - Stream inventory search results as NDJSON
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/inventory/export.ndjson",
+    tag = "inventory",
+    params(InventoryQueryParams),
+    responses(
+        (status = 200, description = "One InventoryItemWithGoods JSON object per line", content_type = "application/x-ndjson", body = String),
+        (status = 400, description = "Invalid request", body = ErrorResponse)
+    )
+))]
+async fn export_inventory_ndjson(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<FilterQuery<InventoryQueryParams>>,
+) -> Result<Response, ApiError> {
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("export inventory ndjson", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
+    log_request_params("export inventory ndjson", &query_params);
+
+    // Check if no parameters provided
+    if !query_params.has_any_params() {
+        let error = "Query parameters required. Use goods_name=* or material_code=* to export all inventory, or specify the same search criteria as GET /inventory";
+        log_validation_error("export inventory ndjson", error);
+        return Err(ApiError::Validation(error.to_string()));
+    }
+
+    // Validate and parse query parameters
+    let search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("export inventory ndjson", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    info!("Streaming inventory NDJSON export");
+    Ok(ndjson_response(state.inventory.search_stream(&search_params)))
+}
+
+// Route: POST /inventory/import - Record an inventory receipt from CSV
+// (created, matched and failed entries are keyed by CSV line number)
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/inventory/import",
+    tag = "inventory",
+    params(
+        ("strict" = Option<bool>, Query, description = "Reject the whole file if any row fails"),
+        ("allow_inactive" = Option<bool>, Query, description = "Accept rows for archived goods instead of failing them")
+    ),
+    request_body(content = String, content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Entries are keyed by CSV line number. Also lists every target in results (id, status ok|failed, error_code, message) with partial set when only some failed", body = ApiResponse<InventoryBatchResult>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn import_inventory_csv(
+    State(state): State<AppState>,
+    ApiQuery(options): ApiQuery<ImportOptions>,
+    body: String,
+) -> Result<Response, ApiError> {
+    log_request_params("import inventory", &body.len());
+
+    // Validate query parameters and parse the CSV
+    let (strict, allow_inactive) = options.strict()
+        .and_then(|strict| Ok((strict, options.allow_inactive()?)))
+        .map_err(|parse_error| {
+            log_validation_error("import inventory", &parse_error);
+            ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+        })?;
+
+    let (entries, failures) = parse_inventory_csv(&body, state.config.server.normalization(), &state.config.validation)
+        .and_then(|parsed| {
+            validate_batch_size(parsed.0.len() + parsed.1.len(), state.config.server.max_batch_size)
+                .map(|_| parsed)
+        })
+        .map_err(|validation_error| {
+            log_validation_error("import inventory", &validation_error);
+            ApiError::Validation(validation_error)
+        })?;
+
+    if strict && !failures.is_empty() {
+        log_validation_error("import inventory", &format!("{} invalid rows", failures.len()));
+        return Err(ApiError::InvalidBatch(failures));
+    }
+
+    // Resolve goods by material_code and insert inside one transaction
+    let result = state.inventory
+        .insert_batch(entries, failures, strict, allow_inactive)
+        .await
+        .map_err(|e| match e {
+            TableError::Database(e) => {
+                log_database_error("import inventory", &e);
+                ApiError::database(e, "inventory import")
+            }
+            e => {
+                log_validation_error("import inventory", &e.to_string());
+                ApiError::from(e)
+            }
+        })?;
+
+    let created_count = result.created.len();
+    log_success("import inventory", &result, created_count);
+    let results = result.target_results();
+    Ok(success_response(bulk_response(result, results), &format_success_message("Inventory import", created_count)))
+}
+
+// Route: GET /inventory/stats - Aggregates over inventory matching the search filters
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/inventory/stats",
+    tag = "inventory",
+    params(InventoryQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<InventoryStats>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_inventory_stats(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<FilterQuery<InventoryQueryParams>>,
+) -> Result<Response, ApiError> {
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("inventory stats", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
+    log_request_params("inventory stats", &query_params);
+
+    // Validate and parse query parameters (no filters means all inventory)
+    let search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("inventory stats", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    // Compute aggregates
+    let stats = state.inventory.stats(&search_params).await.map_err(|e| {
+        log_database_error("inventory stats", &e);
+        ApiError::database(e, "inventory stats")
+    })?;
+
+    log_success("inventory stats", &stats, 1);
+    Ok(success_response(stats, &format_success_message("Inventory stats", 1)))
+}
+
+// Route: GET /inventory/summary - One row per goods with lot count, total quantity and earliest expiry
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/inventory/summary",
+    tag = "inventory",
+    params(InventoryQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Vec<InventorySummary>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_inventory_summary(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<FilterQuery<InventoryQueryParams>>,
+) -> Result<Response, ApiError> {
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("inventory summary", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
+    log_request_params("inventory summary", &query_params);
+
+    // Validate and parse query parameters (no filters means all inventory)
+    let summary_params = query_params.validate_and_parse_summary().map_err(|parse_error| {
+        log_validation_error("inventory summary", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    // Aggregate per goods
+    let summary = state.inventory.summary(&summary_params).await.map_err(|e| {
+        log_database_error("inventory summary", &e);
+        ApiError::database(e, "inventory summary")
+    })?;
+
+    let count = summary.len();
+    log_success("inventory summary", &summary, count);
+    Ok(success_response(summary, &format_success_message("Inventory summary", count)))
+}
+
+// Route: GET /inventory/{item_id} - Get a single inventory item
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/inventory/{item_id}",
+    tag = "inventory",
+    params(
+        ("item_id" = i32, Path, description = "Inventory item id"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return")
+    ),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<InventoryItemWithGoods>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_inventory_item(
+    State(state): State<AppState>,
+    Path(item_id): Path<String>,
+    ApiQuery(options): ApiQuery<FieldsOptions>,
+) -> Result<Response, ApiError> {
+    log_request_params("get inventory item", &item_id);
+
+    let fields = options.fields(InventoryItemWithGoods::FIELDS).map_err(|parse_error| {
+        log_validation_error("get inventory item", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    // Validate path parameter
+    let item_id = parse_safe_integer(&item_id, "item_id").map_err(|parse_error| {
+        log_validation_error("get inventory item", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    // Perform database lookup
+    let item = state.inventory.get_by_item_id(item_id).await.map_err(|e| {
+        log_database_error("get inventory item", &e);
+        ApiError::database(e, "inventory lookup")
+    })?;
+
+    match item {
+        Some(item) => {
+            log_success("get inventory item", &item, 1);
+            Ok(success_response(select_fields(&item, fields.as_deref())?, &format_success_message("Inventory lookup", 1)))
+        }
+        None => {
+            warn!("Inventory item {} not found", item_id);
+            Err(ApiError::NotFound(format!("Inventory item {} not found", item_id)))
+        }
+    }
+}
+
+// Route: PATCH /inventory/{item_id} - Update a single inventory item and its goods by id
+#[cfg_attr(feature = "openapi", utoipa::path(
+    patch,
+    path = "/inventory/{item_id}",
+    tag = "inventory",
+    params(("item_id" = i32, Path, description = "Inventory item id")),
+    request_body = UpdateInventoryRequest,
+    responses(
+        (status = 200, description = "Updated item", body = ApiResponse<InventoryItemWithGoods>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn patch_inventory_item(
+    State(state): State<AppState>,
+    Path(item_id): Path<String>,
+    JsonBody(mut request): JsonBody<UpdateInventoryRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("patch inventory item", &(&item_id, &request));
+
+    // Validate path parameter
+    let item_id = parse_safe_integer(&item_id, "item_id").map_err(|parse_error| {
+        log_validation_error("patch inventory item", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    request.normalize(state.config.server.normalization());
+    request.validate(&state.config.validation).map_err(|validation_error| {
+        log_validation_error("patch inventory item", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    let item = state.inventory.update_by_id(item_id, request).await.map_err(|e| {
+        log_database_error("patch inventory item", &e);
+        ApiError::database(e, "inventory update")
+    })?;
+
+    match item {
+        Some(item) => {
+            log_success("patch inventory item", &item, 1);
+            Ok(success_response(item, &format_success_message("Inventory update", 1)))
+        }
+        None => {
+            warn!("Inventory item {} not found", item_id);
+            Err(ApiError::NotFound(format!("Inventory item {} not found", item_id)))
+        }
+    }
+}
+
+// Route: POST /inventory/{item_id}/adjust - Change quantity by a delta with a reason
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/inventory/{item_id}/adjust",
+    tag = "inventory",
+    params(("item_id" = i32, Path, description = "Inventory item id")),
+    request_body = AdjustInventoryRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<InventoryItemWithGoods>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Quantity would go negative", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn adjust_inventory(
+    State(state): State<AppState>,
+    Path(item_id): Path<String>,
+    JsonBody(request): JsonBody<AdjustInventoryRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("adjust inventory", &request);
+
+    // Validate path parameter and request
+    let item_id = parse_safe_integer(&item_id, "item_id")
+        .and_then(|item_id| request.validate().map(|_| item_id))
+        .map_err(|validation_error| {
+            log_validation_error("adjust inventory", &validation_error);
+            ApiError::Validation(validation_error)
+        })?;
+
+    // Apply the adjustment
+    let item = state.inventory.adjust(item_id, request.delta, &request.reason).await.map_err(|e| match e {
+        TableError::Database(sqlx::Error::RowNotFound) => {
+            warn!("Inventory item {} not found", item_id);
+            ApiError::NotFound(format!("Inventory item {} not found", item_id))
+        }
+        TableError::Database(e) => {
+            log_database_error("adjust inventory", &e);
+            ApiError::database(e, "inventory adjustment")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    log_success(&format!("adjust inventory ({}: {:+})", request.reason, request.delta), &item, 1);
+    Ok(success_response(item, &format_success_message("Inventory adjustment", 1)))
+}
+
+// Route: POST /inventory/{item_id}/decrement - Take stock from an item, never below zero
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/inventory/{item_id}/decrement",
+    tag = "inventory",
+    params(("item_id" = i32, Path, description = "Inventory item id")),
+    request_body = QuantityChangeRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<InventoryItemWithGoods>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Not enough stock; details carry the current quantity", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn decrement_inventory(
+    State(state): State<AppState>,
+    Path(item_id): Path<String>,
+    JsonBody(request): JsonBody<QuantityChangeRequest>,
+) -> Result<Response, ApiError> {
+    change_inventory_quantity(&state, &item_id, &request, -1, "decrement").await
+}
+
+// Route: POST /inventory/{item_id}/increment - Add stock to an item
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/inventory/{item_id}/increment",
+    tag = "inventory",
+    params(("item_id" = i32, Path, description = "Inventory item id")),
+    request_body = QuantityChangeRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<InventoryItemWithGoods>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn increment_inventory(
+    State(state): State<AppState>,
+    Path(item_id): Path<String>,
+    JsonBody(request): JsonBody<QuantityChangeRequest>,
+) -> Result<Response, ApiError> {
+    change_inventory_quantity(&state, &item_id, &request, 1, "increment").await
+}
+
+/// Shared by decrement and increment: one conditional update through `adjust`, so concurrent
+/// requests cannot take the quantity below zero. `operation` is also the movement reason.
+async fn change_inventory_quantity(
+    state: &AppState,
+    item_id: &str,
+    request: &QuantityChangeRequest,
+    sign: i32,
+    operation: &str,
+) -> Result<Response, ApiError> {
+    log_request_params(&format!("{} inventory", operation), request);
+
+    // Validate path parameter and request
+    let item_id = parse_safe_integer(item_id, "item_id")
+        .and_then(|item_id| request.validate().map(|_| item_id))
+        .map_err(|validation_error| {
+            log_validation_error(&format!("{} inventory", operation), &validation_error);
+            ApiError::Validation(validation_error)
+        })?;
+
+    let delta = sign * request.quantity;
+    let item = state.inventory.adjust(item_id, delta, operation).await.map_err(|e| match e {
+        TableError::Database(sqlx::Error::RowNotFound) => {
+            warn!("Inventory item {} not found", item_id);
+            ApiError::NotFound(format!("Inventory item {} not found", item_id))
+        }
+        TableError::Database(e) => {
+            log_database_error(&format!("{} inventory", operation), &e);
+            ApiError::database(e, &format!("inventory {}", operation))
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    log_success(&format!("{} inventory ({:+})", operation, delta), &item, 1);
+    Ok(success_response(item, &format_success_message(&format!("Inventory {}", operation), 1)))
+}
+
+// Route: POST /inventory/consume - Deduct stock for a good, earliest expiry first
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/inventory/consume",
+    tag = "inventory",
+    request_body = ConsumeInventoryRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<ConsumeResult>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Not enough stock", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn consume_inventory(
+    State(state): State<AppState>,
+    JsonBody(request): JsonBody<ConsumeInventoryRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("consume inventory", &request);
 
     // Validate request
-    match request.validate() {
-        Ok(_) => {}
-        Err(validation_error) => {
-            log_validation_error("create inventory", &validation_error);
-            return ErrorResponse::bad_request(&validation_error);
+    request.validate().map_err(|validation_error| {
+        log_validation_error("consume inventory", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    // Deduct stock across inventory items
+    let result = state.inventory.consume(&request).await.map_err(|e| match e {
+        TableError::Database(sqlx::Error::RowNotFound) => {
+            warn!("Goods to consume not found");
+            ApiError::NotFound("Referenced goods not found".to_string())
+        }
+        TableError::Database(e) => {
+            log_database_error("consume inventory", &e);
+            ApiError::database(e, "inventory consumption")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
         }
+    })?;
+
+    let count = result.items.len();
+    log_success("consume inventory", &result, count);
+    Ok(success_response(result, &format_success_message("Inventory consumption", count)))
+}
+
+// Route: POST /inventory/merge-duplicates - Fold items with the same goods, expiry and lot into one
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/inventory/merge-duplicates",
+    tag = "inventory",
+    params(("dry_run" = Option<bool>, Query, description = "Report the groups that would be merged without changing anything")),
+    responses(
+        (status = 200, description = "Merged groups with the surviving and removed item ids", body = ApiResponse<Vec<MergedInventoryGroup>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Requires the admin role", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn merge_duplicate_inventory(
+    State(state): State<AppState>,
+    Extension(role): Extension<Role>,
+    ApiQuery(options): ApiQuery<DryRunOptions>,
+) -> Result<Response, ApiError> {
+    role.require(Role::Admin, "Merging duplicate inventory").inspect_err(|e| warn!("{}", e))?;
+
+    let dry_run = options.dry_run().map_err(|parse_error| {
+        log_validation_error("merge duplicate inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    log_request_params("merge duplicate inventory", &dry_run);
+
+    let groups = state.inventory.merge_duplicates(dry_run).await.map_err(|e| {
+        log_database_error("merge duplicate inventory", &e);
+        ApiError::database(e, "inventory merge")
+    })?;
+
+    let count = groups.len();
+    log_success("merge duplicate inventory", &groups, count);
+    let message = if dry_run {
+        format!("Dry run: {} duplicate inventory groups would be merged", count)
+    } else {
+        format_success_message("Inventory merge", count)
+    };
+    Ok(success_response(groups, &message))
+}
+
+// Route: POST /admin/purge-expired - Zero or delete inventory past the expiry retention window
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/admin/purge-expired",
+    tag = "admin",
+    params(("dry_run" = Option<bool>, Query, description = "Report the item ids that would be affected without changing anything")),
+    responses(
+        (status = 200, description = "Affected item ids, with the configured mode and retention", body = ApiResponse<ExpiredPurgeResult>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Requires the admin role", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn purge_expired_inventory(
+    State(state): State<AppState>,
+    Extension(role): Extension<Role>,
+    ApiQuery(options): ApiQuery<DryRunOptions>,
+) -> Result<Response, ApiError> {
+    role.require(Role::Admin, "Purging expired inventory").inspect_err(|e| warn!("{}", e))?;
+
+    let dry_run = options.dry_run().map_err(|parse_error| {
+        log_validation_error("purge expired inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    log_request_params("purge expired inventory", &dry_run);
+
+    let purge_config = &state.config.expired_purge;
+    let cutoff = purge_cutoff(purge_config.retention_days);
+    let item_ids = state.inventory.purge_expired(cutoff, purge_config.mode, dry_run).await.map_err(|e| {
+        log_database_error("purge expired inventory", &e);
+        ApiError::database(e, "expired inventory purge")
+    })?;
+
+    let result = ExpiredPurgeResult {
+        mode: purge_config.mode,
+        retention_days: purge_config.retention_days,
+        cutoff,
+        item_ids,
+    };
+
+    let count = result.item_ids.len();
+    log_success("purge expired inventory", &result, count);
+    let message = if dry_run {
+        format!("Dry run: {} expired inventory items would be purged", count)
+    } else {
+        format_success_message("Expired inventory purge", count)
+    };
+    Ok(success_response(result, &message))
+}
+
+// Route: POST /admin/snapshot - Record today's stock totals per good
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/admin/snapshot",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Snapshot date and number of goods recorded; an earlier snapshot of the day is replaced", body = ApiResponse<SnapshotSummary>),
+        (status = 403, description = "Requires the admin role", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn take_stock_snapshot(
+    State(state): State<AppState>,
+    Extension(role): Extension<Role>,
+) -> Result<Response, ApiError> {
+    role.require(Role::Admin, "Taking a stock snapshot").inspect_err(|e| warn!("{}", e))?;
+
+    let snapshot_date = Utc::now().date_naive();
+    log_request_params("take stock snapshot", &snapshot_date);
+
+    let summary = state.database.stock_snapshots_table.take(snapshot_date).await.map_err(|e| {
+        log_database_error("take stock snapshot", &e);
+        ApiError::database(e, "stock snapshot")
+    })?;
+
+    let count = summary.goods_count as usize;
+    log_success("take stock snapshot", &summary, count);
+    Ok(success_response(summary, &format_success_message("Stock snapshot", count)))
+}
+
+// Route: POST /admin/maintenance - Switch read-only maintenance mode on or off
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/admin/maintenance",
+    tag = "admin",
+    request_body = MaintenanceMode,
+    responses(
+        (status = 200, description = "Mode now in effect; it lasts until changed again or the server restarts", body = ApiResponse<MaintenanceMode>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Requires the admin role", body = ErrorResponse)
+    )
+))]
+async fn set_maintenance_mode(
+    State(state): State<AppState>,
+    Extension(role): Extension<Role>,
+    JsonBody(request): JsonBody<MaintenanceMode>,
+) -> Result<Response, ApiError> {
+    role.require(Role::Admin, "Switching maintenance mode").inspect_err(|e| warn!("{}", e))?;
+    log_request_params("set maintenance mode", &request);
+
+    let was_read_only = state.read_only.swap(request.read_only, Ordering::Relaxed);
+    if was_read_only != request.read_only {
+        warn!("Maintenance mode {}", if request.read_only { "enabled: rejecting writes" } else { "disabled: accepting writes" });
     }
 
+    log_success("set maintenance mode", &request, 1);
+    Ok(success_response(request, &format_success_message("Maintenance mode change", 1)))
+}
+
+// Route: POST /inventory - Create new inventory item
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/inventory",
+    tag = "inventory",
+    params(
+        ("on_duplicate" = Option<String>, Query, description = "When an item with the same goods, expiry and lot_number exists: return_existing (default) returns it unchanged, add_quantity adds the posted quantity to it, error returns 409"),
+        ("allow_inactive" = Option<bool>, Query, description = "Record inventory for an archived good instead of returning 409"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replay the stored response when a request is retried with the same key")
+    ),
+    request_body = CreateInventoryRequest,
+    responses(
+        (status = 200, description = "Created, or the existing item with the same goods, expiry and lot_number", body = ApiResponse<InventoryItemWithGoods>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "An item with the same goods, expiry and lot_number exists (on_duplicate=error), or the good is archived", body = ErrorResponse),
+        (status = 422, description = "Idempotency-Key reused with a different body", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn create_inventory(
+    State(state): State<AppState>,
+    ApiQuery(options): ApiQuery<InventoryCreateOptions>,
+    JsonBody(mut request): JsonBody<CreateInventoryRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("create inventory", &request);
+
+    // Validate request
+    let (on_duplicate, allow_inactive) = options.on_duplicate()
+        .and_then(|on_duplicate| Ok((on_duplicate, options.allow_inactive()?)))
+        .map_err(|parse_error| {
+            log_validation_error("create inventory", &parse_error);
+            ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+        })?;
+
+    request.normalize(state.config.server.normalization());
+    request.validate(&state.config.validation).map_err(|validation_error| {
+        log_validation_error("create inventory", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
     // Insert inventory item
-    match state.database.inventory_table.insert(request).await {
-        Ok((inventory_item, is_new)) => {
-            if is_new {
-                log_success("create inventory (new)", &inventory_item, 1);
-                success_response(
-                    inventory_item, 
-                    &format_success_message("Inventory creation", 1)
-                )
-            } else {
-                log_success("create inventory (existing found)", &inventory_item, 1);
-                success_response(
-                    inventory_item, 
-                    "Inventory item already exists with same goods and expiration date. Returning existing item."
-                )
-            }
-        }
-        Err(sqlx::Error::RowNotFound) => {
+    let added = request.quantity;
+    let (inventory_item, outcome) = state.inventory.insert(request, on_duplicate, allow_inactive).await.map_err(|e| match e {
+        TableError::Database(sqlx::Error::RowNotFound) => {
             let error = "Referenced goods not found. Please provide valid goods_id, material_code, or complete goods information.";
             log_validation_error("create inventory", error);
-            ErrorResponse::bad_request(error)
+            ApiError::NotFound(error.to_string())
         }
-        Err(e) => {
+        TableError::Database(e) => {
             log_database_error("create inventory", &e);
-            ErrorResponse::internal_server_error(&format_database_error(&e, "inventory creation"))
+            ApiError::database(e, "inventory creation")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    match outcome {
+        InventoryInsertOutcome::Created => {
+            log_success("create inventory (new)", &inventory_item, 1);
+            Ok(success_response(
+                inventory_item, 
+                &format_success_message("Inventory creation", 1)
+            ))
+        }
+        InventoryInsertOutcome::Merged => {
+            log_success("create inventory (merged into existing)", &inventory_item, 1);
+            let message = format!(
+                "Inventory item already exists with same goods, expiration date and lot number. Added {} to item {}; quantity is now {}.",
+                added, inventory_item.item_id, inventory_item.quantity
+            );
+            Ok(success_response(inventory_item, &message))
+        }
+        InventoryInsertOutcome::Existing => {
+            log_success("create inventory (existing found)", &inventory_item, 1);
+            Ok(success_response(
+                inventory_item, 
+                "Inventory item already exists with same goods, expiration date and lot number. Returning existing item."
+            ))
         }
     }
 }
 
+// Route: POST /inventory/batch - Record many inventory items in one transaction
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/inventory/batch",
+    tag = "inventory",
+    params(
+        ("on_error" = Option<String>, Query, description = "abort (default) rejects the batch on any invalid entry; skip processes the valid ones"),
+        ("allow_inactive" = Option<bool>, Query, description = "Accept entries for archived goods instead of failing them")
+    ),
+    request_body = Vec<CreateInventoryRequest>,
+    responses(
+        (status = 200, description = "Success; results has one entry per payload index. Also lists every target in results (id, status ok|failed, error_code, message) with partial set when only some failed", body = ApiResponse<InventoryBatchResult>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn create_inventory_batch(
+    State(state): State<AppState>,
+    ApiQuery(options): ApiQuery<InventoryBatchOptions>,
+    JsonBody(requests): JsonBody<Vec<CreateInventoryRequest>>,
+) -> Result<Response, ApiError> {
+    log_request_params("create inventory batch", &requests.len());
+
+    // Validate batch size and error mode
+    let (error_mode, allow_inactive) = validate_batch_size(requests.len(), state.config.server.max_batch_size)
+        .and_then(|_| Ok((options.on_error()?, options.allow_inactive()?)))
+        .map_err(|validation_error| {
+            log_validation_error("create inventory batch", &validation_error);
+            ApiError::Validation(validation_error)
+        })?;
+
+    // Validate every entry before touching the database
+    let (entries, failures) = validate_inventory_batch(requests, state.config.server.normalization(), &state.config.validation);
+    let abort_on_failure = error_mode == BatchErrorMode::Abort;
+    if !failures.is_empty() && abort_on_failure {
+        log_validation_error("create inventory batch", &format!("{} invalid entries", failures.len()));
+        return Err(ApiError::InvalidBatch(failures));
+    }
+
+    // Resolve goods and insert inside one transaction
+    let result = state.inventory
+        .insert_batch(entries, failures, abort_on_failure, allow_inactive)
+        .await
+        .map_err(|e| match e {
+            TableError::Database(e) => {
+                log_database_error("create inventory batch", &e);
+                ApiError::database(e, "batch inventory creation")
+            }
+            e => {
+                log_validation_error("create inventory batch", &e.to_string());
+                ApiError::from(e)
+            }
+        })?;
+
+    let created_count = result.created.len();
+    log_success("create inventory batch", &result, created_count);
+    let results = result.target_results();
+    Ok(success_response(bulk_response(result, results), &format_success_message("Batch inventory creation", created_count)))
+}
+
 // Route: PUT /inventory - Update inventory with query parameters
+#[cfg_attr(feature = "openapi", utoipa::path(
+    put,
+    path = "/inventory",
+    tag = "inventory",
+    params(
+        InventoryQueryParams,
+        ("max_affected" = Option<i32>, Query, description = "Abort with 409 when the filter matches more rows; defaults to the server cap"),
+        ("dry_run" = Option<bool>, Query, description = "Report the rows that would be updated without changing anything")
+    ),
+    request_body = UpdateInventoryRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Vec<InventoryItemWithGoods>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "No inventory items match the filter", body = ErrorResponse),
+        (status = 409, description = "Conflict", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
 async fn update_inventory(
     State(state): State<AppState>,
-    query: Query<HashMap<String, String>>,
-    Json(request): Json<UpdateInventoryRequest>,
-) -> Response {
-    let query_params = extract_inventory_query_params(query);
+    ApiQuery(query): ApiQuery<FilterQuery<InventoryQueryParams, BulkWriteQueryOptions>>,
+    JsonBody(mut request): JsonBody<UpdateInventoryRequest>,
+) -> Result<Response, ApiError> {
+    let options = query.options.parse(state.config.server.max_affected_rows).map_err(|parse_error| {
+        log_validation_error("update inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("update inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
     log_request_params("update inventory", &(&query_params, &request));
 
     // Validate update request
-    match request.validate() {
-        Ok(_) => {}
-        Err(validation_error) => {
-            log_validation_error("update inventory", &validation_error);
-            return ErrorResponse::bad_request(&validation_error);
-        }
-    }
+    request.normalize(state.config.server.normalization());
+    request.validate(&state.config.validation).map_err(|validation_error| {
+        log_validation_error("update inventory", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
 
     // Check if no parameters provided
     if !query_params.has_any_params() {
         let error = "Query parameters required to specify which inventory items to update";
         log_validation_error("update inventory", error);
-        return ErrorResponse::bad_request(error);
+        return Err(ApiError::Validation(error.to_string()));
     }
 
     // Validate and parse query parameters
-    let search_params = match query_params.validate_and_parse() {
-        Ok(params) => params,
-        Err(parse_error) => {
-            log_validation_error("update inventory", &parse_error);
-            return ErrorResponse::bad_request(&format!("Invalid query parameters: {}", parse_error));
-        }
-    };
+    let search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("update inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
 
     // Perform database update
-    match state.database.inventory_table.update(search_params, request).await {
-        Ok(updated_items) => {
-            if updated_items.is_empty() {
-                warn!("No inventory items found to update");
-                return ErrorResponse::bad_request("No inventory items found to update");
-            }
-            let count = updated_items.len();
-            log_success("update inventory", &updated_items, count);
-            success_response(updated_items, &format_success_message("Inventory update", count))
-        }
-        Err(e) => {
+    let updated_items = state.inventory.update(search_params, request, options).await.map_err(|e| match e {
+        TableError::Database(e) => {
             log_database_error("update inventory", &e);
-            ErrorResponse::internal_server_error(&format_database_error(&e, "inventory update"))
+            ApiError::database(e, "inventory update")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
         }
+    })?;
+
+    if updated_items.is_empty() {
+        warn!("No inventory items found to update");
+        return Err(ApiError::NotFound("No inventory items found to update".to_string()));
     }
+
+    let count = updated_items.len();
+    log_success("update inventory", &updated_items, count);
+    let message = if options.dry_run {
+        format!("Dry run: {} inventory items would be updated", count)
+    } else {
+        format_success_message("Inventory update", count)
+    };
+    Ok(success_response(updated_items, &message))
 }
 
 // Route: DELETE /inventory - Delete inventory with query parameters
+#[cfg_attr(feature = "openapi", utoipa::path(
+    delete,
+    path = "/inventory",
+    tag = "inventory",
+    params(
+        InventoryQueryParams,
+        ("max_affected" = Option<i32>, Query, description = "Abort with 409 when the filter matches more rows; defaults to the server cap"),
+        ("dry_run" = Option<bool>, Query, description = "Report the ids that would be deleted without changing anything"),
+        ("confirm" = Option<bool>, Query, description = "Must be true for a wildcard (*) delete")
+    ),
+    responses(
+        (status = 200, description = "Deleted item ids", body = ApiResponse<Vec<i32>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "No inventory items match the filter", body = ErrorResponse),
+        (status = 409, description = "Filter matches more than max_affected", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
 async fn delete_inventory(
     State(state): State<AppState>,
-    query: Query<HashMap<String, String>>,
-) -> Response {
-    let query_params = extract_inventory_query_params(query);
+    Extension(role): Extension<Role>,
+    ApiQuery(query): ApiQuery<FilterQuery<InventoryQueryParams, BulkWriteQueryOptions>>,
+) -> Result<Response, ApiError> {
+    let (options, confirm) = query.options.parse(state.config.server.max_affected_rows)
+        .and_then(|options| query.options.confirm().map(|confirm| (options, confirm)))
+        .map_err(|parse_error| {
+            log_validation_error("delete inventory", &parse_error);
+            ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+        })?;
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("delete inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
     log_request_params("delete inventory", &query_params);
 
     // Check if no parameters provided
     if !query_params.has_any_params() {
         let error = "Query parameters required to specify which inventory items to delete";
         log_validation_error("delete inventory", error);
-        return ErrorResponse::bad_request(error);
+        return Err(ApiError::Validation(error.to_string()));
     }
 
     // Validate and parse query parameters
-    let search_params = match query_params.validate_and_parse() {
-        Ok(params) => params,
-        Err(parse_error) => {
-            log_validation_error("delete inventory", &parse_error);
-            return ErrorResponse::bad_request(&format!("Invalid query parameters: {}", parse_error));
+    let search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("delete inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    // Wildcard deletes wipe the whole table
+    if search_params.is_get_all() {
+        role.require(Role::Admin, "Wildcard delete").inspect_err(|e| warn!("{}", e))?;
+
+        if !confirm && !options.dry_run {
+            let error = "Wildcard delete requires confirm=true";
+            log_validation_error("delete inventory", error);
+            return Err(ApiError::Validation(error.to_string()));
         }
-    };
+    }
 
     // Perform database deletion
-    match state.database.inventory_table.delete(search_params).await {
-        Ok(deleted_ids) => {
-            if deleted_ids.is_empty() {
-                warn!("No inventory items found to delete");
-                return ErrorResponse::bad_request("No inventory items found to delete");
+    let deleted_ids = state.inventory.delete(search_params, options).await.map_err(|e| match e {
+        TableError::Database(e) => {
+            log_database_error("delete inventory", &e);
+            ApiError::database(e, "inventory deletion")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    if deleted_ids.is_empty() {
+        warn!("No inventory items found to delete");
+        return Err(ApiError::NotFound("No inventory items found to delete".to_string()));
+    }
+
+    let count = deleted_ids.len();
+    log_success("delete inventory", &deleted_ids, count);
+    let message = if options.dry_run {
+        format!("Dry run: {} inventory items would be deleted", count)
+    } else {
+        format_success_message("Inventory deletion", count)
+    };
+    Ok(success_response(deleted_ids, &message))
+}
+
+// Route: DELETE /inventory/by-ids - Delete exactly the listed inventory items
+#[cfg_attr(feature = "openapi", utoipa::path(
+    delete,
+    path = "/inventory/by-ids",
+    tag = "inventory",
+    request_body = DeleteByIdsRequest,
+    responses(
+        (status = 200, description = "Deleted and missing ids. Also lists every target in results (id, status ok|failed, error_code, message) with partial set when only some failed", body = ApiResponse<InventoryDeleteByIdsResult>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn delete_inventory_by_ids(
+    State(state): State<AppState>,
+    JsonBody(request): JsonBody<DeleteByIdsRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("delete inventory by ids", &request);
+
+    request.validate().map_err(|validation_error| {
+        log_validation_error("delete inventory by ids", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    let result = state.inventory.delete_by_ids(&request.ids).await.map_err(|e| {
+        log_database_error("delete inventory by ids", &e);
+        ApiError::database(e, "inventory deletion")
+    })?;
+
+    let count = result.deleted.len();
+    log_success("delete inventory by ids", &result, count);
+    let results = result.target_results();
+    Ok(success_response(bulk_response(result, results), &format_success_message("Inventory deletion", count)))
+}
+
+// CATEGORY ROUTES
+
+// Route: GET /categories - List every category
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/categories",
+    tag = "categories",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Vec<Category>>),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_categories(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let categories = state.database.categories_table.get_all().await.map_err(|e| {
+        log_database_error("get categories", &e);
+        ApiError::database(e, "category search")
+    })?;
+
+    let count = categories.len();
+    log_success("get categories", &categories, count);
+    Ok(success_response(categories, &format_success_message("Category search", count)))
+}
+
+// Route: POST /categories - Create a category
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/categories",
+    tag = "categories",
+    request_body = CreateCategoryRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Category>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Parent category not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn create_category(
+    State(state): State<AppState>,
+    JsonBody(request): JsonBody<CreateCategoryRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("create category", &request);
+
+    // Validate request
+    request.validate().map_err(|validation_error| {
+        log_validation_error("create category", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    let category = state.database.categories_table.insert(&request).await.map_err(|e| {
+        log_database_error("create category", &e);
+        match e {
+            sqlx::Error::Database(ref db_err) if db_err.is_foreign_key_violation() => {
+                ApiError::NotFound(format!("Parent category {} not found", request.parent_id.unwrap_or_default()))
             }
-            let count = deleted_ids.len();
-            log_success("delete inventory", &deleted_ids, count);
-            success_response(deleted_ids, &format_success_message("Inventory deletion", count))
+            e => ApiError::database(e, "category creation"),
         }
-        Err(e) => {
-            log_database_error("delete inventory", &e);
-            ErrorResponse::internal_server_error(&format_database_error(&e, "inventory deletion"))
+    })?;
+
+    log_success("create category", &category, 1);
+    Ok(success_response(category, &format_success_message("Category creation", 1)))
+}
+
+// Route: GET /categories/{category_id} - Get a single category
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/categories/{category_id}",
+    tag = "categories",
+    params(("category_id" = i32, Path, description = "Category id")),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Category>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_category(
+    State(state): State<AppState>,
+    Path(category_id): Path<String>,
+) -> Result<Response, ApiError> {
+    log_request_params("get category", &category_id);
+
+    // Validate path parameter
+    let category_id = parse_safe_integer(&category_id, "category_id").map_err(|parse_error| {
+        log_validation_error("get category", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    let category = state.database.categories_table.get_by_id(category_id).await.map_err(|e| {
+        log_database_error("get category", &e);
+        ApiError::database(e, "category lookup")
+    })?;
+
+    match category {
+        Some(category) => {
+            log_success("get category", &category, 1);
+            Ok(success_response(category, &format_success_message("Category lookup", 1)))
+        }
+        None => {
+            warn!("Category {} not found", category_id);
+            Err(ApiError::NotFound(format!("Category {} not found", category_id)))
+        }
+    }
+}
+
+// Route: PUT /categories/{category_id} - Rename or move a category
+#[cfg_attr(feature = "openapi", utoipa::path(
+    put,
+    path = "/categories/{category_id}",
+    tag = "categories",
+    params(("category_id" = i32, Path, description = "Category id")),
+    request_body = UpdateCategoryRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Category>),
+        (status = 400, description = "Invalid request, or the move would create a cycle", body = ErrorResponse),
+        (status = 404, description = "Category or parent category not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn update_category(
+    State(state): State<AppState>,
+    Path(category_id): Path<String>,
+    JsonBody(request): JsonBody<UpdateCategoryRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("update category", &request);
+
+    // Validate path parameter and request
+    let category_id = parse_safe_integer(&category_id, "category_id").map_err(|parse_error| {
+        log_validation_error("update category", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    request.validate().map_err(|validation_error| {
+        log_validation_error("update category", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    let category = state.database.categories_table.update(category_id, &request).await.map_err(|e| match e {
+        TableError::Database(sqlx::Error::Database(ref db_err)) if db_err.is_foreign_key_violation() => {
+            warn!("{}", e);
+            ApiError::NotFound(format!("Parent category {} not found", request.parent_id.unwrap_or_default()))
+        }
+        TableError::Database(e) => {
+            log_database_error("update category", &e);
+            ApiError::database(e, "category update")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    match category {
+        Some(category) => {
+            log_success("update category", &category, 1);
+            Ok(success_response(category, &format_success_message("Category update", 1)))
+        }
+        None => {
+            warn!("Category {} not found", category_id);
+            Err(ApiError::NotFound(format!("Category {} not found", category_id)))
+        }
+    }
+}
+
+// Route: DELETE /categories/{category_id} - Delete an unreferenced category
+#[cfg_attr(feature = "openapi", utoipa::path(
+    delete,
+    path = "/categories/{category_id}",
+    tag = "categories",
+    params(("category_id" = i32, Path, description = "Category id")),
+    responses(
+        (status = 200, description = "The deleted category", body = ApiResponse<Category>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Still referenced by goods or subcategories", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn delete_category(
+    State(state): State<AppState>,
+    Path(category_id): Path<String>,
+) -> Result<Response, ApiError> {
+    log_request_params("delete category", &category_id);
+
+    // Validate path parameter
+    let category_id = parse_safe_integer(&category_id, "category_id").map_err(|parse_error| {
+        log_validation_error("delete category", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    let category = state.database.categories_table.delete(category_id).await.map_err(|e| match e {
+        TableError::Database(e) => {
+            log_database_error("delete category", &e);
+            ApiError::database(e, "category deletion")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    match category {
+        Some(category) => {
+            log_success("delete category", &category, 1);
+            Ok(success_response(category, &format_success_message("Category deletion", 1)))
+        }
+        None => {
+            warn!("Category {} not found", category_id);
+            Err(ApiError::NotFound(format!("Category {} not found", category_id)))
+        }
+    }
+}
+
+// SYNC ROUTES
+
+// Route: GET /sync/goods - Goods changed since a timestamp or cursor
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/sync/goods",
+    tag = "sync",
+    params(SyncQueryParams),
+    responses(
+        (status = 200, description = "Changed goods and deleted goods_ids, oldest change first", body = ApiResponse<SyncPage<Good>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn sync_goods(
+    State(state): State<AppState>,
+    ApiQuery(query_params): ApiQuery<SyncQueryParams>,
+) -> Result<Response, ApiError> {
+    log_request_params("sync goods", &query_params);
+
+    // Validate and parse query parameters
+    let sync_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("sync goods", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    // Find what changed, then load the live rows
+    let changes = state.database.sync_table.changes(SyncEntity::Goods, sync_params).await.map_err(|e| {
+        log_database_error("sync goods", &e);
+        ApiError::database(e, "goods sync")
+    })?;
+
+    let goods = state.goods.get_by_ids(&changes.updated_ids).await.map_err(|e| {
+        log_database_error("sync goods", &e);
+        ApiError::database(e, "goods sync")
+    })?;
+
+    let count = goods.len() + changes.deleted_ids.len();
+    let page = SyncPage {
+        items: goods,
+        deleted_ids: changes.deleted_ids,
+        next_cursor: changes.next_cursor.encode(),
+        has_more: changes.has_more,
+    };
+
+    log_success("sync goods", &page, count);
+    Ok(success_response(page, &format_success_message("Goods sync", count)))
+}
+
+// Route: GET /sync/inventory - Inventory items changed since a timestamp or cursor
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/sync/inventory",
+    tag = "sync",
+    params(SyncQueryParams),
+    responses(
+        (status = 200, description = "Changed items and deleted item_ids, oldest change first", body = ApiResponse<SyncPage<InventoryItemWithGoods>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn sync_inventory(
+    State(state): State<AppState>,
+    ApiQuery(query_params): ApiQuery<SyncQueryParams>,
+) -> Result<Response, ApiError> {
+    log_request_params("sync inventory", &query_params);
+
+    // Validate and parse query parameters
+    let sync_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("sync inventory", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    // Find what changed, then load the live rows
+    let changes = state.database.sync_table.changes(SyncEntity::Inventory, sync_params).await.map_err(|e| {
+        log_database_error("sync inventory", &e);
+        ApiError::database(e, "inventory sync")
+    })?;
+
+    let items = state.inventory.get_by_item_ids(&changes.updated_ids).await.map_err(|e| {
+        log_database_error("sync inventory", &e);
+        ApiError::database(e, "inventory sync")
+    })?;
+
+    let count = items.len() + changes.deleted_ids.len();
+    let page = SyncPage {
+        items,
+        deleted_ids: changes.deleted_ids,
+        next_cursor: changes.next_cursor.encode(),
+        has_more: changes.has_more,
+    };
+
+    log_success("sync inventory", &page, count);
+    Ok(success_response(page, &format_success_message("Inventory sync", count)))
+}
+
+// ALERT ROUTES
+
+// Route: GET /alerts - Expiry alerts, newest first
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/alerts",
+    tag = "alerts",
+    params(AlertQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Vec<Alert>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_alerts(
+    State(state): State<AppState>,
+    ApiQuery(query_params): ApiQuery<AlertQueryParams>,
+) -> Result<Response, ApiError> {
+    log_request_params("get alerts", &query_params);
+
+    let mut search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("get alerts", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    search_params.pagination = Some(search_params.pagination.unwrap_or_default().limited(state.config.server.page_limits()));
+
+    let page = state.database.alerts_table.search_paginated(search_params).await.map_err(|e| {
+        log_database_error("get alerts", &e);
+        ApiError::database(e, "alert search")
+    })?;
+
+    let count = page.data.len();
+    log_success("get alerts", &page, count);
+    Ok(paginated_response(page, &format_success_message("Alert search", count)))
+}
+
+// Route: POST /alerts/{alert_id}/acknowledge - Mark an alert as handled so it is not refreshed again
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/alerts/{alert_id}/acknowledge",
+    tag = "alerts",
+    params(("alert_id" = i32, Path, description = "Alert id")),
+    responses(
+        (status = 200, description = "Acknowledged alert; acknowledging again keeps the first acknowledgement", body = ApiResponse<Alert>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn acknowledge_alert(
+    State(state): State<AppState>,
+    Path(alert_id): Path<String>,
+) -> Result<Response, ApiError> {
+    log_request_params("acknowledge alert", &alert_id);
+
+    // Validate path parameter
+    let alert_id = parse_safe_integer(&alert_id, "alert_id").map_err(|parse_error| {
+        log_validation_error("acknowledge alert", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    let alert = state.database.alerts_table.acknowledge(alert_id).await.map_err(|e| {
+        log_database_error("acknowledge alert", &e);
+        ApiError::database(e, "alert acknowledgement")
+    })?;
+
+    match alert {
+        Some(alert) => {
+            log_success("acknowledge alert", &alert, 1);
+            Ok(success_response(alert, &format_success_message("Alert acknowledgement", 1)))
+        }
+        None => {
+            warn!("Alert {} not found", alert_id);
+            Err(ApiError::NotFound(format!("Alert {} not found", alert_id)))
         }
     }
 }
+
+// AUDIT ROUTES
+
+// Route: GET /audit - Successful mutating requests, newest first
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/audit",
+    tag = "admin",
+    params(AuditQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Vec<AuditEntry>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Requires the admin role", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_audit_log(
+    State(state): State<AppState>,
+    Extension(role): Extension<Role>,
+    ApiQuery(query_params): ApiQuery<AuditQueryParams>,
+) -> Result<Response, ApiError> {
+    role.require(Role::Admin, "Reading the audit log").inspect_err(|e| warn!("{}", e))?;
+
+    log_request_params("get audit log", &query_params);
+
+    let mut search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("get audit log", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    search_params.pagination = Some(search_params.pagination.unwrap_or_default().limited(state.config.server.page_limits()));
+
+    let page = state.database.audit_log_table.search_paginated(search_params).await.map_err(|e| {
+        log_database_error("get audit log", &e);
+        ApiError::database(e, "audit log search")
+    })?;
+
+    let count = page.data.len();
+    log_success("get audit log", &page, count);
+    Ok(paginated_response(page, &format_success_message("Audit log search", count)))
+}
+
+// REPORT ROUTES
+
+// Route: GET /reports/low-stock - Goods at or below their reorder point
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/reports/low-stock",
+    tag = "reports",
+    params(("include_zero_reorder" = Option<bool>, Query, description = "Include goods without a reorder point (default true)")),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Vec<LowStockGoods>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_low_stock_report(
+    State(state): State<AppState>,
+    ApiQuery(options): ApiQuery<LowStockOptions>,
+) -> Result<Response, ApiError> {
+    log_request_params("low stock report", &options);
+
+    // Validate query parameters
+    let include_zero_reorder = options.include_zero_reorder().map_err(|parse_error| {
+        log_validation_error("low stock report", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    // Build report
+    let goods = state.goods.low_stock(include_zero_reorder).await.map_err(|e| {
+        log_database_error("low stock report", &e);
+        ApiError::database(e, "low stock report")
+    })?;
+
+    let count = goods.len();
+    log_success("low stock report", &goods, count);
+    Ok(success_response(goods, &format_success_message("Low stock report", count)))
+}
+
+// Route: GET /reports/valuation - Stock value (quantity * price) per goods or category
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/reports/valuation",
+    tag = "reports",
+    params(
+        InventoryQueryParams,
+        ("group_by" = Option<String>, Query, description = "goods (default) or category"),
+        ("exclude_expired" = Option<bool>, Query, description = "Leave out items whose expiry date has passed (default false)"),
+        ("format" = Option<String>, Query, description = "json (default) or csv; overrides the Accept header")
+    ),
+    responses(
+        (status = 200, description = "Groups by value, highest first, with the grand total as JSON, or CSV with Accept: text/csv ending in a Total row", body = ApiResponse<ValuationReport>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 406, description = "Accept lists no supported type", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_valuation_report(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ApiQuery(query): ApiQuery<FilterQuery<InventoryQueryParams, ValuationOptions>>,
+) -> Result<Response, ApiError> {
+    let format = OutputFormat::negotiate(&headers, query.options.format.as_deref()).inspect_err(|e| warn!("{}", e))?;
+    query.reject_unknown_params().map_err(|parse_error| {
+        log_validation_error("valuation report", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    let mut query_params = query.filters;
+    query_params.normalize(state.config.server.normalization());
+    log_request_params("valuation report", &query_params);
+
+    // Validate and parse query parameters (no filters means all inventory)
+    let parse = || -> Result<_, String> {
+        let group_by = query.options.group_by()?;
+        let exclude_expired = query.options.exclude_expired()?;
+        query_params.validate_and_parse_valuation(group_by, exclude_expired)
+    };
+    let valuation_params = parse().map_err(|parse_error| {
+        log_validation_error("valuation report", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+
+    // Aggregate in the database so the decimal sums stay exact
+    let report = state.inventory.valuation(&valuation_params).await.map_err(|e| {
+        log_database_error("valuation report", &e);
+        ApiError::database(e, "valuation report")
+    })?;
+
+    let count = report.rows.len();
+    log_success("valuation report", &report, count);
+    let message = format_success_message("Valuation report", count);
+    match format {
+        OutputFormat::Json => Ok(success_response(report, &message)),
+        OutputFormat::Csv => render_rows(format, "valuation", report.into_csv_rows(), None, None, &message),
+    }
+}
+
+// Route: GET /reports/stock-history - Daily stock snapshots per good
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/reports/stock-history",
+    tag = "reports",
+    params(StockHistoryQueryParams),
+    responses(
+        (status = 200, description = "Snapshots ordered by goods_id, then date", body = ApiResponse<Vec<StockSnapshot>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_stock_history(
+    State(state): State<AppState>,
+    ApiQuery(query_params): ApiQuery<StockHistoryQueryParams>,
+) -> Result<Response, ApiError> {
+    log_request_params("stock history report", &query_params);
+
+    let mut search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error("stock history report", &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    search_params.pagination = Some(search_params.pagination.unwrap_or_default().limited(state.config.server.page_limits()));
+
+    let page = state.database.stock_snapshots_table.search_paginated(search_params).await.map_err(|e| {
+        log_database_error("stock history report", &e);
+        ApiError::database(e, "stock history report")
+    })?;
+
+    let count = page.data.len();
+    log_success("stock history report", &page, count);
+    Ok(paginated_response(page, &format_success_message("Stock history report", count)))
+}
+
+// Route: GET /reports/goods-cache - Goods cache hit and miss counters
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/reports/goods-cache",
+    tag = "reports",
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<GoodsCacheStats>)
+    )
+))]
+async fn get_goods_cache_stats(State(state): State<AppState>) -> Response {
+    info!("Goods cache stats requested");
+
+    let stats = state.goods.cache_stats();
+    success_response(stats, &format_success_message("Goods cache stats", 1))
+}
+
+// PURCHASE ORDER ROUTES
+
+// Route: POST /purchase-orders - Create a purchase order with its lines
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/purchase-orders",
+    tag = "purchase-orders",
+    request_body = CreatePurchaseOrderRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<PurchaseOrder>),
+        (status = 400, description = "Invalid request, or lines referencing unknown goods", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn create_purchase_order(
+    State(state): State<AppState>,
+    JsonBody(request): JsonBody<CreatePurchaseOrderRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("create purchase order", &request);
+
+    // Validate request
+    request.validate().map_err(|validation_error| {
+        log_validation_error("create purchase order", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+    let status = request.initial_status().map_err(ApiError::Validation)?;
+
+    let order = state.database.purchase_orders_table.create(&request, status).await.map_err(|e| match e {
+        TableError::Database(e) => {
+            log_database_error("create purchase order", &e);
+            ApiError::database(e, "purchase order creation")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    log_success("create purchase order", &order, 1);
+    Ok(success_response(order, &format_success_message("Purchase order creation", 1)))
+}
+
+// Route: GET /purchase-orders/{purchase_order_id} - Get a purchase order with its lines
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/purchase-orders/{purchase_order_id}",
+    tag = "purchase-orders",
+    params(("purchase_order_id" = i32, Path, description = "Purchase order id")),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<PurchaseOrder>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_purchase_order(
+    State(state): State<AppState>,
+    Path(purchase_order_id): Path<String>,
+) -> Result<Response, ApiError> {
+    log_request_params("get purchase order", &purchase_order_id);
+
+    // Validate path parameter
+    let purchase_order_id = parse_safe_integer(&purchase_order_id, "purchase_order_id").map_err(|parse_error| {
+        log_validation_error("get purchase order", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    let order = state.database.purchase_orders_table.get_by_id(purchase_order_id).await.map_err(|e| {
+        log_database_error("get purchase order", &e);
+        ApiError::database(e, "purchase order lookup")
+    })?;
+
+    match order {
+        Some(order) => {
+            log_success("get purchase order", &order, 1);
+            Ok(success_response(order, &format_success_message("Purchase order lookup", 1)))
+        }
+        None => {
+            warn!("Purchase order {} not found", purchase_order_id);
+            Err(ApiError::NotFound(format!("Purchase order {} not found", purchase_order_id)))
+        }
+    }
+}
+
+// Route: POST /purchase-orders/{purchase_order_id}/order - Place a draft purchase order
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/purchase-orders/{purchase_order_id}/order",
+    tag = "purchase-orders",
+    params(("purchase_order_id" = i32, Path, description = "Purchase order id")),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<PurchaseOrder>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "The order is not a draft", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn place_purchase_order(
+    State(state): State<AppState>,
+    Path(purchase_order_id): Path<String>,
+) -> Result<Response, ApiError> {
+    log_request_params("place purchase order", &purchase_order_id);
+
+    // Validate path parameter
+    let purchase_order_id = parse_safe_integer(&purchase_order_id, "purchase_order_id").map_err(|parse_error| {
+        log_validation_error("place purchase order", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    let order = state.database.purchase_orders_table.place(purchase_order_id).await.map_err(|e| match e {
+        TableError::Database(e) => {
+            log_database_error("place purchase order", &e);
+            ApiError::database(e, "purchase order placement")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    match order {
+        Some(order) => {
+            log_success("place purchase order", &order, 1);
+            Ok(success_response(order, &format_success_message("Purchase order placement", 1)))
+        }
+        None => {
+            warn!("Purchase order {} not found", purchase_order_id);
+            Err(ApiError::NotFound(format!("Purchase order {} not found", purchase_order_id)))
+        }
+    }
+}
+
+// Route: POST /purchase-orders/{purchase_order_id}/receive - Book received quantities into inventory
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/purchase-orders/{purchase_order_id}/receive",
+    tag = "purchase-orders",
+    params(("purchase_order_id" = i32, Path, description = "Purchase order id")),
+    request_body(content = Option<ReceivePurchaseOrderRequest>, description = "Quantities per line; omit the body to receive everything outstanding"),
+    responses(
+        (status = 200, description = "The updated order and the inventory items booked", body = ApiResponse<PurchaseOrderReceipt>),
+        (status = 400, description = "Invalid request, or more than outstanding on a line", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "The order is already fully received", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn receive_purchase_order(
+    State(state): State<AppState>,
+    Path(purchase_order_id): Path<String>,
+    request: Option<JsonBody<ReceivePurchaseOrderRequest>>,
+) -> Result<Response, ApiError> {
+    let request = request.map(|JsonBody(request)| request).unwrap_or_default();
+    log_request_params("receive purchase order", &request);
+
+    // Validate path parameter and request
+    let purchase_order_id = parse_safe_integer(&purchase_order_id, "purchase_order_id").map_err(|parse_error| {
+        log_validation_error("receive purchase order", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    request.validate().map_err(|validation_error| {
+        log_validation_error("receive purchase order", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    let receipt = state.database.purchase_orders_table.receive(purchase_order_id, &request).await.map_err(|e| match e {
+        TableError::Database(e) => {
+            log_database_error("receive purchase order", &e);
+            ApiError::database(e, "purchase order receipt")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    match receipt {
+        Some(receipt) => {
+            let count = receipt.received.len();
+            log_success("receive purchase order", &receipt, count);
+            Ok(success_response(receipt, &format_success_message("Purchase order receipt", count)))
+        }
+        None => {
+            warn!("Purchase order {} not found", purchase_order_id);
+            Err(ApiError::NotFound(format!("Purchase order {} not found", purchase_order_id)))
+        }
+    }
+}
+
+// RESERVATION ROUTES
+
+// Route: POST /reservations - Hold stock on an inventory item
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/reservations",
+    tag = "reservations",
+    request_body = CreateReservationRequest,
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Reservation>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Not enough available stock", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn create_reservation(
+    State(state): State<AppState>,
+    JsonBody(request): JsonBody<CreateReservationRequest>,
+) -> Result<Response, ApiError> {
+    log_request_params("create reservation", &request);
+
+    // Validate request
+    request.validate().map_err(|validation_error| {
+        log_validation_error("create reservation", &validation_error);
+        ApiError::Validation(validation_error)
+    })?;
+
+    // Reserve stock
+    let reservation = state.database.reservations_table.create(&request).await.map_err(|e| match e {
+        TableError::Database(sqlx::Error::RowNotFound) => {
+            warn!("Inventory item {} not found", request.item_id);
+            ApiError::NotFound(format!("Inventory item {} not found", request.item_id))
+        }
+        TableError::Database(e) => {
+            log_database_error("create reservation", &e);
+            ApiError::database(e, "reservation creation")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    log_success("create reservation", &reservation, 1);
+    Ok(success_response(reservation, &format_success_message("Reservation creation", 1)))
+}
+
+// Route: DELETE /reservations/{reservation_id} - Release held stock
+#[cfg_attr(feature = "openapi", utoipa::path(
+    delete,
+    path = "/reservations/{reservation_id}",
+    tag = "reservations",
+    params(("reservation_id" = i32, Path, description = "Reservation id")),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Reservation>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn release_reservation(
+    State(state): State<AppState>,
+    Path(reservation_id): Path<String>,
+) -> Result<Response, ApiError> {
+    log_request_params("release reservation", &reservation_id);
+
+    // Validate path parameter
+    let reservation_id = parse_safe_integer(&reservation_id, "reservation_id").map_err(|parse_error| {
+        log_validation_error("release reservation", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    // Delete the reservation
+    let reservation = state.database.reservations_table.release(reservation_id).await.map_err(|e| {
+        log_database_error("release reservation", &e);
+        ApiError::database(e, "reservation release")
+    })?;
+
+    match reservation {
+        Some(reservation) => {
+            log_success("release reservation", &reservation, 1);
+            Ok(success_response(reservation, &format_success_message("Reservation release", 1)))
+        }
+        None => {
+            warn!("Reservation {} not found", reservation_id);
+            Err(ApiError::NotFound(format!("Reservation {} not found", reservation_id)))
+        }
+    }
+}
+
+// Route: POST /reservations/{reservation_id}/commit - Turn held stock into a quantity decrement
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/reservations/{reservation_id}/commit",
+    tag = "reservations",
+    params(("reservation_id" = i32, Path, description = "Reservation id")),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<InventoryItemWithGoods>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Item no longer has enough stock", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn commit_reservation(
+    State(state): State<AppState>,
+    Path(reservation_id): Path<String>,
+) -> Result<Response, ApiError> {
+    log_request_params("commit reservation", &reservation_id);
+
+    // Validate path parameter
+    let reservation_id = parse_safe_integer(&reservation_id, "reservation_id").map_err(|parse_error| {
+        log_validation_error("commit reservation", &parse_error);
+        ApiError::Validation(parse_error)
+    })?;
+
+    // Decrement stock and remove the reservation
+    let item = state.database.reservations_table.commit(reservation_id).await.map_err(|e| match e {
+        TableError::Database(sqlx::Error::RowNotFound) => {
+            warn!("Reservation {} not found or expired", reservation_id);
+            ApiError::NotFound(format!("Reservation {} not found or expired", reservation_id))
+        }
+        TableError::Database(e) => {
+            log_database_error("commit reservation", &e);
+            ApiError::database(e, "reservation commit")
+        }
+        e => {
+            warn!("{}", e);
+            ApiError::from(e)
+        }
+    })?;
+
+    log_success("commit reservation", &item, 1);
+    Ok(success_response(item, &format_success_message("Reservation commit", 1)))
+}
+
+// STOCK MOVEMENT ROUTES
+
+// Route: GET /inventory/{item_id}/movements - Movement history for one inventory item
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/inventory/{item_id}/movements",
+    tag = "movements",
+    params(("item_id" = i32, Path, description = "Inventory item id"), MovementQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Vec<StockMovement>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_inventory_item_movements(
+    State(state): State<AppState>,
+    Path(item_id): Path<String>,
+    ApiQuery(mut query_params): ApiQuery<MovementQueryParams>,
+) -> Result<Response, ApiError> {
+    query_params.item_id = Some(item_id);
+    log_request_params("get inventory item movements", &query_params);
+
+    movements_response(&state, query_params, "get inventory item movements").await
+}
+
+// Route: GET /movements - Movement history across all inventory items
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/movements",
+    tag = "movements",
+    params(MovementQueryParams),
+    responses(
+        (status = 200, description = "Success", body = ApiResponse<Vec<StockMovement>>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+))]
+async fn get_movements(
+    State(state): State<AppState>,
+    ApiQuery(query_params): ApiQuery<MovementQueryParams>,
+) -> Result<Response, ApiError> {
+    log_request_params("get movements", &query_params);
+
+    movements_response(&state, query_params, "get movements").await
+}
+
+async fn movements_response(
+    state: &AppState,
+    query_params: MovementQueryParams,
+    operation: &str,
+) -> Result<Response, ApiError> {
+    // Validate and parse query parameters
+    let mut search_params = query_params.validate_and_parse().map_err(|parse_error| {
+        log_validation_error(operation, &parse_error);
+        ApiError::Validation(format!("Invalid query parameters: {}", parse_error))
+    })?;
+    search_params.pagination = Some(search_params.pagination.unwrap_or_default().limited(state.config.server.page_limits()));
+
+    // Perform database search
+    let page = state.database.stock_movements_table.search_paginated(search_params).await.map_err(|e| {
+        log_database_error(operation, &e);
+        ApiError::database(e, "movement search")
+    })?;
+
+    let count = page.data.len();
+    log_success(operation, &page, count);
+    Ok(paginated_response(page, &format_success_message("Movement search", count)))
+}
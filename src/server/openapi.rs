@@ -0,0 +1,114 @@
+// src/server/openapi.rs
+//
+// OpenAPI document assembled from the `utoipa::path` annotations on the handlers in
+// `server.rs`. Schemas referenced by those annotations are collected automatically.
+use super::*;
+use crate::auth::API_KEY_HEADER;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityRequirement, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "ONECHILLI Inventory API"),
+    modifiers(&ApiKeyAuth),
+    paths(
+        api_health,
+        database_health,
+        get_version,
+        get_goods,
+        create_goods,
+        update_goods,
+        delete_goods,
+        delete_goods_by_ids,
+        upsert_goods,
+        patch_goods,
+        archive_goods,
+        unarchive_goods,
+        merge_goods,
+        clone_goods,
+        get_goods_by_barcode,
+        get_goods_tags,
+        get_goods_duplicates,
+        create_goods_batch,
+        export_goods_csv,
+        export_goods_ndjson,
+        get_inventory,
+        create_inventory,
+        update_inventory,
+        delete_inventory,
+        delete_inventory_by_ids,
+        create_inventory_batch,
+        export_inventory_csv,
+        export_inventory_ndjson,
+        import_inventory_csv,
+        consume_inventory,
+        merge_duplicate_inventory,
+        get_inventory_stats,
+        get_inventory_summary,
+        get_inventory_item,
+        patch_inventory_item,
+        adjust_inventory,
+        decrement_inventory,
+        increment_inventory,
+        get_inventory_item_movements,
+        get_movements,
+        sync_goods,
+        sync_inventory,
+        get_low_stock_report,
+        get_valuation_report,
+        get_alerts,
+        acknowledge_alert,
+        get_audit_log,
+        get_stock_history,
+        get_goods_cache_stats,
+        get_categories,
+        create_category,
+        get_category,
+        update_category,
+        delete_category,
+        create_purchase_order,
+        get_purchase_order,
+        place_purchase_order,
+        receive_purchase_order,
+        create_reservation,
+        release_reservation,
+        commit_reservation,
+        purge_expired_inventory,
+        take_stock_snapshot,
+        set_maintenance_mode,
+    ),
+    tags(
+        (name = "health", description = "Service and database health"),
+        (name = "goods", description = "Goods catalogue"),
+        (name = "inventory", description = "Inventory items and stock operations"),
+        (name = "categories", description = "Goods categories"),
+        (name = "movements", description = "Stock movement history"),
+        (name = "sync", description = "Incremental change feeds"),
+        (name = "reports", description = "Stock reports"),
+        (name = "alerts", description = "Expiry alerts"),
+        (name = "purchase-orders", description = "Purchase orders and receiving"),
+        (name = "reservations", description = "Stock reserved for pending orders"),
+        (name = "admin", description = "Maintenance operations"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Documents the API key header; keys with the `read` role may only use GET endpoints
+struct ApiKeyAuth;
+
+impl Modify for ApiKeyAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+        openapi.security = Some(vec![SecurityRequirement::new("api_key", Vec::<String>::new())]);
+    }
+}
+
+/// Routes serving the spec at `/openapi.json` and Swagger UI at `/docs`
+pub fn docs_router() -> Router<AppState> {
+    Router::new().merge(SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi()))
+}
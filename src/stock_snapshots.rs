@@ -0,0 +1,48 @@
+// src/stock_snapshots.rs
+//
+// Scheduled daily stock snapshot. Once a day, at the configured UTC time, the totals of every
+// good are recorded under the current date. A failed run, e.g. while the database is down, is
+// logged and retried a few minutes later until it succeeds; taking a day again is harmless.
+use crate::config::StockSnapshotConfig;
+use crate::tables::StockSnapshotsTable;
+use crate::utils::datetime::until_next_time_of_day;
+use crate::utils::logging::log_database_error;
+use chrono::Utc;
+use std::time::Duration;
+use tracing::info;
+
+/// Delay before retrying a failed snapshot
+const RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// Start the daily snapshot; does nothing unless it is enabled
+pub fn spawn_stock_snapshots(table: StockSnapshotsTable, config: StockSnapshotConfig) -> anyhow::Result<()> {
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let run_at = config.run_at_time()?;
+    info!("Taking stock snapshots daily at {} UTC", config.run_at);
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(until_next_time_of_day(run_at)).await;
+
+            // The retries of a run still record the day it was scheduled for
+            let snapshot_date = Utc::now().date_naive();
+            loop {
+                match table.take(snapshot_date).await {
+                    Ok(summary) => {
+                        info!("Stock snapshot for {} recorded {} goods", summary.snapshot_date, summary.goods_count);
+                        break;
+                    }
+                    Err(e) => {
+                        log_database_error("take stock snapshot", &e);
+                        tokio::time::sleep(RETRY_DELAY).await;
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,197 @@
+// src/tables/alerts_table.rs
+//
+// Expiry alerts raised by the daily check in `alerts.rs`. An alert is keyed by item, expiry
+// and the threshold it crossed: a later run refreshes an open alert instead of adding another,
+// an acknowledged one is left alone, and crossing a tighter threshold raises a new alert.
+use crate::utils::pagination::{PaginatedResponse, PaginationParams};
+use crate::utils::query_builder::SearchQueryBuilder;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Alert {
+    pub alert_id: i32,
+    pub item_id: i32,
+    pub goods_id: i32,
+    pub goods_name: String,
+    pub quantity: i32,
+    pub expired_date: DateTime<Utc>,
+    /// The configured threshold this alert was raised for
+    pub threshold_days: i32,
+    /// Days left until expiry when the alert was last refreshed
+    pub days_remaining: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+}
+
+/// An in-stock inventory item expiring soon
+#[derive(Debug, Clone, FromRow)]
+pub struct ExpiringItem {
+    pub item_id: i32,
+    pub goods_id: i32,
+    pub goods_name: String,
+    pub quantity: i32,
+    pub expired_date: DateTime<Utc>,
+}
+
+/// An alert to raise or refresh
+#[derive(Debug, Clone)]
+pub struct NewAlert {
+    pub item: ExpiringItem,
+    pub threshold_days: i32,
+    pub days_remaining: i32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AlertSearchParams {
+    pub acknowledged: Option<bool>,
+    pub pagination: Option<PaginationParams>,
+}
+
+const ALERT_COLUMNS: &str = "alert_id, item_id, goods_id, goods_name, quantity, expired_date, threshold_days, days_remaining, created_at, updated_at, acknowledged_at";
+
+#[derive(Clone)]
+pub struct AlertsTable {
+    pool: PgPool,
+}
+
+impl AlertsTable {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// In-stock items that have not expired yet but will before `until`
+    pub async fn expiring_items(&self, until: DateTime<Utc>) -> Result<Vec<ExpiringItem>, sqlx::Error> {
+        sqlx::query_as::<_, ExpiringItem>(
+            r#"
+            SELECT i.item_id, i.goods_id, g.goods_name, i.quantity, i.expired_date
+            FROM inventory i
+            INNER JOIN goods g ON i.goods_id = g.goods_id
+            WHERE i.quantity > 0 AND i.expired_date > now() AND i.expired_date <= $1
+            ORDER BY i.expired_date ASC, i.item_id ASC
+            "#
+        )
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Raise new alerts and refresh the open ones among `alerts`, in one transaction.
+    /// Returns only the newly raised alerts.
+    pub async fn record(&self, alerts: &[NewAlert]) -> Result<Vec<Alert>, sqlx::Error> {
+        if alerts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let item_ids: Vec<i32> = alerts.iter().map(|alert| alert.item.item_id).collect();
+        let goods_ids: Vec<i32> = alerts.iter().map(|alert| alert.item.goods_id).collect();
+        let goods_names: Vec<String> = alerts.iter().map(|alert| alert.item.goods_name.clone()).collect();
+        let quantities: Vec<i32> = alerts.iter().map(|alert| alert.item.quantity).collect();
+        let expired_dates: Vec<DateTime<Utc>> = alerts.iter().map(|alert| alert.item.expired_date).collect();
+        let thresholds: Vec<i32> = alerts.iter().map(|alert| alert.threshold_days).collect();
+        let days_remaining: Vec<i32> = alerts.iter().map(|alert| alert.days_remaining).collect();
+
+        let mut tx = self.pool.begin().await?;
+
+        // Refresh open alerts first, so the insert below only returns new ones
+        sqlx::query(
+            r#"
+            UPDATE alerts a
+            SET quantity = n.quantity, days_remaining = n.days_remaining, updated_at = now()
+            FROM UNNEST($1::int4[], $2::timestamptz[], $3::int4[], $4::int4[], $5::int4[])
+                AS n(item_id, expired_date, threshold_days, quantity, days_remaining)
+            WHERE a.item_id = n.item_id AND a.expired_date = n.expired_date
+                AND a.threshold_days = n.threshold_days AND a.acknowledged_at IS NULL
+            "#
+        )
+        .bind(&item_ids)
+        .bind(&expired_dates)
+        .bind(&thresholds)
+        .bind(&quantities)
+        .bind(&days_remaining)
+        .execute(&mut *tx)
+        .await?;
+
+        let mut raised = sqlx::query_as::<_, Alert>(&format!(
+            r#"
+            INSERT INTO alerts (item_id, goods_id, goods_name, quantity, expired_date, threshold_days, days_remaining)
+            SELECT * FROM UNNEST($1::int4[], $2::int4[], $3::text[], $4::int4[], $5::timestamptz[], $6::int4[], $7::int4[])
+            ON CONFLICT (item_id, expired_date, threshold_days) DO NOTHING
+            RETURNING {}
+            "#,
+            ALERT_COLUMNS
+        ))
+        .bind(&item_ids)
+        .bind(&goods_ids)
+        .bind(&goods_names)
+        .bind(&quantities)
+        .bind(&expired_dates)
+        .bind(&thresholds)
+        .bind(&days_remaining)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        raised.sort_by_key(|alert| alert.alert_id);
+        Ok(raised)
+    }
+
+    /// Alerts, newest first
+    pub async fn search(&self, params: &AlertSearchParams) -> Result<Vec<Alert>, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new(format!("SELECT {} FROM alerts WHERE 1=1", ALERT_COLUMNS));
+        Self::add_search_conditions(&mut builder, params);
+
+        let mut query = builder.build(Some("created_at DESC, alert_id DESC"));
+
+        if let Some(pagination) = &params.pagination {
+            query.push_str(&pagination.to_sql());
+        }
+
+        builder.bind_values(sqlx::query_as::<_, Alert>(&query))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn count(&self, params: &AlertSearchParams) -> Result<i64, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new("SELECT COUNT(*) FROM alerts WHERE 1=1".to_string());
+        Self::add_search_conditions(&mut builder, params);
+
+        let query = builder.build(None);
+        let (count,) = builder.bind_values(sqlx::query_as::<_, (i64,)>(&query))
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    pub async fn search_paginated(&self, mut params: AlertSearchParams) -> Result<PaginatedResponse<Alert>, sqlx::Error> {
+        let pagination = params.pagination.clone().unwrap_or_default();
+        params.pagination = Some(pagination.clone());
+
+        let (alerts, total_count) = tokio::try_join!(
+            self.search(&params),
+            self.count(&params)
+        )?;
+
+        Ok(PaginatedResponse::new(alerts, &pagination, Some(total_count as u64)))
+    }
+
+    /// Mark an alert as handled; acknowledging twice keeps the first time. `None` when it does not exist.
+    pub async fn acknowledge(&self, alert_id: i32) -> Result<Option<Alert>, sqlx::Error> {
+        sqlx::query_as::<_, Alert>(&format!(
+            "UPDATE alerts SET acknowledged_at = COALESCE(acknowledged_at, now()) WHERE alert_id = $1 RETURNING {}",
+            ALERT_COLUMNS
+        ))
+        .bind(alert_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    fn add_search_conditions(builder: &mut SearchQueryBuilder, params: &AlertSearchParams) {
+        builder.add_optional_condition("(acknowledged_at IS NOT NULL) = ?", params.acknowledged);
+    }
+}
@@ -0,0 +1,221 @@
+// src/tables/audit_log_table.rs
+//
+// Audit trail of mutating requests. The `audit` middleware runs each request with its entry
+// pending; the goods and inventory tables write it with `record_tx` on the transaction of the
+// change itself, so a change commits with its entry or not at all. Requests no table recorded
+// are written by the middleware once they succeeded.
+use crate::utils::pagination::{PaginatedResponse, PaginationParams};
+use crate::utils::query_builder::SearchQueryBuilder;
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+tokio::task_local! {
+    static PENDING_ENTRY: PendingEntry;
+}
+
+/// The entry of the request being handled, and whether a table has written it
+struct PendingEntry {
+    entry: NewAuditEntry,
+    recorded: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AuditEntry {
+    pub audit_id: i64,
+    pub created_at: DateTime<Utc>,
+    /// Label of the API key used; unset for unlabelled keys or with authentication disabled
+    pub api_key_label: Option<String>,
+    pub client_ip: Option<String>,
+    pub role: String,
+    pub request_id: Option<String>,
+    pub method: String,
+    /// Path and query string
+    pub path: String,
+    pub target_table: String,
+    pub affected_ids: Vec<i32>,
+    /// JSON request body with sensitive fields redacted; unset for other bodies
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub request_body: Option<serde_json::Value>,
+    /// Response status; 200 for entries written with the change, which commits only on success
+    pub status_code: Option<i32>,
+}
+
+/// `request_body` is read as text, since the sqlx `json` feature is not enabled
+#[derive(FromRow)]
+struct AuditEntryRow {
+    audit_id: i64,
+    created_at: DateTime<Utc>,
+    api_key_label: Option<String>,
+    client_ip: Option<String>,
+    role: String,
+    request_id: Option<String>,
+    method: String,
+    path: String,
+    target_table: String,
+    affected_ids: Vec<i32>,
+    request_body: Option<String>,
+    status_code: Option<i32>,
+}
+
+impl From<AuditEntryRow> for AuditEntry {
+    fn from(row: AuditEntryRow) -> Self {
+        Self {
+            audit_id: row.audit_id,
+            created_at: row.created_at,
+            api_key_label: row.api_key_label,
+            client_ip: row.client_ip,
+            role: row.role,
+            request_id: row.request_id,
+            method: row.method,
+            path: row.path,
+            target_table: row.target_table,
+            affected_ids: row.affected_ids,
+            request_body: row.request_body.and_then(|body| serde_json::from_str(&body).ok()),
+            status_code: row.status_code,
+        }
+    }
+}
+
+/// A mutating request, recorded once it changed something
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    pub api_key_label: Option<String>,
+    pub client_ip: Option<String>,
+    pub role: String,
+    pub request_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub target_table: String,
+    pub request_body: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AuditSearchParams {
+    pub target_table: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub pagination: Option<PaginationParams>,
+}
+
+const AUDIT_COLUMNS: &str = "audit_id, created_at, api_key_label, client_ip, role, request_id, method, path, target_table, affected_ids, request_body::text AS request_body, status_code";
+
+#[derive(Clone)]
+pub struct AuditLogTable {
+    pool: PgPool,
+}
+
+impl AuditLogTable {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Run `request` with `entry` pending; returns its output and whether a table wrote the
+    /// entry with its change
+    pub async fn with_pending_entry<F: Future>(entry: NewAuditEntry, request: F) -> (F::Output, bool) {
+        let recorded = Arc::new(AtomicBool::new(false));
+        let output = PENDING_ENTRY.scope(PendingEntry { entry, recorded: recorded.clone() }, request).await;
+        (output, recorded.load(Ordering::SeqCst))
+    }
+
+    /// Write the pending entry on the change's transaction; a no-op outside an audited request
+    pub async fn record_tx(conn: &mut PgConnection, target_table: &str, affected_ids: &[i32]) -> Result<(), sqlx::Error> {
+        let Ok((entry, recorded)) = PENDING_ENTRY.try_with(|pending| (pending.entry.clone(), pending.recorded.clone())) else {
+            return Ok(());
+        };
+
+        Self::insert(conn, &entry, target_table, StatusCode::OK, affected_ids).await?;
+        recorded.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Write the entry of a request that succeeded without a table recording it
+    pub async fn record(&self, entry: &NewAuditEntry, status_code: StatusCode, affected_ids: &[i32]) -> Result<(), sqlx::Error> {
+        Self::insert(&mut *self.pool.acquire().await?, entry, &entry.target_table, status_code, affected_ids).await
+    }
+
+    async fn insert(
+        conn: &mut PgConnection,
+        entry: &NewAuditEntry,
+        target_table: &str,
+        status_code: StatusCode,
+        affected_ids: &[i32],
+    ) -> Result<(), sqlx::Error> {
+        let request_body = entry.request_body.as_ref().map(|body| body.to_string());
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (api_key_label, client_ip, role, request_id, method, path, target_table, affected_ids, request_body, status_code)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9::jsonb, $10)
+            "#
+        )
+        .bind(&entry.api_key_label)
+        .bind(&entry.client_ip)
+        .bind(&entry.role)
+        .bind(&entry.request_id)
+        .bind(&entry.method)
+        .bind(&entry.path)
+        .bind(target_table)
+        .bind(affected_ids)
+        .bind(request_body)
+        .bind(i32::from(status_code.as_u16()))
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Audit entries, newest first
+    pub async fn search(&self, params: &AuditSearchParams) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new(format!("SELECT {} FROM audit_log WHERE 1=1", AUDIT_COLUMNS));
+        Self::add_search_conditions(&mut builder, params);
+
+        let mut query = builder.build(Some("created_at DESC, audit_id DESC"));
+
+        if let Some(pagination) = &params.pagination {
+            query.push_str(&pagination.to_sql());
+        }
+
+        let rows = builder.bind_values(sqlx::query_as::<_, AuditEntryRow>(&query))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(AuditEntry::from).collect())
+    }
+
+    pub async fn count(&self, params: &AuditSearchParams) -> Result<i64, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new("SELECT COUNT(*) FROM audit_log WHERE 1=1".to_string());
+        Self::add_search_conditions(&mut builder, params);
+
+        let query = builder.build(None);
+        let (count,) = builder.bind_values(sqlx::query_as::<_, (i64,)>(&query))
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// The log is unbounded, so it is always served one page at a time
+    pub async fn search_paginated(&self, mut params: AuditSearchParams) -> Result<PaginatedResponse<AuditEntry>, sqlx::Error> {
+        let pagination = params.pagination.clone().unwrap_or_default();
+        params.pagination = Some(pagination.clone());
+
+        let (entries, total_count) = tokio::try_join!(
+            self.search(&params),
+            self.count(&params)
+        )?;
+
+        Ok(PaginatedResponse::new(entries, &pagination, Some(total_count as u64)))
+    }
+
+    fn add_search_conditions(builder: &mut SearchQueryBuilder, params: &AuditSearchParams) {
+        builder.add_optional_condition("target_table = ?", params.target_table.clone());
+        builder.add_optional_condition("created_at >= ?", params.from);
+        builder.add_optional_condition("created_at <= ?", params.to);
+    }
+}
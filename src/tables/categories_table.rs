@@ -0,0 +1,178 @@
+// src/tables/categories_table.rs
+//
+// Goods categories. Categories nest through parent_id; the tree is kept acyclic on update
+// and a category cannot be deleted while goods or subcategories still reference it.
+use super::error::TableError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Category {
+    pub category_id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateCategoryRequest {
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateCategoryRequest {
+    pub name: Option<String>,
+    pub parent_id: Option<i32>,
+}
+
+/// SQL selecting the ids of the categories matching `root_condition` and all their descendants
+pub fn category_tree_query(root_condition: &str) -> String {
+    format!(
+        "WITH RECURSIVE category_tree AS (\
+            SELECT category_id FROM categories WHERE {} \
+            UNION \
+            SELECT c.category_id FROM categories c INNER JOIN category_tree t ON c.parent_id = t.category_id\
+        ) SELECT category_id FROM category_tree",
+        root_condition
+    )
+}
+
+#[derive(Clone)]
+pub struct CategoriesTable {
+    pool: PgPool,
+}
+
+impl CategoriesTable {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_all(&self) -> Result<Vec<Category>, sqlx::Error> {
+        sqlx::query_as::<_, Category>(
+            "SELECT category_id, name, parent_id, created_at, updated_at FROM categories ORDER BY category_id ASC"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_by_id(&self, category_id: i32) -> Result<Option<Category>, sqlx::Error> {
+        sqlx::query_as::<_, Category>(
+            "SELECT category_id, name, parent_id, created_at, updated_at FROM categories WHERE category_id = $1"
+        )
+        .bind(category_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn insert(&self, request: &CreateCategoryRequest) -> Result<Category, sqlx::Error> {
+        sqlx::query_as::<_, Category>(
+            r#"
+            INSERT INTO categories (name, parent_id)
+            VALUES ($1, $2)
+            RETURNING category_id, name, parent_id, created_at, updated_at
+            "#
+        )
+        .bind(&request.name)
+        .bind(request.parent_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Rename or move a category, refusing to move it under itself or one of its descendants
+    pub async fn update(&self, category_id: i32, request: &UpdateCategoryRequest) -> Result<Option<Category>, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Serialize moves so two concurrent updates cannot form a cycle together
+        sqlx::query("LOCK TABLE categories IN SHARE ROW EXCLUSIVE MODE")
+            .execute(&mut *tx)
+            .await?;
+
+        if let Some(parent_id) = request.parent_id {
+            let (creates_cycle,) = sqlx::query_as::<_, (bool,)>(&format!(
+                "SELECT $2 IN ({})",
+                category_tree_query("category_id = $1")
+            ))
+            .bind(category_id)
+            .bind(parent_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if creates_cycle {
+                tx.rollback().await?;
+                return Err(TableError::CategoryCycle { category_id, parent_id });
+            }
+        }
+
+        let category = sqlx::query_as::<_, Category>(
+            r#"
+            UPDATE categories
+            SET name = COALESCE($2, name),
+                parent_id = COALESCE($3, parent_id),
+                updated_at = now()
+            WHERE category_id = $1
+            RETURNING category_id, name, parent_id, created_at, updated_at
+            "#
+        )
+        .bind(category_id)
+        .bind(&request.name)
+        .bind(request.parent_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(category)
+    }
+
+    /// Delete a category that no goods or subcategories reference
+    pub async fn delete(&self, category_id: i32) -> Result<Option<Category>, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Lock the row so goods cannot be assigned to it while we check
+        let exists = sqlx::query_as::<_, (i32,)>("SELECT category_id FROM categories WHERE category_id = $1 FOR UPDATE")
+            .bind(category_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        if exists.is_none() {
+            return Ok(None);
+        }
+
+        let (goods_count, subcategory_count) = sqlx::query_as::<_, (i64, i64)>(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM goods WHERE category_id = $1),
+                (SELECT COUNT(*) FROM categories WHERE parent_id = $1)
+            "#
+        )
+        .bind(category_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if goods_count > 0 || subcategory_count > 0 {
+            tx.rollback().await?;
+            return Err(TableError::CategoryInUse { category_id, goods_count, subcategory_count });
+        }
+
+        let category = sqlx::query_as::<_, Category>(
+            r#"
+            DELETE FROM categories
+            WHERE category_id = $1
+            RETURNING category_id, name, parent_id, created_at, updated_at
+            "#
+        )
+        .bind(category_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(category)
+    }
+}
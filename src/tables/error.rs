@@ -0,0 +1,107 @@
+// src/tables/error.rs
+use serde::Serialize;
+use thiserror::Error;
+
+/// A good that cannot be deleted because inventory items still reference it
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BlockedGoods {
+    pub goods_id: i32,
+    pub inventory_count: i64,
+}
+
+/// A good whose version no longer matches the `expected_version` of an update
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleGoods {
+    pub goods_id: i32,
+    pub current_version: i32,
+}
+
+/// A batch entry that could not be processed, keyed by its index in the request payload
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchItemError {
+    pub index: usize,
+    /// VALIDATION_ERROR, NOT_FOUND or CONFLICT, the codes of the matching whole-request errors
+    pub code: &'static str,
+    pub error: String,
+}
+
+impl BatchItemError {
+    /// An entry that is malformed or breaks a rule on its own
+    pub fn invalid(index: usize, error: impl Into<String>) -> Self {
+        Self { index, code: "VALIDATION_ERROR", error: error.into() }
+    }
+
+    /// An entry referencing a row that does not exist
+    pub fn not_found(index: usize, error: impl Into<String>) -> Self {
+        Self { index, code: "NOT_FOUND", error: error.into() }
+    }
+
+    /// An entry clashing with another entry of the same batch
+    pub fn conflict(index: usize, error: impl Into<String>) -> Self {
+        Self { index, code: "CONFLICT", error: error.into() }
+    }
+}
+
+/// Domain errors returned by the table modules
+#[derive(Debug, Error)]
+pub enum TableError {
+    #[error("Cannot delete goods that still have inventory items: {}", describe_blocked(.goods))]
+    BlockedByInventory { goods: Vec<BlockedGoods> },
+
+    #[error("Cannot adjust inventory item {item_id} by {delta}: only {current_quantity} in stock")]
+    InsufficientStock { item_id: i32, current_quantity: i32, delta: i32 },
+
+    #[error("Cannot consume {requested} of goods_id {goods_id}: only {available} in stock")]
+    InsufficientGoodsStock { goods_id: i32, requested: i32, available: i64 },
+
+    #[error("Cannot reserve {requested} of inventory item {item_id}: only {available} available")]
+    InsufficientAvailableStock { item_id: i32, requested: i32, available: i64 },
+
+    #[error("Inventory item {item_id} already exists with the same goods, expiration date and lot number")]
+    DuplicateInventory { item_id: i32 },
+
+    #[error("Goods {goods_id} is archived; pass allow_inactive=true to record inventory for it")]
+    InactiveGoods { goods_id: i32 },
+
+    #[error("Cannot delete category {category_id}: referenced by {goods_count} goods and {subcategory_count} subcategories")]
+    CategoryInUse { category_id: i32, goods_count: i64, subcategory_count: i64 },
+
+    #[error("Cannot move category {category_id} under {parent_id}: it is the category itself or one of its descendants")]
+    CategoryCycle { category_id: i32, parent_id: i32 },
+
+    #[error("Purchase order {purchase_order_id} cannot be {action}: it is {status}")]
+    PurchaseOrderStatus { purchase_order_id: i32, status: String, action: &'static str },
+
+    #[error("Barcode {barcode} is already assigned to goods_id {goods_id}")]
+    DuplicateBarcode { barcode: String, goods_id: i32 },
+
+    #[error("Filter matches {matched} rows, more than max_affected ({max_affected}); narrow the filter or raise max_affected")]
+    TooManyAffected { matched: i64, max_affected: i64 },
+
+    #[error("Goods were modified by another update: {}", describe_stale(.goods))]
+    StaleVersion { goods: Vec<StaleGoods> },
+
+    #[error("Batch rejected: {} invalid entries", .errors.len())]
+    InvalidBatch { errors: Vec<BatchItemError> },
+
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+fn describe_blocked(goods: &[BlockedGoods]) -> String {
+    goods
+        .iter()
+        .map(|blocked| format!("goods_id {} ({} inventory items)", blocked.goods_id, blocked.inventory_count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_stale(goods: &[StaleGoods]) -> String {
+    goods
+        .iter()
+        .map(|stale| format!("goods_id {} (current version {})", stale.goods_id, stale.current_version))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
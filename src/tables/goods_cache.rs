@@ -0,0 +1,100 @@
+// src/tables/goods_cache.rs
+//
+// Read-through cache for single-goods lookups. Goods are cached by id; material codes map
+// to an id and are checked against the cached good, so renaming a code never serves the
+// old mapping. Every write path that touches goods must call `invalidate`.
+use super::goods_table::Good;
+use moka::sync::Cache;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy)]
+pub struct GoodsCacheConfig {
+    pub enabled: bool,
+    pub capacity: u64,
+    pub ttl: Duration,
+}
+
+/// Hit and miss counters since startup
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GoodsCacheStats {
+    pub enabled: bool,
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: u64,
+}
+
+#[derive(Clone)]
+pub struct GoodsCache {
+    /// `None` when caching is disabled
+    caches: Option<(Cache<i32, Good>, Cache<String, i32>)>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl GoodsCache {
+    pub fn new(config: GoodsCacheConfig) -> Self {
+        let caches = config.enabled.then(|| {
+            (
+                Cache::builder().max_capacity(config.capacity).time_to_live(config.ttl).build(),
+                Cache::builder().max_capacity(config.capacity).time_to_live(config.ttl).build(),
+            )
+        });
+
+        Self {
+            caches,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn get_by_id(&self, goods_id: i32) -> Option<Good> {
+        let (by_id, _) = self.caches.as_ref()?;
+        self.record(by_id.get(&goods_id))
+    }
+
+    pub fn get_by_material_code(&self, material_code: &str) -> Option<Good> {
+        let (by_id, by_code) = self.caches.as_ref()?;
+
+        let good = by_code
+            .get(material_code)
+            .and_then(|goods_id| by_id.get(&goods_id))
+            .filter(|good| good.material_code == material_code);
+
+        self.record(good)
+    }
+
+    pub fn insert(&self, good: &Good) {
+        if let Some((by_id, by_code)) = &self.caches {
+            by_code.insert(good.material_code.clone(), good.goods_id);
+            by_id.insert(good.goods_id, good.clone());
+        }
+    }
+
+    /// Drop cached goods after a write; stale material code mappings fail the lookup check
+    pub fn invalidate(&self, goods_ids: &[i32]) {
+        if let Some((by_id, _)) = &self.caches {
+            for goods_id in goods_ids {
+                by_id.invalidate(goods_id);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> GoodsCacheStats {
+        GoodsCacheStats {
+            enabled: self.caches.is_some(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.caches.as_ref().map_or(0, |(by_id, _)| by_id.entry_count()),
+        }
+    }
+
+    fn record(&self, good: Option<Good>) -> Option<Good> {
+        let counter = if good.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        good
+    }
+}
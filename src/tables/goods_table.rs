@@ -1,8 +1,26 @@
 // src/tables/goods_table.rs
+use super::audit_log_table::AuditLogTable;
+use super::error::{BatchItemError, BlockedGoods, StaleGoods, TableError};
+use super::categories_table::category_tree_query;
+use super::goods_cache::GoodsCache;
+use super::sync_table::{SyncEntity, SyncTable};
+use crate::utils::database::{begin_with_statement_timeout, stream_rows};
+use crate::utils::pagination::{PageCursor, PaginatedResponse, PaginationParams};
+use crate::utils::query_builder::{BindValue, Keyset, SearchQueryBuilder};
+use crate::utils::response::TargetResult;
+use crate::utils::sorting::SortOrder;
+use crate::utils::string_utils::{normalize_tags, to_search_pattern, MatchMode, Normalization};
+use chrono::{DateTime, Utc};
+use futures_util::Stream;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Good {
     pub goods_id: i32,
     pub material_code: String,
@@ -13,9 +31,74 @@ pub struct Good {
     pub mass_g: rust_decimal::Decimal,
     pub mass_base: i16,
     pub volumn_base: i16,
+    pub reorder_point: Option<i32>,
+    pub category_id: Option<i32>,
+    /// EAN-8, EAN-13 or Code128; unique across goods
+    pub barcode: Option<String>,
+    /// Lowercase, without duplicates
+    pub tags: Option<Vec<String>>,
+    /// False once archived: hidden from searches unless `include_inactive=true`
+    pub is_active: bool,
+    /// Incremented by every update, for optimistic concurrency
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Trigram similarity of goods_name to the search term; only present with `fuzzy=true`
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
 }
 
+impl Good {
+    /// Field names accepted by `?fields=`
+    pub const FIELDS: &'static [&'static str] = &[
+        "goods_id", "material_code", "goods_name", "description", "price", "volumn_l", "mass_g",
+        "mass_base", "volumn_base", "reorder_point", "category_id", "barcode", "tags", "is_active", "version", "created_at", "updated_at",
+        "similarity",
+    ];
+}
+
+/// A good with totals over its inventory rows, for `?include_stock=true`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GoodWithStock {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub good: Good,
+    /// Quantity summed over the good's inventory rows; 0 without any
+    pub total_quantity: i64,
+    /// Number of inventory rows
+    pub lot_count: i64,
+    /// Soonest expiry among the inventory rows; null when none has one
+    pub earliest_expired_date: Option<DateTime<Utc>>,
+}
+
+impl GoodWithStock {
+    /// Field names accepted by `?fields=` with `include_stock=true`
+    pub const FIELDS: &'static [&'static str] = &[
+        "goods_id", "material_code", "goods_name", "description", "price", "volumn_l", "mass_g",
+        "mass_base", "volumn_base", "reorder_point", "category_id", "barcode", "tags", "is_active", "version", "created_at", "updated_at",
+        "similarity", "total_quantity", "lot_count", "earliest_expired_date",
+    ];
+}
+
+impl Borrow<Good> for GoodWithStock {
+    fn borrow(&self) -> &Good {
+        &self.good
+    }
+}
+
+/// Grouped inventory totals LEFT JOINed onto goods for `include_stock`, so goods without
+/// inventory keep their row. The subquery's columns are renamed to leave the unqualified goods
+/// columns in the search conditions unambiguous.
+const STOCK_JOIN: &str = " LEFT JOIN (SELECT goods_id AS stock_goods_id, SUM(quantity)::BIGINT AS stock_quantity, COUNT(*) AS stock_lots, MIN(expired_date) AS stock_earliest_expired_date FROM inventory GROUP BY goods_id) stock ON stock.stock_goods_id = goods.goods_id";
+const STOCK_COLUMNS: &str = ", COALESCE(stock.stock_quantity, 0) AS total_quantity, COALESCE(stock.stock_lots, 0) AS lot_count, stock.stock_earliest_expired_date AS earliest_expired_date";
+
+/// Similarity goods names must exceed in a fuzzy search unless configured otherwise; pg_trgm's own default
+pub const DEFAULT_FUZZY_THRESHOLD: f32 = 0.3;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CreateGoodRequest {
     pub material_code: String,
     pub goods_name: String,
@@ -25,9 +108,16 @@ pub struct CreateGoodRequest {
     pub mass_g: rust_decimal::Decimal,
     pub mass_base: Option<i16>,
     pub volumn_base: Option<i16>,
+    pub reorder_point: Option<i32>,
+    pub category_id: Option<i32>,
+    pub barcode: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// Defaults to true; false creates the good archived
+    pub is_active: Option<bool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct UpdateGoodRequest {
     pub material_code: Option<String>,
     pub goods_name: Option<String>,
@@ -37,13 +127,329 @@ pub struct UpdateGoodRequest {
     pub mass_g: Option<rust_decimal::Decimal>,
     pub mass_base: Option<i16>,
     pub volumn_base: Option<i16>,
+    pub reorder_point: Option<i32>,
+    pub category_id: Option<i32>,
+    pub barcode: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// False archives the good, true restores it
+    pub is_active: Option<bool>,
+    /// Only update if every matched good is still at this version
+    pub expected_version: Option<i32>,
+}
+
+/// What `POST /goods` does when the material_code already exists (`?on_conflict=`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GoodsConflictMode {
+    /// Return the stored good unchanged with `created: false`
+    #[default]
+    ReturnExisting,
+    /// Reject with 409
+    Error,
+    /// Overwrite the stored good with the request
+    Update,
+}
+
+impl GoodsConflictMode {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "return_existing" => Ok(GoodsConflictMode::ReturnExisting),
+            "error" => Ok(GoodsConflictMode::Error),
+            "update" => Ok(GoodsConflictMode::Update),
+            _ => Err("Invalid on_conflict. Allowed values: return_existing, error, update".to_string()),
+        }
+    }
+}
+
+/// Safety limits for the filter-based updates and deletes
+#[derive(Debug, Clone, Copy)]
+pub struct BulkWriteOptions {
+    /// Abort without changes when the filter matches more rows than this
+    pub max_affected: i64,
+    /// Run the write, report the result and roll it back
+    pub dry_run: bool,
+}
+
+impl BulkWriteOptions {
+    pub(crate) fn check_matched(&self, matched: i64) -> Result<(), TableError> {
+        if matched > self.max_affected {
+            return Err(TableError::TooManyAffected { matched, max_affected: self.max_affected });
+        }
+        Ok(())
+    }
+}
+
+/// Body of `PUT /goods/{material_code}`: a full good, with the material code taken from the path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpsertGoodRequest {
+    pub goods_name: String,
+    pub description: Option<Vec<String>>,
+    pub price: rust_decimal::Decimal,
+    pub volumn_l: rust_decimal::Decimal,
+    pub mass_g: rust_decimal::Decimal,
+    pub mass_base: Option<i16>,
+    pub volumn_base: Option<i16>,
+    pub reorder_point: Option<i32>,
+    pub category_id: Option<i32>,
+    pub barcode: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// Keeps the stored flag when omitted; a new good defaults to true
+    pub is_active: Option<bool>,
+}
+
+impl UpsertGoodRequest {
+    pub fn into_create_request(self, material_code: String) -> CreateGoodRequest {
+        CreateGoodRequest {
+            material_code,
+            goods_name: self.goods_name,
+            description: self.description,
+            price: self.price,
+            volumn_l: self.volumn_l,
+            mass_g: self.mass_g,
+            mass_base: self.mass_base,
+            volumn_base: self.volumn_base,
+            reorder_point: self.reorder_point,
+            category_id: self.category_id,
+            barcode: self.barcode,
+            tags: self.tags,
+            is_active: self.is_active,
+        }
+    }
+}
+
+/// Body of `POST /goods/{goods_id}/clone`: a new material code plus the fields that differ
+/// from the source. Omitted fields are copied, except the barcode, which is unique to the
+/// source; an empty description or tags list clears it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CloneGoodRequest {
+    pub material_code: String,
+    pub goods_name: Option<String>,
+    pub description: Option<Vec<String>>,
+    pub price: Option<rust_decimal::Decimal>,
+    pub volumn_l: Option<rust_decimal::Decimal>,
+    pub mass_g: Option<rust_decimal::Decimal>,
+    pub mass_base: Option<i16>,
+    pub volumn_base: Option<i16>,
+    pub reorder_point: Option<i32>,
+    pub category_id: Option<i32>,
+    pub barcode: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// Defaults to true, whatever the source's flag
+    pub is_active: Option<bool>,
+}
+
+impl CloneGoodRequest {
+    pub fn into_create_request(self, source: Good) -> CreateGoodRequest {
+        let non_empty = |values: Vec<String>| (!values.is_empty()).then_some(values);
+
+        CreateGoodRequest {
+            material_code: self.material_code,
+            goods_name: self.goods_name.unwrap_or(source.goods_name),
+            description: match self.description {
+                Some(description) => non_empty(description),
+                None => source.description,
+            },
+            price: self.price.unwrap_or(source.price),
+            volumn_l: self.volumn_l.unwrap_or(source.volumn_l),
+            mass_g: self.mass_g.unwrap_or(source.mass_g),
+            mass_base: Some(self.mass_base.unwrap_or(source.mass_base)),
+            volumn_base: Some(self.volumn_base.unwrap_or(source.volumn_base)),
+            reorder_point: self.reorder_point.or(source.reorder_point),
+            category_id: self.category_id.or(source.category_id),
+            barcode: self.barcode,
+            tags: match self.tags {
+                Some(tags) => non_empty(tags),
+                None => source.tags,
+            },
+            is_active: self.is_active,
+        }
+    }
+}
+
+/// A created or upserted good and whether the write inserted it
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SavedGood {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub good: Good,
+    pub created: bool,
+}
+
+/// A good whose total stock is at or below its reorder point
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LowStockGoods {
+    pub goods_id: i32,
+    pub material_code: String,
+    pub goods_name: String,
+    pub total_quantity: i64,
+    pub reorder_point: Option<i32>,
+    pub deficit: i64,
+}
+
+/// A tag and how many goods carry it
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TagCount {
+    pub tag: String,
+    pub goods_count: i64,
+}
+
+/// Goods whose material codes are equal once normalized, for `GET /goods/duplicates`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DuplicateMaterialCode {
+    pub normalized_material_code: String,
+    /// Ordered by goods_id
+    pub goods: Vec<Good>,
+}
+
+#[derive(FromRow)]
+struct NormalizedGood {
+    normalized_material_code: String,
+    #[sqlx(flatten)]
+    good: Good,
+}
+
+/// A batch entry that was not inserted because its material_code already exists
+/// Outcome of deleting goods by an explicit id list
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GoodsDeleteByIdsResult {
+    pub deleted: Vec<i32>,
+    pub not_found: Vec<i32>,
+    /// Kept because inventory items still reference them
+    pub blocked: Vec<BlockedGoods>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SkippedGood {
+    pub index: usize,
+    pub material_code: String,
+    pub goods_id: i32,
+}
+
+/// Outcome of a batch goods creation
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GoodsBatchResult {
+    pub created: Vec<Good>,
+    pub skipped_existing: Vec<SkippedGood>,
+    pub errors: Vec<BatchItemError>,
+}
+
+impl GoodsBatchResult {
+    /// Per-entry `results`; `indexes` maps the material_code of each inserted entry to its
+    /// payload index, which is unique since duplicates are rejected up front
+    pub fn target_results(&self, indexes: &HashMap<String, usize>) -> Vec<TargetResult> {
+        let created = self.created.iter().map(|good| match indexes.get(&good.material_code) {
+            Some(&index) => TargetResult::ok(good.goods_id).at(index),
+            None => TargetResult::ok(good.goods_id),
+        });
+        let skipped = self.skipped_existing.iter().map(|skipped| {
+            TargetResult::ok(skipped.goods_id).at(skipped.index).with_message("material_code already exists; left unchanged")
+        });
+
+        created.chain(skipped).chain(self.errors.iter().map(TargetResult::from)).collect()
+    }
+}
+
+impl GoodsDeleteByIdsResult {
+    /// Per-id `results`
+    pub fn target_results(&self) -> Vec<TargetResult> {
+        let deleted = self.deleted.iter().map(|&goods_id| TargetResult::ok(goods_id));
+        let not_found = self.not_found.iter().map(|&goods_id| TargetResult::failed(Some(goods_id), "NOT_FOUND", "Goods not found"));
+        let blocked = self.blocked.iter().map(|blocked| {
+            TargetResult::failed(
+                Some(blocked.goods_id),
+                "CONFLICT",
+                format!("Still referenced by {} inventory items", blocked.inventory_count),
+            )
+        });
+
+        deleted.chain(not_found).chain(blocked).collect()
+    }
+}
+
+/// Columns goods results may be sorted by (whitelisted, never interpolated from input)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoodsSortColumn {
+    GoodsId,
+    MaterialCode,
+    GoodsName,
+    Price,
+    VolumnL,
+    MassG,
+}
+
+impl GoodsSortColumn {
+    pub const ALLOWED: &'static [&'static str] = &["goods_id", "material_code", "goods_name", "price", "volumn_l", "mass_g"];
+
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "goods_id" => Ok(GoodsSortColumn::GoodsId),
+            "material_code" => Ok(GoodsSortColumn::MaterialCode),
+            "goods_name" => Ok(GoodsSortColumn::GoodsName),
+            "price" => Ok(GoodsSortColumn::Price),
+            "volumn_l" => Ok(GoodsSortColumn::VolumnL),
+            "mass_g" => Ok(GoodsSortColumn::MassG),
+            _ => Err(format!("Invalid sort_by. Allowed values: {}", Self::ALLOWED.join(", "))),
+        }
+    }
+
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            GoodsSortColumn::GoodsId => "goods_id",
+            GoodsSortColumn::MaterialCode => "material_code",
+            GoodsSortColumn::GoodsName => "goods_name",
+            GoodsSortColumn::Price => "price",
+            GoodsSortColumn::VolumnL => "volumn_l",
+            GoodsSortColumn::MassG => "mass_g",
+        }
+    }
+
+    /// The value of this column in `good`, as stored in a page cursor
+    pub(crate) fn cursor_value(&self, good: &Good) -> String {
+        match self {
+            GoodsSortColumn::GoodsId => good.goods_id.to_string(),
+            GoodsSortColumn::MaterialCode => good.material_code.clone(),
+            GoodsSortColumn::GoodsName => good.goods_name.clone(),
+            GoodsSortColumn::Price => good.price.to_string(),
+            GoodsSortColumn::VolumnL => good.volumn_l.to_string(),
+            GoodsSortColumn::MassG => good.mass_g.to_string(),
+        }
+    }
+
+    pub(crate) fn parse_cursor_value(&self, value: &str) -> Result<BindValue, String> {
+        let invalid = || "Invalid cursor".to_string();
+        match self {
+            GoodsSortColumn::GoodsId => value.parse::<i32>().map(BindValue::from).map_err(|_| invalid()),
+            GoodsSortColumn::MaterialCode | GoodsSortColumn::GoodsName => Ok(BindValue::from(value.to_string())),
+            GoodsSortColumn::Price | GoodsSortColumn::VolumnL | GoodsSortColumn::MassG => {
+                value.parse::<rust_decimal::Decimal>().map(BindValue::from).map_err(|_| invalid())
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct GoodsSearchParams {
     pub goods_id: Option<i32>,
+    pub goods_ids: Option<Vec<i32>>,
     pub material_code: Option<String>,
     pub goods_name: Option<String>,
+    /// Matches when any description line contains the term
+    pub description_contains: Option<String>,
+    pub barcode: Option<String>,
+    /// Goods carrying this tag
+    pub tag: Option<String>,
+    /// Goods carrying at least one of these tags
+    pub tags_any: Option<Vec<String>>,
+    /// Goods carrying every one of these tags
+    pub tags_all: Option<Vec<String>>,
     pub price: Option<rust_decimal::Decimal>,
     pub volumn_l: Option<rust_decimal::Decimal>,
     pub mass_g: Option<rust_decimal::Decimal>,
@@ -53,14 +459,48 @@ pub struct GoodsSearchParams {
     pub max_mass_g: Option<rust_decimal::Decimal>,
     pub min_price: Option<rust_decimal::Decimal>,
     pub max_price: Option<rust_decimal::Decimal>,
+    pub min_updated_at: Option<DateTime<Utc>>,
+    pub max_updated_at: Option<DateTime<Utc>>,
+    pub category_id: Option<i32>,
+    /// Case-insensitive exact category name
+    pub category_name: Option<String>,
+    /// Widen category_id / category_name to every descendant category
+    pub include_subcategories: bool,
+    /// Goods with (true) or without (false) an inventory row holding stock
+    pub in_stock: Option<bool>,
+    /// Also match archived goods; searches see active goods only by default
+    pub include_inactive: bool,
+    /// Applies to material_code and goods_name
+    pub match_mode: MatchMode,
+    /// Rank goods_name by trigram similarity above this threshold instead of matching it
+    pub fuzzy_threshold: Option<f32>,
+    pub pagination: Option<PaginationParams>,
+    /// Continue after the last row of a previous page instead of at `page`
+    pub cursor: Option<Keyset>,
+    /// Row cap for a search without pagination
+    pub limit: Option<u32>,
+    pub sort_by: Option<GoodsSortColumn>,
+    pub sort_order: Option<SortOrder>,
+}
+
+impl Default for GoodsSearchParams {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GoodsSearchParams {
     pub fn new() -> Self {
         Self {
             goods_id: None,
+            goods_ids: None,
             material_code: None,
             goods_name: None,
+            description_contains: None,
+            barcode: None,
+            tag: None,
+            tags_any: None,
+            tags_all: None,
             price: None,
             volumn_l: None,
             mass_g: None,
@@ -70,6 +510,29 @@ impl GoodsSearchParams {
             max_mass_g: None,
             min_price: None,
             max_price: None,
+            min_updated_at: None,
+            max_updated_at: None,
+            category_id: None,
+            category_name: None,
+            include_subcategories: false,
+            in_stock: None,
+            include_inactive: false,
+            match_mode: MatchMode::default(),
+            fuzzy_threshold: None,
+            pagination: None,
+            cursor: None,
+            limit: None,
+            sort_by: None,
+            sort_order: None,
+        }
+    }
+
+    /// LIMIT/OFFSET of the requested page, else the row cap; empty when neither is set
+    pub fn limit_clause(&self) -> String {
+        match (&self.pagination, self.limit) {
+            (Some(pagination), _) => pagination.to_sql(),
+            (None, Some(limit)) => format!(" LIMIT {}", limit),
+            (None, None) => String::new(),
         }
     }
 
@@ -77,295 +540,1140 @@ impl GoodsSearchParams {
         matches!(self.goods_name.as_deref(), Some("*"))
             || matches!(self.material_code.as_deref(), Some("*"))
     }
+
+    /// `Some(true)` to limit a search to active goods, `None` with `include_inactive`
+    pub fn active_filter(&self) -> Option<bool> {
+        (!self.include_inactive).then_some(true)
+    }
+
+    /// Switch to fuzzy search: goods_name is ranked by similarity, most similar first. It needs a
+    /// goods_name to rank by and decides the order itself, so sorting and cursors are out.
+    pub fn rank_by_similarity(&mut self, threshold: f32) -> Result<(), String> {
+        if matches!(self.goods_name.as_deref(), None | Some("*")) {
+            return Err("fuzzy=true needs a goods_name to rank by".to_string());
+        }
+        if self.match_mode != MatchMode::default() {
+            return Err("fuzzy=true cannot be combined with match_mode or literal".to_string());
+        }
+        if self.sort_by.is_some() || self.sort_order.is_some() {
+            return Err("fuzzy=true orders by similarity and cannot be combined with sort_by or sort_order".to_string());
+        }
+        if self.cursor.is_some() {
+            return Err("fuzzy=true cannot be combined with cursor; use page".to_string());
+        }
+
+        self.fuzzy_threshold = Some(threshold);
+        Ok(())
+    }
+
+    /// Build the ORDER BY expression, always ending with goods_id so pagination stays stable
+    pub fn order_by_clause(&self) -> String {
+        if self.fuzzy_threshold.is_some() {
+            return format!("{} DESC, goods_id ASC", similarity_expression("goods_name"));
+        }
+
+        let sort_order = self.sort_order.unwrap_or(SortOrder::Asc);
+
+        match self.sort_by {
+            Some(column) if column != GoodsSortColumn::GoodsId => {
+                format!("{} {}, goods_id ASC", column.as_sql(), sort_order.as_sql())
+            }
+            _ => format!("goods_id {}", sort_order.as_sql()),
+        }
+    }
+
+    /// The column sorted on ahead of goods_id, if any
+    pub(crate) fn sort_column(&self) -> Option<GoodsSortColumn> {
+        self.sort_by.filter(|column| *column != GoodsSortColumn::GoodsId)
+    }
+
+    /// Resolve a client's cursor; it must come from a page read with the same sort
+    pub fn keyset_from(&self, cursor: &PageCursor) -> Result<Keyset, String> {
+        if cursor.order != self.order_by_clause() {
+            return Err("cursor does not match sort_by and sort_order".to_string());
+        }
+
+        let value = match (self.sort_column(), &cursor.value) {
+            (None, None) => None,
+            (Some(column), Some(value)) => Some(column.parse_cursor_value(value)?),
+            _ => return Err("Invalid cursor".to_string()),
+        };
+
+        Ok(Keyset { value, id: cursor.id })
+    }
+
+    /// Cursor for the page after `goods`; unset unless the page is full and, when counted,
+    /// matches remain past it
+    pub fn next_cursor<T: Borrow<Good>>(&self, goods: &[T], total_count: Option<u64>) -> Option<String> {
+        if self.fuzzy_threshold.is_some() {
+            return None;
+        }
+        let pagination = self.pagination.as_ref()?;
+        if goods.len() < pagination.per_page() as usize {
+            return None;
+        }
+        if let Some(total_count) = total_count
+            && pagination.offset() + goods.len() as u64 >= total_count
+        {
+            return None;
+        }
+
+        let last: &Good = goods.last()?.borrow();
+        let cursor = PageCursor {
+            order: self.order_by_clause(),
+            value: self.sort_column().map(|column| column.cursor_value(last)),
+            id: last.goods_id,
+        };
+        Some(cursor.encode())
+    }
+
+    fn add_keyset_condition(&self, builder: &mut SearchQueryBuilder) {
+        if let Some(keyset) = &self.cursor {
+            let sort_order = self.sort_order.unwrap_or(SortOrder::Asc);
+            builder.add_keyset_condition(self.sort_column().map(|column| column.as_sql()), "goods_id", sort_order, false, keyset);
+        }
+    }
+}
+
+/// pg_trgm similarity of `column` to the fuzzy search term. `add_fuzzy_condition` binds the
+/// term before any other value, so it is always `$1`.
+pub(crate) fn similarity_expression(column: &str) -> String {
+    format!("similarity({}, $1)", column)
+}
+
+/// The `similarity` select column for a fuzzy search on `column`, empty otherwise
+pub(crate) fn similarity_column(column: &str, params: &GoodsSearchParams) -> String {
+    match params.fuzzy_threshold {
+        Some(_) => format!(", {} AS similarity", similarity_expression(column)),
+        None => String::new(),
+    }
+}
+
+/// Keep goods whose `column` is similar enough to goods_name in a fuzzy search. Must be the
+/// first condition added, see `similarity_expression`. Shared with the inventory search.
+pub(crate) fn add_fuzzy_condition(builder: &mut SearchQueryBuilder, column: &str, params: &GoodsSearchParams) {
+    if let (Some(threshold), Some(term)) = (params.fuzzy_threshold, &params.goods_name) {
+        builder.add_condition(&format!("similarity({}, ?) > {}", column, threshold), term.clone());
+    }
+}
+
+/// Add the category_id / category_name filters on `column`, widened to every descendant
+/// category with `include_subcategories`. Shared with the inventory search.
+pub(crate) fn add_category_conditions(builder: &mut SearchQueryBuilder, column: &str, params: &GoodsSearchParams) {
+    let in_categories = |root_condition: &str| {
+        if params.include_subcategories {
+            format!("{} IN ({})", column, category_tree_query(root_condition))
+        } else {
+            format!("{} IN (SELECT category_id FROM categories WHERE {})", column, root_condition)
+        }
+    };
+
+    builder.add_optional_condition(&in_categories("category_id = ?"), params.category_id);
+    builder.add_optional_condition(&in_categories("LOWER(name) = LOWER(?)"), params.category_name.clone());
 }
 
 #[derive(Clone)]
 pub struct GoodsTable {
     pool: PgPool,
+    /// Used by the read-only queries; may point at a replica
+    read_pool: PgPool,
+    /// Applied to search and export queries
+    statement_timeout: Option<Duration>,
+    cache: GoodsCache,
 }
 
 impl GoodsTable {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, read_pool: PgPool, statement_timeout: Option<Duration>, cache: GoodsCache) -> Self {
+        Self { pool, read_pool, statement_timeout, cache }
+    }
+
+    pub fn cache(&self) -> &GoodsCache {
+        &self.cache
     }
 
     pub async fn search(&self, params: GoodsSearchParams) -> Result<Vec<Good>, sqlx::Error> {
-        // Handle get all case
-        if params.is_get_all() {
-            return self.get_all().await;
+        // Handle get all case; a cursor page needs the keyset condition from `search_query`
+        if params.is_get_all() && params.cursor.is_none() {
+            return self.get_all(&params).await;
         }
 
-        // Build dynamic query with parameterized statements to prevent SQL injection
-        let mut query = "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base FROM goods WHERE 1=1".to_string();
-        let mut bind_count = 0;
-        let mut conditions = Vec::new();
+        self.fetch_search(&params, false).await
+    }
 
-        // Build WHERE conditions safely using parameterized queries
-        if params.goods_id.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND goods_id = ${}", bind_count));
-        }
+    /// `search` with each good's inventory totals
+    pub async fn search_with_stock(&self, params: GoodsSearchParams) -> Result<Vec<GoodWithStock>, sqlx::Error> {
+        self.fetch_search(&params, true).await
+    }
 
-        if params.material_code.is_some() && !params.is_get_all() {
-            bind_count += 1;
-            conditions.push(format!(" AND material_code ILIKE ${}", bind_count));
-        }
+    /// Run the search query, with the inventory totals joined in when `with_stock` is set
+    async fn fetch_search<T>(&self, params: &GoodsSearchParams, with_stock: bool) -> Result<Vec<T>, sqlx::Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let (query, builder) = Self::search_query(params, with_stock);
 
-        if params.goods_name.is_some() && !params.is_get_all() {
-            bind_count += 1;
-            conditions.push(format!(" AND goods_name ILIKE ${}", bind_count));
-        }
+        // Execute with values bound in the same order as their conditions
+        let mut tx = begin_with_statement_timeout(&self.read_pool, self.statement_timeout).await?;
+        let rows = builder.bind_values(sqlx::query_as::<_, T>(&query))
+            .fetch_all(&mut *tx)
+            .await?;
+        tx.commit().await?;
 
-        if params.price.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND price = ${}", bind_count));
-        }
+        Ok(rows)
+    }
 
-        if params.volumn_l.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND volumn_l = ${}", bind_count));
-        }
+    /// Stream every good matching `search` without buffering the full result
+    pub fn search_stream(&self, params: &GoodsSearchParams) -> impl Stream<Item = Result<Good, sqlx::Error>> + Send + use<> {
+        let (query, builder) = Self::search_query(params, false);
+        stream_rows(self.read_pool.clone(), self.statement_timeout, query, builder)
+    }
 
-        if params.mass_g.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND mass_g = ${}", bind_count));
-        }
+    /// Build the search SQL and its bind values; the wildcard case matches every good
+    fn search_query(params: &GoodsSearchParams, with_stock: bool) -> (String, SearchQueryBuilder) {
+        let (stock_columns, stock_join) = if with_stock { (STOCK_COLUMNS, STOCK_JOIN) } else { ("", "") };
 
-        if params.min_volumn_l.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND volumn_l >= ${}", bind_count));
-        }
+        // Build dynamic query with parameterized statements to prevent SQL injection
+        let mut builder = SearchQueryBuilder::new(format!(
+            "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at{}{} FROM goods{} WHERE 1=1",
+            similarity_column("goods_name", params),
+            stock_columns,
+            stock_join
+        ));
+        Self::add_search_conditions(&mut builder, params);
+        params.add_keyset_condition(&mut builder);
 
-        if params.max_volumn_l.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND volumn_l <= ${}", bind_count));
-        }
+        let mut query = builder.build(Some(&params.order_by_clause()));
 
-        if params.min_mass_g.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND mass_g >= ${}", bind_count));
-        }
+        query.push_str(&params.limit_clause());
 
-        if params.max_mass_g.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND mass_g <= ${}", bind_count));
-        }
+        (query, builder)
+    }
 
-        if params.min_price.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND price >= ${}", bind_count));
-        }
+    /// Count goods matching the same conditions used by `search`
+    pub async fn count(&self, params: &GoodsSearchParams) -> Result<i64, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new("SELECT COUNT(*) FROM goods WHERE 1=1".to_string());
+        Self::add_search_conditions(&mut builder, params);
 
-        if params.max_price.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND price <= ${}", bind_count));
-        }
+        let query = builder.build(None);
+        let mut tx = begin_with_statement_timeout(&self.read_pool, self.statement_timeout).await?;
+        let (count,) = builder.bind_values(sqlx::query_as::<_, (i64,)>(&query))
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
 
-        // Append conditions to query
-        query.push_str(&conditions.join(""));
-        query.push_str(" ORDER BY goods_id ASC");
+        Ok(count)
+    }
 
-        // Build and execute query with proper parameter binding
-        let mut sql_query = sqlx::query_as::<_, Good>(&query);
+    /// Count matching goods on a write connection, ahead of a filter-based update or delete
+    async fn count_matching(conn: &mut PgConnection, params: &GoodsSearchParams) -> Result<i64, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new("SELECT COUNT(*) FROM goods WHERE 1=1".to_string());
+        Self::add_search_conditions(&mut builder, params);
 
-        // Bind parameters in the same order as they were added
-        if let Some(goods_id) = params.goods_id {
-            sql_query = sql_query.bind(goods_id);
-        }
+        let query = builder.build(None);
+        let (count,) = builder.bind_values(sqlx::query_as::<_, (i64,)>(&query))
+            .fetch_one(conn)
+            .await?;
 
-        if let Some(material_code) = params.material_code {
-            if material_code != "*" {
-                sql_query = sql_query.bind(crate::utils::string_utils::to_search_pattern(&material_code));
-            }
-        }
+        Ok(count)
+    }
 
-        if let Some(goods_name) = params.goods_name {
-            if goods_name != "*" {
-                sql_query = sql_query.bind(crate::utils::string_utils::to_search_pattern(&goods_name));
-            }
-        }
+    /// Search one page of goods and count the total matches in parallel. A cursor page is not
+    /// counted: it exists to avoid scanning past the rows already seen.
+    pub async fn search_paginated(&self, params: GoodsSearchParams) -> Result<PaginatedResponse<Good>, sqlx::Error> {
+        self.search_page(params, false).await
+    }
 
-        if let Some(price) = params.price {
-            sql_query = sql_query.bind(price);
-        }
+    /// `search_paginated` with each good's inventory totals
+    pub async fn search_with_stock_paginated(&self, params: GoodsSearchParams) -> Result<PaginatedResponse<GoodWithStock>, sqlx::Error> {
+        self.search_page(params, true).await
+    }
 
-        if let Some(volumn_l) = params.volumn_l {
-            sql_query = sql_query.bind(volumn_l);
-        }
+    async fn search_page<T>(&self, mut params: GoodsSearchParams, with_stock: bool) -> Result<PaginatedResponse<T>, sqlx::Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Borrow<Good> + Send + Unpin,
+    {
+        let pagination = params.pagination.clone().unwrap_or_default();
+        params.pagination = Some(pagination.clone());
 
-        if let Some(mass_g) = params.mass_g {
-            sql_query = sql_query.bind(mass_g);
-        }
+        let (goods, total_count) = if params.cursor.is_some() {
+            (self.fetch_search::<T>(&params, with_stock).await?, None)
+        } else {
+            let (goods, total_count) = tokio::try_join!(
+                self.fetch_search::<T>(&params, with_stock),
+                self.count(&params)
+            )?;
+            (goods, Some(total_count as u64))
+        };
 
-        if let Some(min_volumn_l) = params.min_volumn_l {
-            sql_query = sql_query.bind(min_volumn_l);
-        }
+        let next_cursor = params.next_cursor(&goods, total_count);
+        Ok(PaginatedResponse::new(goods, &pagination, total_count).with_next_cursor(next_cursor))
+    }
 
-        if let Some(max_volumn_l) = params.max_volumn_l {
-            sql_query = sql_query.bind(max_volumn_l);
+    /// Add the WHERE conditions for the given search params to a builder.
+    /// The wildcard (get all) case adds only the active filter, matching `get_all`.
+    fn add_search_conditions(builder: &mut SearchQueryBuilder, params: &GoodsSearchParams) {
+        // First, so the fuzzy term is bound as `$1`
+        if !params.is_get_all() {
+            add_fuzzy_condition(builder, "goods_name", params);
         }
-
-        if let Some(min_mass_g) = params.min_mass_g {
-            sql_query = sql_query.bind(min_mass_g);
+        builder.add_optional_condition("is_active = ?", params.active_filter());
+        if params.is_get_all() {
+            return;
         }
 
-        if let Some(max_mass_g) = params.max_mass_g {
-            sql_query = sql_query.bind(max_mass_g);
+        builder.add_optional_condition("goods_id = ?", params.goods_id);
+        builder.add_optional_condition("goods_id = ANY(?)", params.goods_ids.clone());
+        builder.add_optional_match("material_code", params.material_code.as_deref(), params.match_mode);
+        if params.fuzzy_threshold.is_none() {
+            builder.add_optional_match("goods_name", params.goods_name.as_deref(), params.match_mode);
         }
+        builder.add_optional_condition(
+            "EXISTS (SELECT 1 FROM unnest(description) d WHERE d ILIKE ? ESCAPE '\\')",
+            params.description_contains.as_deref().map(|term| to_search_pattern(term, MatchMode::Contains)),
+        );
+        builder.add_optional_condition("barcode = ?", params.barcode.clone());
+        builder.add_optional_condition("? = ANY(tags)", params.tag.clone());
+        builder.add_optional_condition("tags && ?", params.tags_any.clone());
+        builder.add_optional_condition("tags @> ?", params.tags_all.clone());
+        builder.add_optional_condition("price = ?", params.price);
+        builder.add_optional_condition("volumn_l = ?", params.volumn_l);
+        builder.add_optional_condition("mass_g = ?", params.mass_g);
+        builder.add_optional_condition("volumn_l >= ?", params.min_volumn_l);
+        builder.add_optional_condition("volumn_l <= ?", params.max_volumn_l);
+        builder.add_optional_condition("mass_g >= ?", params.min_mass_g);
+        builder.add_optional_condition("mass_g <= ?", params.max_mass_g);
+        builder.add_optional_condition("price >= ?", params.min_price);
+        builder.add_optional_condition("price <= ?", params.max_price);
+        builder.add_optional_condition("updated_at >= ?", params.min_updated_at);
+        builder.add_optional_condition("updated_at <= ?", params.max_updated_at);
+        builder.add_optional_condition(
+            "EXISTS (SELECT 1 FROM inventory i WHERE i.goods_id = goods.goods_id AND i.quantity > 0) = ?",
+            params.in_stock,
+        );
+        add_category_conditions(builder, "category_id", params);
+    }
+
+    async fn get_all(&self, params: &GoodsSearchParams) -> Result<Vec<Good>, sqlx::Error> {
+        let mut query = format!(
+            "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at FROM goods{} ORDER BY {}",
+            if params.include_inactive { "" } else { " WHERE is_active" },
+            params.order_by_clause()
+        );
+
+        query.push_str(&params.limit_clause());
+
+        let mut tx = begin_with_statement_timeout(&self.read_pool, self.statement_timeout).await?;
+        let goods = sqlx::query_as::<_, Good>(&query)
+            .fetch_all(&mut *tx)
+            .await?;
+        tx.commit().await?;
 
-        if let Some(min_price) = params.min_price {
-            sql_query = sql_query.bind(min_price);
+        Ok(goods)
+    }
+
+    /// Lookup by id, answered from the cache when it holds the good. The row read on a miss is
+    /// committed, so it fills the cache.
+    pub async fn get_by_id(&self, goods_id: i32) -> Result<Option<Good>, sqlx::Error> {
+        let good = self.get_by_id_tx(&mut *self.pool.acquire().await?, goods_id).await?;
+        if let Some(good) = &good {
+            self.cache.insert(good);
         }
+        Ok(good)
+    }
 
-        if let Some(max_price) = params.max_price {
-            sql_query = sql_query.bind(max_price);
+    /// Lookup on the caller's transaction. Cached goods are committed, so a hit is answered from
+    /// the cache; a miss reads through `conn`, finding goods created earlier in the transaction,
+    /// and is not cached since the transaction may yet roll back.
+    pub async fn get_by_id_tx(&self, conn: &mut PgConnection, goods_id: i32) -> Result<Option<Good>, sqlx::Error> {
+        if let Some(good) = self.cache.get_by_id(goods_id) {
+            return Ok(Some(good));
         }
 
-        sql_query.fetch_all(&self.pool).await
+        sqlx::query_as::<_, Good>(
+            "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at FROM goods WHERE goods_id = $1"
+        )
+        .bind(goods_id)
+        .fetch_optional(conn)
+        .await
     }
 
-    async fn get_all(&self) -> Result<Vec<Good>, sqlx::Error> {
+    /// Lookup by material code on the caller's transaction; see `get_by_id_tx`
+    pub async fn get_by_material_code_tx(&self, conn: &mut PgConnection, material_code: &str) -> Result<Option<Good>, sqlx::Error> {
+        if let Some(good) = self.cache.get_by_material_code(material_code) {
+            return Ok(Some(good));
+        }
+
         sqlx::query_as::<_, Good>(
-            "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base FROM goods ORDER BY goods_id ASC"
+            "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at FROM goods WHERE material_code = $1"
         )
-        .fetch_all(&self.pool)
+        .bind(material_code)
+        .fetch_optional(conn)
         .await
     }
 
-    pub async fn get_by_id(&self, goods_id: i32) -> Result<Option<Good>, sqlx::Error> {
+    /// Every tag in use with its goods count, most used first
+    pub async fn tag_counts(&self) -> Result<Vec<TagCount>, sqlx::Error> {
+        sqlx::query_as::<_, TagCount>(
+            r#"
+            SELECT tag, COUNT(*) AS goods_count
+            FROM goods, unnest(tags) AS tag
+            GROUP BY tag
+            ORDER BY goods_count DESC, tag ASC
+            "#
+        )
+        .fetch_all(&self.read_pool)
+        .await
+    }
+
+    /// Goods sharing a material code once trimmed (and uppercased, per `normalization`), to clean
+    /// up rows stored before input was normalized. Read-only; nothing is merged or renamed.
+    pub async fn duplicate_material_codes(&self, normalization: Normalization) -> Result<Vec<DuplicateMaterialCode>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, NormalizedGood>(
+            r#"
+            SELECT normalized_material_code, goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base,
+                   volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at
+            FROM (
+                SELECT normalized.*, COUNT(*) OVER (PARTITION BY normalized_material_code) AS group_size
+                FROM (
+                    SELECT goods.*,
+                           CASE WHEN $1 THEN UPPER(BTRIM(material_code, E' \t\r\n')) ELSE BTRIM(material_code, E' \t\r\n') END
+                               AS normalized_material_code
+                    FROM goods
+                ) normalized
+            ) grouped
+            WHERE group_size > 1
+            ORDER BY normalized_material_code ASC, goods_id ASC
+            "#
+        )
+        .bind(normalization.uppercase_material_code)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        let mut groups: Vec<DuplicateMaterialCode> = Vec::new();
+        for row in rows {
+            match groups.last_mut() {
+                Some(group) if group.normalized_material_code == row.normalized_material_code => group.goods.push(row.good),
+                _ => groups.push(DuplicateMaterialCode {
+                    normalized_material_code: row.normalized_material_code,
+                    goods: vec![row.good],
+                }),
+            }
+        }
+
+        Ok(groups)
+    }
+
+    /// Scanner lookup by exact barcode
+    pub async fn get_by_barcode(&self, barcode: &str) -> Result<Option<Good>, sqlx::Error> {
         sqlx::query_as::<_, Good>(
-            "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base FROM goods WHERE goods_id = $1"
+            "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at FROM goods WHERE barcode = $1"
         )
-        .bind(goods_id)
-        .fetch_optional(&self.pool)
+        .bind(barcode)
+        .fetch_optional(&self.read_pool)
         .await
     }
 
-    pub async fn get_by_material_code(&self, material_code: &str) -> Result<Option<Good>, sqlx::Error> {
+    /// Report a unique violation on the barcode as `DuplicateBarcode`, naming the good that holds it
+    async fn barcode_conflict(&self, error: sqlx::Error, barcode: Option<&str>) -> TableError {
+        let is_barcode_violation = matches!(
+            &error,
+            sqlx::Error::Database(db_err) if db_err.constraint() == Some("goods_barcode_key")
+        );
+        let Some(barcode) = barcode.filter(|_| is_barcode_violation) else {
+            return error.into();
+        };
+
+        match sqlx::query_scalar::<_, i32>("SELECT goods_id FROM goods WHERE barcode = $1")
+            .bind(barcode)
+            .fetch_optional(&self.pool)
+            .await
+        {
+            Ok(Some(goods_id)) => TableError::DuplicateBarcode { barcode: barcode.to_string(), goods_id },
+            _ => error.into(),
+        }
+    }
+
+    /// Load goods by id, in change-feed order
+    pub async fn get_by_ids(&self, goods_ids: &[i32]) -> Result<Vec<Good>, sqlx::Error> {
         sqlx::query_as::<_, Good>(
-            "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base FROM goods WHERE material_code = $1"
+            "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at FROM goods WHERE goods_id = ANY($1) ORDER BY updated_at ASC, goods_id ASC"
         )
-        .bind(material_code)
-        .fetch_optional(&self.pool)
+        .bind(goods_ids)
+        .fetch_all(&self.pool)
         .await
     }
 
-    pub async fn insert(&self, request: CreateGoodRequest) -> Result<Good, sqlx::Error> {
-        // Check if goods with same material_code already exists
-        let existing = self.get_by_material_code(&request.material_code).await?;
+    pub async fn insert(&self, request: CreateGoodRequest, on_conflict: GoodsConflictMode) -> Result<SavedGood, TableError> {
+        let mut tx = self.pool.begin().await?;
+        let saved = self.insert_tx(&mut tx, request, on_conflict).await?;
+        AuditLogTable::record_tx(&mut tx, "goods", &[saved.good.goods_id]).await?;
+        tx.commit().await?;
 
-        if let Some(existing_good) = existing {
-            // Return the existing good instead of error
-            return Ok(existing_good);
+        self.refresh_cache(&saved);
+        Ok(saved)
+    }
+
+    /// `insert` on the caller's transaction. The cache is left alone; after committing, pass
+    /// the result to `refresh_cache`.
+    pub async fn insert_tx(&self, conn: &mut PgConnection, request: CreateGoodRequest, on_conflict: GoodsConflictMode) -> Result<SavedGood, TableError> {
+        match on_conflict {
+            GoodsConflictMode::Update => return self.upsert_by_material_code_tx(conn, request).await,
+            GoodsConflictMode::ReturnExisting => {
+                // Check if goods with same material_code already exists
+                if let Some(existing_good) = self.get_by_material_code_tx(conn, &request.material_code).await? {
+                    return Ok(SavedGood { good: existing_good, created: false });
+                }
+            }
+            // A duplicate fails the insert with a unique violation
+            GoodsConflictMode::Error => {}
         }
 
         // Insert new good
         let new_good = sqlx::query_as::<_, Good>(
             r#"
-            INSERT INTO goods (material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-            RETURNING goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base
+            INSERT INTO goods (material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            RETURNING goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at
             "#
         )
         .bind(&request.material_code)
         .bind(&request.goods_name)
         .bind(&request.description)
-        .bind(&request.price)
-        .bind(&request.volumn_l)
-        .bind(&request.mass_g)
+        .bind(request.price)
+        .bind(request.volumn_l)
+        .bind(request.mass_g)
         .bind(request.mass_base.unwrap_or(0))
         .bind(request.volumn_base.unwrap_or(0))
-        .fetch_one(&self.pool)
-        .await?;
+        .bind(request.reorder_point)
+        .bind(request.category_id)
+        .bind(&request.barcode)
+        .bind(request.tags.as_deref().map(normalize_tags))
+        .bind(request.is_active.unwrap_or(true))
+        .fetch_one(&mut *conn)
+        .await;
 
-        Ok(new_good)
+        match new_good {
+            Ok(good) => Ok(SavedGood { good, created: true }),
+            Err(e) => Err(self.barcode_conflict(e, request.barcode.as_deref()).await),
+        }
     }
 
-    pub async fn update(&self, params: GoodsSearchParams, update_request: UpdateGoodRequest) -> Result<Vec<Good>, sqlx::Error> {
-        // First, find goods to update using the same search logic
-        let goods_to_update = self.search(params).await?;
-        
-        if goods_to_update.is_empty() {
-            return Ok(Vec::new()); // Return empty vector if nothing to update
+    /// Bring the cache up to date with a committed `insert_tx` or `upsert_by_material_code_tx`;
+    /// the returned row is the good's current state either way
+    pub fn refresh_cache(&self, saved: &SavedGood) {
+        self.cache.insert(&saved.good);
+    }
+
+    /// Insert the good, or replace every field of the existing good with the same material_code
+    pub async fn upsert_by_material_code(&self, request: CreateGoodRequest) -> Result<SavedGood, TableError> {
+        let mut tx = self.pool.begin().await?;
+        let saved = self.upsert_by_material_code_tx(&mut tx, request).await?;
+        AuditLogTable::record_tx(&mut tx, "goods", &[saved.good.goods_id]).await?;
+        tx.commit().await?;
+
+        self.refresh_cache(&saved);
+        Ok(saved)
+    }
+
+    /// `upsert_by_material_code` on the caller's transaction; see `insert_tx` for the cache
+    pub async fn upsert_by_material_code_tx(&self, conn: &mut PgConnection, request: CreateGoodRequest) -> Result<SavedGood, TableError> {
+        let result = sqlx::query_as::<_, SavedGood>(
+            r#"
+            INSERT INTO goods (material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, COALESCE($13, TRUE))
+            ON CONFLICT (material_code) DO UPDATE
+            SET goods_name = EXCLUDED.goods_name,
+                description = EXCLUDED.description,
+                price = EXCLUDED.price,
+                volumn_l = EXCLUDED.volumn_l,
+                mass_g = EXCLUDED.mass_g,
+                mass_base = EXCLUDED.mass_base,
+                volumn_base = EXCLUDED.volumn_base,
+                reorder_point = EXCLUDED.reorder_point,
+                category_id = EXCLUDED.category_id,
+                barcode = EXCLUDED.barcode,
+                tags = EXCLUDED.tags,
+                is_active = COALESCE($13, goods.is_active),
+                version = goods.version + 1,
+                updated_at = now()
+            RETURNING goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at,
+                (xmax = 0) AS created
+            "#
+        )
+        .bind(&request.material_code)
+        .bind(&request.goods_name)
+        .bind(&request.description)
+        .bind(request.price)
+        .bind(request.volumn_l)
+        .bind(request.mass_g)
+        .bind(request.mass_base.unwrap_or(0))
+        .bind(request.volumn_base.unwrap_or(0))
+        .bind(request.reorder_point)
+        .bind(request.category_id)
+        .bind(&request.barcode)
+        .bind(request.tags.as_deref().map(normalize_tags))
+        .bind(request.is_active)
+        .fetch_one(&mut *conn)
+        .await;
+
+        match result {
+            Ok(saved) => Ok(saved),
+            Err(e) => Err(self.barcode_conflict(e, request.barcode.as_deref()).await),
+        }
+    }
+
+    /// Insert already validated entries (paired with their payload index) in one transaction.
+    /// Entries whose material_code already exists are skipped and reported with the existing goods_id.
+    pub async fn insert_batch(&self, entries: Vec<(usize, CreateGoodRequest)>) -> Result<GoodsBatchResult, sqlx::Error> {
+        let mut result = GoodsBatchResult {
+            created: Vec::new(),
+            skipped_existing: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        if entries.is_empty() {
+            return Ok(result);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        // Multi-row insert, leaving existing material codes untouched
+        let mut builder = QueryBuilder::<Postgres>::new(
+            "INSERT INTO goods (material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active) "
+        );
+        builder.push_values(&entries, |mut row, (_, request)| {
+            row.push_bind(request.material_code.clone())
+                .push_bind(request.goods_name.clone())
+                .push_bind(request.description.clone())
+                .push_bind(request.price)
+                .push_bind(request.volumn_l)
+                .push_bind(request.mass_g)
+                .push_bind(request.mass_base.unwrap_or(0))
+                .push_bind(request.volumn_base.unwrap_or(0))
+                .push_bind(request.reorder_point)
+                .push_bind(request.category_id)
+                .push_bind(request.barcode.clone())
+                .push_bind(request.tags.as_deref().map(normalize_tags))
+                .push_bind(request.is_active.unwrap_or(true));
+        });
+        builder.push(
+            " ON CONFLICT (material_code) DO NOTHING \
+            RETURNING goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at"
+        );
+
+        let mut created = builder.build_query_as::<Good>()
+            .fetch_all(&mut *tx)
+            .await?;
+        created.sort_by_key(|good| good.goods_id);
+
+        // Anything not returned by the insert already existed; look up its goods_id
+        let skipped: Vec<&(usize, CreateGoodRequest)> = entries
+            .iter()
+            .filter(|(_, request)| !created.iter().any(|good| good.material_code == request.material_code))
+            .collect();
+
+        if !skipped.is_empty() {
+            let material_codes: Vec<String> = skipped.iter().map(|(_, request)| request.material_code.clone()).collect();
+            let existing = sqlx::query_as::<_, (i32, String)>(
+                "SELECT goods_id, material_code FROM goods WHERE material_code = ANY($1)"
+            )
+            .bind(&material_codes)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for (index, request) in skipped {
+                if let Some((goods_id, _)) = existing.iter().find(|(_, code)| *code == request.material_code) {
+                    result.skipped_existing.push(SkippedGood {
+                        index: *index,
+                        material_code: request.material_code.clone(),
+                        goods_id: *goods_id,
+                    });
+                }
+            }
+        }
+
+        let created_ids: Vec<i32> = created.iter().map(|good| good.goods_id).collect();
+        AuditLogTable::record_tx(&mut tx, "goods", &created_ids).await?;
+
+        tx.commit().await?;
+
+        result.created = created;
+        Ok(result)
+    }
+
+    /// Goods whose summed inventory is at or below their reorder point, largest deficit first.
+    /// Goods without inventory count as 0; a missing reorder point counts as 0 unless
+    /// `include_zero_reorder` is false, in which case those goods are skipped.
+    pub async fn low_stock(&self, include_zero_reorder: bool) -> Result<Vec<LowStockGoods>, sqlx::Error> {
+        sqlx::query_as::<_, LowStockGoods>(
+            r#"
+            SELECT
+                g.goods_id, g.material_code, g.goods_name,
+                COALESCE(SUM(i.quantity), 0)::int8 AS total_quantity,
+                g.reorder_point,
+                (COALESCE(g.reorder_point, 0) - COALESCE(SUM(i.quantity), 0))::int8 AS deficit
+            FROM goods g
+            LEFT JOIN inventory i ON i.goods_id = g.goods_id
+            WHERE $1 OR COALESCE(g.reorder_point, 0) > 0
+            GROUP BY g.goods_id
+            HAVING COALESCE(SUM(i.quantity), 0) <= COALESCE(g.reorder_point, 0)
+            ORDER BY deficit DESC, g.goods_id ASC
+            "#
+        )
+        .bind(include_zero_reorder)
+        .fetch_all(&self.read_pool)
+        .await
+    }
+
+    /// Update every matching good. With `expected_version`, nothing is updated unless all
+    /// matched goods are still at that version; the stale ones are reported instead.
+    /// A dry run returns the goods as they would be after the update and rolls back.
+    pub async fn update(&self, params: GoodsSearchParams, update_request: UpdateGoodRequest, options: BulkWriteOptions) -> Result<Vec<Good>, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        options.check_matched(Self::count_matching(&mut tx, &params).await?)?;
+
+        if let Some(expected_version) = update_request.expected_version {
+            let mut builder = SearchQueryBuilder::new(
+                "SELECT goods_id, version FROM goods WHERE 1=1".to_string()
+            );
+            Self::add_search_conditions(&mut builder, &params);
+            let query = format!("{} FOR UPDATE", builder.build(Some("goods_id ASC")));
+
+            let stale: Vec<StaleGoods> = builder.bind_values(sqlx::query_as::<_, (i32, i32)>(&query))
+                .fetch_all(&mut *tx)
+                .await?
+                .into_iter()
+                .filter(|(_, version)| *version != expected_version)
+                .map(|(goods_id, current_version)| StaleGoods { goods_id, current_version })
+                .collect();
+
+            if !stale.is_empty() {
+                tx.rollback().await?;
+                return Err(TableError::StaleVersion { goods: stale });
+            }
         }
 
-        let mut updated_goods = Vec::new();
+        // Update every matching good in one statement; the SET values take
+        // $1..$13 and the search conditions are numbered after them
+        let mut builder = SearchQueryBuilder::new(String::new()).with_bind_offset(13);
+        Self::add_search_conditions(&mut builder, &params);
 
-        // Update each found good
-        for good in goods_to_update {
-            let updated_good = sqlx::query_as::<_, Good>(
-                r#"
+        let query = format!(
+            r#"
+            WITH updated AS (
                 UPDATE goods 
                 SET 
-                    material_code = COALESCE($2, material_code),
-                    goods_name = COALESCE($3, goods_name),
-                    description = COALESCE($4, description),
-                    price = COALESCE($5, price),
-                    volumn_l = COALESCE($6, volumn_l),
-                    mass_g = COALESCE($7, mass_g),
-                    mass_base = COALESCE($8, mass_base),
-                    volumn_base = COALESCE($9, volumn_base)
-                WHERE goods_id = $1
-                RETURNING goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base
-                "#
+                    material_code = COALESCE($1, material_code),
+                    goods_name = COALESCE($2, goods_name),
+                    description = COALESCE($3, description),
+                    price = COALESCE($4, price),
+                    volumn_l = COALESCE($5, volumn_l),
+                    mass_g = COALESCE($6, mass_g),
+                    mass_base = COALESCE($7, mass_base),
+                    volumn_base = COALESCE($8, volumn_base),
+                    reorder_point = COALESCE($9, reorder_point),
+                    category_id = COALESCE($10, category_id),
+                    barcode = COALESCE($11, barcode),
+                    tags = COALESCE($12, tags),
+                    is_active = COALESCE($13, is_active),
+                    version = version + 1,
+                    updated_at = now()
+                WHERE 1=1{}
+                RETURNING goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at
             )
-            .bind(good.goods_id)
+            SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at
+            FROM updated
+            ORDER BY goods_id ASC
+            "#,
+            builder.build(None)
+        );
+
+        let sql_query = sqlx::query_as::<_, Good>(&query)
             .bind(&update_request.material_code)
             .bind(&update_request.goods_name)
             .bind(&update_request.description)
-            .bind(&update_request.price)
-            .bind(&update_request.volumn_l)
-            .bind(&update_request.mass_g)
-            .bind(&update_request.mass_base)
-            .bind(&update_request.volumn_base)
-            .fetch_one(&self.pool)
-            .await?;
+            .bind(update_request.price)
+            .bind(update_request.volumn_l)
+            .bind(update_request.mass_g)
+            .bind(update_request.mass_base)
+            .bind(update_request.volumn_base)
+            .bind(update_request.reorder_point)
+            .bind(update_request.category_id)
+            .bind(&update_request.barcode)
+            .bind(update_request.tags.as_deref().map(normalize_tags))
+            .bind(update_request.is_active);
 
-            updated_goods.push(updated_good);
+        let updated = match builder.bind_values(sql_query).fetch_all(&mut *tx).await {
+            Ok(updated) => updated,
+            Err(e) => return Err(self.barcode_conflict(e, update_request.barcode.as_deref()).await),
+        };
+
+        if options.dry_run {
+            tx.rollback().await?;
+            return Ok(updated);
         }
 
-        // Use get_by_id to verify the updates were successful (this uses the method)
-        if let Some(first_updated) = updated_goods.first() {
-            let _verification = self.get_by_id(first_updated.goods_id).await?;
+        let updated_ids: Vec<i32> = updated.iter().map(|good| good.goods_id).collect();
+        AuditLogTable::record_tx(&mut tx, "goods", &updated_ids).await?;
+
+        tx.commit().await?;
+
+        self.cache.invalidate(&updated_ids);
+
+        Ok(updated)
+    }
+
+    /// Update one good by id. `None` when it does not exist; with `expected_version`,
+    /// a good at another version is reported as stale instead of being updated.
+    pub async fn update_by_id(&self, goods_id: i32, update_request: UpdateGoodRequest) -> Result<Option<Good>, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        let updated = sqlx::query_as::<_, Good>(
+            r#"
+            UPDATE goods
+            SET
+                material_code = COALESCE($2, material_code),
+                goods_name = COALESCE($3, goods_name),
+                description = COALESCE($4, description),
+                price = COALESCE($5, price),
+                volumn_l = COALESCE($6, volumn_l),
+                mass_g = COALESCE($7, mass_g),
+                mass_base = COALESCE($8, mass_base),
+                volumn_base = COALESCE($9, volumn_base),
+                reorder_point = COALESCE($10, reorder_point),
+                category_id = COALESCE($11, category_id),
+                barcode = COALESCE($12, barcode),
+                tags = COALESCE($13, tags),
+                is_active = COALESCE($14, is_active),
+                version = version + 1,
+                updated_at = now()
+            WHERE goods_id = $1 AND ($15::int4 IS NULL OR version = $15)
+            RETURNING goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at
+            "#
+        )
+        .bind(goods_id)
+        .bind(&update_request.material_code)
+        .bind(&update_request.goods_name)
+        .bind(&update_request.description)
+        .bind(update_request.price)
+        .bind(update_request.volumn_l)
+        .bind(update_request.mass_g)
+        .bind(update_request.mass_base)
+        .bind(update_request.volumn_base)
+        .bind(update_request.reorder_point)
+        .bind(update_request.category_id)
+        .bind(&update_request.barcode)
+        .bind(update_request.tags.as_deref().map(normalize_tags))
+        .bind(update_request.is_active)
+        .bind(update_request.expected_version)
+        .fetch_optional(&mut *tx)
+        .await;
+
+        let updated = match updated {
+            Ok(updated) => updated,
+            Err(e) => return Err(self.barcode_conflict(e, update_request.barcode.as_deref()).await),
+        };
+
+        if let Some(good) = &updated {
+            AuditLogTable::record_tx(&mut tx, "goods", &[good.goods_id]).await?;
+            tx.commit().await?;
+
+            self.cache.invalidate(&[good.goods_id]);
+            return Ok(updated);
+        }
+
+        // Nothing updated: either the good is missing or its version moved on
+        if update_request.expected_version.is_some() {
+            let current_version = sqlx::query_scalar::<_, i32>("SELECT version FROM goods WHERE goods_id = $1")
+                .bind(goods_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+            if let Some(current_version) = current_version {
+                return Err(TableError::StaleVersion { goods: vec![StaleGoods { goods_id, current_version }] });
+            }
         }
 
-        Ok(updated_goods)
+        Ok(None)
     }
 
-    pub async fn delete(&self, params: GoodsSearchParams) -> Result<Vec<i32>, sqlx::Error> {
-        // First, find goods to delete using the same search logic
-        let goods_to_delete = self.search(params).await?;
-        
-        if goods_to_delete.is_empty() {
-            return Ok(Vec::new()); // Return empty vector if nothing to delete
+    /// Delete exactly these goods, skipping the ones inventory items still reference
+    pub async fn delete_by_ids(&self, goods_ids: &[i32]) -> Result<GoodsDeleteByIdsResult, sqlx::Error> {
+        let mut requested = goods_ids.to_vec();
+        requested.sort_unstable();
+        requested.dedup();
+
+        let mut tx = self.pool.begin().await?;
+
+        // Lock the goods first so no inventory can be added for them while we decide
+        let existing: Vec<i32> = sqlx::query_scalar("SELECT goods_id FROM goods WHERE goods_id = ANY($1) ORDER BY goods_id FOR UPDATE")
+            .bind(&requested)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let blocked: Vec<BlockedGoods> = sqlx::query_as::<_, (i32, i64)>(
+            "SELECT goods_id, COUNT(*) FROM inventory WHERE goods_id = ANY($1) GROUP BY goods_id ORDER BY goods_id ASC"
+        )
+        .bind(&existing)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|(goods_id, inventory_count)| BlockedGoods { goods_id, inventory_count })
+        .collect();
+
+        let deletable: Vec<i32> = existing.iter()
+            .copied()
+            .filter(|goods_id| blocked.binary_search_by_key(goods_id, |blocked| blocked.goods_id).is_err())
+            .collect();
+
+        let mut deleted: Vec<i32> = sqlx::query_scalar("DELETE FROM goods WHERE goods_id = ANY($1) RETURNING goods_id")
+            .bind(&deletable)
+            .fetch_all(&mut *tx)
+            .await?;
+        deleted.sort_unstable();
+
+        SyncTable::record_deleted(&mut tx, SyncEntity::Goods, &deleted).await?;
+        AuditLogTable::record_tx(&mut tx, "goods", &deleted).await?;
+
+        tx.commit().await?;
+
+        self.cache.invalidate(&deleted);
+
+        let not_found = requested.into_iter().filter(|goods_id| existing.binary_search(goods_id).is_err()).collect();
+
+        Ok(GoodsDeleteByIdsResult { deleted, not_found, blocked })
+    }
+
+    /// Delete every matching good; a dry run returns the ids that would be deleted and rolls back
+    pub async fn delete(&self, params: GoodsSearchParams, options: BulkWriteOptions) -> Result<Vec<i32>, TableError> {
+        let mut builder = SearchQueryBuilder::new(String::new());
+        Self::add_search_conditions(&mut builder, &params);
+        let conditions = builder.build(None);
+
+        let mut tx = self.pool.begin().await?;
+
+        options.check_matched(Self::count_matching(&mut tx, &params).await?)?;
+
+        // Check if any matching goods have inventory items before deletion
+        let inventory_query = format!(
+            r#"
+            SELECT goods_id, COUNT(*) FROM inventory
+            WHERE goods_id IN (SELECT goods_id FROM goods WHERE 1=1{} FOR UPDATE)
+            GROUP BY goods_id
+            ORDER BY goods_id ASC
+            "#,
+            conditions
+        );
+        let blocked = builder.bind_values(sqlx::query_as::<_, (i32, i64)>(&inventory_query))
+            .fetch_all(&mut *tx)
+            .await?;
+
+        if !blocked.is_empty() {
+            return Err(TableError::BlockedByInventory {
+                goods: blocked
+                    .into_iter()
+                    .map(|(goods_id, inventory_count)| BlockedGoods { goods_id, inventory_count })
+                    .collect(),
+            });
+        }
+
+        // Delete every matching good in one statement
+        let delete_query = format!(
+            r#"
+            WITH deleted AS (
+                DELETE FROM goods WHERE 1=1{}
+                RETURNING goods_id
+            )
+            SELECT goods_id FROM deleted ORDER BY goods_id ASC
+            "#,
+            conditions
+        );
+        let deleted = builder.bind_values(sqlx::query_as::<_, (i32,)>(&delete_query))
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let deleted_ids: Vec<i32> = deleted.iter().map(|(goods_id,)| *goods_id).collect();
+
+        if options.dry_run {
+            tx.rollback().await?;
+            return Ok(deleted_ids);
         }
 
-        let mut deleted_ids = Vec::new();
+        SyncTable::record_deleted(&mut tx, SyncEntity::Goods, &deleted_ids).await?;
+        AuditLogTable::record_tx(&mut tx, "goods", &deleted_ids).await?;
+
+        tx.commit().await?;
 
-        // Check if any goods have inventory items before deletion
-        for good in goods_to_delete {
-            // Use utility function to count inventory items
-            let inventory_count = crate::utils::database::count_by_foreign_key(
-                &self.pool, 
-                "inventory", 
-                "goods_id", 
-                good.goods_id
-            ).await?;
+        self.cache.invalidate(&deleted_ids);
 
-            if inventory_count > 0 {
-                // Return error indicating which goods cannot be deleted
-                return Err(sqlx::Error::RowNotFound);
+        Ok(deleted_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fuzzy_params(goods_name: &str) -> GoodsSearchParams {
+        let mut params = GoodsSearchParams { goods_name: Some(goods_name.to_string()), ..GoodsSearchParams::new() };
+        params.rank_by_similarity(0.3).unwrap();
+        params
+    }
+
+    #[test]
+    fn fuzzy_search_binds_the_term_as_first_placeholder() {
+        let (query, builder) = GoodsTable::search_query(&fuzzy_params("chili"), false);
+
+        assert!(query.contains("similarity(goods_name, $1) AS similarity"), "{}", query);
+        assert!(query.contains("AND similarity(goods_name, $1) > 0.3"), "{}", query);
+        assert!(query.contains("AND is_active = $2"), "{}", query);
+        assert_eq!(builder.values(), [BindValue::Text("chili".to_string()), BindValue::Bool(true)]);
+    }
+
+    #[test]
+    fn fuzzy_search_with_inactive_goods_binds_only_the_term() {
+        let params = GoodsSearchParams { include_inactive: true, ..fuzzy_params("chili") };
+        let (query, builder) = GoodsTable::search_query(&params, false);
+
+        assert!(!query.contains("is_active = "), "{}", query);
+        assert_eq!(builder.values(), [BindValue::Text("chili".to_string())]);
+    }
+
+    #[test]
+    fn search_without_fuzzy_binds_the_active_filter_first() {
+        let params = GoodsSearchParams { goods_name: Some("chili".to_string()), ..GoodsSearchParams::new() };
+        let (query, builder) = GoodsTable::search_query(&params, false);
+
+        assert!(!query.contains("similarity"), "{}", query);
+        assert!(query.contains("AND is_active = $1"), "{}", query);
+        assert_eq!(builder.values()[0], BindValue::Bool(true));
+    }
+
+    /// `conditions` with `?` numbered from `$1`, as the builder appends them
+    fn numbered(conditions: &[&str]) -> String {
+        conditions.iter().enumerate().map(|(i, condition)| format!(" AND {}", condition.replace('?', &format!("${}", i + 1)))).collect()
+    }
+
+    #[test]
+    fn every_filter_combination_binds_values_in_placeholder_order() {
+        for combination in 0..16 {
+            let mut params = GoodsSearchParams::new();
+            let mut conditions = vec!["is_active = ?"];
+            let mut values = vec![BindValue::Bool(true)];
+            if combination & 1 != 0 {
+                params.material_code = Some("CH-1".to_string());
+                conditions.push("material_code ILIKE ? ESCAPE '\\'");
+                values.push(BindValue::Text("%CH-1%".to_string()));
+            }
+            if combination & 2 != 0 {
+                params.goods_name = Some("chili".to_string());
+                conditions.push("goods_name ILIKE ? ESCAPE '\\'");
+                values.push(BindValue::Text("%chili%".to_string()));
+            }
+            if combination & 4 != 0 {
+                params.min_price = Some(rust_decimal::Decimal::new(250, 2));
+                conditions.push("price >= ?");
+                values.push(BindValue::Decimal(rust_decimal::Decimal::new(250, 2)));
+            }
+            if combination & 8 != 0 {
+                params.in_stock = Some(false);
+                conditions.push("EXISTS (SELECT 1 FROM inventory i WHERE i.goods_id = goods.goods_id AND i.quantity > 0) = ?");
+                values.push(BindValue::Bool(false));
             }
 
-            // If no inventory items, proceed with deletion
-            sqlx::query("DELETE FROM goods WHERE goods_id = $1")
-                .bind(good.goods_id)
-                .execute(&self.pool)
-                .await?;
-            
-            deleted_ids.push(good.goods_id);
+            let (query, builder) = GoodsTable::search_query(&params, false);
+
+            assert!(query.contains(&format!("WHERE 1=1{} ORDER BY", numbered(&conditions))), "{}", query);
+            assert_eq!(builder.values(), values.as_slice());
         }
+    }
 
-        Ok(deleted_ids)
+    #[test]
+    fn wildcard_search_binds_only_the_active_filter() {
+        let params = GoodsSearchParams { goods_name: Some("*".to_string()), min_price: Some(rust_decimal::Decimal::ONE), ..GoodsSearchParams::new() };
+
+        let (query, builder) = GoodsTable::search_query(&params, false);
+
+        assert!(query.contains("WHERE 1=1 AND is_active = $1 ORDER BY"), "{}", query);
+        assert_eq!(builder.values(), [BindValue::Bool(true)]);
+    }
+
+    #[test]
+    fn updated_at_filters_bind_in_placeholder_order() {
+        let min_updated_at: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let max_updated_at: DateTime<Utc> = "2024-02-01T00:00:00Z".parse().unwrap();
+        let params = GoodsSearchParams {
+            max_price: Some(rust_decimal::Decimal::TEN),
+            min_updated_at: Some(min_updated_at),
+            max_updated_at: Some(max_updated_at),
+            ..GoodsSearchParams::new()
+        };
+
+        let (query, builder) = GoodsTable::search_query(&params, false);
+
+        assert!(query.contains(&numbered(&["is_active = ?", "price <= ?", "updated_at >= ?", "updated_at <= ?"])), "{}", query);
+        assert_eq!(
+            builder.values(),
+            [BindValue::Bool(true), BindValue::Decimal(rust_decimal::Decimal::TEN), BindValue::DateTime(min_updated_at), BindValue::DateTime(max_updated_at)]
+        );
+    }
+
+    #[test]
+    fn like_metacharacters_in_filters_are_escaped() {
+        let params = GoodsSearchParams {
+            material_code: Some("CH_100".to_string()),
+            description_contains: Some("50%\\off".to_string()),
+            ..GoodsSearchParams::new()
+        };
+
+        let (query, builder) = GoodsTable::search_query(&params, false);
+
+        assert!(query.contains("AND material_code ILIKE $2 ESCAPE '\\'"), "{}", query);
+        assert!(query.contains("WHERE d ILIKE $3 ESCAPE '\\'"), "{}", query);
+        assert_eq!(builder.values()[1..], [BindValue::Text("%CH\\_100%".to_string()), BindValue::Text("%50\\%\\\\off%".to_string())]);
+    }
+
+    #[test]
+    fn literal_matching_compares_the_stored_value_unchanged() {
+        let params = GoodsSearchParams {
+            material_code: Some("CH_100".to_string()),
+            goods_name: Some("50% Chili".to_string()),
+            match_mode: MatchMode::Literal,
+            ..GoodsSearchParams::new()
+        };
+
+        let (query, builder) = GoodsTable::search_query(&params, false);
+
+        assert!(query.contains(&numbered(&["is_active = ?", "material_code = ?", "goods_name = ?"])), "{}", query);
+        assert_eq!(builder.values()[1..], [BindValue::Text("CH_100".to_string()), BindValue::Text("50% Chili".to_string())]);
+    }
+
+    fn by_price() -> GoodsSearchParams {
+        GoodsSearchParams { sort_by: Some(GoodsSortColumn::Price), ..GoodsSearchParams::new() }
+    }
+
+    #[test]
+    fn cursor_continues_after_the_last_sort_value_and_id() {
+        let mut params = by_price();
+        let cursor = PageCursor { order: params.order_by_clause(), value: Some("4.50".to_string()), id: 17 };
+        params.cursor = Some(params.keyset_from(&cursor).unwrap());
+
+        let (query, builder) = GoodsTable::search_query(&params, false);
+
+        assert!(query.contains("AND (price > $2 OR (price = $3 AND goods_id > $4)) ORDER BY price ASC, goods_id ASC"), "{}", query);
+        let price = BindValue::Decimal("4.50".parse().unwrap());
+        assert_eq!(builder.values()[1..], [price.clone(), price, BindValue::Int(17)]);
+    }
+
+    #[test]
+    fn cursor_from_another_ordering_or_with_a_bad_value_is_rejected() {
+        let params = by_price();
+
+        let other_order = PageCursor { order: "goods_id ASC".to_string(), value: None, id: 17 };
+        assert_eq!(params.keyset_from(&other_order).unwrap_err(), "cursor does not match sort_by and sort_order");
+
+        let bad_value = PageCursor { order: params.order_by_clause(), value: Some("cheap".to_string()), id: 17 };
+        assert_eq!(params.keyset_from(&bad_value).unwrap_err(), "Invalid cursor");
     }
 }
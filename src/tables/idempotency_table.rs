@@ -0,0 +1,124 @@
+// src/tables/idempotency_table.rs
+//
+// Stored responses for requests sent with an `Idempotency-Key` header. A key is claimed
+// with an empty response before the handler runs, so a concurrent retry sees it as in
+// progress instead of executing the write a second time.
+use sqlx::{FromRow, PgPool};
+use std::time::Duration;
+
+/// A client's key for one path; the same key sent by another caller is a different key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyKey {
+    pub key: String,
+    pub request_path: String,
+    /// API key label or fingerprint of the caller, empty when authentication is disabled
+    pub caller: String,
+}
+
+/// `status_code` and `response_body` stay empty until the original request has finished
+#[derive(Debug, Clone, FromRow)]
+pub struct IdempotencyRecord {
+    pub request_hash: String,
+    pub status_code: Option<i32>,
+    pub response_body: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct IdempotencyTable {
+    pool: PgPool,
+}
+
+impl IdempotencyTable {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Claim a key for this request. Returns the existing record if the key is already
+    /// taken and unexpired; an expired record is taken over, and so is one still in
+    /// progress after `stale_after`, whose request can no longer be running.
+    pub async fn claim(
+        &self,
+        key: &IdempotencyKey,
+        request_hash: &str,
+        ttl: Duration,
+        stale_after: Duration,
+    ) -> Result<Option<IdempotencyRecord>, sqlx::Error> {
+        let claimed = sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (idempotency_key, request_path, caller, request_hash, expires_at)
+            VALUES ($1, $2, $3, $4, now() + make_interval(secs => $5))
+            ON CONFLICT (idempotency_key, request_path, caller) DO UPDATE
+            SET request_hash = EXCLUDED.request_hash,
+                status_code = NULL,
+                response_body = NULL,
+                created_at = now(),
+                expires_at = EXCLUDED.expires_at
+            WHERE idempotency_keys.expires_at <= now()
+                OR (idempotency_keys.status_code IS NULL AND idempotency_keys.created_at <= now() - make_interval(secs => $6))
+            "#
+        )
+        .bind(&key.key)
+        .bind(&key.request_path)
+        .bind(&key.caller)
+        .bind(request_hash)
+        .bind(ttl.as_secs_f64())
+        .bind(stale_after.as_secs_f64())
+        .execute(&self.pool)
+        .await?
+        .rows_affected() > 0;
+
+        if claimed {
+            return Ok(None);
+        }
+
+        sqlx::query_as::<_, IdempotencyRecord>(
+            r#"
+            SELECT request_hash, status_code, response_body
+            FROM idempotency_keys
+            WHERE idempotency_key = $1 AND request_path = $2 AND caller = $3
+            "#
+        )
+        .bind(&key.key)
+        .bind(&key.request_path)
+        .bind(&key.caller)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Store the response for a claimed key
+    pub async fn complete(&self, key: &IdempotencyKey, status_code: u16, response_body: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+            SET status_code = $4, response_body = $5
+            WHERE idempotency_key = $1 AND request_path = $2 AND caller = $3
+            "#
+        )
+        .bind(&key.key)
+        .bind(&key.request_path)
+        .bind(&key.caller)
+        .bind(status_code as i32)
+        .bind(response_body)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    /// Give a key back so the request can be retried, e.g. after a server error
+    pub async fn release(&self, key: &IdempotencyKey) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE idempotency_key = $1 AND request_path = $2 AND caller = $3")
+            .bind(&key.key)
+            .bind(&key.request_path)
+            .bind(&key.caller)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn delete_expired(&self) -> Result<u64, sqlx::Error> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= now()")
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
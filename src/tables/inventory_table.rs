@@ -1,8 +1,27 @@
 // src/tables/inventory_table.rs
+use futures_util::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool, Row};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::time::Duration;
 use chrono::{DateTime, Utc};
-use super::goods_table::GoodsSearchParams;
+use super::audit_log_table::AuditLogTable;
+use super::error::{BatchItemError, TableError};
+use super::reservations_table::ACTIVE_RESERVATION_CONDITION;
+use super::goods_table::{
+    add_category_conditions, add_fuzzy_condition, similarity_column, similarity_expression, BulkWriteOptions, Good, GoodsSearchParams, GoodsTable,
+};
+use super::stock_movements_table::{NewStockMovement, StockMovementsTable};
+use super::sync_table::{SyncEntity, SyncTable};
+use super::webhook_outbox_table::{InventoryEvent, InventoryEventType, WebhookOutboxTable};
+use crate::utils::database::{begin_with_statement_timeout, database_now, stream_rows};
+use crate::utils::datetime::{days_until_expiration_at, is_expired_at};
+use crate::utils::pagination::{PageCursor, PaginatedResponse, PaginationParams};
+use crate::utils::query_builder::{BindValue, Keyset, SearchQueryBuilder};
+use crate::utils::response::TargetResult;
+use crate::utils::sorting::SortOrder;
+use crate::utils::string_utils::{normalize_tags, to_search_pattern, MatchMode};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct InventoryItem {
@@ -10,9 +29,13 @@ pub struct InventoryItem {
     pub goods_id: i32,
     pub quantity: i32,
     pub expired_date: Option<DateTime<Utc>>,
+    pub lot_number: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct InventoryItemWithGoods {
     pub item_id: i32,
     pub goods_id: i32,
@@ -26,9 +49,263 @@ pub struct InventoryItemWithGoods {
     pub volumn_base: i16,
     pub quantity: i32,
     pub expired_date: Option<DateTime<Utc>>,
+    /// Supplier lot/batch number, for traceability and recalls
+    pub lot_number: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Whether expired_date has passed by the database clock; false without an expiry date
+    #[sqlx(skip)]
+    #[serde(skip_deserializing)]
+    pub is_expired: bool,
+    /// Whole days until expired_date, negative once expired; null without an expiry date
+    #[sqlx(skip)]
+    #[serde(skip_deserializing)]
+    pub days_until_expiration: Option<i64>,
+    /// Quantity minus active reservations; only present when `include_reserved=true`
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available_quantity: Option<i32>,
+    /// Trigram similarity of goods_name to the search term; only present with `fuzzy=true`
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
+}
+
+impl InventoryItemWithGoods {
+    /// Field names accepted by `?fields=`
+    pub const FIELDS: &'static [&'static str] = &[
+        "item_id", "goods_id", "material_code", "goods_name", "description", "price", "volumn_l", "mass_g",
+        "mass_base", "volumn_base", "quantity", "expired_date", "lot_number", "created_at", "updated_at",
+        "is_expired", "days_until_expiration", "available_quantity", "similarity",
+    ];
+
+    /// Fill in `is_expired` and `days_until_expiration` as of `now`
+    pub fn set_expiry_fields(&mut self, now: DateTime<Utc>) {
+        self.is_expired = self.expired_date.is_some_and(|date| is_expired_at(&date, now));
+        self.days_until_expiration = self.expired_date.map(|date| days_until_expiration_at(&date, now));
+    }
+}
+
+/// What `POST /inventory` does when an item with the same goods, expiry and lot exists (`?on_duplicate=`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InventoryDuplicateMode {
+    /// Return the stored item unchanged, ignoring the posted quantity
+    #[default]
+    ReturnExisting,
+    /// Add the posted quantity to the stored item
+    AddQuantity,
+    /// Reject with 409
+    Error,
+}
+
+impl InventoryDuplicateMode {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "return_existing" => Ok(InventoryDuplicateMode::ReturnExisting),
+            "add_quantity" => Ok(InventoryDuplicateMode::AddQuantity),
+            "error" => Ok(InventoryDuplicateMode::Error),
+            _ => Err("Invalid on_duplicate. Allowed values: return_existing, add_quantity, error".to_string()),
+        }
+    }
+}
+
+/// Quantity of one good with its expiry and lot, as passed to `InventoryTable::store_quantity`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StockToStore<'a> {
+    pub goods_id: i32,
+    pub quantity: i32,
+    pub expired_date: Option<DateTime<Utc>>,
+    pub lot_number: Option<&'a str>,
+}
+
+/// How `InventoryTable::insert` handled the request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryInsertOutcome {
+    Created,
+    /// An item with the same goods, expiry and lot was returned unchanged
+    Existing,
+    /// The posted quantity was added to an existing item
+    Merged,
+}
+
+/// What the expired-stock purge does with items past the retention window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ExpiredPurgeMode {
+    /// Set the quantity to zero and keep the row
+    #[default]
+    Zero,
+    /// Delete the row
+    Delete,
+}
+
+impl ExpiredPurgeMode {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "zero" => Ok(ExpiredPurgeMode::Zero),
+            "delete" => Ok(ExpiredPurgeMode::Delete),
+            _ => Err("Invalid purge mode. Allowed values: zero, delete".to_string()),
+        }
+    }
+}
+
+/// Items zeroed or deleted by an expired-stock purge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExpiredPurgeResult {
+    pub mode: ExpiredPurgeMode,
+    pub retention_days: u32,
+    /// Items that expired before this instant were affected
+    pub cutoff: DateTime<Utc>,
+    pub item_ids: Vec<i32>,
+}
+
+/// Aggregates over the inventory items matching a search
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InventoryStats {
+    pub total_goods: i64,
+    pub total_items: i64,
+    pub total_quantity: i64,
+    pub total_value: rust_decimal::Decimal,
+    pub expired_items: i64,
+    pub expiring_within_30_days: i64,
+}
+
+/// Inventory items sharing goods, expiry and lot that were (or would be) folded into one row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MergedInventoryGroup {
+    pub goods_id: i32,
+    pub expired_date: Option<DateTime<Utc>>,
+    pub lot_number: Option<String>,
+    /// Lowest item_id in the group, which keeps the combined quantity
+    pub surviving_item_id: i32,
+    pub removed_item_ids: Vec<i32>,
+    pub quantity: i32,
+}
+
+/// A source item of a goods merge whose quantity went into a target item with the same expiry and lot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MergedGoodsItem {
+    /// Deleted once its quantity is added to the target item
+    pub source_item_id: i32,
+    pub target_item_id: i32,
+    /// Quantity added to the target item
+    pub quantity: i32,
+}
+
+/// Outcome of merging a duplicate good into another
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GoodsMergeResult {
+    /// The duplicate, deleted by the merge
+    pub source_goods_id: i32,
+    /// The surviving good, with the combined description and tags
+    pub target: Good,
+    /// Source items repointed to the target unchanged
+    pub moved_item_ids: Vec<i32>,
+    pub merged_items: Vec<MergedGoodsItem>,
+    /// Purchase order lines repointed to the target
+    pub purchase_order_lines: u64,
+}
+
+/// Relative quantity change for a single inventory item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AdjustInventoryRequest {
+    pub delta: i32,
+    pub reason: String,
+}
+
+/// Amount to take from or add to a single inventory item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct QuantityChangeRequest {
+    pub quantity: i32,
+}
+
+/// Stock to remove for one good, taken from the earliest expiring items first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConsumeInventoryRequest {
+    pub goods_id: Option<i32>,
+    pub material_code: Option<String>,
+    pub quantity: i32,
+    /// Delete inventory items that end up empty instead of keeping them at zero
+    pub delete_empty: Option<bool>,
+}
+
+/// How much was taken from a single inventory item by a consume request
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConsumedItem {
+    pub item_id: i32,
+    pub taken: i32,
+    pub remaining_quantity: i32,
+    pub deleted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConsumeResult {
+    pub goods_id: i32,
+    pub quantity: i32,
+    pub items: Vec<ConsumedItem>,
+}
+
+/// An inventory item produced by a batch entry, tagged with that entry's payload index
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchInventoryItem {
+    pub index: usize,
+    #[serde(flatten)]
+    pub item: InventoryItemWithGoods,
+}
+
+/// Outcome of deleting inventory items by an explicit id list
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InventoryDeleteByIdsResult {
+    pub deleted: Vec<i32>,
+    pub not_found: Vec<i32>,
+}
+
+/// Outcome of a batch inventory creation
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InventoryBatchResult {
+    pub created: Vec<BatchInventoryItem>,
+    pub matched_existing: Vec<BatchInventoryItem>,
+    pub failures: Vec<BatchItemError>,
+}
+
+impl InventoryDeleteByIdsResult {
+    /// Per-id `results`
+    pub fn target_results(&self) -> Vec<TargetResult> {
+        let deleted = self.deleted.iter().map(|&item_id| TargetResult::ok(item_id));
+        let not_found = self.not_found.iter().map(|&item_id| TargetResult::failed(Some(item_id), "NOT_FOUND", "Inventory item not found"));
+
+        deleted.chain(not_found).collect()
+    }
+}
+
+impl InventoryBatchResult {
+    /// Per-entry `results`
+    pub fn target_results(&self) -> Vec<TargetResult> {
+        let created = self.created.iter().map(|entry| TargetResult::ok(entry.item.item_id).at(entry.index));
+        let matched = self.matched_existing.iter().map(|entry| {
+            TargetResult::ok(entry.item.item_id).at(entry.index).with_message("Matched an existing item; not inserted again")
+        });
+
+        created.chain(matched).chain(self.failures.iter().map(TargetResult::from)).collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CreateInventoryRequest {
     // Option 1: Use existing goods by ID or material code
     pub goods_id: Option<i32>,
@@ -46,9 +323,12 @@ pub struct CreateInventoryRequest {
     // Inventory specific fields
     pub quantity: i32,
     pub expired_date: Option<DateTime<Utc>>,
+    /// Supplier lot/batch number; items of the same goods and expiry with different lots stay separate
+    pub lot_number: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct UpdateInventoryRequest {
     // Goods fields (optional updates)
     pub material_code: Option<String>,
@@ -63,340 +343,813 @@ pub struct UpdateInventoryRequest {
     // Inventory fields (optional updates)
     pub quantity: Option<i32>,
     pub expired_date: Option<DateTime<Utc>>,
+    pub lot_number: Option<String>,
+}
+
+/// Columns inventory results may be sorted by (whitelisted, never interpolated from input)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventorySortColumn {
+    ItemId,
+    Quantity,
+    ExpiredDate,
+    GoodsName,
+    Price,
+    MaterialCode,
+}
+
+impl InventorySortColumn {
+    pub const ALLOWED: &'static [&'static str] = &["item_id", "quantity", "expired_date", "goods_name", "price", "material_code"];
+
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "item_id" => Ok(InventorySortColumn::ItemId),
+            "quantity" => Ok(InventorySortColumn::Quantity),
+            "expired_date" => Ok(InventorySortColumn::ExpiredDate),
+            "goods_name" => Ok(InventorySortColumn::GoodsName),
+            "price" => Ok(InventorySortColumn::Price),
+            "material_code" => Ok(InventorySortColumn::MaterialCode),
+            _ => Err(format!("Invalid sort_by. Allowed values: {}", Self::ALLOWED.join(", "))),
+        }
+    }
+
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            InventorySortColumn::ItemId => "i.item_id",
+            InventorySortColumn::Quantity => "i.quantity",
+            InventorySortColumn::ExpiredDate => "i.expired_date",
+            InventorySortColumn::GoodsName => "g.goods_name",
+            InventorySortColumn::Price => "g.price",
+            InventorySortColumn::MaterialCode => "g.material_code",
+        }
+    }
+
+    /// The value of this column in `item`, as stored in a page cursor; None for a NULL
+    pub(crate) fn cursor_value(&self, item: &InventoryItemWithGoods) -> Option<String> {
+        match self {
+            InventorySortColumn::ItemId => Some(item.item_id.to_string()),
+            InventorySortColumn::Quantity => Some(item.quantity.to_string()),
+            InventorySortColumn::ExpiredDate => item.expired_date.map(|expired_date| expired_date.to_rfc3339()),
+            InventorySortColumn::GoodsName => Some(item.goods_name.clone()),
+            InventorySortColumn::Price => Some(item.price.to_string()),
+            InventorySortColumn::MaterialCode => Some(item.material_code.clone()),
+        }
+    }
+
+    pub(crate) fn parse_cursor_value(&self, value: &str) -> Result<BindValue, String> {
+        let invalid = || "Invalid cursor".to_string();
+        match self {
+            InventorySortColumn::ItemId | InventorySortColumn::Quantity => {
+                value.parse::<i32>().map(BindValue::from).map_err(|_| invalid())
+            }
+            InventorySortColumn::ExpiredDate => DateTime::parse_from_rfc3339(value)
+                .map(|expired_date| BindValue::from(expired_date.with_timezone(&Utc)))
+                .map_err(|_| invalid()),
+            InventorySortColumn::GoodsName | InventorySortColumn::MaterialCode => Ok(BindValue::from(value.to_string())),
+            InventorySortColumn::Price => value.parse::<rust_decimal::Decimal>().map(BindValue::from).map_err(|_| invalid()),
+        }
+    }
+}
+
+/// Columns the per-goods summary may be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummarySortColumn {
+    GoodsId,
+    MaterialCode,
+    GoodsName,
+    LotCount,
+    TotalQuantity,
+    EarliestExpiredDate,
+}
+
+impl SummarySortColumn {
+    pub const ALLOWED: &'static [&'static str] = &["goods_id", "material_code", "goods_name", "lot_count", "total_quantity", "earliest_expired_date"];
+
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "goods_id" => Ok(SummarySortColumn::GoodsId),
+            "material_code" => Ok(SummarySortColumn::MaterialCode),
+            "goods_name" => Ok(SummarySortColumn::GoodsName),
+            "lot_count" => Ok(SummarySortColumn::LotCount),
+            "total_quantity" => Ok(SummarySortColumn::TotalQuantity),
+            "earliest_expired_date" => Ok(SummarySortColumn::EarliestExpiredDate),
+            _ => Err(format!("Invalid sort_by. Allowed values: {}", Self::ALLOWED.join(", "))),
+        }
+    }
+
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            SummarySortColumn::GoodsId => "goods_id",
+            SummarySortColumn::MaterialCode => "material_code",
+            SummarySortColumn::GoodsName => "goods_name",
+            SummarySortColumn::LotCount => "lot_count",
+            SummarySortColumn::TotalQuantity => "total_quantity",
+            SummarySortColumn::EarliestExpiredDate => "earliest_expired_date",
+        }
+    }
+}
+
+/// One row of the per-goods inventory summary
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InventorySummary {
+    pub goods_id: i32,
+    pub material_code: String,
+    pub goods_name: String,
+    pub lot_count: i64,
+    pub total_quantity: i64,
+    pub earliest_expired_date: Option<DateTime<Utc>>,
+}
+
+/// Inventory filters plus the summary's own sort options
+#[derive(Debug, Clone)]
+pub struct InventorySummaryParams {
+    pub search: InventorySearchParams,
+    pub sort_by: Option<SummarySortColumn>,
+    pub sort_order: Option<SortOrder>,
+}
+
+impl InventorySummaryParams {
+    /// Build the ORDER BY expression, always ending with goods_id so pagination stays stable
+    pub fn order_by_clause(&self) -> String {
+        let sort_order = self.sort_order.unwrap_or(SortOrder::Asc);
+
+        match self.sort_by {
+            Some(SummarySortColumn::EarliestExpiredDate) => {
+                format!("earliest_expired_date {} NULLS LAST, goods_id ASC", sort_order.as_sql())
+            }
+            Some(column) if column != SummarySortColumn::GoodsId => {
+                format!("{} {}, goods_id ASC", column.as_sql(), sort_order.as_sql())
+            }
+            _ => format!("goods_id {}", sort_order.as_sql()),
+        }
+    }
+}
+
+/// What the stock valuation report groups by (`?group_by=`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ValuationGroupBy {
+    #[default]
+    Goods,
+    /// The goods' category; goods without one are grouped as "Uncategorized"
+    Category,
+}
+
+impl ValuationGroupBy {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "goods" => Ok(ValuationGroupBy::Goods),
+            "category" => Ok(ValuationGroupBy::Category),
+            _ => Err("Invalid group_by. Allowed values: goods, category".to_string()),
+        }
+    }
+
+    /// (group key, group label, extra join) of the valuation query
+    fn as_sql(&self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            ValuationGroupBy::Goods => ("g.goods_id", "g.goods_name", ""),
+            ValuationGroupBy::Category => (
+                "c.category_id",
+                "COALESCE(c.name, 'Uncategorized')",
+                " LEFT JOIN categories c ON c.category_id = g.category_id",
+            ),
+        }
+    }
+}
+
+/// Inventory filters plus the valuation report's grouping
+#[derive(Debug, Clone)]
+pub struct ValuationParams {
+    pub search: InventorySearchParams,
+    pub group_by: ValuationGroupBy,
+    /// Leave out items whose expiry date has passed
+    pub exclude_expired: bool,
+}
+
+/// Stock value of one group in the valuation report
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ValuationRow {
+    /// goods_id or category_id; null for uncategorized goods
+    pub group_key: Option<i32>,
+    pub group_label: String,
+    pub total_quantity: i64,
+    /// SUM(quantity * price), exact
+    pub total_value: rust_decimal::Decimal,
+}
+
+/// Stock value per group, highest value first, with the grand total
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ValuationReport {
+    pub group_by: ValuationGroupBy,
+    pub exclude_expired: bool,
+    pub rows: Vec<ValuationRow>,
+    pub total_quantity: i64,
+    pub total_value: rust_decimal::Decimal,
+}
+
+impl ValuationReport {
+    pub fn new(params: &ValuationParams, rows: Vec<ValuationRow>) -> Self {
+        Self {
+            group_by: params.group_by,
+            exclude_expired: params.exclude_expired,
+            total_quantity: rows.iter().map(|row| row.total_quantity).sum(),
+            total_value: rows.iter().map(|row| row.total_value).sum(),
+            rows,
+        }
+    }
+
+    /// The rows followed by a "Total" row, for the CSV export
+    pub fn into_csv_rows(self) -> Vec<ValuationRow> {
+        let mut rows = self.rows;
+        rows.push(ValuationRow {
+            group_key: None,
+            group_label: "Total".to_string(),
+            total_quantity: self.total_quantity,
+            total_value: self.total_value,
+        });
+        rows
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct InventorySearchParams {
     // Inventory specific search params
     pub item_id: Option<i32>,
+    pub item_ids: Option<Vec<i32>>,
     pub quantity: Option<i32>,
     pub min_quantity: Option<i32>,
     pub max_quantity: Option<i32>,
     pub expired_date: Option<DateTime<Utc>>,
     pub min_expired_date: Option<DateTime<Utc>>,
     pub max_expired_date: Option<DateTime<Utc>>,
+    pub expiring_within_days: Option<i32>,
+    pub expired: Option<bool>,
+    /// `Some(false)` selects items without an expiry date
+    pub has_expired_date: Option<bool>,
+    pub lot_number: Option<String>,
+    /// Case-insensitive substring of the lot number
+    pub lot_number_contains: Option<String>,
+    /// `Some(true)` selects items with quantity > 0, `Some(false)` those at zero
+    pub in_stock: Option<bool>,
+    pub include_reserved: bool,
+    pub min_updated_at: Option<DateTime<Utc>>,
+    pub max_updated_at: Option<DateTime<Utc>>,
     
     // Goods search params (inherited)
     pub goods_params: GoodsSearchParams,
+
+    pub pagination: Option<PaginationParams>,
+    /// Continue after the last row of a previous page instead of at `page`
+    pub cursor: Option<Keyset>,
+    /// Row cap for a search without pagination
+    pub limit: Option<u32>,
+    pub sort_by: Option<InventorySortColumn>,
+    pub sort_order: Option<SortOrder>,
+}
+
+impl Default for InventorySearchParams {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl InventorySearchParams {
     pub fn new() -> Self {
         Self {
             item_id: None,
+            item_ids: None,
             quantity: None,
             min_quantity: None,
             max_quantity: None,
             expired_date: None,
             min_expired_date: None,
             max_expired_date: None,
+            expiring_within_days: None,
+            expired: None,
+            has_expired_date: None,
+            lot_number: None,
+            lot_number_contains: None,
+            in_stock: None,
+            include_reserved: false,
+            min_updated_at: None,
+            max_updated_at: None,
             goods_params: GoodsSearchParams::new(),
+            pagination: None,
+            cursor: None,
+            limit: None,
+            sort_by: None,
+            sort_order: None,
+        }
+    }
+
+    /// LIMIT/OFFSET of the requested page, else the row cap; empty when neither is set
+    pub fn limit_clause(&self) -> String {
+        match (&self.pagination, self.limit) {
+            (Some(pagination), _) => pagination.to_sql(),
+            (None, Some(limit)) => format!(" LIMIT {}", limit),
+            (None, None) => String::new(),
         }
     }
 
     pub fn is_get_all(&self) -> bool {
         self.goods_params.is_get_all()
     }
-}
 
-#[derive(Clone)]
-pub struct InventoryTable {
-    pool: PgPool,
-}
+    /// Extra select column for `available_quantity`, empty unless reservations were requested
+    pub fn available_quantity_column(&self) -> String {
+        if !self.include_reserved {
+            return String::new();
+        }
 
-impl InventoryTable {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        format!(
+            r#",
+                (i.quantity - COALESCE((
+                    SELECT SUM(r.quantity) FROM reservations r
+                    WHERE r.item_id = i.item_id AND {}
+                ), 0))::int4 AS available_quantity"#,
+            ACTIVE_RESERVATION_CONDITION
+        )
     }
 
-    pub async fn search(&self, params: InventorySearchParams) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
-        // Handle get all case
-        if params.is_get_all() {
-            return self.get_all().await;
+    /// Build the ORDER BY expression, always ending with item_id so pagination stays stable.
+    /// Items without an expiry date sort last so they don't bury the urgent ones.
+    pub fn order_by_clause(&self) -> String {
+        if self.goods_params.fuzzy_threshold.is_some() {
+            return format!("{} DESC, i.item_id ASC", similarity_expression("g.goods_name"));
         }
 
-        // Build dynamic query with JOIN to goods table
-        let mut query = r#"
-            SELECT 
-                i.item_id, i.goods_id, i.quantity, i.expired_date,
-                g.material_code, g.goods_name, g.description, g.price, 
-                g.volumn_l, g.mass_g, g.mass_base, g.volumn_base
-            FROM inventory i
-            INNER JOIN goods g ON i.goods_id = g.goods_id
-            WHERE 1=1"#.to_string();
-        
-        let mut bind_count = 0;
-        let mut conditions = Vec::new();
+        let sort_order = self.sort_order.unwrap_or(SortOrder::Asc);
 
-        // Inventory specific conditions
-        if params.item_id.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND i.item_id = ${}", bind_count));
+        match self.sort_by {
+            Some(InventorySortColumn::ExpiredDate) => {
+                format!("i.expired_date {} NULLS LAST, i.item_id ASC", sort_order.as_sql())
+            }
+            Some(column) if column != InventorySortColumn::ItemId => {
+                format!("{} {}, i.item_id ASC", column.as_sql(), sort_order.as_sql())
+            }
+            _ => format!("i.item_id {}", sort_order.as_sql()),
         }
+    }
 
-        if params.quantity.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND i.quantity = ${}", bind_count));
+    /// `GoodsSearchParams::rank_by_similarity`, which also rules out the inventory sort and cursor
+    pub fn rank_by_similarity(&mut self, threshold: f32) -> Result<(), String> {
+        if self.sort_by.is_some() || self.sort_order.is_some() {
+            return Err("fuzzy=true orders by similarity and cannot be combined with sort_by or sort_order".to_string());
         }
-
-        if params.min_quantity.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND i.quantity >= ${}", bind_count));
+        if self.cursor.is_some() {
+            return Err("fuzzy=true cannot be combined with cursor; use page".to_string());
         }
 
-        if params.max_quantity.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND i.quantity <= ${}", bind_count));
-        }
+        self.goods_params.rank_by_similarity(threshold)
+    }
 
-        if params.expired_date.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND i.expired_date = ${}", bind_count));
-        }
+    /// The column sorted on ahead of item_id, if any
+    pub(crate) fn sort_column(&self) -> Option<InventorySortColumn> {
+        self.sort_by.filter(|column| *column != InventorySortColumn::ItemId)
+    }
 
-        if params.min_expired_date.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND i.expired_date >= ${}", bind_count));
+    /// Resolve a client's cursor; it must come from a page read with the same sort
+    pub fn keyset_from(&self, cursor: &PageCursor) -> Result<Keyset, String> {
+        if cursor.order != self.order_by_clause() {
+            return Err("cursor does not match sort_by and sort_order".to_string());
         }
 
-        if params.max_expired_date.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND i.expired_date <= ${}", bind_count));
-        }
+        let value = match (self.sort_column(), &cursor.value) {
+            (None, None) | (Some(InventorySortColumn::ExpiredDate), None) => None,
+            (Some(column), Some(value)) => Some(column.parse_cursor_value(value)?),
+            _ => return Err("Invalid cursor".to_string()),
+        };
 
-        // Goods related conditions
-        if params.goods_params.goods_id.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.goods_id = ${}", bind_count));
-        }
+        Ok(Keyset { value, id: cursor.id })
+    }
 
-        if params.goods_params.material_code.is_some() && !params.goods_params.is_get_all() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.material_code ILIKE ${}", bind_count));
+    /// Cursor for the page after `items`; unset unless the page is full and, when counted,
+    /// matches remain past it
+    pub fn next_cursor(&self, items: &[InventoryItemWithGoods], total_count: Option<u64>) -> Option<String> {
+        if self.goods_params.fuzzy_threshold.is_some() {
+            return None;
         }
-
-        if params.goods_params.goods_name.is_some() && !params.goods_params.is_get_all() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.goods_name ILIKE ${}", bind_count));
+        let pagination = self.pagination.as_ref()?;
+        if items.len() < pagination.per_page() as usize {
+            return None;
         }
-
-        if params.goods_params.price.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.price = ${}", bind_count));
+        if let Some(total_count) = total_count
+            && pagination.offset() + items.len() as u64 >= total_count
+        {
+            return None;
         }
 
-        if params.goods_params.volumn_l.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.volumn_l = ${}", bind_count));
-        }
+        let last = items.last()?;
+        let cursor = PageCursor {
+            order: self.order_by_clause(),
+            value: self.sort_column().and_then(|column| column.cursor_value(last)),
+            id: last.item_id,
+        };
+        Some(cursor.encode())
+    }
 
-        if params.goods_params.mass_g.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.mass_g = ${}", bind_count));
+    fn add_keyset_condition(&self, builder: &mut SearchQueryBuilder) {
+        if let Some(keyset) = &self.cursor {
+            let sort_order = self.sort_order.unwrap_or(SortOrder::Asc);
+            let sort_column = self.sort_column();
+            let nulls_last = sort_column == Some(InventorySortColumn::ExpiredDate);
+            builder.add_keyset_condition(sort_column.map(|column| column.as_sql()), "i.item_id", sort_order, nulls_last, keyset);
         }
+    }
+}
 
-        if params.goods_params.min_volumn_l.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.volumn_l >= ${}", bind_count));
-        }
+#[derive(Clone)]
+pub struct InventoryTable {
+    pool: PgPool,
+    /// Used by the read-only queries; may point at a replica
+    read_pool: PgPool,
+    /// Applied to search and export queries
+    statement_timeout: Option<Duration>,
+    /// Cached goods lookups, and invalidation when an update touches goods fields
+    goods_table: GoodsTable,
+    /// Write create/update/delete/adjust events to the webhook outbox
+    webhook_events: bool,
+    /// Let adjust and consume take quantities below zero
+    allow_negative_stock: bool,
+}
 
-        if params.goods_params.max_volumn_l.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.volumn_l <= ${}", bind_count));
-        }
+impl InventoryTable {
+    pub fn new(pool: PgPool, read_pool: PgPool, statement_timeout: Option<Duration>, goods_table: GoodsTable) -> Self {
+        Self { pool, read_pool, statement_timeout, goods_table, webhook_events: false, allow_negative_stock: false }
+    }
 
-        if params.goods_params.min_mass_g.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.mass_g >= ${}", bind_count));
-        }
+    pub fn with_webhook_events(mut self, enabled: bool) -> Self {
+        self.webhook_events = enabled;
+        self
+    }
 
-        if params.goods_params.max_mass_g.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.mass_g <= ${}", bind_count));
-        }
+    pub fn with_negative_stock(mut self, allowed: bool) -> Self {
+        self.allow_negative_stock = allowed;
+        self
+    }
 
-        if params.goods_params.min_price.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.price >= ${}", bind_count));
+    /// Queue webhook events on the change's transaction; a no-op when no webhooks are configured
+    async fn enqueue_events(&self, conn: &mut PgConnection, events: &[InventoryEvent]) -> Result<(), sqlx::Error> {
+        if !self.webhook_events {
+            return Ok(());
         }
+        WebhookOutboxTable::enqueue(conn, events).await
+    }
 
-        if params.goods_params.max_price.is_some() {
-            bind_count += 1;
-            conditions.push(format!(" AND g.price <= ${}", bind_count));
+    pub async fn search(&self, params: InventorySearchParams) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
+        self.search_on(&self.read_pool, params).await
+    }
+
+    /// Run `search` against a specific pool; writes look up their targets on the primary
+    async fn search_on(&self, pool: &PgPool, params: InventorySearchParams) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
+        // Handle get all case; a cursor page needs the keyset condition from `search_query`
+        if params.is_get_all() && params.cursor.is_none() {
+            return self.get_all(pool, &params).await;
         }
 
-        // Append conditions to query
-        query.push_str(&conditions.join(""));
-        query.push_str(" ORDER BY i.item_id ASC");
+        let (query, builder) = Self::search_query(&params);
 
-        // Build and execute query with proper parameter binding
-        let mut sql_query = sqlx::query(&query);
+        // Execute with values bound in the same order as their conditions
+        let mut tx = begin_with_statement_timeout(pool, self.statement_timeout).await?;
+        let mut items = builder.bind_values(sqlx::query_as::<_, InventoryItemWithGoods>(&query))
+            .fetch_all(&mut *tx)
+            .await?;
+        set_expiry_fields(&mut tx, &mut items).await?;
+        tx.commit().await?;
 
-        // Bind parameters in the same order as they were added
-        if let Some(item_id) = params.item_id {
-            sql_query = sql_query.bind(item_id);
-        }
+        Ok(items)
+    }
 
-        if let Some(quantity) = params.quantity {
-            sql_query = sql_query.bind(quantity);
-        }
+    /// Stream every inventory item matching `search` without buffering the full result.
+    /// The expiry fields use the server clock, read once when the stream starts.
+    pub fn search_stream(&self, params: &InventorySearchParams) -> impl Stream<Item = Result<InventoryItemWithGoods, sqlx::Error>> + Send + use<> {
+        let (query, builder) = Self::search_query(params);
+        let now = Utc::now();
+        stream_rows(self.read_pool.clone(), self.statement_timeout, query, builder).map(move |row| {
+            row.map(|mut item: InventoryItemWithGoods| {
+                item.set_expiry_fields(now);
+                item
+            })
+        })
+    }
 
-        if let Some(min_quantity) = params.min_quantity {
-            sql_query = sql_query.bind(min_quantity);
-        }
+    /// Build the search SQL and its bind values; the wildcard case matches every item
+    fn search_query(params: &InventorySearchParams) -> (String, SearchQueryBuilder) {
+        // Build dynamic query with JOIN to goods table
+        let mut builder = SearchQueryBuilder::new(format!(r#"
+            SELECT 
+                i.item_id, i.goods_id, i.quantity, i.expired_date, i.lot_number, i.created_at, i.updated_at,
+                g.material_code, g.goods_name, g.description, g.price, 
+                g.volumn_l, g.mass_g, g.mass_base, g.volumn_base{}{}
+            FROM inventory i
+            INNER JOIN goods g ON i.goods_id = g.goods_id
+            WHERE 1=1"#, params.available_quantity_column(), similarity_column("g.goods_name", &params.goods_params)));
+        Self::add_search_conditions(&mut builder, params);
+        params.add_keyset_condition(&mut builder);
 
-        if let Some(max_quantity) = params.max_quantity {
-            sql_query = sql_query.bind(max_quantity);
-        }
+        let mut query = builder.build(Some(&params.order_by_clause()));
 
-        if let Some(expired_date) = params.expired_date {
-            sql_query = sql_query.bind(expired_date);
-        }
+        query.push_str(&params.limit_clause());
 
-        if let Some(min_expired_date) = params.min_expired_date {
-            sql_query = sql_query.bind(min_expired_date);
-        }
+        (query, builder)
+    }
 
-        if let Some(max_expired_date) = params.max_expired_date {
-            sql_query = sql_query.bind(max_expired_date);
-        }
+    /// Count inventory items matching the same conditions used by `search`
+    pub async fn count(&self, params: &InventorySearchParams) -> Result<i64, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new(r#"
+            SELECT COUNT(*)
+            FROM inventory i
+            INNER JOIN goods g ON i.goods_id = g.goods_id
+            WHERE 1=1"#.to_string());
+        Self::add_search_conditions(&mut builder, params);
 
-        if let Some(goods_id) = params.goods_params.goods_id {
-            sql_query = sql_query.bind(goods_id);
-        }
+        let query = builder.build(None);
+        let mut tx = begin_with_statement_timeout(&self.read_pool, self.statement_timeout).await?;
+        let (count,) = builder.bind_values(sqlx::query_as::<_, (i64,)>(&query))
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
 
-        if let Some(material_code) = params.goods_params.material_code {
-            if material_code != "*" {
-                sql_query = sql_query.bind(crate::utils::string_utils::to_search_pattern(&material_code));
-            }
-        }
+        Ok(count)
+    }
 
-        if let Some(goods_name) = params.goods_params.goods_name {
-            if goods_name != "*" {
-                sql_query = sql_query.bind(crate::utils::string_utils::to_search_pattern(&goods_name));
-            }
-        }
+    /// Count matching items on a write connection, ahead of a filter-based update or delete
+    async fn count_matching(conn: &mut PgConnection, params: &InventorySearchParams) -> Result<i64, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new(r#"
+            SELECT COUNT(*)
+            FROM inventory i
+            INNER JOIN goods g ON i.goods_id = g.goods_id
+            WHERE 1=1"#.to_string());
+        Self::add_search_conditions(&mut builder, params);
 
-        if let Some(price) = params.goods_params.price {
-            sql_query = sql_query.bind(price);
-        }
+        let query = builder.build(None);
+        let (count,) = builder.bind_values(sqlx::query_as::<_, (i64,)>(&query))
+            .fetch_one(conn)
+            .await?;
 
-        if let Some(volumn_l) = params.goods_params.volumn_l {
-            sql_query = sql_query.bind(volumn_l);
-        }
+        Ok(count)
+    }
 
-        if let Some(mass_g) = params.goods_params.mass_g {
-            sql_query = sql_query.bind(mass_g);
-        }
+    /// Aggregate the inventory items matching the same conditions used by `search`
+    pub async fn stats(&self, params: &InventorySearchParams) -> Result<InventoryStats, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new(r#"
+            SELECT
+                COUNT(DISTINCT i.goods_id) AS total_goods,
+                COUNT(*) AS total_items,
+                COALESCE(SUM(i.quantity), 0)::int8 AS total_quantity,
+                COALESCE(SUM(i.quantity * g.price), 0) AS total_value,
+                COUNT(*) FILTER (WHERE i.expired_date < now()) AS expired_items,
+                COUNT(*) FILTER (
+                    WHERE i.expired_date BETWEEN now() AND now() + interval '30 days'
+                ) AS expiring_within_30_days
+            FROM inventory i
+            INNER JOIN goods g ON i.goods_id = g.goods_id
+            WHERE 1=1"#.to_string());
+        Self::add_search_conditions(&mut builder, params);
 
-        if let Some(min_volumn_l) = params.goods_params.min_volumn_l {
-            sql_query = sql_query.bind(min_volumn_l);
-        }
+        let query = builder.build(None);
+        builder.bind_values(sqlx::query_as::<_, InventoryStats>(&query))
+            .fetch_one(&self.read_pool)
+            .await
+    }
 
-        if let Some(max_volumn_l) = params.goods_params.max_volumn_l {
-            sql_query = sql_query.bind(max_volumn_l);
-        }
+    /// One row per goods over the inventory items matching the same conditions used by `search`
+    pub async fn summary(&self, params: &InventorySummaryParams) -> Result<Vec<InventorySummary>, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new(r#"
+            SELECT
+                g.goods_id, g.material_code, g.goods_name,
+                COUNT(i.item_id) AS lot_count,
+                COALESCE(SUM(i.quantity), 0)::int8 AS total_quantity,
+                MIN(i.expired_date) AS earliest_expired_date
+            FROM inventory i
+            INNER JOIN goods g ON i.goods_id = g.goods_id
+            WHERE 1=1"#.to_string());
+        Self::add_search_conditions(&mut builder, &params.search);
 
-        if let Some(min_mass_g) = params.goods_params.min_mass_g {
-            sql_query = sql_query.bind(min_mass_g);
-        }
+        let mut query = format!(
+            "{} GROUP BY g.goods_id, g.material_code, g.goods_name ORDER BY {}",
+            builder.build(None),
+            params.order_by_clause()
+        );
 
-        if let Some(max_mass_g) = params.goods_params.max_mass_g {
-            sql_query = sql_query.bind(max_mass_g);
+        if let Some(pagination) = &params.search.pagination {
+            query.push_str(&pagination.to_sql());
         }
 
-        if let Some(min_price) = params.goods_params.min_price {
-            sql_query = sql_query.bind(min_price);
-        }
+        builder.bind_values(sqlx::query_as::<_, InventorySummary>(&query))
+            .fetch_all(&self.read_pool)
+            .await
+    }
 
-        if let Some(max_price) = params.goods_params.max_price {
-            sql_query = sql_query.bind(max_price);
-        }
+    /// Stock value of the inventory items matching the same conditions used by `search`,
+    /// grouped by goods or category
+    pub async fn valuation(&self, params: &ValuationParams) -> Result<ValuationReport, sqlx::Error> {
+        let (group_key, group_label, join) = params.group_by.as_sql();
+        // Applied outside the search conditions, which the wildcard case skips
+        let not_expired = if params.exclude_expired {
+            " AND NOT (i.expired_date IS NOT NULL AND i.expired_date < now())"
+        } else {
+            ""
+        };
 
-        // Execute and map results
-        let rows = sql_query.fetch_all(&self.pool).await?;
-        
-        let mut results = Vec::new();
-        for row in rows {
-            results.push(InventoryItemWithGoods {
-                item_id: row.get("item_id"),
-                goods_id: row.get("goods_id"),
-                material_code: row.get("material_code"),
-                goods_name: row.get("goods_name"),
-                description: row.get("description"),
-                price: row.get("price"),
-                volumn_l: row.get("volumn_l"),
-                mass_g: row.get("mass_g"),
-                mass_base: row.get("mass_base"),
-                volumn_base: row.get("volumn_base"),
-                quantity: row.get("quantity"),
-                expired_date: row.get("expired_date"),
-            });
-        }
+        let mut builder = SearchQueryBuilder::new(format!(r#"
+            SELECT
+                {group_key} AS group_key,
+                {group_label} AS group_label,
+                COALESCE(SUM(i.quantity), 0)::int8 AS total_quantity,
+                COALESCE(SUM(i.quantity * g.price), 0) AS total_value
+            FROM inventory i
+            INNER JOIN goods g ON i.goods_id = g.goods_id{join}
+            WHERE 1=1{not_expired}"#));
+        Self::add_search_conditions(&mut builder, &params.search);
+
+        let query = format!(
+            "{} GROUP BY {}, {} ORDER BY total_value DESC, group_key ASC NULLS LAST",
+            builder.build(None),
+            group_key,
+            group_label
+        );
+
+        let mut tx = begin_with_statement_timeout(&self.read_pool, self.statement_timeout).await?;
+        let rows = builder.bind_values(sqlx::query_as::<_, ValuationRow>(&query))
+            .fetch_all(&mut *tx)
+            .await?;
+        tx.commit().await?;
 
-        Ok(results)
+        Ok(ValuationReport::new(params, rows))
     }
 
-    async fn get_all(&self) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
-        let query = r#"
-            SELECT 
-                i.item_id, i.goods_id, i.quantity, i.expired_date,
-                g.material_code, g.goods_name, g.description, g.price, 
-                g.volumn_l, g.mass_g, g.mass_base, g.volumn_base
+    /// Search one page of inventory and count the total matches in parallel. A cursor page is
+    /// not counted: it exists to avoid scanning past the rows already seen.
+    pub async fn search_paginated(&self, mut params: InventorySearchParams) -> Result<PaginatedResponse<InventoryItemWithGoods>, sqlx::Error> {
+        let pagination = params.pagination.clone().unwrap_or_default();
+        params.pagination = Some(pagination.clone());
+
+        let (items, total_count) = if params.cursor.is_some() {
+            (self.search(params.clone()).await?, None)
+        } else {
+            let (items, total_count) = tokio::try_join!(
+                self.search(params.clone()),
+                self.count(&params)
+            )?;
+            (items, Some(total_count as u64))
+        };
+
+        let next_cursor = params.next_cursor(&items, total_count);
+        Ok(PaginatedResponse::new(items, &pagination, total_count).with_next_cursor(next_cursor))
+    }
+
+    /// Add the WHERE conditions for the given search params to a builder.
+    /// The wildcard (get all) case adds only the active goods filter, matching `get_all`.
+    fn add_search_conditions(builder: &mut SearchQueryBuilder, params: &InventorySearchParams) {
+        // First, so the fuzzy term is bound as `$1`
+        if !params.is_get_all() {
+            add_fuzzy_condition(builder, "g.goods_name", &params.goods_params);
+        }
+        builder.add_optional_condition("g.is_active = ?", params.goods_params.active_filter());
+        if params.is_get_all() {
+            return;
+        }
+
+        // Inventory specific conditions
+        builder.add_optional_condition("i.item_id = ?", params.item_id);
+        builder.add_optional_condition("i.item_id = ANY(?)", params.item_ids.clone());
+        builder.add_optional_condition("i.quantity = ?", params.quantity);
+        builder.add_optional_condition("i.quantity >= ?", params.min_quantity);
+        builder.add_optional_condition("i.quantity <= ?", params.max_quantity);
+        builder.add_optional_condition("i.expired_date = ?", params.expired_date);
+        builder.add_optional_condition("i.expired_date >= ?", params.min_expired_date);
+        builder.add_optional_condition("i.expired_date <= ?", params.max_expired_date);
+        // Compared against the database clock; already expired items are excluded
+        builder.add_optional_condition(
+            "i.expired_date IS NOT NULL AND i.expired_date BETWEEN now() AND now() + make_interval(days => ?)",
+            params.expiring_within_days,
+        );
+        // Items with no expiry date never count as expired
+        builder.add_optional_condition(
+            "(i.expired_date IS NOT NULL AND i.expired_date < now()) = ?",
+            params.expired,
+        );
+        builder.add_optional_condition("(i.expired_date IS NOT NULL) = ?", params.has_expired_date);
+        builder.add_optional_condition("i.lot_number = ?", params.lot_number.clone());
+        builder.add_optional_condition(
+            "i.lot_number ILIKE ? ESCAPE '\\'",
+            params.lot_number_contains.as_deref().map(|term| to_search_pattern(term, MatchMode::Contains)),
+        );
+        builder.add_optional_condition("(i.quantity > 0) = ?", params.in_stock);
+        builder.add_optional_condition("i.updated_at >= ?", params.min_updated_at);
+        builder.add_optional_condition("i.updated_at <= ?", params.max_updated_at);
+
+        // Goods related conditions
+        let goods_params = &params.goods_params;
+        builder.add_optional_condition("g.goods_id = ?", goods_params.goods_id);
+        builder.add_optional_condition("g.goods_id = ANY(?)", goods_params.goods_ids.clone());
+        builder.add_optional_match("g.material_code", goods_params.material_code.as_deref(), goods_params.match_mode);
+        if goods_params.fuzzy_threshold.is_none() {
+            builder.add_optional_match("g.goods_name", goods_params.goods_name.as_deref(), goods_params.match_mode);
+        }
+        builder.add_optional_condition(
+            "EXISTS (SELECT 1 FROM unnest(g.description) d WHERE d ILIKE ? ESCAPE '\\')",
+            goods_params.description_contains.as_deref().map(|term| to_search_pattern(term, MatchMode::Contains)),
+        );
+        builder.add_optional_condition("g.barcode = ?", goods_params.barcode.clone());
+        builder.add_optional_condition("? = ANY(g.tags)", goods_params.tag.clone());
+        builder.add_optional_condition("g.tags && ?", goods_params.tags_any.clone());
+        builder.add_optional_condition("g.tags @> ?", goods_params.tags_all.clone());
+        builder.add_optional_condition("g.price = ?", goods_params.price);
+        builder.add_optional_condition("g.volumn_l = ?", goods_params.volumn_l);
+        builder.add_optional_condition("g.mass_g = ?", goods_params.mass_g);
+        builder.add_optional_condition("g.volumn_l >= ?", goods_params.min_volumn_l);
+        builder.add_optional_condition("g.volumn_l <= ?", goods_params.max_volumn_l);
+        builder.add_optional_condition("g.mass_g >= ?", goods_params.min_mass_g);
+        builder.add_optional_condition("g.mass_g <= ?", goods_params.max_mass_g);
+        builder.add_optional_condition("g.price >= ?", goods_params.min_price);
+        builder.add_optional_condition("g.price <= ?", goods_params.max_price);
+        add_category_conditions(builder, "g.category_id", goods_params);
+    }
+
+    async fn get_all(&self, pool: &PgPool, params: &InventorySearchParams) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
+        let mut query = format!(r#"
+            SELECT 
+                i.item_id, i.goods_id, i.quantity, i.expired_date, i.lot_number, i.created_at, i.updated_at,
+                g.material_code, g.goods_name, g.description, g.price, 
+                g.volumn_l, g.mass_g, g.mass_base, g.volumn_base{}
             FROM inventory i
-            INNER JOIN goods g ON i.goods_id = g.goods_id
-            ORDER BY i.item_id ASC"#;
-
-        let rows = sqlx::query(query).fetch_all(&self.pool).await?;
-        
-        let mut results = Vec::new();
-        for row in rows {
-            results.push(InventoryItemWithGoods {
-                item_id: row.get("item_id"),
-                goods_id: row.get("goods_id"),
-                material_code: row.get("material_code"),
-                goods_name: row.get("goods_name"),
-                description: row.get("description"),
-                price: row.get("price"),
-                volumn_l: row.get("volumn_l"),
-                mass_g: row.get("mass_g"),
-                mass_base: row.get("mass_base"),
-                volumn_base: row.get("volumn_base"),
-                quantity: row.get("quantity"),
-                expired_date: row.get("expired_date"),
-            });
+            INNER JOIN goods g ON i.goods_id = g.goods_id{}
+            ORDER BY {}"#,
+            params.available_quantity_column(),
+            if params.goods_params.include_inactive { "" } else { " WHERE g.is_active" },
+            params.order_by_clause());
+
+        query.push_str(&params.limit_clause());
+
+        let mut tx = begin_with_statement_timeout(pool, self.statement_timeout).await?;
+        let mut items = sqlx::query_as::<_, InventoryItemWithGoods>(&query)
+            .fetch_all(&mut *tx)
+            .await?;
+        set_expiry_fields(&mut tx, &mut items).await?;
+        tx.commit().await?;
+
+        Ok(items)
+    }
+
+    /// Record inventory, rejecting archived goods unless `allow_inactive`
+    pub async fn insert(
+        &self,
+        request: CreateInventoryRequest,
+        on_duplicate: InventoryDuplicateMode,
+        allow_inactive: bool,
+    ) -> Result<(InventoryItemWithGoods, InventoryInsertOutcome), TableError> {
+        let mut tx = self.pool.begin().await?;
+        let (goods_id, existing_good) = self.resolve_goods_tx(&mut tx, &request, allow_inactive).await?;
+        let inserted = self.insert_for_goods_tx(&mut tx, goods_id, request, on_duplicate).await?;
+        AuditLogTable::record_tx(&mut tx, "inventory", &[inserted.0.item_id]).await?;
+        tx.commit().await?;
+
+        // The good is known to be committed now, so it may fill the cache
+        if let Some(good) = existing_good {
+            self.goods_table.cache().insert(&good);
         }
 
-        Ok(results)
+        Ok(inserted)
     }
 
-    pub async fn insert(&self, request: CreateInventoryRequest) -> Result<(InventoryItemWithGoods, bool), sqlx::Error> {
-        // Determine goods_id to use
-        let goods_id = if let Some(id) = request.goods_id {
-            // Use utility function to verify goods exists
-            let exists = crate::utils::database::exists_by_id(&self.pool, "goods", "goods_id", id).await?;
-            
-            if !exists {
-                return Err(sqlx::Error::RowNotFound);
+    /// `insert` on the caller's transaction: resolving or creating the good, the duplicate check
+    /// and the insert or merge all see and take part in the same transaction
+    pub async fn insert_tx(
+        &self,
+        conn: &mut PgConnection,
+        request: CreateInventoryRequest,
+        on_duplicate: InventoryDuplicateMode,
+        allow_inactive: bool,
+    ) -> Result<(InventoryItemWithGoods, InventoryInsertOutcome), TableError> {
+        let (goods_id, _) = self.resolve_goods_tx(conn, &request, allow_inactive).await?;
+        self.insert_for_goods_tx(conn, goods_id, request, on_duplicate).await
+    }
+
+    /// The goods_id an insert refers to, creating the good when the request carries its details.
+    /// An existing good is returned as well so the caller can cache it after committing; an
+    /// archived one is rejected unless `allow_inactive`.
+    async fn resolve_goods_tx(&self, conn: &mut PgConnection, request: &CreateInventoryRequest, allow_inactive: bool) -> Result<(i32, Option<Good>), TableError> {
+        if let Some(id) = request.goods_id {
+            // Verify goods exists
+            match self.goods_table.get_by_id_tx(conn, id).await? {
+                Some(good) if !good.is_active && !allow_inactive => Err(TableError::InactiveGoods { goods_id: id }),
+                Some(good) => Ok((id, Some(good))),
+                None => Err(sqlx::Error::RowNotFound.into()),
             }
-            id
         } else if let Some(material_code) = &request.material_code {
-            // Use utility function to find goods by material_code
-            let goods_id = crate::utils::database::get_id_by_string(
-                &self.pool, 
-                "goods", 
-                "goods_id", 
-                "material_code", 
-                material_code
-            ).await?;
-            
-            if let Some(id) = goods_id {
-                id
-            } else {
-                return Err(sqlx::Error::RowNotFound);
+            // Find goods by material_code
+            match self.goods_table.get_by_material_code_tx(conn, material_code).await? {
+                Some(good) if !good.is_active && !allow_inactive => Err(TableError::InactiveGoods { goods_id: good.goods_id }),
+                Some(good) => Ok((good.goods_id, Some(good))),
+                None => Err(sqlx::Error::RowNotFound.into()),
             }
-        } else if request.goods_name.is_some() && request.price.is_some() && 
-                  request.volumn_l.is_some() && request.mass_g.is_some() {
+        } else if let (Some(goods_name), Some(price), Some(volumn_l), Some(mass_g)) =
+            (&request.goods_name, request.price, request.volumn_l, request.mass_g) {
             // Create new goods if all required fields are provided
             let material_code = request.material_code.clone()
                 .ok_or_else(|| sqlx::Error::ColumnNotFound("material_code is required for new goods".into()))?;
@@ -409,203 +1162,1466 @@ impl InventoryTable {
                 "#
             )
             .bind(&material_code)
-            .bind(request.goods_name.as_ref().unwrap())
+            .bind(goods_name)
             .bind(&request.description)
-            .bind(request.price.unwrap())
-            .bind(request.volumn_l.unwrap())
-            .bind(request.mass_g.unwrap())
+            .bind(price)
+            .bind(volumn_l)
+            .bind(mass_g)
             .bind(request.mass_base.unwrap_or(0))
             .bind(request.volumn_base.unwrap_or(0))
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *conn)
             .await?;
             
-            new_goods.0
+            Ok((new_goods.0, None))
         } else {
-            return Err(sqlx::Error::ColumnNotFound(
+            Err(sqlx::Error::ColumnNotFound(
                 "Either goods_id, material_code, or complete goods information is required".into()
-            ));
+            ).into())
+        }
+    }
+
+    /// Store the requested quantity for a resolved good and load the resulting item
+    async fn insert_for_goods_tx(
+        &self,
+        conn: &mut PgConnection,
+        goods_id: i32,
+        request: CreateInventoryRequest,
+        on_duplicate: InventoryDuplicateMode,
+    ) -> Result<(InventoryItemWithGoods, InventoryInsertOutcome), TableError> {
+        // Log warning if creating inventory that's already expired
+        if let Some(expired_date) = &request.expired_date
+            && crate::utils::datetime::is_expired(expired_date) {
+            tracing::warn!("Creating inventory item that's already expired for goods_id: {}", goods_id);
+        }
+
+        let stock = StockToStore {
+            goods_id,
+            quantity: request.quantity,
+            expired_date: request.expired_date,
+            lot_number: request.lot_number.as_deref(),
         };
+        let (item_id, outcome) = self.store_quantity(conn, stock, on_duplicate, None).await?;
+
+        // Get the full inventory item with goods details
+        let item = Self::get_by_item_id_tx(conn, item_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        Ok((item, outcome))
+    }
+
+    /// Store `stock` on the caller's transaction, applying `on_duplicate` when an item with the
+    /// same goods, expiry and lot already exists. Movements are recorded with `reason`, or
+    /// "created" / "merged" when it is `None`, and the created or merged item is queued as a
+    /// webhook event.
+    pub(crate) async fn store_quantity(
+        &self,
+        conn: &mut PgConnection,
+        stock: StockToStore<'_>,
+        on_duplicate: InventoryDuplicateMode,
+        reason: Option<&str>,
+    ) -> Result<(i32, InventoryInsertOutcome), TableError> {
+        let StockToStore { goods_id, quantity, expired_date, lot_number } = stock;
+
+        // Check if inventory item with same goods_id, expired_date and lot_number already exists
+        let existing_item = sqlx::query_as::<_, InventoryItem>(
+            r#"
+            SELECT item_id, goods_id, quantity, expired_date, lot_number, created_at, updated_at
+            FROM inventory
+            WHERE goods_id = $1 AND expired_date IS NOT DISTINCT FROM $2 AND lot_number IS NOT DISTINCT FROM $3
+            ORDER BY item_id ASC LIMIT 1
+            "#
+        )
+        .bind(goods_id)
+        .bind(expired_date)
+        .bind(lot_number)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(existing) = existing_item {
+            match on_duplicate {
+                InventoryDuplicateMode::ReturnExisting => {
+                    return Ok((existing.item_id, InventoryInsertOutcome::Existing));
+                }
+                InventoryDuplicateMode::Error => {
+                    return Err(TableError::DuplicateInventory { item_id: existing.item_id });
+                }
+                InventoryDuplicateMode::AddQuantity => {
+                    // Increment in place so concurrent receipts are never lost
+                    let merged = sqlx::query_scalar::<_, i32>(
+                        "UPDATE inventory SET quantity = quantity + $2, updated_at = now() WHERE item_id = $1 RETURNING quantity"
+                    )
+                    .bind(existing.item_id)
+                    .bind(quantity)
+                    .fetch_optional(&mut *conn)
+                    .await?;
+
+                    // The item was deleted since the lookup; fall through and create a new one
+                    if let Some(resulting_quantity) = merged {
+                        if quantity != 0 {
+                            StockMovementsTable::record(conn, &[NewStockMovement {
+                                item_id: existing.item_id,
+                                delta: quantity,
+                                reason: reason.unwrap_or("merged").to_string(),
+                                resulting_quantity,
+                            }]).await?;
+                        }
+                        self.enqueue_events(conn, &[InventoryEvent::new(
+                            InventoryEventType::Updated, existing.item_id, goods_id, Some(resulting_quantity - quantity), Some(resulting_quantity),
+                        )]).await?;
+
+                        return Ok((existing.item_id, InventoryInsertOutcome::Merged));
+                    }
+                }
+            }
+        }
+
+        // Insert new inventory item if no duplicate found, recording its opening movement
+        let new_item = sqlx::query_as::<_, InventoryItem>(
+            r#"
+            INSERT INTO inventory (goods_id, quantity, expired_date, lot_number)
+            VALUES ($1, $2, $3, $4)
+            RETURNING item_id, goods_id, quantity, expired_date, lot_number, created_at, updated_at
+            "#
+        )
+        .bind(goods_id)
+        .bind(quantity)
+        .bind(expired_date)
+        .bind(lot_number)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        StockMovementsTable::record(conn, &[NewStockMovement {
+            item_id: new_item.item_id,
+            delta: new_item.quantity,
+            reason: reason.unwrap_or("created").to_string(),
+            resulting_quantity: new_item.quantity,
+        }]).await?;
+        self.enqueue_events(conn, &[InventoryEvent::new(
+            InventoryEventType::Created, new_item.item_id, goods_id, None, Some(new_item.quantity),
+        )]).await?;
+
+        Ok((new_item.item_id, InventoryInsertOutcome::Created))
+    }
 
-        // Check if inventory item with same goods_id and expired_date already exists
-        let existing_item = if let Some(expired_date) = request.expired_date {
-            // Log warning if creating inventory that's already expired
-            if crate::utils::datetime::is_expired(&expired_date) {
+    /// Insert validated entries (paired with their payload index) in one transaction.
+    /// Goods are resolved in a single query; entries matching an existing item (same goods_id,
+    /// expired_date and lot_number) or an earlier entry in the payload are not inserted again.
+    /// With `abort_on_failure` any unresolved entry rolls back the whole batch.
+    pub async fn insert_batch(
+        &self,
+        entries: Vec<(usize, CreateInventoryRequest)>,
+        mut failures: Vec<BatchItemError>,
+        abort_on_failure: bool,
+        allow_inactive: bool,
+    ) -> Result<InventoryBatchResult, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Resolve every goods_id / material_code reference at once
+        let goods_ids: Vec<i32> = entries.iter().filter_map(|(_, request)| request.goods_id).collect();
+        let material_codes: Vec<String> = entries.iter().filter_map(|(_, request)| request.material_code.clone()).collect();
+
+        let known_goods = sqlx::query_as::<_, (i32, String, bool)>(
+            "SELECT goods_id, material_code, is_active FROM goods WHERE goods_id = ANY($1) OR material_code = ANY($2)"
+        )
+        .bind(&goods_ids)
+        .bind(&material_codes)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // Pair each entry with its goods_id, dropping unresolved entries and in-payload duplicates
+        let mut resolved: Vec<(usize, i32, CreateInventoryRequest)> = Vec::new();
+        for (index, request) in entries {
+            let goods = if let Some(id) = request.goods_id {
+                known_goods.iter().find(|(goods_id, _, _)| *goods_id == id)
+            } else {
+                known_goods.iter().find(|(_, code, _)| Some(code) == request.material_code.as_ref())
+            };
+
+            let Some(&(goods_id, _, is_active)) = goods else {
+                let reference = match (request.goods_id, &request.material_code) {
+                    (Some(id), _) => format!("goods_id {}", id),
+                    (None, Some(code)) => format!("material_code '{}'", code),
+                    (None, None) => "entry".to_string(),
+                };
+                failures.push(BatchItemError::not_found(index, format!("Goods not found for {}", reference)));
+                continue;
+            };
+
+            if !is_active && !allow_inactive {
+                failures.push(BatchItemError::conflict(index, TableError::InactiveGoods { goods_id }.to_string()));
+                continue;
+            }
+
+            if let Some((first_index, _, _)) = resolved.iter()
+                .find(|(_, id, other)| *id == goods_id && other.expired_date == request.expired_date && other.lot_number == request.lot_number) {
+                failures.push(BatchItemError::conflict(
+                    index,
+                    format!("Duplicate of index {} (same goods, expired_date and lot_number)", first_index),
+                ));
+                continue;
+            }
+
+            if let Some(expired_date) = request.expired_date
+                && crate::utils::datetime::is_expired(&expired_date) {
                 tracing::warn!("Creating inventory item that's already expired for goods_id: {}", goods_id);
             }
-            
-            sqlx::query_as::<_, InventoryItem>(
-                "SELECT item_id, goods_id, quantity, expired_date FROM inventory WHERE goods_id = $1 AND expired_date = $2"
-            )
-            .bind(goods_id)
-            .bind(expired_date)
-            .fetch_optional(&self.pool)
-            .await?
-        } else {
-            // Check for items with NULL expired_date
-            sqlx::query_as::<_, InventoryItem>(
-                "SELECT item_id, goods_id, quantity, expired_date FROM inventory WHERE goods_id = $1 AND expired_date IS NULL"
+
+            resolved.push((index, goods_id, request));
+        }
+
+        failures.sort_by_key(|failure| failure.index);
+
+        if abort_on_failure && !failures.is_empty() {
+            tx.rollback().await?;
+            return Err(TableError::InvalidBatch { errors: failures });
+        }
+
+        let mut result = InventoryBatchResult {
+            created: Vec::new(),
+            matched_existing: Vec::new(),
+            failures,
+        };
+
+        if resolved.is_empty() {
+            tx.commit().await?;
+            return Ok(result);
+        }
+
+        let keys_goods: Vec<i32> = resolved.iter().map(|(_, goods_id, _)| *goods_id).collect();
+        let keys_expiry: Vec<Option<DateTime<Utc>>> = resolved.iter().map(|(_, _, request)| request.expired_date).collect();
+        let keys_lot: Vec<Option<String>> = resolved.iter().map(|(_, _, request)| request.lot_number.clone()).collect();
+        let keys_index: Vec<i64> = resolved.iter().map(|(index, _, _)| *index as i64).collect();
+
+        // Match entries against existing items with the same goods_id, expired_date and lot_number
+        let existing = sqlx::query_as::<_, (i64, i32)>(
+            r#"
+            SELECT DISTINCT ON (k.idx) k.idx, i.item_id
+            FROM UNNEST($1::int4[], $2::timestamptz[], $3::text[], $4::int8[]) AS k(goods_id, expired_date, lot_number, idx)
+            INNER JOIN inventory i
+                ON i.goods_id = k.goods_id
+                AND i.expired_date IS NOT DISTINCT FROM k.expired_date
+                AND i.lot_number IS NOT DISTINCT FROM k.lot_number
+            ORDER BY k.idx, i.item_id
+            "#
+        )
+        .bind(&keys_goods)
+        .bind(&keys_expiry)
+        .bind(&keys_lot)
+        .bind(&keys_index)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let (matched, to_insert): (Vec<_>, Vec<_>) = resolved
+            .into_iter()
+            .partition(|(index, _, _)| existing.iter().any(|(idx, _)| *idx == *index as i64));
+
+        // Insert the remaining entries with one multi-row statement
+        let mut inserted: Vec<(usize, i32)> = Vec::new();
+        if !to_insert.is_empty() {
+            let insert_goods: Vec<i32> = to_insert.iter().map(|(_, goods_id, _)| *goods_id).collect();
+            let insert_quantity: Vec<i32> = to_insert.iter().map(|(_, _, request)| request.quantity).collect();
+            let insert_expiry: Vec<Option<DateTime<Utc>>> = to_insert.iter().map(|(_, _, request)| request.expired_date).collect();
+            let insert_lot: Vec<Option<String>> = to_insert.iter().map(|(_, _, request)| request.lot_number.clone()).collect();
+
+            let new_rows = sqlx::query_as::<_, InventoryItem>(
+                r#"
+                INSERT INTO inventory (goods_id, quantity, expired_date, lot_number)
+                SELECT * FROM UNNEST($1::int4[], $2::int4[], $3::timestamptz[], $4::text[])
+                RETURNING item_id, goods_id, quantity, expired_date, lot_number, created_at, updated_at
+                "#
             )
-            .bind(goods_id)
-            .fetch_optional(&self.pool)
-            .await?
+            .bind(&insert_goods)
+            .bind(&insert_quantity)
+            .bind(&insert_expiry)
+            .bind(&insert_lot)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            // (goods_id, expired_date, lot_number) is unique among inserted entries, so map rows back by it
+            inserted = to_insert.iter()
+                .filter_map(|(index, goods_id, request)| {
+                    new_rows.iter()
+                        .find(|row| row.goods_id == *goods_id && row.expired_date == request.expired_date && row.lot_number == request.lot_number)
+                        .map(|row| (*index, row.item_id))
+                })
+                .collect();
+
+            let movements: Vec<NewStockMovement> = new_rows.iter()
+                .map(|row| NewStockMovement {
+                    item_id: row.item_id,
+                    delta: row.quantity,
+                    reason: "created".to_string(),
+                    resulting_quantity: row.quantity,
+                })
+                .collect();
+            StockMovementsTable::record(&mut tx, &movements).await?;
+
+            let events: Vec<InventoryEvent> = new_rows.iter()
+                .map(|row| InventoryEvent::new(InventoryEventType::Created, row.item_id, row.goods_id, None, Some(row.quantity)))
+                .collect();
+            self.enqueue_events(&mut tx, &events).await?;
+        }
+
+        let matched: Vec<(usize, i32)> = matched.iter()
+            .filter_map(|(index, _, _)| {
+                existing.iter()
+                    .find(|(idx, _)| *idx == *index as i64)
+                    .map(|(_, item_id)| (*index, *item_id))
+            })
+            .collect();
+
+        // Load goods details for every created and matched item
+        let item_ids: Vec<i32> = inserted.iter().chain(matched.iter()).map(|(_, item_id)| *item_id).collect();
+        let mut items = sqlx::query_as::<_, InventoryItemWithGoods>(
+            r#"
+            SELECT 
+                i.item_id, i.goods_id, i.quantity, i.expired_date, i.lot_number, i.created_at, i.updated_at,
+                g.material_code, g.goods_name, g.description, g.price, 
+                g.volumn_l, g.mass_g, g.mass_base, g.volumn_base
+            FROM inventory i
+            INNER JOIN goods g ON i.goods_id = g.goods_id
+            WHERE i.item_id = ANY($1)"#
+        )
+        .bind(&item_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+        set_expiry_fields(&mut tx, &mut items).await?;
+        AuditLogTable::record_tx(&mut tx, "inventory", &item_ids).await?;
+
+        tx.commit().await?;
+
+        let attach = |pairs: &[(usize, i32)]| -> Vec<BatchInventoryItem> {
+            pairs.iter()
+                .filter_map(|(index, item_id)| {
+                    items.iter()
+                        .find(|item| item.item_id == *item_id)
+                        .map(|item| BatchInventoryItem { index: *index, item: item.clone() })
+                })
+                .collect()
         };
 
-        if let Some(existing) = existing_item {
-            // Return the existing inventory item with goods details and flag as existing
-            let existing_with_goods = self.get_by_item_id(existing.item_id).await?;
-            return Ok((existing_with_goods, false)); // false = not newly created
+        result.created = attach(&inserted);
+        result.matched_existing = attach(&matched);
+        Ok(result)
+    }
+
+    /// Apply a relative quantity change atomically, refusing to take the quantity below zero
+    pub async fn adjust(&self, item_id: i32, delta: i32, reason: &str) -> Result<InventoryItemWithGoods, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut adjusted = sqlx::query_as::<_, InventoryItemWithGoods>(
+            r#"
+            WITH adjusted AS (
+                UPDATE inventory
+                SET quantity = quantity + $1, updated_at = now()
+                WHERE item_id = $2 AND ($3 OR quantity + $1 >= 0)
+                RETURNING item_id, goods_id, quantity, expired_date, lot_number, created_at, updated_at
+            )
+            SELECT 
+                a.item_id, a.goods_id, a.quantity, a.expired_date, a.lot_number, a.created_at, a.updated_at,
+                g.material_code, g.goods_name, g.description, g.price, 
+                g.volumn_l, g.mass_g, g.mass_base, g.volumn_base
+            FROM adjusted a
+            INNER JOIN goods g ON a.goods_id = g.goods_id
+            "#
+        )
+        .bind(delta)
+        .bind(item_id)
+        .bind(self.allow_negative_stock)
+        .fetch_optional(&mut *tx)
+        .await?;
+        set_expiry_fields(&mut tx, adjusted.as_mut_slice()).await?;
+
+        if let Some(item) = adjusted {
+            StockMovementsTable::record(&mut tx, &[NewStockMovement {
+                item_id,
+                delta,
+                reason: reason.to_string(),
+                resulting_quantity: item.quantity,
+            }]).await?;
+            self.enqueue_events(&mut tx, &[InventoryEvent::new(
+                InventoryEventType::Adjusted, item_id, item.goods_id, Some(item.quantity - delta), Some(item.quantity),
+            )]).await?;
+            AuditLogTable::record_tx(&mut tx, "inventory", &[item_id]).await?;
+
+            tx.commit().await?;
+            return Ok(item);
         }
 
-        // Insert new inventory item if no duplicate found
-        let new_item = sqlx::query_as::<_, InventoryItem>(
+        // Nothing updated: either the item doesn't exist or the guard refused the change
+        let current = sqlx::query_as::<_, (i32,)>("SELECT quantity FROM inventory WHERE item_id = $1")
+            .bind(item_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        match current {
+            Some((current_quantity,)) => Err(TableError::InsufficientStock { item_id, current_quantity, delta }),
+            None => Err(TableError::Database(sqlx::Error::RowNotFound)),
+        }
+    }
+
+    /// Deduct stock for one good across its inventory items, earliest expiry first (no expiry last).
+    /// Rows are locked for the duration of the transaction; if the total available is short,
+    /// nothing is changed, unless negative stock is allowed (see `plan_consumption`).
+    pub async fn consume(&self, request: &ConsumeInventoryRequest) -> Result<ConsumeResult, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Resolve the good being consumed
+        let goods_id = match (request.goods_id, &request.material_code) {
+            (Some(goods_id), _) => sqlx::query_as::<_, (i32,)>("SELECT goods_id FROM goods WHERE goods_id = $1")
+                .bind(goods_id)
+                .fetch_optional(&mut *tx)
+                .await?,
+            (None, Some(material_code)) => sqlx::query_as::<_, (i32,)>("SELECT goods_id FROM goods WHERE material_code = $1")
+                .bind(material_code)
+                .fetch_optional(&mut *tx)
+                .await?,
+            (None, None) => None,
+        }
+        .map(|(goods_id,)| goods_id)
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+        let stock = sqlx::query_as::<_, (i32, i32)>(
             r#"
-            INSERT INTO inventory (goods_id, quantity, expired_date)
-            VALUES ($1, $2, $3)
-            RETURNING item_id, goods_id, quantity, expired_date
+            SELECT item_id, quantity
+            FROM inventory
+            WHERE goods_id = $1 AND (quantity > 0 OR $2)
+            ORDER BY expired_date ASC NULLS LAST, item_id ASC
+            FOR UPDATE
             "#
         )
         .bind(goods_id)
-        .bind(request.quantity)
-        .bind(request.expired_date)
-        .fetch_one(&self.pool)
+        .bind(self.allow_negative_stock)
+        .fetch_all(&mut *tx)
         .await?;
 
-        // Get the full inventory item with goods details
-        let new_with_goods = self.get_by_item_id(new_item.item_id).await?;
-        Ok((new_with_goods, true)) // true = newly created
+        let delete_empty = request.delete_empty.unwrap_or(false);
+        let items = match plan_consumption(&stock, request.quantity, delete_empty, self.allow_negative_stock) {
+            Ok(items) => items,
+            Err(available) => {
+                tx.rollback().await?;
+                return Err(TableError::InsufficientGoodsStock { goods_id, requested: request.quantity, available });
+            }
+        };
+
+        let item_ids: Vec<i32> = items.iter().map(|item| item.item_id).collect();
+        let taken: Vec<i32> = items.iter().map(|item| item.taken).collect();
+
+        sqlx::query(
+            r#"
+            UPDATE inventory i
+            SET quantity = i.quantity - t.taken, updated_at = now()
+            FROM UNNEST($1::int4[], $2::int4[]) AS t(item_id, taken)
+            WHERE i.item_id = t.item_id
+            "#
+        )
+        .bind(&item_ids)
+        .bind(&taken)
+        .execute(&mut *tx)
+        .await?;
+
+        if delete_empty {
+            let emptied: Vec<i32> = items.iter().filter(|item| item.deleted).map(|item| item.item_id).collect();
+            sqlx::query("DELETE FROM inventory WHERE item_id = ANY($1)")
+                .bind(&emptied)
+                .execute(&mut *tx)
+                .await?;
+            SyncTable::record_deleted(&mut tx, SyncEntity::Inventory, &emptied).await?;
+        }
+
+        let movements: Vec<NewStockMovement> = items.iter()
+            .map(|item| NewStockMovement {
+                item_id: item.item_id,
+                delta: -item.taken,
+                reason: "consumed".to_string(),
+                resulting_quantity: item.remaining_quantity,
+            })
+            .collect();
+        StockMovementsTable::record(&mut tx, &movements).await?;
+
+        let events: Vec<InventoryEvent> = items.iter()
+            .map(|item| {
+                let (event_type, new_quantity) = if item.deleted {
+                    (InventoryEventType::Deleted, None)
+                } else {
+                    (InventoryEventType::Adjusted, Some(item.remaining_quantity))
+                };
+                InventoryEvent::new(event_type, item.item_id, goods_id, Some(item.remaining_quantity + item.taken), new_quantity)
+            })
+            .collect();
+        self.enqueue_events(&mut tx, &events).await?;
+
+        let item_ids: Vec<i32> = items.iter().map(|item| item.item_id).collect();
+        AuditLogTable::record_tx(&mut tx, "inventory", &item_ids).await?;
+
+        tx.commit().await?;
+
+        Ok(ConsumeResult {
+            goods_id,
+            quantity: request.quantity,
+            items,
+        })
+    }
+
+    pub async fn get_by_item_id(&self, item_id: i32) -> Result<Option<InventoryItemWithGoods>, sqlx::Error> {
+        Self::get_by_item_id_tx(&mut *self.pool.acquire().await?, item_id).await
     }
 
-    pub async fn get_by_item_id(&self, item_id: i32) -> Result<InventoryItemWithGoods, sqlx::Error> {
+    /// `get_by_item_id` on the caller's transaction
+    pub async fn get_by_item_id_tx(conn: &mut PgConnection, item_id: i32) -> Result<Option<InventoryItemWithGoods>, sqlx::Error> {
         let query = r#"
             SELECT 
-                i.item_id, i.goods_id, i.quantity, i.expired_date,
+                i.item_id, i.goods_id, i.quantity, i.expired_date, i.lot_number, i.created_at, i.updated_at,
                 g.material_code, g.goods_name, g.description, g.price, 
                 g.volumn_l, g.mass_g, g.mass_base, g.volumn_base
             FROM inventory i
             INNER JOIN goods g ON i.goods_id = g.goods_id
             WHERE i.item_id = $1"#;
 
-        let row = sqlx::query(query)
+        let mut item = sqlx::query_as::<_, InventoryItemWithGoods>(query)
             .bind(item_id)
-            .fetch_one(&self.pool)
+            .fetch_optional(&mut *conn)
             .await?;
+        set_expiry_fields(conn, item.as_mut_slice()).await?;
 
-        Ok(InventoryItemWithGoods {
-            item_id: row.get("item_id"),
-            goods_id: row.get("goods_id"),
-            material_code: row.get("material_code"),
-            goods_name: row.get("goods_name"),
-            description: row.get("description"),
-            price: row.get("price"),
-            volumn_l: row.get("volumn_l"),
-            mass_g: row.get("mass_g"),
-            mass_base: row.get("mass_base"),
-            volumn_base: row.get("volumn_base"),
-            quantity: row.get("quantity"),
-            expired_date: row.get("expired_date"),
-        })
+        Ok(item)
+    }
+
+    /// Load inventory items by id, in change-feed order
+    pub async fn get_by_item_ids(&self, item_ids: &[i32]) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
+        let query = r#"
+            SELECT 
+                i.item_id, i.goods_id, i.quantity, i.expired_date, i.lot_number, i.created_at, i.updated_at,
+                g.material_code, g.goods_name, g.description, g.price, 
+                g.volumn_l, g.mass_g, g.mass_base, g.volumn_base
+            FROM inventory i
+            INNER JOIN goods g ON i.goods_id = g.goods_id
+            WHERE i.item_id = ANY($1)
+            ORDER BY i.updated_at ASC, i.item_id ASC"#;
+
+        let mut conn = self.pool.acquire().await?;
+        let mut items = sqlx::query_as::<_, InventoryItemWithGoods>(query)
+            .bind(item_ids)
+            .fetch_all(&mut *conn)
+            .await?;
+        set_expiry_fields(&mut conn, &mut items).await?;
+
+        Ok(items)
+    }
+
+    /// Update one item by id in a single transaction: goods fields go to the item's goods row,
+    /// quantity, expiry and lot to the item itself. `None` when the item does not exist.
+    pub async fn update_by_id(&self, item_id: i32, update_request: UpdateInventoryRequest) -> Result<Option<InventoryItemWithGoods>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Lock the item so the goods row we update is still the one it points to
+        let Some((goods_id,)) = sqlx::query_as::<_, (i32,)>("SELECT goods_id FROM inventory WHERE item_id = $1 FOR UPDATE")
+            .bind(item_id)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let updates_goods = update_request.material_code.is_some() ||
+            update_request.goods_name.is_some() ||
+            update_request.description.is_some() ||
+            update_request.price.is_some() ||
+            update_request.volumn_l.is_some() ||
+            update_request.mass_g.is_some() ||
+            update_request.mass_base.is_some() ||
+            update_request.volumn_base.is_some();
+
+        if updates_goods {
+            sqlx::query(
+                r#"
+                UPDATE goods
+                SET
+                    material_code = COALESCE($2, material_code),
+                    goods_name = COALESCE($3, goods_name),
+                    description = COALESCE($4, description),
+                    price = COALESCE($5, price),
+                    volumn_l = COALESCE($6, volumn_l),
+                    mass_g = COALESCE($7, mass_g),
+                    mass_base = COALESCE($8, mass_base),
+                    volumn_base = COALESCE($9, volumn_base),
+                    version = version + 1,
+                    updated_at = now()
+                WHERE goods_id = $1
+                "#
+            )
+            .bind(goods_id)
+            .bind(&update_request.material_code)
+            .bind(&update_request.goods_name)
+            .bind(&update_request.description)
+            .bind(update_request.price)
+            .bind(update_request.volumn_l)
+            .bind(update_request.mass_g)
+            .bind(update_request.mass_base)
+            .bind(update_request.volumn_base)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let mut previous_quantity = None;
+        if update_request.quantity.is_some() || update_request.expired_date.is_some() || update_request.lot_number.is_some() {
+            // Join the row to itself to read the quantity from before the update
+            let (old_quantity, quantity) = sqlx::query_as::<_, (i32, i32)>(
+                r#"
+                UPDATE inventory i
+                SET
+                    quantity = COALESCE($2, i.quantity),
+                    expired_date = COALESCE($3, i.expired_date),
+                    lot_number = COALESCE($4, i.lot_number),
+                    updated_at = now()
+                FROM inventory previous
+                WHERE i.item_id = $1 AND previous.item_id = i.item_id
+                RETURNING previous.quantity, i.quantity
+                "#
+            )
+            .bind(item_id)
+            .bind(update_request.quantity)
+            .bind(update_request.expired_date)
+            .bind(&update_request.lot_number)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if quantity != old_quantity {
+                StockMovementsTable::record(&mut tx, &[NewStockMovement {
+                    item_id,
+                    delta: quantity - old_quantity,
+                    reason: "updated".to_string(),
+                    resulting_quantity: quantity,
+                }]).await?;
+            }
+            previous_quantity = Some(old_quantity);
+        }
+
+        let mut item = sqlx::query_as::<_, InventoryItemWithGoods>(
+            r#"
+            SELECT
+                i.item_id, i.goods_id, i.quantity, i.expired_date, i.lot_number, i.created_at, i.updated_at,
+                g.material_code, g.goods_name, g.description, g.price,
+                g.volumn_l, g.mass_g, g.mass_base, g.volumn_base
+            FROM inventory i
+            INNER JOIN goods g ON i.goods_id = g.goods_id
+            WHERE i.item_id = $1"#
+        )
+        .bind(item_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        set_expiry_fields(&mut tx, std::slice::from_mut(&mut item)).await?;
+
+        self.enqueue_events(&mut tx, &[InventoryEvent::new(
+            InventoryEventType::Updated, item_id, goods_id, Some(previous_quantity.unwrap_or(item.quantity)), Some(item.quantity),
+        )]).await?;
+        AuditLogTable::record_tx(&mut tx, "inventory", &[item_id]).await?;
+
+        tx.commit().await?;
+
+        if updates_goods {
+            self.goods_table.cache().invalidate(&[goods_id]);
+        }
+
+        Ok(Some(item))
+    }
+
+    /// Update every matching item in one transaction: one statement for the goods of the matched
+    /// items, one for the items themselves and one read of the results, however many rows match.
+    /// A dry run returns the matched items unchanged.
+    pub async fn update(&self, params: InventorySearchParams, update_request: UpdateInventoryRequest, options: BulkWriteOptions) -> Result<Vec<InventoryItemWithGoods>, TableError> {
+        let mut tx = self.pool.begin().await?;
+        options.check_matched(Self::count_matching(&mut tx, &params).await?)?;
+
+        // The matched items in search order, locked so they cannot change or vanish before the update
+        let (query, builder) = Self::search_query(&params);
+        let mut items_to_update = builder.bind_values(sqlx::query_as::<_, InventoryItemWithGoods>(&format!("{} FOR UPDATE OF i", query)))
+            .fetch_all(&mut *tx)
+            .await?;
+
+        if items_to_update.is_empty() || options.dry_run {
+            set_expiry_fields(&mut tx, &mut items_to_update).await?;
+            tx.rollback().await?;
+            return Ok(items_to_update);
+        }
+
+        let item_ids: Vec<i32> = items_to_update.iter().map(|item| item.item_id).collect();
+        let mut goods_ids: Vec<i32> = items_to_update.iter().map(|item| item.goods_id).collect();
+        goods_ids.sort_unstable();
+        goods_ids.dedup();
+
+        let updates_goods = update_request.material_code.is_some() ||
+            update_request.goods_name.is_some() ||
+            update_request.description.is_some() ||
+            update_request.price.is_some() ||
+            update_request.volumn_l.is_some() ||
+            update_request.mass_g.is_some() ||
+            update_request.mass_base.is_some() ||
+            update_request.volumn_base.is_some();
+
+        // Each good is updated once, however many of its items matched
+        if updates_goods {
+            sqlx::query(
+                r#"
+                UPDATE goods 
+                SET 
+                    material_code = COALESCE($2, material_code),
+                    goods_name = COALESCE($3, goods_name),
+                    description = COALESCE($4, description),
+                    price = COALESCE($5, price),
+                    volumn_l = COALESCE($6, volumn_l),
+                    mass_g = COALESCE($7, mass_g),
+                    mass_base = COALESCE($8, mass_base),
+                    volumn_base = COALESCE($9, volumn_base),
+                    version = version + 1,
+                    updated_at = now()
+                WHERE goods_id = ANY($1)
+                "#
+            )
+            .bind(&goods_ids)
+            .bind(&update_request.material_code)
+            .bind(&update_request.goods_name)
+            .bind(&update_request.description)
+            .bind(update_request.price)
+            .bind(update_request.volumn_l)
+            .bind(update_request.mass_g)
+            .bind(update_request.mass_base)
+            .bind(update_request.volumn_base)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // (item_id, goods_id, quantity before, quantity after) of every updated item
+        let quantities: Vec<(i32, i32, i32, i32)> = if update_request.quantity.is_some() || update_request.expired_date.is_some() || update_request.lot_number.is_some() {
+            // Join the rows to themselves to read the quantities from before the update
+            sqlx::query_as::<_, (i32, i32, i32, i32)>(
+                r#"
+                UPDATE inventory i
+                SET 
+                    quantity = COALESCE($2, i.quantity),
+                    expired_date = COALESCE($3, i.expired_date),
+                    lot_number = COALESCE($4, i.lot_number),
+                    updated_at = now()
+                FROM inventory previous
+                WHERE i.item_id = ANY($1) AND previous.item_id = i.item_id
+                RETURNING i.item_id, i.goods_id, previous.quantity, i.quantity
+                "#
+            )
+            .bind(&item_ids)
+            .bind(update_request.quantity)
+            .bind(update_request.expired_date)
+            .bind(&update_request.lot_number)
+            .fetch_all(&mut *tx)
+            .await?
+        } else {
+            items_to_update.iter().map(|item| (item.item_id, item.goods_id, item.quantity, item.quantity)).collect()
+        };
+
+        let movements: Vec<NewStockMovement> = quantities.iter()
+            .filter(|(_, _, previous_quantity, quantity)| quantity != previous_quantity)
+            .map(|(item_id, _, previous_quantity, quantity)| NewStockMovement {
+                item_id: *item_id,
+                delta: quantity - previous_quantity,
+                reason: "updated".to_string(),
+                resulting_quantity: *quantity,
+            })
+            .collect();
+        StockMovementsTable::record(&mut tx, &movements).await?;
+
+        let events: Vec<InventoryEvent> = quantities.iter()
+            .map(|(item_id, goods_id, previous_quantity, quantity)| {
+                InventoryEvent::new(InventoryEventType::Updated, *item_id, *goods_id, Some(*previous_quantity), Some(*quantity))
+            })
+            .collect();
+        self.enqueue_events(&mut tx, &events).await?;
+
+        // Read the results back in the order they were matched
+        let mut updated_items = sqlx::query_as::<_, InventoryItemWithGoods>(
+            r#"
+            SELECT 
+                i.item_id, i.goods_id, i.quantity, i.expired_date, i.lot_number, i.created_at, i.updated_at,
+                g.material_code, g.goods_name, g.description, g.price, 
+                g.volumn_l, g.mass_g, g.mass_base, g.volumn_base
+            FROM UNNEST($1::int4[]) WITH ORDINALITY AS u(item_id, position)
+            INNER JOIN inventory i ON i.item_id = u.item_id
+            INNER JOIN goods g ON i.goods_id = g.goods_id
+            ORDER BY u.position
+            "#
+        )
+        .bind(&item_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+        set_expiry_fields(&mut tx, &mut updated_items).await?;
+        AuditLogTable::record_tx(&mut tx, "inventory", &item_ids).await?;
+
+        tx.commit().await?;
+
+        if updates_goods {
+            self.goods_table.cache().invalidate(&goods_ids);
+        }
+
+        Ok(updated_items)
+    }
+
+    /// Delete exactly these inventory items, reporting the ids that did not exist
+    pub async fn delete_by_ids(&self, item_ids: &[i32]) -> Result<InventoryDeleteByIdsResult, sqlx::Error> {
+        let mut requested = item_ids.to_vec();
+        requested.sort_unstable();
+        requested.dedup();
+
+        let mut tx = self.pool.begin().await?;
+
+        let mut deleted = sqlx::query_as::<_, (i32, i32, i32)>("DELETE FROM inventory WHERE item_id = ANY($1) RETURNING item_id, goods_id, quantity")
+            .bind(&requested)
+            .fetch_all(&mut *tx)
+            .await?;
+        deleted.sort_unstable();
+
+        // Close out each item's history with a final movement down to zero
+        let movements: Vec<NewStockMovement> = deleted.iter()
+            .map(|(item_id, _, quantity)| NewStockMovement {
+                item_id: *item_id,
+                delta: -quantity,
+                reason: "deleted".to_string(),
+                resulting_quantity: 0,
+            })
+            .collect();
+        StockMovementsTable::record(&mut tx, &movements).await?;
+        self.enqueue_events(&mut tx, &Self::deleted_events(&deleted)).await?;
+
+        let deleted: Vec<i32> = deleted.into_iter().map(|(item_id, _, _)| item_id).collect();
+        SyncTable::record_deleted(&mut tx, SyncEntity::Inventory, &deleted).await?;
+        AuditLogTable::record_tx(&mut tx, "inventory", &deleted).await?;
+
+        tx.commit().await?;
+
+        let not_found = requested.into_iter().filter(|item_id| deleted.binary_search(item_id).is_err()).collect();
+
+        Ok(InventoryDeleteByIdsResult { deleted, not_found })
     }
 
-    pub async fn update(&self, params: InventorySearchParams, update_request: UpdateInventoryRequest) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
-        // First, find inventory items to update
-        let items_to_update = self.search(params).await?;
-        
-        if items_to_update.is_empty() {
-            return Ok(Vec::new());
+    /// Delete every matching item; a dry run returns the ids that would be deleted and rolls back
+    pub async fn delete(&self, params: InventorySearchParams, options: BulkWriteOptions) -> Result<Vec<i32>, TableError> {
+        let mut builder = SearchQueryBuilder::new(String::new());
+        Self::add_search_conditions(&mut builder, &params);
+
+        // Delete every matching inventory item in one statement, joining goods
+        // so the goods-level filters behave exactly as in `search`
+        let query = format!(
+            r#"
+            WITH deleted AS (
+                DELETE FROM inventory i
+                USING goods g
+                WHERE i.goods_id = g.goods_id{}
+                RETURNING i.item_id, i.goods_id, i.quantity
+            )
+            SELECT item_id, goods_id, quantity FROM deleted ORDER BY item_id ASC
+            "#,
+            builder.build(None)
+        );
+
+        let mut tx = self.pool.begin().await?;
+
+        options.check_matched(Self::count_matching(&mut tx, &params).await?)?;
+
+        let deleted = builder.bind_values(sqlx::query_as::<_, (i32, i32, i32)>(&query))
+            .fetch_all(&mut *tx)
+            .await?;
+
+        if options.dry_run {
+            tx.rollback().await?;
+            return Ok(deleted.into_iter().map(|(item_id, _, _)| item_id).collect());
         }
 
-        let mut updated_items = Vec::new();
+        // Close out each item's history with a final movement down to zero
+        let movements: Vec<NewStockMovement> = deleted.iter()
+            .map(|(item_id, _, quantity)| NewStockMovement {
+                item_id: *item_id,
+                delta: -quantity,
+                reason: "deleted".to_string(),
+                resulting_quantity: 0,
+            })
+            .collect();
+        StockMovementsTable::record(&mut tx, &movements).await?;
+        self.enqueue_events(&mut tx, &Self::deleted_events(&deleted)).await?;
+
+        let deleted_ids: Vec<i32> = deleted.into_iter().map(|(item_id, _, _)| item_id).collect();
+        SyncTable::record_deleted(&mut tx, SyncEntity::Inventory, &deleted_ids).await?;
+        AuditLogTable::record_tx(&mut tx, "inventory", &deleted_ids).await?;
+
+        tx.commit().await?;
+
+        Ok(deleted_ids)
+    }
+
+    /// Webhook events for deleted `(item_id, goods_id, quantity)` rows
+    fn deleted_events(deleted: &[(i32, i32, i32)]) -> Vec<InventoryEvent> {
+        deleted.iter()
+            .map(|(item_id, goods_id, quantity)| {
+                InventoryEvent::new(InventoryEventType::Deleted, *item_id, *goods_id, Some(*quantity), None)
+            })
+            .collect()
+    }
 
-        for item in items_to_update {
-            // Start transaction for each item to handle both goods and inventory updates
-            let mut tx = self.pool.begin().await?;
+    /// Zero or delete every item that expired before `cutoff`, in one transaction, recording
+    /// "expired" movements. Zeroed items also lose their reservations, which can no longer be
+    /// fulfilled. Returns the affected item ids; a dry run reports them and rolls back.
+    pub async fn purge_expired(&self, cutoff: DateTime<Utc>, mode: ExpiredPurgeMode, dry_run: bool) -> Result<Vec<i32>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
 
-            // Update goods if goods-related fields are provided
-            if update_request.material_code.is_some() || 
-               update_request.goods_name.is_some() ||
-               update_request.description.is_some() ||
-               update_request.price.is_some() ||
-               update_request.volumn_l.is_some() ||
-               update_request.mass_g.is_some() ||
-               update_request.mass_base.is_some() ||
-               update_request.volumn_base.is_some() {
-                
-                sqlx::query(
+        // (item_id, goods_id, quantity before the purge)
+        let mut purged = match mode {
+            ExpiredPurgeMode::Zero => {
+                sqlx::query_as::<_, (i32, i32, i32)>(
                     r#"
-                    UPDATE goods 
-                    SET 
-                        material_code = COALESCE($2, material_code),
-                        goods_name = COALESCE($3, goods_name),
-                        description = COALESCE($4, description),
-                        price = COALESCE($5, price),
-                        volumn_l = COALESCE($6, volumn_l),
-                        mass_g = COALESCE($7, mass_g),
-                        mass_base = COALESCE($8, mass_base),
-                        volumn_base = COALESCE($9, volumn_base)
-                    WHERE goods_id = $1
+                    UPDATE inventory i
+                    SET quantity = 0, updated_at = now()
+                    FROM inventory previous
+                    WHERE i.expired_date < $1 AND i.quantity <> 0 AND previous.item_id = i.item_id
+                    RETURNING i.item_id, i.goods_id, previous.quantity
                     "#
                 )
-                .bind(item.goods_id)
-                .bind(&update_request.material_code)
-                .bind(&update_request.goods_name)
-                .bind(&update_request.description)
-                .bind(&update_request.price)
-                .bind(&update_request.volumn_l)
-                .bind(&update_request.mass_g)
-                .bind(&update_request.mass_base)
-                .bind(&update_request.volumn_base)
-                .execute(&mut *tx)
-                .await?;
+                .bind(cutoff)
+                .fetch_all(&mut *tx)
+                .await?
             }
-
-            // Update inventory if inventory-related fields are provided
-            if update_request.quantity.is_some() || update_request.expired_date.is_some() {
-                sqlx::query(
-                    r#"
-                    UPDATE inventory 
-                    SET 
-                        quantity = COALESCE($2, quantity),
-                        expired_date = COALESCE($3, expired_date)
-                    WHERE item_id = $1
-                    "#
+            ExpiredPurgeMode::Delete => {
+                sqlx::query_as::<_, (i32, i32, i32)>(
+                    "DELETE FROM inventory WHERE expired_date < $1 RETURNING item_id, goods_id, quantity"
                 )
-                .bind(item.item_id)
-                .bind(&update_request.quantity)
-                .bind(&update_request.expired_date)
+                .bind(cutoff)
+                .fetch_all(&mut *tx)
+                .await?
+            }
+        };
+        purged.sort_unstable();
+
+        let item_ids: Vec<i32> = purged.iter().map(|(item_id, _, _)| *item_id).collect();
+
+        if dry_run || purged.is_empty() {
+            tx.rollback().await?;
+            return Ok(item_ids);
+        }
+
+        let movements: Vec<NewStockMovement> = purged.iter()
+            .filter(|(_, _, quantity)| *quantity != 0)
+            .map(|(item_id, _, quantity)| NewStockMovement {
+                item_id: *item_id,
+                delta: -quantity,
+                reason: "expired".to_string(),
+                resulting_quantity: 0,
+            })
+            .collect();
+        StockMovementsTable::record(&mut tx, &movements).await?;
+
+        match mode {
+            ExpiredPurgeMode::Zero => {
+                sqlx::query("DELETE FROM reservations WHERE item_id = ANY($1)")
+                    .bind(&item_ids)
+                    .execute(&mut *tx)
+                    .await?;
+
+                let events: Vec<InventoryEvent> = purged.iter()
+                    .map(|(item_id, goods_id, quantity)| {
+                        InventoryEvent::new(InventoryEventType::Updated, *item_id, *goods_id, Some(*quantity), Some(0))
+                    })
+                    .collect();
+                self.enqueue_events(&mut tx, &events).await?;
+            }
+            ExpiredPurgeMode::Delete => {
+                SyncTable::record_deleted(&mut tx, SyncEntity::Inventory, &item_ids).await?;
+                self.enqueue_events(&mut tx, &Self::deleted_events(&purged)).await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(item_ids)
+    }
+
+    /// Fold inventory items with the same goods, expiry and lot (NULLs included) into the lowest
+    /// item_id: quantities are summed, reservations move to the survivor and the other rows are
+    /// deleted, all in one transaction. With `dry_run` the groups are reported and nothing changes.
+    pub async fn merge_duplicates(&self, dry_run: bool) -> Result<Vec<MergedInventoryGroup>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Block concurrent inserts and updates so the groups cannot change under us
+        if !dry_run {
+            sqlx::query("LOCK TABLE inventory IN SHARE ROW EXCLUSIVE MODE")
                 .execute(&mut *tx)
                 .await?;
+        }
+
+        let groups = sqlx::query_as::<_, (i32, Option<DateTime<Utc>>, Option<String>, Vec<i32>, Vec<i32>, i32)>(
+            r#"
+            SELECT goods_id, expired_date, lot_number,
+                   array_agg(item_id ORDER BY item_id),
+                   array_agg(quantity ORDER BY item_id),
+                   SUM(quantity)::INTEGER
+            FROM inventory
+            GROUP BY goods_id, expired_date, lot_number
+            HAVING COUNT(*) > 1
+            ORDER BY goods_id ASC, expired_date ASC NULLS LAST, lot_number ASC NULLS LAST
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut merged = Vec::with_capacity(groups.len());
+        let mut movements = Vec::new();
+        let mut removed_ids = Vec::new();
+
+        for (goods_id, expired_date, lot_number, item_ids, quantities, total) in groups {
+            let surviving_item_id = item_ids[0];
+            let removed_item_ids = item_ids[1..].to_vec();
+
+            if !dry_run {
+                sqlx::query("UPDATE inventory SET quantity = $2, updated_at = now() WHERE item_id = $1")
+                    .bind(surviving_item_id)
+                    .bind(total)
+                    .execute(&mut *tx)
+                    .await?;
+
+                sqlx::query("UPDATE reservations SET item_id = $1 WHERE item_id = ANY($2)")
+                    .bind(surviving_item_id)
+                    .bind(&removed_item_ids)
+                    .execute(&mut *tx)
+                    .await?;
+
+                if total != quantities[0] {
+                    movements.push(NewStockMovement {
+                        item_id: surviving_item_id,
+                        delta: total - quantities[0],
+                        reason: "merged".to_string(),
+                        resulting_quantity: total,
+                    });
+                }
+                for (item_id, quantity) in removed_item_ids.iter().zip(&quantities[1..]) {
+                    movements.push(NewStockMovement {
+                        item_id: *item_id,
+                        delta: -quantity,
+                        reason: "merged".to_string(),
+                        resulting_quantity: 0,
+                    });
+                }
+                removed_ids.extend_from_slice(&removed_item_ids);
             }
 
-            tx.commit().await?;
+            merged.push(MergedInventoryGroup {
+                goods_id,
+                expired_date,
+                lot_number,
+                surviving_item_id,
+                removed_item_ids,
+                quantity: total,
+            });
+        }
 
-            // Get updated item
-            let updated_item = self.get_by_item_id(item.item_id).await?;
-            updated_items.push(updated_item);
+        if dry_run {
+            tx.rollback().await?;
+            return Ok(merged);
         }
 
-        Ok(updated_items)
+        sqlx::query("DELETE FROM inventory WHERE item_id = ANY($1)")
+            .bind(&removed_ids)
+            .execute(&mut *tx)
+            .await?;
+
+        StockMovementsTable::record(&mut tx, &movements).await?;
+        SyncTable::record_deleted(&mut tx, SyncEntity::Inventory, &removed_ids).await?;
+
+        tx.commit().await?;
+
+        Ok(merged)
     }
 
-    pub async fn delete(&self, params: InventorySearchParams) -> Result<Vec<i32>, sqlx::Error> {
-        // First, find inventory items to delete
-        let items_to_delete = self.search(params).await?;
-        
-        if items_to_delete.is_empty() {
-            return Ok(Vec::new());
+    /// Merge the source good into the target in one transaction. Source items move to the
+    /// target, unless the target already has an item with the same expiry and lot: then their
+    /// quantity is added to it and they are deleted. Purchase order lines and alerts follow,
+    /// the description lines and tags are combined and the source is deleted.
+    /// `None` when either good does not exist.
+    pub async fn merge_goods(&self, source_id: i32, target_id: i32) -> Result<Option<GoodsMergeResult>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Lock both goods, then their items, so nothing is added to the source meanwhile
+        let goods = sqlx::query_as::<_, Good>(
+            "SELECT goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at FROM goods WHERE goods_id = ANY($1) ORDER BY goods_id FOR UPDATE"
+        )
+        .bind([source_id, target_id])
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let (Some(source), Some(target)) = (
+            goods.iter().find(|good| good.goods_id == source_id),
+            goods.iter().find(|good| good.goods_id == target_id),
+        ) else {
+            tx.rollback().await?;
+            return Ok(None);
+        };
+
+        let items = sqlx::query_as::<_, InventoryItem>(
+            "SELECT item_id, goods_id, quantity, expired_date, lot_number, created_at, updated_at FROM inventory WHERE goods_id = ANY($1) ORDER BY item_id FOR UPDATE"
+        )
+        .bind([source_id, target_id])
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // Each expiry and lot lands on the target's lowest item with it, else on the first
+        // source item moved over with it
+        let mut landing: HashMap<(Option<DateTime<Utc>>, Option<String>), i32> = items.iter()
+            .rev()
+            .filter(|item| item.goods_id == target_id)
+            .map(|item| ((item.expired_date, item.lot_number.clone()), item.item_id))
+            .collect();
+
+        let mut moved: Vec<&InventoryItem> = Vec::new();
+        let mut merged_items = Vec::new();
+        for item in items.iter().filter(|item| item.goods_id == source_id) {
+            match landing.entry((item.expired_date, item.lot_number.clone())) {
+                Entry::Occupied(entry) => merged_items.push(MergedGoodsItem {
+                    source_item_id: item.item_id,
+                    target_item_id: *entry.get(),
+                    quantity: item.quantity,
+                }),
+                Entry::Vacant(entry) => {
+                    entry.insert(item.item_id);
+                    moved.push(item);
+                }
+            }
         }
 
-        let mut deleted_ids = Vec::new();
+        let moved_item_ids: Vec<i32> = moved.iter().map(|item| item.item_id).collect();
+        sqlx::query("UPDATE inventory SET goods_id = $2, updated_at = now() WHERE item_id = ANY($1)")
+            .bind(&moved_item_ids)
+            .bind(target_id)
+            .execute(&mut *tx)
+            .await?;
 
-        // Delete each found inventory item
-        for item in items_to_delete {
-            sqlx::query("DELETE FROM inventory WHERE item_id = $1")
-                .bind(item.item_id)
-                .execute(&self.pool)
-                .await?;
-            
-            deleted_ids.push(item.item_id);
+        let source_item_ids: Vec<i32> = merged_items.iter().map(|merged| merged.source_item_id).collect();
+        let target_item_ids: Vec<i32> = merged_items.iter().map(|merged| merged.target_item_id).collect();
+        let quantities: Vec<i32> = merged_items.iter().map(|merged| merged.quantity).collect();
+
+        // Add the merged quantities to their target items, one update per target item
+        let increased = sqlx::query_as::<_, (i32, i32, i32)>(
+            r#"
+            UPDATE inventory i
+            SET quantity = i.quantity + m.added, updated_at = now()
+            FROM (
+                SELECT target_item_id, SUM(quantity)::INTEGER AS added
+                FROM unnest($1::int4[], $2::int4[]) AS m(target_item_id, quantity)
+                GROUP BY target_item_id
+            ) m
+            WHERE i.item_id = m.target_item_id
+            RETURNING i.item_id, m.added, i.quantity
+            "#
+        )
+        .bind(&target_item_ids)
+        .bind(&quantities)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE reservations r SET item_id = m.target_item_id FROM unnest($1::int4[], $2::int4[]) AS m(source_item_id, target_item_id) WHERE r.item_id = m.source_item_id"
+        )
+        .bind(&source_item_ids)
+        .bind(&target_item_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM inventory WHERE item_id = ANY($1)")
+            .bind(&source_item_ids)
+            .execute(&mut *tx)
+            .await?;
+
+        let purchase_order_lines = sqlx::query("UPDATE purchase_order_lines SET goods_id = $2 WHERE goods_id = $1")
+            .bind(source_id)
+            .bind(target_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        sqlx::query("UPDATE alerts SET goods_id = $2, goods_name = $3, updated_at = now() WHERE goods_id = $1")
+            .bind(source_id)
+            .bind(target_id)
+            .bind(&target.goods_name)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM goods WHERE goods_id = $1")
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let (description, tags) = merge_goods_details(source, target);
+        let target = sqlx::query_as::<_, Good>(
+            r#"
+            UPDATE goods
+            SET description = $2, tags = $3, version = version + 1, updated_at = now()
+            WHERE goods_id = $1
+            RETURNING goods_id, material_code, goods_name, description, price, volumn_l, mass_g, mass_base, volumn_base, reorder_point, category_id, barcode, tags, is_active, version, created_at, updated_at
+            "#
+        )
+        .bind(target_id)
+        .bind(description)
+        .bind(tags)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // Moved items keep their quantity; merged ones are closed out into their target item
+        let mut movements: Vec<NewStockMovement> = moved.iter()
+            .map(|item| NewStockMovement {
+                item_id: item.item_id,
+                delta: 0,
+                reason: "moved".to_string(),
+                resulting_quantity: item.quantity,
+            })
+            .collect();
+        movements.extend(increased.iter().map(|(item_id, added, quantity)| NewStockMovement {
+            item_id: *item_id,
+            delta: *added,
+            reason: "merged".to_string(),
+            resulting_quantity: *quantity,
+        }));
+        movements.extend(merged_items.iter().map(|merged| NewStockMovement {
+            item_id: merged.source_item_id,
+            delta: -merged.quantity,
+            reason: "merged".to_string(),
+            resulting_quantity: 0,
+        }));
+        StockMovementsTable::record(&mut tx, &movements).await?;
+
+        let mut events: Vec<InventoryEvent> = moved.iter()
+            .map(|item| InventoryEvent::new(InventoryEventType::Updated, item.item_id, target_id, Some(item.quantity), Some(item.quantity)))
+            .collect();
+        events.extend(increased.iter().map(|(item_id, added, quantity)| {
+            InventoryEvent::new(InventoryEventType::Updated, *item_id, target_id, Some(quantity - added), Some(*quantity))
+        }));
+        let deleted: Vec<(i32, i32, i32)> = merged_items.iter()
+            .map(|merged| (merged.source_item_id, source_id, merged.quantity))
+            .collect();
+        events.extend(Self::deleted_events(&deleted));
+        self.enqueue_events(&mut tx, &events).await?;
+
+        SyncTable::record_deleted(&mut tx, SyncEntity::Inventory, &source_item_ids).await?;
+        SyncTable::record_deleted(&mut tx, SyncEntity::Goods, &[source_id]).await?;
+
+        tx.commit().await?;
+
+        self.goods_table.cache().invalidate(&[source_id, target_id]);
+
+        Ok(Some(GoodsMergeResult {
+            source_goods_id: source_id,
+            target,
+            moved_item_ids,
+            merged_items,
+            purchase_order_lines,
+        }))
+    }
+}
+
+/// Split `quantity` over `stock` (item id and quantity, in FIFO order), taking from positive
+/// quantities only. A shortfall fails with the available total, or with negative stock allowed
+/// is taken from the last item, which then goes below zero. Items that end at zero are marked
+/// `deleted` when `delete_empty` is set.
+pub fn plan_consumption(stock: &[(i32, i32)], quantity: i32, delete_empty: bool, allow_negative_stock: bool) -> Result<Vec<ConsumedItem>, i64> {
+    let available: i64 = stock.iter().map(|(_, quantity)| (*quantity).max(0) as i64).sum();
+    if available < quantity as i64 && (!allow_negative_stock || stock.is_empty()) {
+        return Err(available);
+    }
+
+    let mut outstanding = quantity;
+    let mut items: Vec<ConsumedItem> = Vec::new();
+    for &(item_id, item_quantity) in stock {
+        if outstanding == 0 {
+            break;
+        }
+        let taken = item_quantity.max(0).min(outstanding);
+        if taken == 0 {
+            continue;
         }
+        outstanding -= taken;
+        items.push(ConsumedItem {
+            item_id,
+            taken,
+            remaining_quantity: item_quantity - taken,
+            deleted: delete_empty && item_quantity == taken,
+        });
+    }
 
-        Ok(deleted_ids)
+    if outstanding > 0 && let Some(&(item_id, item_quantity)) = stock.last() {
+        match items.iter_mut().find(|item| item.item_id == item_id) {
+            Some(item) => {
+                item.taken += outstanding;
+                item.remaining_quantity -= outstanding;
+                item.deleted = false;
+            }
+            None => items.push(ConsumedItem {
+                item_id,
+                taken: outstanding,
+                remaining_quantity: item_quantity - outstanding,
+                deleted: false,
+            }),
+        }
+    }
+
+    Ok(items)
+}
+
+/// The target's description lines followed by the source's it lacks, and the union of their tags
+pub fn merge_goods_details(source: &Good, target: &Good) -> (Option<Vec<String>>, Option<Vec<String>>) {
+    let combine = |target: &Option<Vec<String>>, source: &Option<Vec<String>>| {
+        if target.is_none() && source.is_none() {
+            return None;
+        }
+        let mut combined = target.clone().unwrap_or_default();
+        for value in source.iter().flatten() {
+            if !combined.contains(value) {
+                combined.push(value.clone());
+            }
+        }
+        Some(combined)
+    };
+
+    let tags = combine(&target.tags, &source.tags).map(|tags| normalize_tags(&tags));
+    (combine(&target.description, &source.description), tags)
+}
+
+/// Fill in the expiry fields of `items` against a single reading of the database clock
+pub(crate) async fn set_expiry_fields(conn: &mut PgConnection, items: &mut [InventoryItemWithGoods]) -> Result<(), sqlx::Error> {
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    let now = database_now(conn).await?;
+    for item in items {
+        item.set_expiry_fields(now);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_search_binds_the_term_as_first_placeholder() {
+        let mut goods_params = GoodsSearchParams { goods_name: Some("chili".to_string()), ..GoodsSearchParams::new() };
+        goods_params.rank_by_similarity(0.3).unwrap();
+        let params = InventorySearchParams { goods_params, ..InventorySearchParams::new() };
+
+        let (query, builder) = InventoryTable::search_query(&params);
+
+        assert!(query.contains("similarity(g.goods_name, $1) AS similarity"), "{}", query);
+        assert!(query.contains("AND similarity(g.goods_name, $1) > 0.3"), "{}", query);
+        assert!(query.contains("AND g.is_active = $2"), "{}", query);
+        assert_eq!(builder.values(), [BindValue::Text("chili".to_string()), BindValue::Bool(true)]);
+    }
+
+    /// `conditions` with `?` numbered from `$1`, as the builder appends them
+    fn numbered(conditions: &[&str]) -> String {
+        conditions.iter().enumerate().map(|(i, condition)| format!(" AND {}", condition.replace('?', &format!("${}", i + 1)))).collect()
+    }
+
+    #[test]
+    fn every_filter_combination_binds_values_in_placeholder_order() {
+        for combination in 0..16 {
+            let mut params = InventorySearchParams::new();
+            let mut conditions = vec!["g.is_active = ?"];
+            let mut values = vec![BindValue::Bool(true)];
+            if combination & 1 != 0 {
+                params.min_quantity = Some(5);
+                conditions.push("i.quantity >= ?");
+                values.push(BindValue::Int(5));
+            }
+            if combination & 2 != 0 {
+                params.lot_number = Some("LOT-7".to_string());
+                conditions.push("i.lot_number = ?");
+                values.push(BindValue::Text("LOT-7".to_string()));
+            }
+            if combination & 4 != 0 {
+                params.goods_params.material_code = Some("CH-1".to_string());
+                conditions.push("g.material_code ILIKE ? ESCAPE '\\'");
+                values.push(BindValue::Text("%CH-1%".to_string()));
+            }
+            if combination & 8 != 0 {
+                params.goods_params.max_price = Some(rust_decimal::Decimal::TEN);
+                conditions.push("g.price <= ?");
+                values.push(BindValue::Decimal(rust_decimal::Decimal::TEN));
+            }
+
+            let (query, builder) = InventoryTable::search_query(&params);
+
+            assert!(query.contains(&format!("WHERE 1=1{} ORDER BY", numbered(&conditions))), "{}", query);
+            assert_eq!(builder.values(), values.as_slice());
+        }
+    }
+
+    #[test]
+    fn updated_at_filters_apply_to_the_item_and_bind_in_placeholder_order() {
+        let min_updated_at: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let params = InventorySearchParams {
+            in_stock: Some(true),
+            min_updated_at: Some(min_updated_at),
+            goods_params: GoodsSearchParams { goods_id: Some(4), ..GoodsSearchParams::new() },
+            ..InventorySearchParams::new()
+        };
+
+        let (query, builder) = InventoryTable::search_query(&params);
+
+        assert!(query.contains(&numbered(&["g.is_active = ?", "(i.quantity > 0) = ?", "i.updated_at >= ?", "g.goods_id = ?"])), "{}", query);
+        assert_eq!(builder.values(), [BindValue::Bool(true), BindValue::Bool(true), BindValue::DateTime(min_updated_at), BindValue::Int(4)]);
+    }
+
+    #[test]
+    fn like_metacharacters_in_filters_are_escaped() {
+        let params = InventorySearchParams {
+            lot_number_contains: Some("L_24%".to_string()),
+            goods_params: GoodsSearchParams { material_code: Some("CH\\100".to_string()), ..GoodsSearchParams::new() },
+            ..InventorySearchParams::new()
+        };
+
+        let (query, builder) = InventoryTable::search_query(&params);
+
+        assert!(query.contains("AND i.lot_number ILIKE $2 ESCAPE '\\'"), "{}", query);
+        assert!(query.contains("AND g.material_code ILIKE $3 ESCAPE '\\'"), "{}", query);
+        assert_eq!(builder.values()[1..], [BindValue::Text("%L\\_24\\%%".to_string()), BindValue::Text("%CH\\\\100%".to_string())]);
+    }
+
+    /// (item_id, taken, remaining_quantity, deleted) of each planned item
+    fn planned(items: Vec<ConsumedItem>) -> Vec<(i32, i32, i32, bool)> {
+        items.into_iter().map(|item| (item.item_id, item.taken, item.remaining_quantity, item.deleted)).collect()
+    }
+
+    #[test]
+    fn consumption_takes_from_items_in_order() {
+        let items = plan_consumption(&[(1, 3), (2, 5), (3, 4)], 4, true, false).unwrap();
+
+        assert_eq!(planned(items), [(1, 3, 0, true), (2, 1, 4, false)]);
+    }
+
+    #[test]
+    fn shortfall_fails_with_the_available_total_when_negative_stock_is_refused() {
+        // Items already below zero add nothing to what is available
+        assert_eq!(plan_consumption(&[(1, 3), (2, -2)], 5, false, false).unwrap_err(), 3);
+        assert_eq!(plan_consumption(&[], 1, false, true).unwrap_err(), 0);
+    }
+
+    #[test]
+    fn shortfall_goes_on_the_last_item_when_negative_stock_is_allowed() {
+        let items = plan_consumption(&[(1, 3), (2, 5)], 10, true, true).unwrap();
+        assert_eq!(planned(items), [(1, 3, 0, true), (2, 7, -2, false)]);
+
+        let items = plan_consumption(&[(1, 3), (2, -1)], 5, false, true).unwrap();
+        assert_eq!(planned(items), [(1, 3, 0, false), (2, 2, -3, false)]);
+    }
+
+    #[test]
+    fn negative_max_quantity_selects_items_below_zero() {
+        let params = InventorySearchParams { max_quantity: Some(-1), ..InventorySearchParams::new() };
+
+        let (query, builder) = InventoryTable::search_query(&params);
+
+        assert!(query.contains(&numbered(&["g.is_active = ?", "i.quantity <= ?"])), "{}", query);
+        assert_eq!(builder.values(), [BindValue::Bool(true), BindValue::Int(-1)]);
     }
 }
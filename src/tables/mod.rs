@@ -1,6 +1,32 @@
 // src/tables/mod.rs
+pub mod alerts_table;
+pub mod audit_log_table;
+pub mod categories_table;
+pub mod error;
+pub mod goods_cache;
 pub mod goods_table;
+pub mod idempotency_table;
 pub mod inventory_table;
+pub mod purchase_orders_table;
+pub mod repository;
+pub mod reservations_table;
+pub mod stock_movements_table;
+pub mod stock_snapshots_table;
+pub mod sync_table;
+pub mod webhook_outbox_table;
 
+pub use alerts_table::*;
+pub use audit_log_table::*;
+pub use categories_table::*;
+pub use error::*;
+pub use goods_cache::*;
 pub use goods_table::*;
+pub use idempotency_table::*;
 pub use inventory_table::*;
+pub use purchase_orders_table::*;
+pub use repository::*;
+pub use reservations_table::*;
+pub use stock_movements_table::*;
+pub use stock_snapshots_table::*;
+pub use sync_table::*;
+pub use webhook_outbox_table::*;
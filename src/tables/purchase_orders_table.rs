@@ -0,0 +1,350 @@
+// src/tables/purchase_orders_table.rs
+//
+// Purchase orders and their receipt into stock. An order starts as `draft`, becomes `ordered`
+// when placed or partially received, and `received` once every line is fully received.
+use super::error::{BatchItemError, TableError};
+use super::inventory_table::{InventoryDuplicateMode, InventoryInsertOutcome, InventoryTable, StockToStore};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PurchaseOrderStatus {
+    Draft,
+    Ordered,
+    Received,
+}
+
+impl PurchaseOrderStatus {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "draft" => Ok(PurchaseOrderStatus::Draft),
+            "ordered" => Ok(PurchaseOrderStatus::Ordered),
+            "received" => Ok(PurchaseOrderStatus::Received),
+            _ => Err("Invalid status. Allowed values: draft, ordered, received".to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PurchaseOrderStatus::Draft => "draft",
+            PurchaseOrderStatus::Ordered => "ordered",
+            PurchaseOrderStatus::Received => "received",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PurchaseOrder {
+    pub purchase_order_id: i32,
+    pub reference: Option<String>,
+    /// `draft`, `ordered` or `received`
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    pub lines: Vec<PurchaseOrderLine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PurchaseOrderLine {
+    pub line_id: i32,
+    pub purchase_order_id: i32,
+    pub goods_id: i32,
+    pub quantity: i32,
+    pub received_quantity: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreatePurchaseOrderRequest {
+    pub reference: Option<String>,
+    /// `draft` (default) or `ordered`
+    pub status: Option<String>,
+    pub lines: Vec<CreatePurchaseOrderLine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreatePurchaseOrderLine {
+    pub goods_id: i32,
+    pub quantity: i32,
+}
+
+/// Body of `POST /purchase-orders/{id}/receive`; without `lines` every outstanding quantity is received
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReceivePurchaseOrderRequest {
+    pub lines: Option<Vec<ReceivePurchaseOrderLine>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReceivePurchaseOrderLine {
+    pub line_id: i32,
+    pub quantity: i32,
+    pub expired_date: Option<DateTime<Utc>>,
+}
+
+/// Stock booked in for one order line
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReceivedLine {
+    pub line_id: i32,
+    pub item_id: i32,
+    pub quantity: i32,
+    /// Whether the quantity was added to an existing item with the same goods and expiry and no lot number
+    pub merged: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PurchaseOrderReceipt {
+    pub purchase_order: PurchaseOrder,
+    pub received: Vec<ReceivedLine>,
+}
+
+#[derive(Clone)]
+pub struct PurchaseOrdersTable {
+    pool: PgPool,
+    /// Stores received quantities, queueing their webhook events
+    inventory_table: InventoryTable,
+}
+
+impl PurchaseOrdersTable {
+    pub fn new(pool: PgPool, inventory_table: InventoryTable) -> Self {
+        Self { pool, inventory_table }
+    }
+
+    pub async fn get_by_id(&self, purchase_order_id: i32) -> Result<Option<PurchaseOrder>, sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        Self::load(&mut conn, purchase_order_id, false).await
+    }
+
+    /// Create an order with its lines. Every line must reference existing goods.
+    pub async fn create(
+        &self,
+        request: &CreatePurchaseOrderRequest,
+        status: PurchaseOrderStatus,
+    ) -> Result<PurchaseOrder, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        let goods_ids: Vec<i32> = request.lines.iter().map(|line| line.goods_id).collect();
+        let known_goods: Vec<i32> = sqlx::query_scalar("SELECT goods_id FROM goods WHERE goods_id = ANY($1)")
+            .bind(&goods_ids)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let errors: Vec<BatchItemError> = request.lines.iter()
+            .enumerate()
+            .filter(|(_, line)| !known_goods.contains(&line.goods_id))
+            .map(|(index, line)| BatchItemError::not_found(index, format!("Referenced goods not found: goods_id {}", line.goods_id)))
+            .collect();
+
+        if !errors.is_empty() {
+            tx.rollback().await?;
+            return Err(TableError::InvalidBatch { errors });
+        }
+
+        let (purchase_order_id,) = sqlx::query_as::<_, (i32,)>(
+            "INSERT INTO purchase_orders (reference, status) VALUES ($1, $2) RETURNING purchase_order_id"
+        )
+        .bind(&request.reference)
+        .bind(status.as_str())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let quantities: Vec<i32> = request.lines.iter().map(|line| line.quantity).collect();
+        sqlx::query(
+            r#"
+            INSERT INTO purchase_order_lines (purchase_order_id, goods_id, quantity)
+            SELECT $1, * FROM UNNEST($2::int4[], $3::int4[])
+            "#
+        )
+        .bind(purchase_order_id)
+        .bind(&goods_ids)
+        .bind(&quantities)
+        .execute(&mut *tx)
+        .await?;
+
+        let order = Self::load(&mut tx, purchase_order_id, false).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        tx.commit().await?;
+
+        Ok(order)
+    }
+
+    /// Move a draft order to `ordered`
+    pub async fn place(&self, purchase_order_id: i32) -> Result<Option<PurchaseOrder>, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(order) = Self::load(&mut tx, purchase_order_id, true).await? else {
+            return Ok(None);
+        };
+
+        if order.status != PurchaseOrderStatus::Draft.as_str() {
+            tx.rollback().await?;
+            return Err(TableError::PurchaseOrderStatus {
+                purchase_order_id,
+                status: order.status,
+                action: "placed",
+            });
+        }
+
+        Self::set_status(&mut tx, purchase_order_id, PurchaseOrderStatus::Ordered).await?;
+        let order = Self::load(&mut tx, purchase_order_id, false).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        tx.commit().await?;
+
+        Ok(Some(order))
+    }
+
+    /// Book received quantities into inventory in one transaction, merging into existing items
+    /// with the same goods and expiry and no lot number. The order stays `ordered` until every
+    /// line is fully received.
+    pub async fn receive(
+        &self,
+        purchase_order_id: i32,
+        request: &ReceivePurchaseOrderRequest,
+    ) -> Result<Option<PurchaseOrderReceipt>, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(order) = Self::load(&mut tx, purchase_order_id, true).await? else {
+            return Ok(None);
+        };
+
+        if order.status == PurchaseOrderStatus::Received.as_str() {
+            tx.rollback().await?;
+            return Err(TableError::PurchaseOrderStatus {
+                purchase_order_id,
+                status: order.status,
+                action: "received",
+            });
+        }
+
+        // Without explicit lines, receive everything still outstanding
+        let receipts: Vec<ReceivePurchaseOrderLine> = match &request.lines {
+            Some(lines) => lines.clone(),
+            None => order.lines.iter()
+                .filter(|line| line.received_quantity < line.quantity)
+                .map(|line| ReceivePurchaseOrderLine {
+                    line_id: line.line_id,
+                    quantity: line.quantity - line.received_quantity,
+                    expired_date: None,
+                })
+                .collect(),
+        };
+
+        let mut errors = Vec::new();
+        for (index, receipt) in receipts.iter().enumerate() {
+            match order.lines.iter().find(|line| line.line_id == receipt.line_id) {
+                None => errors.push(BatchItemError::not_found(
+                    index,
+                    format!("Line {} does not belong to purchase order {}", receipt.line_id, purchase_order_id),
+                )),
+                Some(line) if receipt.quantity > line.quantity - line.received_quantity => errors.push(BatchItemError::invalid(
+                    index,
+                    format!(
+                        "Cannot receive {} on line {}: only {} outstanding",
+                        receipt.quantity, line.line_id, line.quantity - line.received_quantity
+                    ),
+                )),
+                Some(_) => {}
+            }
+        }
+
+        if !errors.is_empty() {
+            tx.rollback().await?;
+            return Err(TableError::InvalidBatch { errors });
+        }
+
+        let reason = match &order.reference {
+            Some(reference) => format!("purchase order {} received ({})", purchase_order_id, reference),
+            None => format!("purchase order {} received", purchase_order_id),
+        };
+
+        let mut received = Vec::with_capacity(receipts.len());
+        for receipt in &receipts {
+            let goods_id = order.lines.iter()
+                .find(|line| line.line_id == receipt.line_id)
+                .map(|line| line.goods_id)
+                .ok_or(sqlx::Error::RowNotFound)?;
+
+            let stock = StockToStore { goods_id, quantity: receipt.quantity, expired_date: receipt.expired_date, lot_number: None };
+            let (item_id, outcome) = self.inventory_table
+                .store_quantity(&mut tx, stock, InventoryDuplicateMode::AddQuantity, Some(&reason))
+                .await?;
+
+            sqlx::query("UPDATE purchase_order_lines SET received_quantity = received_quantity + $2 WHERE line_id = $1")
+                .bind(receipt.line_id)
+                .bind(receipt.quantity)
+                .execute(&mut *tx)
+                .await?;
+
+            received.push(ReceivedLine {
+                line_id: receipt.line_id,
+                item_id,
+                quantity: receipt.quantity,
+                merged: outcome == InventoryInsertOutcome::Merged,
+            });
+        }
+
+        let (fully_received,) = sqlx::query_as::<_, (bool,)>(
+            "SELECT bool_and(received_quantity >= quantity) FROM purchase_order_lines WHERE purchase_order_id = $1"
+        )
+        .bind(purchase_order_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let status = if fully_received { PurchaseOrderStatus::Received } else { PurchaseOrderStatus::Ordered };
+        Self::set_status(&mut tx, purchase_order_id, status).await?;
+
+        let purchase_order = Self::load(&mut tx, purchase_order_id, false).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        tx.commit().await?;
+
+        Ok(Some(PurchaseOrderReceipt { purchase_order, received }))
+    }
+
+    /// Load an order with its lines, optionally locking both for the rest of the transaction
+    async fn load(conn: &mut PgConnection, purchase_order_id: i32, lock: bool) -> Result<Option<PurchaseOrder>, sqlx::Error> {
+        let lock_clause = if lock { " FOR UPDATE" } else { "" };
+
+        let order = sqlx::query_as::<_, PurchaseOrder>(&format!(
+            "SELECT purchase_order_id, reference, status, created_at, updated_at FROM purchase_orders WHERE purchase_order_id = $1{}",
+            lock_clause
+        ))
+        .bind(purchase_order_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some(mut order) = order else {
+            return Ok(None);
+        };
+
+        order.lines = sqlx::query_as::<_, PurchaseOrderLine>(&format!(
+            "SELECT line_id, purchase_order_id, goods_id, quantity, received_quantity FROM purchase_order_lines WHERE purchase_order_id = $1 ORDER BY line_id ASC{}",
+            lock_clause
+        ))
+        .bind(purchase_order_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(Some(order))
+    }
+
+    async fn set_status(conn: &mut PgConnection, purchase_order_id: i32, status: PurchaseOrderStatus) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE purchase_orders SET status = $2, updated_at = now() WHERE purchase_order_id = $1")
+            .bind(purchase_order_id)
+            .bind(status.as_str())
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+}
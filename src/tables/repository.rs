@@ -0,0 +1,289 @@
+// src/tables/repository.rs
+//
+// The goods and inventory operations the HTTP handlers use, as object-safe traits. `AppState`
+// holds them as `Arc<dyn ...>`, so handlers run against `GoodsTable` / `InventoryTable` in
+// production and against the in-memory fakes in `crate::testing` without a database.
+// Background jobs keep using the concrete tables.
+use super::error::{BatchItemError, TableError};
+use super::goods_cache::GoodsCacheStats;
+use super::goods_table::{
+    BulkWriteOptions, CreateGoodRequest, DuplicateMaterialCode, Good, GoodWithStock, GoodsBatchResult, GoodsConflictMode, GoodsDeleteByIdsResult, GoodsSearchParams,
+    GoodsTable, LowStockGoods, SavedGood, TagCount, UpdateGoodRequest,
+};
+use super::inventory_table::{
+    ConsumeInventoryRequest, ConsumeResult, CreateInventoryRequest, ExpiredPurgeMode, GoodsMergeResult, InventoryBatchResult, InventoryDeleteByIdsResult,
+    InventoryDuplicateMode, InventoryInsertOutcome, InventoryItemWithGoods, InventorySearchParams, InventoryStats, InventorySummary,
+    InventorySummaryParams, InventoryTable, MergedInventoryGroup, UpdateInventoryRequest, ValuationParams, ValuationReport,
+};
+use crate::utils::pagination::PaginatedResponse;
+use crate::utils::string_utils::Normalization;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+
+#[async_trait]
+pub trait GoodsRepository: Send + Sync {
+    async fn search(&self, params: GoodsSearchParams) -> Result<Vec<Good>, sqlx::Error>;
+
+    /// Matching goods one row at a time, for exports
+    fn search_stream(&self, params: &GoodsSearchParams) -> BoxStream<'static, Result<Good, sqlx::Error>>;
+
+    async fn search_paginated(&self, params: GoodsSearchParams) -> Result<PaginatedResponse<Good>, sqlx::Error>;
+
+    /// `search` with each good's inventory totals
+    async fn search_with_stock(&self, params: GoodsSearchParams) -> Result<Vec<GoodWithStock>, sqlx::Error>;
+
+    async fn search_with_stock_paginated(&self, params: GoodsSearchParams) -> Result<PaginatedResponse<GoodWithStock>, sqlx::Error>;
+
+    async fn get_by_barcode(&self, barcode: &str) -> Result<Option<Good>, sqlx::Error>;
+
+    async fn get_by_id(&self, goods_id: i32) -> Result<Option<Good>, sqlx::Error>;
+
+    async fn get_by_ids(&self, goods_ids: &[i32]) -> Result<Vec<Good>, sqlx::Error>;
+
+    async fn insert(&self, request: CreateGoodRequest, on_conflict: GoodsConflictMode) -> Result<SavedGood, TableError>;
+
+    async fn upsert_by_material_code(&self, request: CreateGoodRequest) -> Result<SavedGood, TableError>;
+
+    async fn insert_batch(&self, entries: Vec<(usize, CreateGoodRequest)>) -> Result<GoodsBatchResult, sqlx::Error>;
+
+    async fn low_stock(&self, include_zero_reorder: bool) -> Result<Vec<LowStockGoods>, sqlx::Error>;
+
+    async fn tag_counts(&self) -> Result<Vec<TagCount>, sqlx::Error>;
+
+    /// Goods whose material codes collide once normalized
+    async fn duplicate_material_codes(&self, normalization: Normalization) -> Result<Vec<DuplicateMaterialCode>, sqlx::Error>;
+
+    async fn update(&self, params: GoodsSearchParams, update_request: UpdateGoodRequest, options: BulkWriteOptions) -> Result<Vec<Good>, TableError>;
+
+    async fn update_by_id(&self, goods_id: i32, update_request: UpdateGoodRequest) -> Result<Option<Good>, TableError>;
+
+    async fn delete(&self, params: GoodsSearchParams, options: BulkWriteOptions) -> Result<Vec<i32>, TableError>;
+
+    async fn delete_by_ids(&self, goods_ids: &[i32]) -> Result<GoodsDeleteByIdsResult, sqlx::Error>;
+
+    fn cache_stats(&self) -> GoodsCacheStats;
+}
+
+#[async_trait]
+pub trait InventoryRepository: Send + Sync {
+    async fn search(&self, params: InventorySearchParams) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error>;
+
+    /// Matching items one row at a time, for exports
+    fn search_stream(&self, params: &InventorySearchParams) -> BoxStream<'static, Result<InventoryItemWithGoods, sqlx::Error>>;
+
+    async fn search_paginated(&self, params: InventorySearchParams) -> Result<PaginatedResponse<InventoryItemWithGoods>, sqlx::Error>;
+
+    async fn stats(&self, params: &InventorySearchParams) -> Result<InventoryStats, sqlx::Error>;
+
+    async fn summary(&self, params: &InventorySummaryParams) -> Result<Vec<InventorySummary>, sqlx::Error>;
+
+    async fn valuation(&self, params: &ValuationParams) -> Result<ValuationReport, sqlx::Error>;
+
+    async fn get_by_item_id(&self, item_id: i32) -> Result<Option<InventoryItemWithGoods>, sqlx::Error>;
+
+    async fn get_by_item_ids(&self, item_ids: &[i32]) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error>;
+
+    async fn insert(
+        &self,
+        request: CreateInventoryRequest,
+        on_duplicate: InventoryDuplicateMode,
+        allow_inactive: bool,
+    ) -> Result<(InventoryItemWithGoods, InventoryInsertOutcome), TableError>;
+
+    async fn insert_batch(
+        &self,
+        entries: Vec<(usize, CreateInventoryRequest)>,
+        failures: Vec<BatchItemError>,
+        abort_on_failure: bool,
+        allow_inactive: bool,
+    ) -> Result<InventoryBatchResult, TableError>;
+
+    async fn update(&self, params: InventorySearchParams, update_request: UpdateInventoryRequest, options: BulkWriteOptions) -> Result<Vec<InventoryItemWithGoods>, TableError>;
+
+    async fn update_by_id(&self, item_id: i32, update_request: UpdateInventoryRequest) -> Result<Option<InventoryItemWithGoods>, sqlx::Error>;
+
+    async fn adjust(&self, item_id: i32, delta: i32, reason: &str) -> Result<InventoryItemWithGoods, TableError>;
+
+    async fn consume(&self, request: &ConsumeInventoryRequest) -> Result<ConsumeResult, TableError>;
+
+    async fn delete(&self, params: InventorySearchParams, options: BulkWriteOptions) -> Result<Vec<i32>, TableError>;
+
+    async fn delete_by_ids(&self, item_ids: &[i32]) -> Result<InventoryDeleteByIdsResult, sqlx::Error>;
+
+    async fn merge_duplicates(&self, dry_run: bool) -> Result<Vec<MergedInventoryGroup>, sqlx::Error>;
+
+    async fn merge_goods(&self, source_id: i32, target_id: i32) -> Result<Option<GoodsMergeResult>, sqlx::Error>;
+
+    async fn purge_expired(&self, cutoff: DateTime<Utc>, mode: ExpiredPurgeMode, dry_run: bool) -> Result<Vec<i32>, sqlx::Error>;
+}
+
+#[async_trait]
+impl GoodsRepository for GoodsTable {
+    async fn search(&self, params: GoodsSearchParams) -> Result<Vec<Good>, sqlx::Error> {
+        GoodsTable::search(self, params).await
+    }
+
+    fn search_stream(&self, params: &GoodsSearchParams) -> BoxStream<'static, Result<Good, sqlx::Error>> {
+        GoodsTable::search_stream(self, params).boxed()
+    }
+
+    async fn search_paginated(&self, params: GoodsSearchParams) -> Result<PaginatedResponse<Good>, sqlx::Error> {
+        GoodsTable::search_paginated(self, params).await
+    }
+
+    async fn search_with_stock(&self, params: GoodsSearchParams) -> Result<Vec<GoodWithStock>, sqlx::Error> {
+        GoodsTable::search_with_stock(self, params).await
+    }
+
+    async fn search_with_stock_paginated(&self, params: GoodsSearchParams) -> Result<PaginatedResponse<GoodWithStock>, sqlx::Error> {
+        GoodsTable::search_with_stock_paginated(self, params).await
+    }
+
+    async fn get_by_barcode(&self, barcode: &str) -> Result<Option<Good>, sqlx::Error> {
+        GoodsTable::get_by_barcode(self, barcode).await
+    }
+
+    async fn get_by_id(&self, goods_id: i32) -> Result<Option<Good>, sqlx::Error> {
+        GoodsTable::get_by_id(self, goods_id).await
+    }
+
+    async fn get_by_ids(&self, goods_ids: &[i32]) -> Result<Vec<Good>, sqlx::Error> {
+        GoodsTable::get_by_ids(self, goods_ids).await
+    }
+
+    async fn insert(&self, request: CreateGoodRequest, on_conflict: GoodsConflictMode) -> Result<SavedGood, TableError> {
+        GoodsTable::insert(self, request, on_conflict).await
+    }
+
+    async fn upsert_by_material_code(&self, request: CreateGoodRequest) -> Result<SavedGood, TableError> {
+        GoodsTable::upsert_by_material_code(self, request).await
+    }
+
+    async fn insert_batch(&self, entries: Vec<(usize, CreateGoodRequest)>) -> Result<GoodsBatchResult, sqlx::Error> {
+        GoodsTable::insert_batch(self, entries).await
+    }
+
+    async fn low_stock(&self, include_zero_reorder: bool) -> Result<Vec<LowStockGoods>, sqlx::Error> {
+        GoodsTable::low_stock(self, include_zero_reorder).await
+    }
+
+    async fn tag_counts(&self) -> Result<Vec<TagCount>, sqlx::Error> {
+        GoodsTable::tag_counts(self).await
+    }
+
+    async fn duplicate_material_codes(&self, normalization: Normalization) -> Result<Vec<DuplicateMaterialCode>, sqlx::Error> {
+        GoodsTable::duplicate_material_codes(self, normalization).await
+    }
+
+    async fn update(&self, params: GoodsSearchParams, update_request: UpdateGoodRequest, options: BulkWriteOptions) -> Result<Vec<Good>, TableError> {
+        GoodsTable::update(self, params, update_request, options).await
+    }
+
+    async fn update_by_id(&self, goods_id: i32, update_request: UpdateGoodRequest) -> Result<Option<Good>, TableError> {
+        GoodsTable::update_by_id(self, goods_id, update_request).await
+    }
+
+    async fn delete(&self, params: GoodsSearchParams, options: BulkWriteOptions) -> Result<Vec<i32>, TableError> {
+        GoodsTable::delete(self, params, options).await
+    }
+
+    async fn delete_by_ids(&self, goods_ids: &[i32]) -> Result<GoodsDeleteByIdsResult, sqlx::Error> {
+        GoodsTable::delete_by_ids(self, goods_ids).await
+    }
+
+    fn cache_stats(&self) -> GoodsCacheStats {
+        self.cache().stats()
+    }
+}
+
+#[async_trait]
+impl InventoryRepository for InventoryTable {
+    async fn search(&self, params: InventorySearchParams) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
+        InventoryTable::search(self, params).await
+    }
+
+    fn search_stream(&self, params: &InventorySearchParams) -> BoxStream<'static, Result<InventoryItemWithGoods, sqlx::Error>> {
+        InventoryTable::search_stream(self, params).boxed()
+    }
+
+    async fn search_paginated(&self, params: InventorySearchParams) -> Result<PaginatedResponse<InventoryItemWithGoods>, sqlx::Error> {
+        InventoryTable::search_paginated(self, params).await
+    }
+
+    async fn stats(&self, params: &InventorySearchParams) -> Result<InventoryStats, sqlx::Error> {
+        InventoryTable::stats(self, params).await
+    }
+
+    async fn summary(&self, params: &InventorySummaryParams) -> Result<Vec<InventorySummary>, sqlx::Error> {
+        InventoryTable::summary(self, params).await
+    }
+
+    async fn valuation(&self, params: &ValuationParams) -> Result<ValuationReport, sqlx::Error> {
+        InventoryTable::valuation(self, params).await
+    }
+
+    async fn get_by_item_id(&self, item_id: i32) -> Result<Option<InventoryItemWithGoods>, sqlx::Error> {
+        InventoryTable::get_by_item_id(self, item_id).await
+    }
+
+    async fn get_by_item_ids(&self, item_ids: &[i32]) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
+        InventoryTable::get_by_item_ids(self, item_ids).await
+    }
+
+    async fn insert(
+        &self,
+        request: CreateInventoryRequest,
+        on_duplicate: InventoryDuplicateMode,
+        allow_inactive: bool,
+    ) -> Result<(InventoryItemWithGoods, InventoryInsertOutcome), TableError> {
+        InventoryTable::insert(self, request, on_duplicate, allow_inactive).await
+    }
+
+    async fn insert_batch(
+        &self,
+        entries: Vec<(usize, CreateInventoryRequest)>,
+        failures: Vec<BatchItemError>,
+        abort_on_failure: bool,
+        allow_inactive: bool,
+    ) -> Result<InventoryBatchResult, TableError> {
+        InventoryTable::insert_batch(self, entries, failures, abort_on_failure, allow_inactive).await
+    }
+
+    async fn update(&self, params: InventorySearchParams, update_request: UpdateInventoryRequest, options: BulkWriteOptions) -> Result<Vec<InventoryItemWithGoods>, TableError> {
+        InventoryTable::update(self, params, update_request, options).await
+    }
+
+    async fn update_by_id(&self, item_id: i32, update_request: UpdateInventoryRequest) -> Result<Option<InventoryItemWithGoods>, sqlx::Error> {
+        InventoryTable::update_by_id(self, item_id, update_request).await
+    }
+
+    async fn adjust(&self, item_id: i32, delta: i32, reason: &str) -> Result<InventoryItemWithGoods, TableError> {
+        InventoryTable::adjust(self, item_id, delta, reason).await
+    }
+
+    async fn consume(&self, request: &ConsumeInventoryRequest) -> Result<ConsumeResult, TableError> {
+        InventoryTable::consume(self, request).await
+    }
+
+    async fn delete(&self, params: InventorySearchParams, options: BulkWriteOptions) -> Result<Vec<i32>, TableError> {
+        InventoryTable::delete(self, params, options).await
+    }
+
+    async fn delete_by_ids(&self, item_ids: &[i32]) -> Result<InventoryDeleteByIdsResult, sqlx::Error> {
+        InventoryTable::delete_by_ids(self, item_ids).await
+    }
+
+    async fn merge_duplicates(&self, dry_run: bool) -> Result<Vec<MergedInventoryGroup>, sqlx::Error> {
+        InventoryTable::merge_duplicates(self, dry_run).await
+    }
+
+    async fn merge_goods(&self, source_id: i32, target_id: i32) -> Result<Option<GoodsMergeResult>, sqlx::Error> {
+        InventoryTable::merge_goods(self, source_id, target_id).await
+    }
+
+    async fn purge_expired(&self, cutoff: DateTime<Utc>, mode: ExpiredPurgeMode, dry_run: bool) -> Result<Vec<i32>, sqlx::Error> {
+        InventoryTable::purge_expired(self, cutoff, mode, dry_run).await
+    }
+}
@@ -0,0 +1,196 @@
+// src/tables/reservations_table.rs
+//
+// Stock held for pending orders. A reservation is active until it expires, is released,
+// or is committed into an actual quantity decrement.
+use super::error::TableError;
+use super::inventory_table::{set_expiry_fields, InventoryItemWithGoods};
+use super::stock_movements_table::{NewStockMovement, StockMovementsTable};
+use super::webhook_outbox_table::{InventoryEvent, InventoryEventType, WebhookOutboxTable};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+/// SQL condition selecting reservations that still hold stock
+pub const ACTIVE_RESERVATION_CONDITION: &str = "(r.expires_at IS NULL OR r.expires_at > now())";
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Reservation {
+    pub reservation_id: i32,
+    pub item_id: i32,
+    pub quantity: i32,
+    pub reference: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateReservationRequest {
+    pub item_id: i32,
+    pub quantity: i32,
+    pub reference: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone)]
+pub struct ReservationsTable {
+    pool: PgPool,
+    /// Let a commit take the item below zero
+    allow_negative_stock: bool,
+    /// Write an adjusted event to the webhook outbox for each commit
+    webhook_events: bool,
+}
+
+impl ReservationsTable {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool, allow_negative_stock: false, webhook_events: false }
+    }
+
+    pub fn with_negative_stock(mut self, allowed: bool) -> Self {
+        self.allow_negative_stock = allowed;
+        self
+    }
+
+    pub fn with_webhook_events(mut self, enabled: bool) -> Self {
+        self.webhook_events = enabled;
+        self
+    }
+
+    /// Reserve stock on an inventory item, refusing to reserve more than is still available
+    pub async fn create(&self, request: &CreateReservationRequest) -> Result<Reservation, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Lock the item so concurrent reservations see each other
+        let (quantity,) = sqlx::query_as::<_, (i32,)>("SELECT quantity FROM inventory WHERE item_id = $1 FOR UPDATE")
+            .bind(request.item_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        let (reserved,) = sqlx::query_as::<_, (i64,)>(&format!(
+            "SELECT COALESCE(SUM(r.quantity), 0) FROM reservations r WHERE r.item_id = $1 AND {}",
+            ACTIVE_RESERVATION_CONDITION
+        ))
+        .bind(request.item_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let available = quantity as i64 - reserved;
+        if request.quantity as i64 > available {
+            tx.rollback().await?;
+            return Err(TableError::InsufficientAvailableStock {
+                item_id: request.item_id,
+                requested: request.quantity,
+                available,
+            });
+        }
+
+        let reservation = sqlx::query_as::<_, Reservation>(
+            r#"
+            INSERT INTO reservations (item_id, quantity, reference, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING reservation_id, item_id, quantity, reference, expires_at, created_at
+            "#
+        )
+        .bind(request.item_id)
+        .bind(request.quantity)
+        .bind(&request.reference)
+        .bind(request.expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(reservation)
+    }
+
+    /// Release a reservation without touching stock
+    pub async fn release(&self, reservation_id: i32) -> Result<Option<Reservation>, sqlx::Error> {
+        sqlx::query_as::<_, Reservation>(
+            r#"
+            DELETE FROM reservations
+            WHERE reservation_id = $1
+            RETURNING reservation_id, item_id, quantity, reference, expires_at, created_at
+            "#
+        )
+        .bind(reservation_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Turn an active reservation into a quantity decrement in one transaction
+    pub async fn commit(&self, reservation_id: i32) -> Result<InventoryItemWithGoods, TableError> {
+        let mut tx = self.pool.begin().await?;
+
+        let reservation = sqlx::query_as::<_, Reservation>(&format!(
+            r#"
+            DELETE FROM reservations r
+            WHERE r.reservation_id = $1 AND {}
+            RETURNING r.reservation_id, r.item_id, r.quantity, r.reference, r.expires_at, r.created_at
+            "#,
+            ACTIVE_RESERVATION_CONDITION
+        ))
+        .bind(reservation_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+        let mut item = sqlx::query_as::<_, InventoryItemWithGoods>(
+            r#"
+            WITH committed AS (
+                UPDATE inventory
+                SET quantity = quantity - $1, updated_at = now()
+                WHERE item_id = $2 AND ($3 OR quantity >= $1)
+                RETURNING item_id, goods_id, quantity, expired_date, lot_number, created_at, updated_at
+            )
+            SELECT
+                c.item_id, c.goods_id, c.quantity, c.expired_date, c.lot_number, c.created_at, c.updated_at,
+                g.material_code, g.goods_name, g.description, g.price,
+                g.volumn_l, g.mass_g, g.mass_base, g.volumn_base
+            FROM committed c
+            INNER JOIN goods g ON c.goods_id = g.goods_id
+            "#
+        )
+        .bind(reservation.quantity)
+        .bind(reservation.item_id)
+        .bind(self.allow_negative_stock)
+        .fetch_optional(&mut *tx)
+        .await?;
+        set_expiry_fields(&mut tx, item.as_mut_slice()).await?;
+
+        // The item lost stock some other way since the reservation was made
+        let Some(item) = item else {
+            let (current_quantity,) = sqlx::query_as::<_, (i32,)>("SELECT quantity FROM inventory WHERE item_id = $1")
+                .bind(reservation.item_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            tx.rollback().await?;
+            return Err(TableError::InsufficientStock {
+                item_id: reservation.item_id,
+                current_quantity,
+                delta: -reservation.quantity,
+            });
+        };
+
+        let reason = match &reservation.reference {
+            Some(reference) => format!("reservation {} committed ({})", reservation.reservation_id, reference),
+            None => format!("reservation {} committed", reservation.reservation_id),
+        };
+        StockMovementsTable::record(&mut tx, &[NewStockMovement {
+            item_id: item.item_id,
+            delta: -reservation.quantity,
+            reason,
+            resulting_quantity: item.quantity,
+        }]).await?;
+        if self.webhook_events {
+            WebhookOutboxTable::enqueue(&mut tx, &[InventoryEvent::new(
+                InventoryEventType::Adjusted, item.item_id, item.goods_id, Some(item.quantity + reservation.quantity), Some(item.quantity),
+            )]).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(item)
+    }
+}
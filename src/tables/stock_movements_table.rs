@@ -0,0 +1,125 @@
+// src/tables/stock_movements_table.rs
+//
+// History of quantity changes for inventory items. Rows are written by `InventoryTable`
+// inside the same transaction as the change itself. `item_id` deliberately has no
+// foreign key to `inventory`, so the history outlives deleted items.
+use crate::utils::pagination::{PaginatedResponse, PaginationParams};
+use crate::utils::query_builder::SearchQueryBuilder;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StockMovement {
+    pub movement_id: i64,
+    pub item_id: i32,
+    pub delta: i32,
+    pub reason: String,
+    pub resulting_quantity: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A movement to record alongside a quantity change
+#[derive(Debug, Clone)]
+pub struct NewStockMovement {
+    pub item_id: i32,
+    pub delta: i32,
+    pub reason: String,
+    pub resulting_quantity: i32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MovementSearchParams {
+    pub item_id: Option<i32>,
+    pub min_created_at: Option<DateTime<Utc>>,
+    pub max_created_at: Option<DateTime<Utc>>,
+    pub pagination: Option<PaginationParams>,
+}
+
+#[derive(Clone)]
+pub struct StockMovementsTable {
+    pool: PgPool,
+}
+
+impl StockMovementsTable {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record movements on the caller's connection so they commit or roll back with the change
+    pub async fn record(conn: &mut PgConnection, movements: &[NewStockMovement]) -> Result<(), sqlx::Error> {
+        if movements.is_empty() {
+            return Ok(());
+        }
+
+        let item_ids: Vec<i32> = movements.iter().map(|movement| movement.item_id).collect();
+        let deltas: Vec<i32> = movements.iter().map(|movement| movement.delta).collect();
+        let reasons: Vec<String> = movements.iter().map(|movement| movement.reason.clone()).collect();
+        let resulting: Vec<i32> = movements.iter().map(|movement| movement.resulting_quantity).collect();
+
+        sqlx::query(
+            r#"
+            INSERT INTO stock_movements (item_id, delta, reason, resulting_quantity)
+            SELECT * FROM UNNEST($1::int4[], $2::int4[], $3::text[], $4::int4[])
+            "#
+        )
+        .bind(&item_ids)
+        .bind(&deltas)
+        .bind(&reasons)
+        .bind(&resulting)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Search movements, newest first
+    pub async fn search(&self, params: &MovementSearchParams) -> Result<Vec<StockMovement>, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new(
+            "SELECT movement_id, item_id, delta, reason, resulting_quantity, created_at FROM stock_movements WHERE 1=1".to_string()
+        );
+        Self::add_search_conditions(&mut builder, params);
+
+        let mut query = builder.build(Some("created_at DESC, movement_id DESC"));
+
+        if let Some(pagination) = &params.pagination {
+            query.push_str(&pagination.to_sql());
+        }
+
+        builder.bind_values(sqlx::query_as::<_, StockMovement>(&query))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn count(&self, params: &MovementSearchParams) -> Result<i64, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new("SELECT COUNT(*) FROM stock_movements WHERE 1=1".to_string());
+        Self::add_search_conditions(&mut builder, params);
+
+        let query = builder.build(None);
+        let (count,) = builder.bind_values(sqlx::query_as::<_, (i64,)>(&query))
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Movement history is unbounded, so it is always served one page at a time
+    pub async fn search_paginated(&self, mut params: MovementSearchParams) -> Result<PaginatedResponse<StockMovement>, sqlx::Error> {
+        let pagination = params.pagination.clone().unwrap_or_default();
+        params.pagination = Some(pagination.clone());
+
+        let (movements, total_count) = tokio::try_join!(
+            self.search(&params),
+            self.count(&params)
+        )?;
+
+        Ok(PaginatedResponse::new(movements, &pagination, Some(total_count as u64)))
+    }
+
+    fn add_search_conditions(builder: &mut SearchQueryBuilder, params: &MovementSearchParams) {
+        builder.add_optional_condition("item_id = ?", params.item_id);
+        builder.add_optional_condition("created_at >= ?", params.min_created_at);
+        builder.add_optional_condition("created_at <= ?", params.max_created_at);
+    }
+}
@@ -0,0 +1,124 @@
+// src/tables/stock_snapshots_table.rs
+//
+// Daily per-goods stock totals for historical reporting. A snapshot covers every goods row,
+// including goods without stock, so a time series has no gaps. Snapshots are keyed by
+// (snapshot_date, goods_id) and re-taking a day overwrites it with the current totals.
+use crate::utils::pagination::{PaginatedResponse, PaginationParams};
+use crate::utils::query_builder::SearchQueryBuilder;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StockSnapshot {
+    pub snapshot_date: NaiveDate,
+    pub goods_id: i32,
+    pub total_quantity: i64,
+    /// Total quantity times the goods price on the snapshot date
+    pub total_value: rust_decimal::Decimal,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Result of taking a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SnapshotSummary {
+    pub snapshot_date: NaiveDate,
+    /// Goods rows written for the date
+    pub goods_count: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StockHistoryParams {
+    pub goods_id: Option<i32>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub pagination: Option<PaginationParams>,
+}
+
+#[derive(Clone)]
+pub struct StockSnapshotsTable {
+    pool: PgPool,
+}
+
+impl StockSnapshotsTable {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record the current totals of every good under `snapshot_date`, replacing an earlier
+    /// snapshot of the same date
+    pub async fn take(&self, snapshot_date: NaiveDate) -> Result<SnapshotSummary, sqlx::Error> {
+        let goods_count = sqlx::query(
+            r#"
+            INSERT INTO stock_snapshots (snapshot_date, goods_id, total_quantity, total_value)
+            SELECT $1, g.goods_id,
+                   COALESCE(SUM(i.quantity), 0)::BIGINT,
+                   COALESCE(SUM(i.quantity * g.price), 0)
+            FROM goods g
+            LEFT JOIN inventory i ON i.goods_id = g.goods_id
+            GROUP BY g.goods_id
+            ON CONFLICT (snapshot_date, goods_id) DO UPDATE
+            SET total_quantity = EXCLUDED.total_quantity,
+                total_value = EXCLUDED.total_value,
+                created_at = now()
+            "#
+        )
+        .bind(snapshot_date)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(SnapshotSummary { snapshot_date, goods_count })
+    }
+
+    /// Snapshots ordered by goods, then date
+    pub async fn search(&self, params: &StockHistoryParams) -> Result<Vec<StockSnapshot>, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new(
+            "SELECT snapshot_date, goods_id, total_quantity, total_value, created_at FROM stock_snapshots WHERE 1=1".to_string()
+        );
+        Self::add_search_conditions(&mut builder, params);
+
+        let mut query = builder.build(Some("goods_id ASC, snapshot_date ASC"));
+
+        if let Some(pagination) = &params.pagination {
+            query.push_str(&pagination.to_sql());
+        }
+
+        builder.bind_values(sqlx::query_as::<_, StockSnapshot>(&query))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn count(&self, params: &StockHistoryParams) -> Result<i64, sqlx::Error> {
+        let mut builder = SearchQueryBuilder::new("SELECT COUNT(*) FROM stock_snapshots WHERE 1=1".to_string());
+        Self::add_search_conditions(&mut builder, params);
+
+        let query = builder.build(None);
+        let (count,) = builder.bind_values(sqlx::query_as::<_, (i64,)>(&query))
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// History grows by one row per good per day, so it is always served one page at a time
+    pub async fn search_paginated(&self, mut params: StockHistoryParams) -> Result<PaginatedResponse<StockSnapshot>, sqlx::Error> {
+        let pagination = params.pagination.clone().unwrap_or_default();
+        params.pagination = Some(pagination.clone());
+
+        let (snapshots, total_count) = tokio::try_join!(
+            self.search(&params),
+            self.count(&params)
+        )?;
+
+        Ok(PaginatedResponse::new(snapshots, &pagination, Some(total_count as u64)))
+    }
+
+    fn add_search_conditions(builder: &mut SearchQueryBuilder, params: &StockHistoryParams) {
+        builder.add_optional_condition("goods_id = ?", params.goods_id);
+        builder.add_optional_condition("snapshot_date >= ?", params.from);
+        builder.add_optional_condition("snapshot_date <= ?", params.to);
+    }
+}
@@ -0,0 +1,162 @@
+// src/tables/sync_table.rs
+//
+// Change feed for incremental sync. Live rows are ordered by `(updated_at, id)`; deletes
+// are written to `sync_tombstones` in the same transaction as the delete, so both kinds
+// of change share one cursor.
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+
+/// Tables exposed through the sync endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncEntity {
+    Goods,
+    Inventory,
+}
+
+impl SyncEntity {
+    fn table_name(&self) -> &'static str {
+        match self {
+            SyncEntity::Goods => "goods",
+            SyncEntity::Inventory => "inventory",
+        }
+    }
+
+    fn id_column(&self) -> &'static str {
+        match self {
+            SyncEntity::Goods => "goods_id",
+            SyncEntity::Inventory => "item_id",
+        }
+    }
+}
+
+/// Position in the change feed: everything up to and including this change has been seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncCursor {
+    pub changed_at: DateTime<Utc>,
+    pub id: i32,
+}
+
+impl SyncCursor {
+    /// Start after every change made at or before `since`
+    pub fn since(since: DateTime<Utc>) -> Self {
+        Self { changed_at: since, id: i32::MAX }
+    }
+
+    /// Opaque, URL-safe token handed to clients as `next_cursor`
+    pub fn encode(&self) -> String {
+        hex::encode(format!("{}|{}", self.changed_at.to_rfc3339(), self.id))
+    }
+
+    pub fn decode(token: &str) -> Result<Self, String> {
+        let invalid = || "Invalid cursor".to_string();
+
+        let decoded = hex::decode(token).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (changed_at, id) = decoded.split_once('|').ok_or_else(invalid)?;
+
+        Ok(Self {
+            changed_at: DateTime::parse_from_rfc3339(changed_at).map_err(|_| invalid())?.with_timezone(&Utc),
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Where to resume the change feed and how many changes to return
+#[derive(Debug, Clone, Copy)]
+pub struct SyncParams {
+    pub cursor: SyncCursor,
+    pub limit: i64,
+}
+
+/// Ids changed after a cursor, split into live and deleted rows
+#[derive(Debug, Clone)]
+pub struct ChangeSet {
+    pub updated_ids: Vec<i32>,
+    pub deleted_ids: Vec<i32>,
+    pub next_cursor: SyncCursor,
+    pub has_more: bool,
+}
+
+/// One page of the change feed as returned to clients
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SyncPage<T> {
+    pub items: Vec<T>,
+    pub deleted_ids: Vec<i32>,
+    /// Pass back as `cursor` to continue; stays put when there are no new changes
+    pub next_cursor: String,
+    pub has_more: bool,
+}
+
+#[derive(Clone)]
+pub struct SyncTable {
+    pool: PgPool,
+}
+
+impl SyncTable {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record deleted rows on the caller's connection so the tombstones commit with the delete
+    pub async fn record_deleted(conn: &mut PgConnection, entity: SyncEntity, ids: &[i32]) -> Result<(), sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query("INSERT INTO sync_tombstones (table_name, row_id) SELECT $1, * FROM UNNEST($2::int4[])")
+            .bind(entity.table_name())
+            .bind(ids)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Up to `limit` changes after the cursor, oldest first
+    pub async fn changes(&self, entity: SyncEntity, params: SyncParams) -> Result<ChangeSet, sqlx::Error> {
+        let SyncParams { cursor, limit } = params;
+
+        let query = format!(
+            r#"
+            SELECT id, changed_at, deleted FROM (
+                SELECT {id} AS id, updated_at AS changed_at, false AS deleted FROM {table}
+                UNION ALL
+                SELECT row_id, deleted_at, true FROM sync_tombstones WHERE table_name = $1
+            ) changes
+            WHERE (changed_at, id) > ($2, $3)
+            ORDER BY changed_at ASC, id ASC
+            LIMIT $4
+            "#,
+            id = entity.id_column(),
+            table = entity.table_name()
+        );
+
+        // Fetch one extra row to tell whether another page follows
+        let mut rows = sqlx::query_as::<_, (i32, DateTime<Utc>, bool)>(&query)
+            .bind(entity.table_name())
+            .bind(cursor.changed_at)
+            .bind(cursor.id)
+            .bind(limit + 1)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let has_more = rows.len() as i64 > limit;
+        rows.truncate(limit as usize);
+
+        let next_cursor = rows
+            .last()
+            .map(|(id, changed_at, _)| SyncCursor { changed_at: *changed_at, id: *id })
+            .unwrap_or(cursor);
+
+        let (deleted, updated): (Vec<_>, Vec<_>) = rows.into_iter().partition(|(_, _, deleted)| *deleted);
+
+        Ok(ChangeSet {
+            updated_ids: updated.into_iter().map(|(id, _, _)| id).collect(),
+            deleted_ids: deleted.into_iter().map(|(id, _, _)| id).collect(),
+            next_cursor,
+            has_more,
+        })
+    }
+}
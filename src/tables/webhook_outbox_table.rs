@@ -0,0 +1,182 @@
+// src/tables/webhook_outbox_table.rs
+//
+// Outbox of inventory events for webhook delivery. `InventoryTable` and `ReservationsTable`
+// write events on the transaction of the change itself, so an event is stored exactly when
+// the change commits and is still delivered after a restart. Delivery is done by `webhooks::spawn_delivery`.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::time::Duration;
+
+/// How long a claimed event is hidden from other delivery workers
+const CLAIM_LEASE: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InventoryEventType {
+    #[serde(rename = "inventory.created")]
+    Created,
+    #[serde(rename = "inventory.updated")]
+    Updated,
+    #[serde(rename = "inventory.deleted")]
+    Deleted,
+    #[serde(rename = "inventory.adjusted")]
+    Adjusted,
+}
+
+impl InventoryEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InventoryEventType::Created => "inventory.created",
+            InventoryEventType::Updated => "inventory.updated",
+            InventoryEventType::Deleted => "inventory.deleted",
+            InventoryEventType::Adjusted => "inventory.adjusted",
+        }
+    }
+}
+
+/// Webhook payload; `old_quantity` is null for created items and `new_quantity` for deleted ones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryEvent {
+    pub event_type: InventoryEventType,
+    pub item_id: i32,
+    pub goods_id: i32,
+    pub old_quantity: Option<i32>,
+    pub new_quantity: Option<i32>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl InventoryEvent {
+    pub fn new(event_type: InventoryEventType, item_id: i32, goods_id: i32, old_quantity: Option<i32>, new_quantity: Option<i32>) -> Self {
+        Self {
+            event_type,
+            item_id,
+            goods_id,
+            old_quantity,
+            new_quantity,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// A pending event claimed for delivery
+#[derive(Debug, Clone, FromRow)]
+pub struct OutboxEvent {
+    pub event_id: i64,
+    pub event_type: String,
+    /// The JSON body, exactly as it is signed and sent
+    pub payload: String,
+    pub attempts: i32,
+    pub delivered_to: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct WebhookOutboxTable {
+    pool: PgPool,
+}
+
+impl WebhookOutboxTable {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store events on the caller's connection so they commit or roll back with the change
+    pub async fn enqueue(conn: &mut PgConnection, events: &[InventoryEvent]) -> Result<(), sqlx::Error> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let event_types: Vec<&str> = events.iter().map(|event| event.event_type.as_str()).collect();
+        let payloads = events.iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_outbox (event_type, payload)
+            SELECT event_type, payload::jsonb FROM UNNEST($1::text[], $2::text[]) AS e(event_type, payload)
+            "#
+        )
+        .bind(&event_types)
+        .bind(&payloads)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Claim up to `limit` due events, oldest first. Claimed events are leased for a while so
+    /// several API instances can share the outbox without sending the same event twice.
+    pub async fn claim_due(&self, limit: i64) -> Result<Vec<OutboxEvent>, sqlx::Error> {
+        sqlx::query_as::<_, OutboxEvent>(
+            r#"
+            UPDATE webhook_outbox
+            SET next_attempt_at = now() + make_interval(secs => $2)
+            WHERE event_id IN (
+                SELECT event_id FROM webhook_outbox
+                WHERE delivered_at IS NULL AND failed_at IS NULL AND next_attempt_at <= now()
+                ORDER BY event_id ASC
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING event_id, event_type, payload::text AS payload, attempts, delivered_to
+            "#
+        )
+        .bind(limit)
+        .bind(CLAIM_LEASE.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await
+        .map(|mut events| {
+            events.sort_by_key(|event| event.event_id);
+            events
+        })
+    }
+
+    pub async fn mark_delivered(&self, event_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE webhook_outbox SET attempts = attempts + 1, delivered_at = now(), last_error = NULL WHERE event_id = $1"
+        )
+        .bind(event_id)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    /// Record a failed attempt. The event is retried after `retry_in`, or dead-lettered when it is `None`.
+    pub async fn mark_failed(
+        &self,
+        event_id: i64,
+        delivered_to: &[String],
+        error: &str,
+        retry_in: Option<Duration>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_outbox
+            SET
+                attempts = attempts + 1,
+                delivered_to = $2,
+                last_error = $3,
+                next_attempt_at = now() + make_interval(secs => COALESCE($4, 0)),
+                failed_at = CASE WHEN $4 IS NULL THEN now() END
+            WHERE event_id = $1
+            "#
+        )
+        .bind(event_id)
+        .bind(delivered_to)
+        .bind(error)
+        .bind(retry_in.map(|delay| delay.as_secs_f64()))
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    /// Delete delivered events older than `retention`; dead-lettered events are kept for inspection
+    pub async fn delete_delivered(&self, retention: Duration) -> Result<u64, sqlx::Error> {
+        sqlx::query("DELETE FROM webhook_outbox WHERE delivered_at <= now() - make_interval(secs => $1)")
+            .bind(retention.as_secs_f64())
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
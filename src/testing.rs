@@ -0,0 +1,1315 @@
+// src/testing.rs
+//
+// In-memory goods and inventory for exercising the handlers without PostgreSQL (the `testing`
+// feature). `InMemoryStore` keeps both tables behind one lock, so inventory sees the goods it
+// references, and follows the database behaviour the handlers depend on: unique material codes
+// and barcodes, duplicate-item modes, guarded adjustments, FIFO consumption, bulk-write limits
+// and dry runs. Fuzzy name searches are scored the way pg_trgm's `similarity` does. Every
+// write works on a copy of the store that replaces it only on success, the way the tables use
+// a transaction.
+//
+// Not modelled: categories (`category_name` and `include_subcategories` are ignored, so
+// `category_id` matches only directly), reservations, stock movements, webhook events and the
+// goods cache.
+use crate::config::AppConfig;
+use crate::database::Database;
+use crate::server::AppState;
+use crate::tables::{
+    BatchInventoryItem, BatchItemError, BlockedGoods, BulkWriteOptions, ConsumeInventoryRequest, ConsumeResult,
+    CreateGoodRequest, CreateInventoryRequest, DuplicateMaterialCode, ExpiredPurgeMode, Good, GoodWithStock, GoodsBatchResult, GoodsCacheStats, GoodsConflictMode,
+    GoodsDeleteByIdsResult, GoodsMergeResult, GoodsRepository, GoodsSearchParams, GoodsSortColumn, InventoryBatchResult, InventoryDeleteByIdsResult,
+    InventoryDuplicateMode, InventoryInsertOutcome, InventoryItem, InventoryItemWithGoods, InventoryRepository, InventorySearchParams,
+    InventorySortColumn, InventoryStats, InventorySummary, InventorySummaryParams, LowStockGoods, MergedGoodsItem, MergedInventoryGroup, SavedGood,
+    SkippedGood, StaleGoods, SummarySortColumn, TableError, TagCount, UpdateGoodRequest, UpdateInventoryRequest, ValuationGroupBy,
+    ValuationParams, ValuationReport, ValuationRow, merge_goods_details, plan_consumption,
+};
+use crate::utils::pagination::{PaginatedResponse, PaginationParams};
+use crate::utils::query_builder::{BindValue, Keyset};
+use crate::utils::sorting::SortOrder;
+use crate::utils::string_utils::{normalize_tags, MatchMode, Normalization};
+use async_trait::async_trait;
+use chrono::{DateTime, Days, Utc};
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
+use rust_decimal::Decimal;
+use sqlx::error::{DatabaseError, ErrorKind};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Debug, Clone, Default)]
+struct Tables {
+    goods: Vec<Good>,
+    items: Vec<InventoryItem>,
+    last_goods_id: i32,
+    last_item_id: i32,
+}
+
+/// Goods and inventory shared by an `InMemoryGoods` and `InMemoryInventory` pair
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryStore {
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn goods(&self) -> Arc<dyn GoodsRepository> {
+        Arc::new(InMemoryGoods { store: self.clone() })
+    }
+
+    pub fn inventory(&self) -> Arc<dyn InventoryRepository> {
+        Arc::new(InMemoryInventory { store: self.clone(), allow_negative_stock: false })
+    }
+
+    /// State for `Server::create_router` serving goods and inventory from this store. The
+    /// remaining tables sit on a pool that connects on first use, so handlers touching them
+    /// fail with a database error instead of reaching a server. The audit log has no fake and
+    /// is turned off, so that writes do not fail on it.
+    pub fn app_state(&self, mut config: AppConfig) -> anyhow::Result<AppState> {
+        config.server.audit_log_enabled = false;
+        let database = Database::connect_lazy(&config.database)?;
+        let inventory = Arc::new(InMemoryInventory {
+            store: self.clone(),
+            allow_negative_stock: config.inventory.allow_negative_stock,
+        });
+        Ok(AppState::new(database, Arc::new(config)).with_repositories(self.goods(), inventory))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Run `write` on a copy of the tables and keep the copy only when it succeeds and `commit` is set
+    fn write<T, E>(&self, commit: bool, write: impl FnOnce(&mut Tables) -> Result<T, E>) -> Result<T, E> {
+        let mut tables = self.lock();
+        let mut draft = tables.clone();
+        let result = write(&mut draft)?;
+        if commit {
+            *tables = draft;
+        }
+        Ok(result)
+    }
+}
+
+pub struct InMemoryGoods {
+    store: InMemoryStore,
+}
+
+pub struct InMemoryInventory {
+    store: InMemoryStore,
+    allow_negative_stock: bool,
+}
+
+/// A unique violation as PostgreSQL reports it, so handlers map it like the real one
+#[derive(Debug)]
+struct UniqueViolation {
+    constraint: &'static str,
+    message: String,
+}
+
+impl std::fmt::Display for UniqueViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for UniqueViolation {}
+
+impl DatabaseError for UniqueViolation {
+    fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed("23505"))
+    }
+
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self
+    }
+
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::UniqueViolation
+    }
+
+    fn constraint(&self) -> Option<&str> {
+        Some(self.constraint)
+    }
+}
+
+fn unique_violation(constraint: &'static str) -> sqlx::Error {
+    sqlx::Error::Database(Box::new(UniqueViolation {
+        constraint,
+        message: format!("duplicate key value violates unique constraint \"{}\"", constraint),
+    }))
+}
+
+/// The requested page of `rows`, else at most `limit` of them, like `limit_clause`
+fn paginate<T>(rows: Vec<T>, pagination: Option<&PaginationParams>, limit: Option<u32>) -> Vec<T> {
+    match (pagination, limit) {
+        (Some(pagination), _) => rows.into_iter().skip(pagination.offset() as usize).take(pagination.limit() as usize).collect(),
+        (None, Some(limit)) => rows.into_iter().take(limit as usize).collect(),
+        (None, None) => rows,
+    }
+}
+
+fn directed(ordering: Ordering, sort_order: Option<SortOrder>) -> Ordering {
+    match sort_order {
+        Some(SortOrder::Desc) => ordering.reverse(),
+        _ => ordering,
+    }
+}
+
+/// Whether a row comes after `keyset`, like `add_keyset_condition`. `value` is the row's sort
+/// value (None for a NULL, which sorts last), or None for every row when `sorted` is false and
+/// rows are ordered by id alone.
+fn after_keyset(value: Option<BindValue>, id: i32, keyset: &Keyset, sort_order: Option<SortOrder>, sorted: bool) -> bool {
+    if !sorted {
+        return directed(id.cmp(&keyset.id), sort_order) == Ordering::Greater;
+    }
+
+    let ordering = match (&value, &keyset.value) {
+        (Some(value), Some(after)) => directed(value.partial_cmp(after).unwrap_or(Ordering::Equal), sort_order),
+        (value, after) => value.is_none().cmp(&after.is_none()),
+    };
+    ordering.then(id.cmp(&keyset.id)) == Ordering::Greater
+}
+
+/// pg_trgm's trigrams: every lowercased alphanumeric word padded with two spaces in front and one behind
+fn trigrams(text: &str) -> BTreeSet<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .flat_map(|word| {
+            let padded: Vec<char> = format!("  {} ", word).chars().collect();
+            padded.windows(3).map(|window| window.iter().collect::<String>()).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// pg_trgm's `similarity`: shared trigrams over all trigrams of either text
+fn similarity(a: &str, b: &str) -> f32 {
+    let (a, b) = (trigrams(a), trigrams(b));
+    let all = a.union(&b).count();
+    if all == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / all as f32
+}
+
+/// The threshold and searched name of a fuzzy search
+fn fuzzy_search(params: &GoodsSearchParams) -> Option<(f32, &str)> {
+    Some((params.fuzzy_threshold?, params.goods_name.as_deref()?))
+}
+
+/// Best match first, like `ORDER BY similarity DESC`
+fn by_similarity(a: Option<f32>, b: Option<f32>) -> Ordering {
+    b.unwrap_or_default().total_cmp(&a.unwrap_or_default())
+}
+
+/// `add_optional_match`: case-insensitive unless literal, with `*` matching everything
+fn text_matches(value: &str, filter: Option<&str>, mode: MatchMode) -> bool {
+    let Some(filter) = filter.filter(|filter| *filter != "*") else {
+        return true;
+    };
+    if mode == MatchMode::Literal {
+        return value == filter;
+    }
+    let (value, filter) = (value.to_lowercase(), filter.to_lowercase());
+    match mode {
+        MatchMode::Exact | MatchMode::Literal => value == filter,
+        MatchMode::Prefix => value.starts_with(&filter),
+        MatchMode::Contains => value.contains(&filter),
+    }
+}
+
+fn at_least<T: PartialOrd>(value: T, bound: Option<T>) -> bool {
+    bound.is_none_or(|bound| value >= bound)
+}
+
+fn at_most<T: PartialOrd>(value: T, bound: Option<T>) -> bool {
+    bound.is_none_or(|bound| value <= bound)
+}
+
+fn good_matches(good: &Good, params: &GoodsSearchParams) -> bool {
+    if params.active_filter().is_some_and(|active| good.is_active != active) {
+        return false;
+    }
+    if params.is_get_all() {
+        return true;
+    }
+
+    let tags = good.tags.as_deref().unwrap_or_default();
+    params.goods_id.is_none_or(|id| good.goods_id == id)
+        && params.goods_ids.as_ref().is_none_or(|ids| ids.contains(&good.goods_id))
+        && text_matches(&good.material_code, params.material_code.as_deref(), params.match_mode)
+        && (params.fuzzy_threshold.is_some() || text_matches(&good.goods_name, params.goods_name.as_deref(), params.match_mode))
+        && params.description_contains.as_deref().is_none_or(|term| {
+            good.description.iter().flatten().any(|line| text_matches(line, Some(term), MatchMode::Contains))
+        })
+        && params.barcode.as_ref().is_none_or(|barcode| good.barcode.as_ref() == Some(barcode))
+        && params.tag.as_ref().is_none_or(|tag| tags.contains(tag))
+        && params.tags_any.as_ref().is_none_or(|any| any.iter().any(|tag| tags.contains(tag)))
+        && params.tags_all.as_ref().is_none_or(|all| all.iter().all(|tag| tags.contains(tag)))
+        && params.price.is_none_or(|price| good.price == price)
+        && params.volumn_l.is_none_or(|volumn_l| good.volumn_l == volumn_l)
+        && params.mass_g.is_none_or(|mass_g| good.mass_g == mass_g)
+        && at_least(good.volumn_l, params.min_volumn_l)
+        && at_most(good.volumn_l, params.max_volumn_l)
+        && at_least(good.mass_g, params.min_mass_g)
+        && at_most(good.mass_g, params.max_mass_g)
+        && at_least(good.price, params.min_price)
+        && at_most(good.price, params.max_price)
+        && at_least(good.updated_at, params.min_updated_at)
+        && at_most(good.updated_at, params.max_updated_at)
+        && params.category_id.is_none_or(|category_id| good.category_id == Some(category_id))
+}
+
+fn sort_goods(goods: &mut [Good], params: &GoodsSearchParams) {
+    goods.sort_by(|a, b| {
+        let ordering = match params.sort_by.unwrap_or(GoodsSortColumn::GoodsId) {
+            GoodsSortColumn::GoodsId => a.goods_id.cmp(&b.goods_id),
+            GoodsSortColumn::MaterialCode => a.material_code.cmp(&b.material_code),
+            GoodsSortColumn::GoodsName => a.goods_name.cmp(&b.goods_name),
+            GoodsSortColumn::Price => a.price.cmp(&b.price),
+            GoodsSortColumn::VolumnL => a.volumn_l.cmp(&b.volumn_l),
+            GoodsSortColumn::MassG => a.mass_g.cmp(&b.mass_g),
+        };
+        directed(ordering, params.sort_order).then(a.goods_id.cmp(&b.goods_id))
+    });
+}
+
+impl Tables {
+    fn good(&self, goods_id: i32) -> Option<&Good> {
+        self.goods.iter().find(|good| good.goods_id == goods_id)
+    }
+
+    fn good_by_material_code(&self, material_code: &str) -> Option<&Good> {
+        self.goods.iter().find(|good| good.material_code == material_code)
+    }
+
+    fn item(&self, item_id: i32) -> Option<&InventoryItem> {
+        self.items.iter().find(|item| item.item_id == item_id)
+    }
+
+    fn search_goods(&self, params: &GoodsSearchParams) -> Vec<Good> {
+        let mut goods: Vec<Good> = self.goods.iter()
+            .filter(|good| good_matches(good, params))
+            .filter(|good| params.is_get_all() || params.in_stock.is_none_or(|in_stock| self.has_stock(good.goods_id) == in_stock))
+            .cloned()
+            .collect();
+
+        if let Some((threshold, term)) = fuzzy_search(params) {
+            for good in &mut goods {
+                good.similarity = Some(similarity(&good.goods_name, term));
+            }
+            goods.retain(|good| good.similarity.is_some_and(|score| score > threshold));
+            goods.sort_by(|a, b| by_similarity(a.similarity, b.similarity).then(a.goods_id.cmp(&b.goods_id)));
+            return goods;
+        }
+        sort_goods(&mut goods, params);
+
+        if let Some(keyset) = &params.cursor {
+            let column = params.sort_column();
+            goods.retain(|good| {
+                let value = column.and_then(|column| column.parse_cursor_value(&column.cursor_value(good)).ok());
+                after_keyset(value, good.goods_id, keyset, params.sort_order, column.is_some())
+            });
+        }
+        goods
+    }
+
+    /// Fail like the unique constraints would if `good` were stored
+    fn check_unique(&self, good: &Good) -> Result<(), TableError> {
+        let others = || self.goods.iter().filter(|other| other.goods_id != good.goods_id);
+
+        if others().any(|other| other.material_code == good.material_code) {
+            return Err(unique_violation("goods_material_code_key").into());
+        }
+        if let Some(barcode) = &good.barcode
+            && let Some(other) = others().find(|other| other.barcode.as_ref() == Some(barcode)) {
+            return Err(TableError::DuplicateBarcode { barcode: barcode.clone(), goods_id: other.goods_id });
+        }
+        Ok(())
+    }
+
+    fn insert_good(&mut self, request: CreateGoodRequest) -> Result<Good, TableError> {
+        let now = Utc::now();
+        let good = Good {
+            goods_id: self.last_goods_id + 1,
+            material_code: request.material_code,
+            goods_name: request.goods_name,
+            description: request.description,
+            price: request.price,
+            volumn_l: request.volumn_l,
+            mass_g: request.mass_g,
+            mass_base: request.mass_base.unwrap_or(0),
+            volumn_base: request.volumn_base.unwrap_or(0),
+            reorder_point: request.reorder_point,
+            category_id: request.category_id,
+            barcode: request.barcode,
+            tags: request.tags.as_deref().map(normalize_tags),
+            is_active: request.is_active.unwrap_or(true),
+            version: 1,
+            created_at: now,
+            updated_at: now,
+            similarity: None,
+        };
+        self.check_unique(&good)?;
+
+        self.last_goods_id = good.goods_id;
+        self.goods.push(good.clone());
+        Ok(good)
+    }
+
+    /// Replace a stored good, bumping its version
+    fn store_good(&mut self, mut good: Good) -> Result<Good, TableError> {
+        self.check_unique(&good)?;
+
+        good.version += 1;
+        good.updated_at = Utc::now();
+        if let Some(stored) = self.goods.iter_mut().find(|stored| stored.goods_id == good.goods_id) {
+            *stored = good.clone();
+        }
+        Ok(good)
+    }
+
+    fn upsert_good(&mut self, request: CreateGoodRequest) -> Result<SavedGood, TableError> {
+        let Some(existing) = self.good_by_material_code(&request.material_code).cloned() else {
+            return Ok(SavedGood { good: self.insert_good(request)?, created: true });
+        };
+
+        let good = self.store_good(Good {
+            goods_name: request.goods_name,
+            description: request.description,
+            price: request.price,
+            volumn_l: request.volumn_l,
+            mass_g: request.mass_g,
+            mass_base: request.mass_base.unwrap_or(0),
+            volumn_base: request.volumn_base.unwrap_or(0),
+            reorder_point: request.reorder_point,
+            category_id: request.category_id,
+            barcode: request.barcode,
+            tags: request.tags.as_deref().map(normalize_tags),
+            is_active: request.is_active.unwrap_or(existing.is_active),
+            ..existing
+        })?;
+        Ok(SavedGood { good, created: false })
+    }
+
+    fn update_good(&mut self, goods_id: i32, request: &UpdateGoodRequest) -> Result<Good, TableError> {
+        let good = self.good(goods_id).cloned().ok_or(sqlx::Error::RowNotFound)?;
+        self.store_good(Good {
+            material_code: request.material_code.clone().unwrap_or(good.material_code),
+            goods_name: request.goods_name.clone().unwrap_or(good.goods_name),
+            description: request.description.clone().or(good.description),
+            price: request.price.unwrap_or(good.price),
+            volumn_l: request.volumn_l.unwrap_or(good.volumn_l),
+            mass_g: request.mass_g.unwrap_or(good.mass_g),
+            mass_base: request.mass_base.unwrap_or(good.mass_base),
+            volumn_base: request.volumn_base.unwrap_or(good.volumn_base),
+            reorder_point: request.reorder_point.or(good.reorder_point),
+            category_id: request.category_id.or(good.category_id),
+            barcode: request.barcode.clone().or(good.barcode),
+            tags: request.tags.as_deref().map(normalize_tags).or(good.tags),
+            is_active: request.is_active.unwrap_or(good.is_active),
+            ..good
+        })
+    }
+
+    /// `good` with the totals `include_stock` joins in
+    fn with_stock(&self, good: Good) -> GoodWithStock {
+        let lots: Vec<&InventoryItem> = self.items.iter().filter(|item| item.goods_id == good.goods_id).collect();
+        GoodWithStock {
+            total_quantity: lots.iter().map(|item| item.quantity as i64).sum(),
+            lot_count: lots.len() as i64,
+            earliest_expired_date: lots.iter().filter_map(|item| item.expired_date).min(),
+            good,
+        }
+    }
+
+    fn is_active(&self, goods_id: i32) -> bool {
+        self.good(goods_id).is_some_and(|good| good.is_active)
+    }
+
+    fn has_stock(&self, goods_id: i32) -> bool {
+        self.items.iter().any(|item| item.goods_id == goods_id && item.quantity > 0)
+    }
+
+    fn inventory_count(&self, goods_id: i32) -> i64 {
+        self.items.iter().filter(|item| item.goods_id == goods_id).count() as i64
+    }
+
+    fn with_goods(&self, item: &InventoryItem, include_reserved: bool) -> Option<InventoryItemWithGoods> {
+        let good = self.good(item.goods_id)?;
+        let mut with_goods = InventoryItemWithGoods {
+            item_id: item.item_id,
+            goods_id: item.goods_id,
+            material_code: good.material_code.clone(),
+            goods_name: good.goods_name.clone(),
+            description: good.description.clone(),
+            price: good.price,
+            volumn_l: good.volumn_l,
+            mass_g: good.mass_g,
+            mass_base: good.mass_base,
+            volumn_base: good.volumn_base,
+            quantity: item.quantity,
+            expired_date: item.expired_date,
+            lot_number: item.lot_number.clone(),
+            created_at: item.created_at,
+            updated_at: item.updated_at,
+            is_expired: false,
+            days_until_expiration: None,
+            // Nothing is ever reserved here
+            available_quantity: include_reserved.then_some(item.quantity),
+            similarity: None,
+        };
+        with_goods.set_expiry_fields(Utc::now());
+        Some(with_goods)
+    }
+
+    fn item_with_goods(&self, item_id: i32) -> Option<InventoryItemWithGoods> {
+        self.with_goods(self.item(item_id)?, false)
+    }
+
+    fn item_matches(&self, item: &InventoryItem, good: &Good, params: &InventorySearchParams, now: DateTime<Utc>) -> bool {
+        // The wildcard still leaves out archived goods
+        if params.is_get_all() {
+            return good_matches(good, &params.goods_params);
+        }
+
+        params.item_id.is_none_or(|id| item.item_id == id)
+            && params.item_ids.as_ref().is_none_or(|ids| ids.contains(&item.item_id))
+            && params.quantity.is_none_or(|quantity| item.quantity == quantity)
+            && at_least(item.quantity, params.min_quantity)
+            && at_most(item.quantity, params.max_quantity)
+            && params.expired_date.is_none_or(|date| item.expired_date == Some(date))
+            && params.min_expired_date.is_none_or(|min| item.expired_date.is_some_and(|date| date >= min))
+            && params.max_expired_date.is_none_or(|max| item.expired_date.is_some_and(|date| date <= max))
+            && params.expiring_within_days.is_none_or(|days| {
+                item.expired_date.is_some_and(|date| date >= now && date <= now + chrono::Duration::days(days as i64))
+            })
+            && params.expired.is_none_or(|expired| item.expired_date.is_some_and(|date| date < now) == expired)
+            && params.has_expired_date.is_none_or(|has| item.expired_date.is_some() == has)
+            && params.lot_number.as_ref().is_none_or(|lot_number| item.lot_number.as_ref() == Some(lot_number))
+            && params.lot_number_contains.as_ref().is_none_or(|term| {
+                item.lot_number.as_ref().is_some_and(|lot_number| lot_number.to_lowercase().contains(&term.to_lowercase()))
+            })
+            && params.in_stock.is_none_or(|in_stock| (item.quantity > 0) == in_stock)
+            && at_least(item.updated_at, params.min_updated_at)
+            && at_most(item.updated_at, params.max_updated_at)
+            && good_matches(good, &params.goods_params)
+    }
+
+    /// Matching items, sorted but not paginated
+    fn search_items(&self, params: &InventorySearchParams) -> Vec<InventoryItemWithGoods> {
+        let now = Utc::now();
+        let mut items: Vec<InventoryItemWithGoods> = self.items.iter()
+            .filter(|item| self.good(item.goods_id).is_some_and(|good| self.item_matches(item, good, params, now)))
+            .filter_map(|item| self.with_goods(item, params.include_reserved))
+            .collect();
+
+        if let Some((threshold, term)) = fuzzy_search(&params.goods_params) {
+            for item in &mut items {
+                item.similarity = Some(similarity(&item.goods_name, term));
+            }
+            items.retain(|item| item.similarity.is_some_and(|score| score > threshold));
+            items.sort_by(|a, b| by_similarity(a.similarity, b.similarity).then(a.item_id.cmp(&b.item_id)));
+            return items;
+        }
+
+        items.sort_by(|a, b| {
+            let ordering = match params.sort_by.unwrap_or(InventorySortColumn::ItemId) {
+                InventorySortColumn::ItemId => a.item_id.cmp(&b.item_id),
+                InventorySortColumn::Quantity => a.quantity.cmp(&b.quantity),
+                InventorySortColumn::ExpiredDate => match (a.expired_date, b.expired_date) {
+                    (Some(a_date), Some(b_date)) => a_date.cmp(&b_date),
+                    // NULLS LAST in either direction
+                    (a_date, b_date) => return a_date.is_none().cmp(&b_date.is_none()).then(a.item_id.cmp(&b.item_id)),
+                },
+                InventorySortColumn::GoodsName => a.goods_name.cmp(&b.goods_name),
+                InventorySortColumn::Price => a.price.cmp(&b.price),
+                InventorySortColumn::MaterialCode => a.material_code.cmp(&b.material_code),
+            };
+            directed(ordering, params.sort_order).then(a.item_id.cmp(&b.item_id))
+        });
+
+        if let Some(keyset) = &params.cursor {
+            let column = params.sort_column();
+            items.retain(|item| {
+                let value = column.and_then(|column| column.parse_cursor_value(&column.cursor_value(item)?).ok());
+                after_keyset(value, item.item_id, keyset, params.sort_order, column.is_some())
+            });
+        }
+        items
+    }
+
+    fn resolve_goods(&self, goods_id: Option<i32>, material_code: Option<&str>) -> Option<i32> {
+        match (goods_id, material_code) {
+            (Some(goods_id), _) => self.good(goods_id).map(|good| good.goods_id),
+            (None, Some(material_code)) => self.good_by_material_code(material_code).map(|good| good.goods_id),
+            (None, None) => None,
+        }
+    }
+
+    fn insert_item(&mut self, goods_id: i32, quantity: i32, expired_date: Option<DateTime<Utc>>, lot_number: Option<&str>) -> i32 {
+        let now = Utc::now();
+        self.last_item_id += 1;
+        self.items.push(InventoryItem {
+            item_id: self.last_item_id,
+            goods_id,
+            quantity,
+            expired_date,
+            lot_number: lot_number.map(str::to_string),
+            created_at: now,
+            updated_at: now,
+        });
+        self.last_item_id
+    }
+
+    /// `InventoryTable::store_quantity` without the movements
+    fn store_quantity(
+        &mut self,
+        goods_id: i32,
+        quantity: i32,
+        expired_date: Option<DateTime<Utc>>,
+        lot_number: Option<&str>,
+        on_duplicate: InventoryDuplicateMode,
+    ) -> Result<(i32, InventoryInsertOutcome), TableError> {
+        let existing = self.items.iter_mut()
+            .filter(|item| item.goods_id == goods_id && item.expired_date == expired_date && item.lot_number.as_deref() == lot_number)
+            .min_by_key(|item| item.item_id);
+
+        match (existing, on_duplicate) {
+            (None, _) => Ok((self.insert_item(goods_id, quantity, expired_date, lot_number), InventoryInsertOutcome::Created)),
+            (Some(existing), InventoryDuplicateMode::ReturnExisting) => Ok((existing.item_id, InventoryInsertOutcome::Existing)),
+            (Some(existing), InventoryDuplicateMode::Error) => Err(TableError::DuplicateInventory { item_id: existing.item_id }),
+            (Some(existing), InventoryDuplicateMode::AddQuantity) => {
+                existing.quantity += quantity;
+                existing.updated_at = Utc::now();
+                Ok((existing.item_id, InventoryInsertOutcome::Merged))
+            }
+        }
+    }
+
+    /// Apply an inventory update to these items and, once each, to their goods rows
+    fn update_items(&mut self, item_ids: &[i32], request: &UpdateInventoryRequest) -> Result<Vec<InventoryItemWithGoods>, TableError> {
+        let mut goods_ids: Vec<i32> = item_ids.iter()
+            .map(|item_id| self.item(*item_id).map(|item| item.goods_id).ok_or(sqlx::Error::RowNotFound))
+            .collect::<Result<_, _>>()?;
+        goods_ids.sort_unstable();
+        goods_ids.dedup();
+
+        let updates_goods = request.material_code.is_some() ||
+            request.goods_name.is_some() ||
+            request.description.is_some() ||
+            request.price.is_some() ||
+            request.volumn_l.is_some() ||
+            request.mass_g.is_some() ||
+            request.mass_base.is_some() ||
+            request.volumn_base.is_some();
+
+        if updates_goods {
+            let goods_request = UpdateGoodRequest {
+                material_code: request.material_code.clone(),
+                goods_name: request.goods_name.clone(),
+                description: request.description.clone(),
+                price: request.price,
+                volumn_l: request.volumn_l,
+                mass_g: request.mass_g,
+                mass_base: request.mass_base,
+                volumn_base: request.volumn_base,
+                reorder_point: None,
+                category_id: None,
+                barcode: None,
+                tags: None,
+                is_active: None,
+                expected_version: None,
+            };
+            for goods_id in goods_ids {
+                self.update_good(goods_id, &goods_request)?;
+            }
+        }
+
+        let now = Utc::now();
+        for item in self.items.iter_mut().filter(|item| item_ids.contains(&item.item_id)) {
+            item.quantity = request.quantity.unwrap_or(item.quantity);
+            item.expired_date = request.expired_date.or(item.expired_date);
+            item.lot_number = request.lot_number.clone().or(item.lot_number.clone());
+            item.updated_at = now;
+        }
+
+        Ok(item_ids.iter().filter_map(|item_id| self.item_with_goods(*item_id)).collect())
+    }
+
+    fn remove_items(&mut self, item_ids: &[i32]) {
+        self.items.retain(|item| !item_ids.contains(&item.item_id));
+    }
+}
+
+#[async_trait]
+impl GoodsRepository for InMemoryGoods {
+    async fn search(&self, params: GoodsSearchParams) -> Result<Vec<Good>, sqlx::Error> {
+        let goods = self.store.lock().search_goods(&params);
+        Ok(paginate(goods, params.pagination.as_ref(), params.limit))
+    }
+
+    fn search_stream(&self, params: &GoodsSearchParams) -> BoxStream<'static, Result<Good, sqlx::Error>> {
+        let goods = self.store.lock().search_goods(params);
+        stream::iter(paginate(goods, params.pagination.as_ref(), params.limit).into_iter().map(Ok)).boxed()
+    }
+
+    async fn search_paginated(&self, params: GoodsSearchParams) -> Result<PaginatedResponse<Good>, sqlx::Error> {
+        let pagination = params.pagination.clone().unwrap_or_default();
+        let goods = self.store.lock().search_goods(&params);
+        let total_count = params.cursor.is_none().then_some(goods.len() as u64);
+
+        let page = paginate(goods, Some(&pagination), None);
+        let next_cursor = GoodsSearchParams { pagination: Some(pagination.clone()), ..params }.next_cursor(&page, total_count);
+        Ok(PaginatedResponse::new(page, &pagination, total_count).with_next_cursor(next_cursor))
+    }
+
+    async fn search_with_stock(&self, params: GoodsSearchParams) -> Result<Vec<GoodWithStock>, sqlx::Error> {
+        let tables = self.store.lock();
+        let goods = paginate(tables.search_goods(&params), params.pagination.as_ref(), params.limit);
+        Ok(goods.into_iter().map(|good| tables.with_stock(good)).collect())
+    }
+
+    async fn search_with_stock_paginated(&self, params: GoodsSearchParams) -> Result<PaginatedResponse<GoodWithStock>, sqlx::Error> {
+        let page = self.search_paginated(params).await?;
+        let tables = self.store.lock();
+        let data = page.data.iter().map(|good| tables.with_stock(good.clone())).collect();
+        Ok(page.with_data(data))
+    }
+
+    async fn get_by_barcode(&self, barcode: &str) -> Result<Option<Good>, sqlx::Error> {
+        Ok(self.store.lock().goods.iter().find(|good| good.barcode.as_deref() == Some(barcode)).cloned())
+    }
+
+    async fn get_by_id(&self, goods_id: i32) -> Result<Option<Good>, sqlx::Error> {
+        Ok(self.store.lock().good(goods_id).cloned())
+    }
+
+    async fn get_by_ids(&self, goods_ids: &[i32]) -> Result<Vec<Good>, sqlx::Error> {
+        let mut goods: Vec<Good> = self.store.lock().goods.iter().filter(|good| goods_ids.contains(&good.goods_id)).cloned().collect();
+        goods.sort_by_key(|good| (good.updated_at, good.goods_id));
+        Ok(goods)
+    }
+
+    async fn insert(&self, request: CreateGoodRequest, on_conflict: GoodsConflictMode) -> Result<SavedGood, TableError> {
+        self.store.write(true, |tables| match on_conflict {
+            GoodsConflictMode::Update => tables.upsert_good(request),
+            GoodsConflictMode::ReturnExisting if tables.good_by_material_code(&request.material_code).is_some() => {
+                let existing = tables.good_by_material_code(&request.material_code).cloned().ok_or(sqlx::Error::RowNotFound)?;
+                Ok(SavedGood { good: existing, created: false })
+            }
+            _ => Ok(SavedGood { good: tables.insert_good(request)?, created: true }),
+        })
+    }
+
+    async fn upsert_by_material_code(&self, request: CreateGoodRequest) -> Result<SavedGood, TableError> {
+        self.store.write(true, |tables| tables.upsert_good(request))
+    }
+
+    async fn insert_batch(&self, entries: Vec<(usize, CreateGoodRequest)>) -> Result<GoodsBatchResult, sqlx::Error> {
+        self.store.write(true, |tables| {
+            let mut result = GoodsBatchResult {
+                created: Vec::new(),
+                skipped_existing: Vec::new(),
+                errors: Vec::new(),
+            };
+
+            for (index, request) in entries {
+                if let Some(existing) = tables.good_by_material_code(&request.material_code) {
+                    result.skipped_existing.push(SkippedGood {
+                        index,
+                        material_code: request.material_code.clone(),
+                        goods_id: existing.goods_id,
+                    });
+                    continue;
+                }
+
+                // A barcode clash fails the whole statement, as the multi-row insert does
+                match tables.insert_good(request) {
+                    Ok(good) => result.created.push(good),
+                    Err(TableError::Database(e)) => return Err(e),
+                    Err(_) => return Err(unique_violation("goods_barcode_key")),
+                }
+            }
+
+            Ok(result)
+        })
+    }
+
+    async fn low_stock(&self, include_zero_reorder: bool) -> Result<Vec<LowStockGoods>, sqlx::Error> {
+        let tables = self.store.lock();
+        let mut goods: Vec<LowStockGoods> = tables.goods.iter()
+            .filter(|good| include_zero_reorder || good.reorder_point.unwrap_or(0) > 0)
+            .filter_map(|good| {
+                let total_quantity: i64 = tables.items.iter()
+                    .filter(|item| item.goods_id == good.goods_id)
+                    .map(|item| item.quantity as i64)
+                    .sum();
+                let reorder_point = good.reorder_point.unwrap_or(0) as i64;
+
+                (total_quantity <= reorder_point).then(|| LowStockGoods {
+                    goods_id: good.goods_id,
+                    material_code: good.material_code.clone(),
+                    goods_name: good.goods_name.clone(),
+                    total_quantity,
+                    reorder_point: good.reorder_point,
+                    deficit: reorder_point - total_quantity,
+                })
+            })
+            .collect();
+
+        goods.sort_by(|a, b| b.deficit.cmp(&a.deficit).then(a.goods_id.cmp(&b.goods_id)));
+        Ok(goods)
+    }
+
+    async fn tag_counts(&self) -> Result<Vec<TagCount>, sqlx::Error> {
+        let mut counts: BTreeMap<String, i64> = BTreeMap::new();
+        for tag in self.store.lock().goods.iter().flat_map(|good| good.tags.iter().flatten()) {
+            *counts.entry(tag.clone()).or_default() += 1;
+        }
+
+        let mut tags: Vec<TagCount> = counts.into_iter().map(|(tag, goods_count)| TagCount { tag, goods_count }).collect();
+        tags.sort_by(|a, b| b.goods_count.cmp(&a.goods_count).then_with(|| a.tag.cmp(&b.tag)));
+        Ok(tags)
+    }
+
+    async fn duplicate_material_codes(&self, normalization: Normalization) -> Result<Vec<DuplicateMaterialCode>, sqlx::Error> {
+        let mut groups: BTreeMap<String, Vec<Good>> = BTreeMap::new();
+        for good in &self.store.lock().goods {
+            groups.entry(normalization.material_code(&good.material_code)).or_default().push(good.clone());
+        }
+
+        Ok(groups
+            .into_iter()
+            .filter(|(_, goods)| goods.len() > 1)
+            .map(|(normalized_material_code, mut goods)| {
+                goods.sort_by_key(|good| good.goods_id);
+                DuplicateMaterialCode { normalized_material_code, goods }
+            })
+            .collect())
+    }
+
+    async fn update(&self, params: GoodsSearchParams, update_request: UpdateGoodRequest, options: BulkWriteOptions) -> Result<Vec<Good>, TableError> {
+        self.store.write(!options.dry_run, |tables| {
+            let matched = tables.search_goods(&GoodsSearchParams { pagination: None, ..params });
+            options.check_matched(matched.len() as i64)?;
+
+            if let Some(expected_version) = update_request.expected_version {
+                let stale: Vec<StaleGoods> = matched.iter()
+                    .filter(|good| good.version != expected_version)
+                    .map(|good| StaleGoods { goods_id: good.goods_id, current_version: good.version })
+                    .collect();
+                if !stale.is_empty() {
+                    return Err(TableError::StaleVersion { goods: stale });
+                }
+            }
+
+            let mut updated = matched.iter()
+                .map(|good| tables.update_good(good.goods_id, &update_request))
+                .collect::<Result<Vec<Good>, TableError>>()?;
+            updated.sort_by_key(|good| good.goods_id);
+            Ok(updated)
+        })
+    }
+
+    async fn update_by_id(&self, goods_id: i32, update_request: UpdateGoodRequest) -> Result<Option<Good>, TableError> {
+        self.store.write(true, |tables| {
+            let Some(current_version) = tables.good(goods_id).map(|good| good.version) else {
+                return Ok(None);
+            };
+            if update_request.expected_version.is_some_and(|expected| expected != current_version) {
+                return Err(TableError::StaleVersion { goods: vec![StaleGoods { goods_id, current_version }] });
+            }
+
+            tables.update_good(goods_id, &update_request).map(Some)
+        })
+    }
+
+    async fn delete(&self, params: GoodsSearchParams, options: BulkWriteOptions) -> Result<Vec<i32>, TableError> {
+        self.store.write(!options.dry_run, |tables| {
+            let matched: Vec<i32> = tables.search_goods(&GoodsSearchParams { pagination: None, ..params })
+                .iter()
+                .map(|good| good.goods_id)
+                .collect();
+            options.check_matched(matched.len() as i64)?;
+
+            let blocked: Vec<BlockedGoods> = matched.iter()
+                .map(|goods_id| BlockedGoods { goods_id: *goods_id, inventory_count: tables.inventory_count(*goods_id) })
+                .filter(|blocked| blocked.inventory_count > 0)
+                .collect();
+            if !blocked.is_empty() {
+                return Err(TableError::BlockedByInventory { goods: blocked });
+            }
+
+            tables.goods.retain(|good| !matched.contains(&good.goods_id));
+            Ok(matched)
+        })
+    }
+
+    async fn delete_by_ids(&self, goods_ids: &[i32]) -> Result<GoodsDeleteByIdsResult, sqlx::Error> {
+        let mut requested = goods_ids.to_vec();
+        requested.sort_unstable();
+        requested.dedup();
+
+        self.store.write(true, |tables| {
+            let (existing, not_found): (Vec<i32>, Vec<i32>) = requested.iter().partition(|goods_id| tables.good(**goods_id).is_some());
+            let blocked: Vec<BlockedGoods> = existing.iter()
+                .map(|goods_id| BlockedGoods { goods_id: *goods_id, inventory_count: tables.inventory_count(*goods_id) })
+                .filter(|blocked| blocked.inventory_count > 0)
+                .collect();
+            let deleted: Vec<i32> = existing.into_iter()
+                .filter(|goods_id| !blocked.iter().any(|blocked| blocked.goods_id == *goods_id))
+                .collect();
+
+            tables.goods.retain(|good| !deleted.contains(&good.goods_id));
+            Ok(GoodsDeleteByIdsResult { deleted, not_found, blocked })
+        })
+    }
+
+    fn cache_stats(&self) -> GoodsCacheStats {
+        GoodsCacheStats { enabled: false, hits: 0, misses: 0, entries: 0 }
+    }
+}
+
+#[async_trait]
+impl InventoryRepository for InMemoryInventory {
+    async fn search(&self, params: InventorySearchParams) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
+        let items = self.store.lock().search_items(&params);
+        Ok(paginate(items, params.pagination.as_ref(), params.limit))
+    }
+
+    fn search_stream(&self, params: &InventorySearchParams) -> BoxStream<'static, Result<InventoryItemWithGoods, sqlx::Error>> {
+        let items = self.store.lock().search_items(params);
+        stream::iter(paginate(items, params.pagination.as_ref(), params.limit).into_iter().map(Ok)).boxed()
+    }
+
+    async fn search_paginated(&self, params: InventorySearchParams) -> Result<PaginatedResponse<InventoryItemWithGoods>, sqlx::Error> {
+        let pagination = params.pagination.clone().unwrap_or_default();
+        let items = self.store.lock().search_items(&params);
+        let total_count = params.cursor.is_none().then_some(items.len() as u64);
+
+        let page = paginate(items, Some(&pagination), None);
+        let next_cursor = InventorySearchParams { pagination: Some(pagination.clone()), ..params }.next_cursor(&page, total_count);
+        Ok(PaginatedResponse::new(page, &pagination, total_count).with_next_cursor(next_cursor))
+    }
+
+    async fn stats(&self, params: &InventorySearchParams) -> Result<InventoryStats, sqlx::Error> {
+        let items = self.store.lock().search_items(params);
+        let now = Utc::now();
+        let soon = now + Days::new(30);
+
+        let mut goods_ids: Vec<i32> = items.iter().map(|item| item.goods_id).collect();
+        goods_ids.sort_unstable();
+        goods_ids.dedup();
+
+        Ok(InventoryStats {
+            total_goods: goods_ids.len() as i64,
+            total_items: items.len() as i64,
+            total_quantity: items.iter().map(|item| item.quantity as i64).sum(),
+            total_value: items.iter().map(|item| Decimal::from(item.quantity) * item.price).sum(),
+            expired_items: items.iter().filter(|item| item.expired_date.is_some_and(|date| date < now)).count() as i64,
+            expiring_within_30_days: items.iter()
+                .filter(|item| item.expired_date.is_some_and(|date| date >= now && date <= soon))
+                .count() as i64,
+        })
+    }
+
+    async fn summary(&self, params: &InventorySummaryParams) -> Result<Vec<InventorySummary>, sqlx::Error> {
+        let items = self.store.lock().search_items(&params.search);
+
+        let mut by_goods: BTreeMap<i32, InventorySummary> = BTreeMap::new();
+        for item in items {
+            let summary = by_goods.entry(item.goods_id).or_insert_with(|| InventorySummary {
+                goods_id: item.goods_id,
+                material_code: item.material_code.clone(),
+                goods_name: item.goods_name.clone(),
+                lot_count: 0,
+                total_quantity: 0,
+                earliest_expired_date: None,
+            });
+            summary.lot_count += 1;
+            summary.total_quantity += item.quantity as i64;
+            summary.earliest_expired_date = match (summary.earliest_expired_date, item.expired_date) {
+                (Some(earliest), Some(date)) => Some(earliest.min(date)),
+                (earliest, date) => earliest.or(date),
+            };
+        }
+
+        let mut rows: Vec<InventorySummary> = by_goods.into_values().collect();
+        rows.sort_by(|a, b| {
+            let ordering = match params.sort_by.unwrap_or(SummarySortColumn::GoodsId) {
+                SummarySortColumn::GoodsId => a.goods_id.cmp(&b.goods_id),
+                SummarySortColumn::MaterialCode => a.material_code.cmp(&b.material_code),
+                SummarySortColumn::GoodsName => a.goods_name.cmp(&b.goods_name),
+                SummarySortColumn::LotCount => a.lot_count.cmp(&b.lot_count),
+                SummarySortColumn::TotalQuantity => a.total_quantity.cmp(&b.total_quantity),
+                SummarySortColumn::EarliestExpiredDate => match (a.earliest_expired_date, b.earliest_expired_date) {
+                    (Some(a_date), Some(b_date)) => a_date.cmp(&b_date),
+                    // NULLS LAST in either direction
+                    (a_date, b_date) => return a_date.is_none().cmp(&b_date.is_none()).then(a.goods_id.cmp(&b.goods_id)),
+                },
+            };
+            directed(ordering, params.sort_order).then(a.goods_id.cmp(&b.goods_id))
+        });
+
+        Ok(paginate(rows, params.search.pagination.as_ref(), None))
+    }
+
+    async fn valuation(&self, params: &ValuationParams) -> Result<ValuationReport, sqlx::Error> {
+        let tables = self.store.lock();
+        let now = Utc::now();
+
+        let mut by_group: BTreeMap<Option<i32>, ValuationRow> = BTreeMap::new();
+        for item in tables.search_items(&params.search) {
+            if params.exclude_expired && item.expired_date.is_some_and(|date| date < now) {
+                continue;
+            }
+            let (group_key, group_label) = match params.group_by {
+                ValuationGroupBy::Goods => (Some(item.goods_id), item.goods_name.clone()),
+                // Categories are not modelled, so the id stands in for the name
+                ValuationGroupBy::Category => match tables.good(item.goods_id).and_then(|good| good.category_id) {
+                    Some(category_id) => (Some(category_id), category_id.to_string()),
+                    None => (None, "Uncategorized".to_string()),
+                },
+            };
+            let row = by_group.entry(group_key).or_insert_with(|| ValuationRow {
+                group_key,
+                group_label,
+                total_quantity: 0,
+                total_value: Decimal::ZERO,
+            });
+            row.total_quantity += item.quantity as i64;
+            row.total_value += Decimal::from(item.quantity) * item.price;
+        }
+
+        let mut rows: Vec<ValuationRow> = by_group.into_values().collect();
+        // total_value DESC, then group_key with NULL last
+        rows.sort_by(|a, b| {
+            b.total_value.cmp(&a.total_value)
+                .then(a.group_key.is_none().cmp(&b.group_key.is_none()))
+                .then(a.group_key.cmp(&b.group_key))
+        });
+
+        Ok(ValuationReport::new(params, rows))
+    }
+
+    async fn get_by_item_id(&self, item_id: i32) -> Result<Option<InventoryItemWithGoods>, sqlx::Error> {
+        Ok(self.store.lock().item_with_goods(item_id))
+    }
+
+    async fn get_by_item_ids(&self, item_ids: &[i32]) -> Result<Vec<InventoryItemWithGoods>, sqlx::Error> {
+        let tables = self.store.lock();
+        let mut items: Vec<InventoryItemWithGoods> = item_ids.iter().filter_map(|item_id| tables.item_with_goods(*item_id)).collect();
+        items.sort_by_key(|item| (item.updated_at, item.item_id));
+        items.dedup_by_key(|item| item.item_id);
+        Ok(items)
+    }
+
+    async fn insert(
+        &self,
+        request: CreateInventoryRequest,
+        on_duplicate: InventoryDuplicateMode,
+        allow_inactive: bool,
+    ) -> Result<(InventoryItemWithGoods, InventoryInsertOutcome), TableError> {
+        self.store.write(true, |tables| {
+            let goods_id = match tables.resolve_goods(request.goods_id, request.material_code.as_deref()) {
+                Some(goods_id) if !allow_inactive && !tables.is_active(goods_id) => return Err(TableError::InactiveGoods { goods_id }),
+                Some(goods_id) => goods_id,
+                None if request.goods_id.is_some() || request.material_code.is_some() => return Err(sqlx::Error::RowNotFound.into()),
+                // Same outcome as the table: new goods need a material_code, which this branch lacks
+                None if request.goods_name.is_some() && request.price.is_some() && request.volumn_l.is_some() && request.mass_g.is_some() => {
+                    return Err(sqlx::Error::ColumnNotFound("material_code is required for new goods".into()).into());
+                }
+                None => {
+                    return Err(sqlx::Error::ColumnNotFound(
+                        "Either goods_id, material_code, or complete goods information is required".into()
+                    ).into());
+                }
+            };
+
+            let (item_id, outcome) = tables.store_quantity(goods_id, request.quantity, request.expired_date, request.lot_number.as_deref(), on_duplicate)?;
+            let item = tables.item_with_goods(item_id).ok_or(sqlx::Error::RowNotFound)?;
+            Ok((item, outcome))
+        })
+    }
+
+    async fn insert_batch(
+        &self,
+        entries: Vec<(usize, CreateInventoryRequest)>,
+        mut failures: Vec<BatchItemError>,
+        abort_on_failure: bool,
+        allow_inactive: bool,
+    ) -> Result<InventoryBatchResult, TableError> {
+        self.store.write(true, |tables| {
+            let mut resolved: Vec<(usize, i32, CreateInventoryRequest)> = Vec::new();
+            for (index, request) in entries {
+                let Some(goods_id) = tables.resolve_goods(request.goods_id, request.material_code.as_deref()) else {
+                    let reference = match (request.goods_id, &request.material_code) {
+                        (Some(id), _) => format!("goods_id {}", id),
+                        (None, Some(code)) => format!("material_code '{}'", code),
+                        (None, None) => "entry".to_string(),
+                    };
+                    failures.push(BatchItemError::not_found(index, format!("Goods not found for {}", reference)));
+                    continue;
+                };
+
+                if !allow_inactive && !tables.is_active(goods_id) {
+                    failures.push(BatchItemError::conflict(index, TableError::InactiveGoods { goods_id }.to_string()));
+                    continue;
+                }
+
+                if let Some((first_index, _, _)) = resolved.iter()
+                    .find(|(_, id, other)| *id == goods_id && other.expired_date == request.expired_date && other.lot_number == request.lot_number) {
+                    failures.push(BatchItemError::conflict(
+                        index,
+                        format!("Duplicate of index {} (same goods, expired_date and lot_number)", first_index),
+                    ));
+                    continue;
+                }
+
+                resolved.push((index, goods_id, request));
+            }
+
+            failures.sort_by_key(|failure| failure.index);
+
+            if abort_on_failure && !failures.is_empty() {
+                return Err(TableError::InvalidBatch { errors: failures });
+            }
+
+            let mut result = InventoryBatchResult {
+                created: Vec::new(),
+                matched_existing: Vec::new(),
+                failures,
+            };
+
+            for (index, goods_id, request) in resolved {
+                let (item_id, outcome) = tables.store_quantity(
+                    goods_id, request.quantity, request.expired_date, request.lot_number.as_deref(), InventoryDuplicateMode::ReturnExisting,
+                )?;
+                let item = BatchInventoryItem { index, item: tables.item_with_goods(item_id).ok_or(sqlx::Error::RowNotFound)? };
+                match outcome {
+                    InventoryInsertOutcome::Created => result.created.push(item),
+                    _ => result.matched_existing.push(item),
+                }
+            }
+
+            Ok(result)
+        })
+    }
+
+    async fn update(&self, params: InventorySearchParams, update_request: UpdateInventoryRequest, options: BulkWriteOptions) -> Result<Vec<InventoryItemWithGoods>, TableError> {
+        self.store.write(!options.dry_run, |tables| {
+            options.check_matched(tables.search_items(&InventorySearchParams { pagination: None, ..params.clone() }).len() as i64)?;
+
+            let items = paginate(tables.search_items(&params), params.pagination.as_ref(), params.limit);
+            if options.dry_run {
+                return Ok(items);
+            }
+
+            let item_ids: Vec<i32> = items.iter().map(|item| item.item_id).collect();
+            tables.update_items(&item_ids, &update_request)
+        })
+    }
+
+    async fn update_by_id(&self, item_id: i32, update_request: UpdateInventoryRequest) -> Result<Option<InventoryItemWithGoods>, sqlx::Error> {
+        self.store.write(true, |tables| {
+            if tables.item(item_id).is_none() {
+                return Ok(None);
+            }
+
+            match tables.update_items(&[item_id], &update_request) {
+                Ok(items) => Ok(items.into_iter().next()),
+                Err(TableError::Database(e)) => Err(e),
+                // Only the material_code constraint can fail here
+                Err(_) => Err(unique_violation("goods_material_code_key")),
+            }
+        })
+    }
+
+    async fn adjust(&self, item_id: i32, delta: i32, _reason: &str) -> Result<InventoryItemWithGoods, TableError> {
+        self.store.write(true, |tables| {
+            let item = tables.items.iter_mut().find(|item| item.item_id == item_id).ok_or(sqlx::Error::RowNotFound)?;
+            if item.quantity + delta < 0 && !self.allow_negative_stock {
+                return Err(TableError::InsufficientStock { item_id, current_quantity: item.quantity, delta });
+            }
+
+            item.quantity += delta;
+            item.updated_at = Utc::now();
+            Ok(tables.item_with_goods(item_id).ok_or(sqlx::Error::RowNotFound)?)
+        })
+    }
+
+    async fn consume(&self, request: &ConsumeInventoryRequest) -> Result<ConsumeResult, TableError> {
+        self.store.write(true, |tables| {
+            let goods_id = tables.resolve_goods(request.goods_id, request.material_code.as_deref()).ok_or(sqlx::Error::RowNotFound)?;
+
+            let mut stock: Vec<&InventoryItem> = tables.items.iter()
+                .filter(|item| item.goods_id == goods_id && (item.quantity > 0 || self.allow_negative_stock))
+                .collect();
+            // Earliest expiry first, no expiry last
+            stock.sort_by_key(|item| (item.expired_date.is_none(), item.expired_date, item.item_id));
+            let stock: Vec<(i32, i32)> = stock.iter().map(|item| (item.item_id, item.quantity)).collect();
+
+            let delete_empty = request.delete_empty.unwrap_or(false);
+            let items = plan_consumption(&stock, request.quantity, delete_empty, self.allow_negative_stock)
+                .map_err(|available| TableError::InsufficientGoodsStock { goods_id, requested: request.quantity, available })?;
+
+            let now = Utc::now();
+            for consumed in &items {
+                if let Some(item) = tables.items.iter_mut().find(|item| item.item_id == consumed.item_id) {
+                    item.quantity = consumed.remaining_quantity;
+                    item.updated_at = now;
+                }
+            }
+            let emptied: Vec<i32> = items.iter().filter(|item| item.deleted).map(|item| item.item_id).collect();
+            tables.remove_items(&emptied);
+
+            Ok(ConsumeResult { goods_id, quantity: request.quantity, items })
+        })
+    }
+
+    async fn delete(&self, params: InventorySearchParams, options: BulkWriteOptions) -> Result<Vec<i32>, TableError> {
+        self.store.write(!options.dry_run, |tables| {
+            let matched: Vec<i32> = tables.search_items(&InventorySearchParams { pagination: None, ..params })
+                .iter()
+                .map(|item| item.item_id)
+                .collect();
+            options.check_matched(matched.len() as i64)?;
+
+            let mut deleted = matched;
+            deleted.sort_unstable();
+            tables.remove_items(&deleted);
+            Ok(deleted)
+        })
+    }
+
+    async fn delete_by_ids(&self, item_ids: &[i32]) -> Result<InventoryDeleteByIdsResult, sqlx::Error> {
+        let mut requested = item_ids.to_vec();
+        requested.sort_unstable();
+        requested.dedup();
+
+        self.store.write(true, |tables| {
+            let (deleted, not_found): (Vec<i32>, Vec<i32>) = requested.iter().partition(|item_id| tables.item(**item_id).is_some());
+            tables.remove_items(&deleted);
+            Ok(InventoryDeleteByIdsResult { deleted, not_found })
+        })
+    }
+
+    async fn merge_duplicates(&self, dry_run: bool) -> Result<Vec<MergedInventoryGroup>, sqlx::Error> {
+        self.store.write(!dry_run, |tables| {
+            type GroupKey = (i32, bool, Option<DateTime<Utc>>, bool, Option<String>);
+            let mut groups: BTreeMap<GroupKey, Vec<InventoryItem>> = BTreeMap::new();
+            for item in &tables.items {
+                // Keyed so groups come out by goods, then expiry and lot with NULL last
+                let key = (item.goods_id, item.expired_date.is_none(), item.expired_date, item.lot_number.is_none(), item.lot_number.clone());
+                groups.entry(key).or_default().push(item.clone());
+            }
+
+            let mut merged = Vec::new();
+            for ((goods_id, _, expired_date, _, lot_number), mut items) in groups.into_iter().filter(|(_, items)| items.len() > 1) {
+                items.sort_by_key(|item| item.item_id);
+                let surviving_item_id = items[0].item_id;
+                let removed_item_ids: Vec<i32> = items[1..].iter().map(|item| item.item_id).collect();
+                let quantity: i32 = items.iter().map(|item| item.quantity).sum();
+
+                if let Some(survivor) = tables.items.iter_mut().find(|item| item.item_id == surviving_item_id) {
+                    survivor.quantity = quantity;
+                    survivor.updated_at = Utc::now();
+                }
+                tables.remove_items(&removed_item_ids);
+
+                merged.push(MergedInventoryGroup { goods_id, expired_date, lot_number, surviving_item_id, removed_item_ids, quantity });
+            }
+
+            Ok(merged)
+        })
+    }
+
+    async fn merge_goods(&self, source_id: i32, target_id: i32) -> Result<Option<GoodsMergeResult>, sqlx::Error> {
+        self.store.write(true, |tables| {
+            let (Some(source), Some(target)) = (tables.good(source_id).cloned(), tables.good(target_id).cloned()) else {
+                return Ok(None);
+            };
+
+            let mut moved_item_ids = Vec::new();
+            let mut merged_items = Vec::new();
+            let source_items: Vec<InventoryItem> = tables.items.iter().filter(|item| item.goods_id == source_id).cloned().collect();
+            for item in source_items {
+                let landing = tables.items.iter()
+                    .filter(|other| other.goods_id == target_id && other.expired_date == item.expired_date && other.lot_number == item.lot_number)
+                    .map(|other| other.item_id)
+                    .min();
+
+                match landing {
+                    Some(target_item_id) => {
+                        if let Some(target_item) = tables.items.iter_mut().find(|other| other.item_id == target_item_id) {
+                            target_item.quantity += item.quantity;
+                            target_item.updated_at = Utc::now();
+                        }
+                        tables.remove_items(&[item.item_id]);
+                        merged_items.push(MergedGoodsItem { source_item_id: item.item_id, target_item_id, quantity: item.quantity });
+                    }
+                    None => {
+                        if let Some(moved) = tables.items.iter_mut().find(|other| other.item_id == item.item_id) {
+                            moved.goods_id = target_id;
+                            moved.updated_at = Utc::now();
+                        }
+                        moved_item_ids.push(item.item_id);
+                    }
+                }
+            }
+
+            let (description, tags) = merge_goods_details(&source, &target);
+            tables.goods.retain(|good| good.goods_id != source_id);
+            let target = Good { description, tags, version: target.version + 1, updated_at: Utc::now(), ..target };
+            if let Some(stored) = tables.goods.iter_mut().find(|good| good.goods_id == target_id) {
+                *stored = target.clone();
+            }
+
+            // Purchase orders are not modelled, so there are no lines to repoint
+            Ok(Some(GoodsMergeResult { source_goods_id: source_id, target, moved_item_ids, merged_items, purchase_order_lines: 0 }))
+        })
+    }
+
+    async fn purge_expired(&self, cutoff: DateTime<Utc>, mode: ExpiredPurgeMode, dry_run: bool) -> Result<Vec<i32>, sqlx::Error> {
+        self.store.write(!dry_run, |tables| {
+            let expired = |item: &InventoryItem| item.expired_date.is_some_and(|date| date < cutoff);
+
+            let mut item_ids: Vec<i32> = tables.items.iter()
+                .filter(|item| expired(item) && (mode == ExpiredPurgeMode::Delete || item.quantity != 0))
+                .map(|item| item.item_id)
+                .collect();
+            item_ids.sort_unstable();
+
+            match mode {
+                ExpiredPurgeMode::Zero => {
+                    let now = Utc::now();
+                    for item in tables.items.iter_mut().filter(|item| item_ids.contains(&item.item_id)) {
+                        item.quantity = 0;
+                        item.updated_at = now;
+                    }
+                }
+                ExpiredPurgeMode::Delete => tables.remove_items(&item_ids),
+            }
+
+            Ok(item_ids)
+        })
+    }
+}
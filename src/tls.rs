@@ -0,0 +1,217 @@
+// src/tls.rs
+//
+// HTTPS served by the API itself, for deployments without a reverse proxy. The certificate is
+// re-read on SIGHUP and when its files change, so renewed certificates (e.g. Let's Encrypt)
+// apply without a restart; a renewal that fails to load is logged and the current one is kept.
+use crate::config::TlsConfig;
+use anyhow::{bail, Context, Result};
+use axum::serve::Listener;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+
+/// Clients that have not finished the handshake by then are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handshaken connections waiting for the server to pick them up
+const ACCEPT_BACKLOG: usize = 128;
+
+/// Wait after a failed `accept`, e.g. when out of file descriptors
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_secs(1);
+
+/// TLS connections for `axum::serve`. Handshakes run in their own tasks, so a slow client
+/// doesn't hold up the others.
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    connections: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+}
+
+impl TlsListener {
+    /// Load the certificate and start accepting on `listener`; fails if the certificate or key
+    /// cannot be used
+    pub fn new(listener: TcpListener, config: &TlsConfig) -> Result<Self> {
+        let provider = Arc::new(ring::default_provider());
+        let certificate = Arc::new(ReloadableCertificate::load(config.clone(), provider.clone())?);
+
+        let mut server_config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .context("TLS protocol versions unsupported by the crypto provider")?
+            .with_no_client_auth()
+            .with_cert_resolver(certificate.clone());
+        // axum is built with HTTP/1 only, so h2 must not be offered
+        server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        spawn_reload(certificate)?;
+
+        let local_addr = listener.local_addr()?;
+        let (sender, connections) = mpsc::channel(ACCEPT_BACKLOG);
+        tokio::spawn(accept_connections(listener, TlsAcceptor::from(Arc::new(server_config)), sender));
+
+        Ok(Self { local_addr, connections })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.connections.recv().await {
+            Some(connection) => connection,
+            // The accept task only stops once this listener is dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+async fn accept_connections(listener: TcpListener, acceptor: TlsAcceptor, connections: mpsc::Sender<(TlsStream<TcpStream>, SocketAddr)>) {
+    loop {
+        let (stream, peer_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Failed to accept connection: {}", e);
+                tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                continue;
+            }
+        };
+        if connections.is_closed() {
+            return;
+        }
+
+        let acceptor = acceptor.clone();
+        let connections = connections.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => {
+                    let _ = connections.send((stream, peer_addr)).await;
+                }
+                // Scanners and plain-HTTP clients end up here; not worth more than debug
+                Ok(Err(e)) => debug!("TLS handshake with {} failed: {}", peer_addr, e),
+                Err(_) => debug!("TLS handshake with {} timed out", peer_addr),
+            }
+        });
+    }
+}
+
+/// The certificate offered to every client, swapped on reload
+#[derive(Debug)]
+struct ReloadableCertificate {
+    config: TlsConfig,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ReloadableCertificate {
+    fn load(config: TlsConfig, provider: Arc<CryptoProvider>) -> Result<Self> {
+        let current = RwLock::new(Arc::new(load_certified_key(&config, &provider)?));
+        Ok(Self { config, provider, current })
+    }
+
+    fn reload(&self) -> Result<()> {
+        let certified_key = load_certified_key(&self.config, &self.provider)?;
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(certified_key);
+        Ok(())
+    }
+
+    /// Modification times of the certificate and key; `None` where they can't be read
+    fn modified(&self) -> (Option<SystemTime>, Option<SystemTime>) {
+        let modified = |path: &str| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        (modified(&self.config.cert_path), modified(&self.config.key_path))
+    }
+}
+
+impl ResolvesServerCert for ReloadableCertificate {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap_or_else(PoisonError::into_inner).clone())
+    }
+}
+
+fn load_certified_key(config: &TlsConfig, provider: &CryptoProvider) -> Result<CertifiedKey> {
+    let cert_chain = CertificateDer::pem_file_iter(&config.cert_path)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("failed to read TLS certificate {}", config.cert_path))?;
+    if cert_chain.is_empty() {
+        bail!("no certificates found in {}", config.cert_path);
+    }
+
+    let key = PrivateKeyDer::from_pem_file(&config.key_path)
+        .with_context(|| format!("failed to read TLS private key {}", config.key_path))?;
+
+    CertifiedKey::from_der(cert_chain, key, provider)
+        .with_context(|| format!("TLS private key {} cannot be used with certificate {}", config.key_path, config.cert_path))
+}
+
+/// Reload the certificate on SIGHUP, and when its files have changed at the next check
+fn spawn_reload(certificate: Arc<ReloadableCertificate>) -> Result<()> {
+    let mut hangup = Hangup::new().context("failed to listen for SIGHUP")?;
+    let mut interval = tokio::time::interval(Duration::from_secs(certificate.config.reload_interval_secs));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    tokio::spawn(async move {
+        // The first tick completes immediately
+        interval.tick().await;
+        let mut last_modified = certificate.modified();
+
+        loop {
+            let requested = tokio::select! {
+                _ = interval.tick() => false,
+                _ = hangup.recv() => true,
+            };
+
+            let modified = certificate.modified();
+            if !requested && modified == last_modified {
+                continue;
+            }
+
+            match certificate.reload() {
+                Ok(()) => {
+                    info!("Reloaded TLS certificate {}", certificate.config.cert_path);
+                    last_modified = modified;
+                }
+                Err(e) => warn!("Keeping the current TLS certificate: {:#}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// SIGHUP, which never arrives on platforms without it
+struct Hangup {
+    #[cfg(unix)]
+    signal: tokio::signal::unix::Signal,
+}
+
+impl Hangup {
+    fn new() -> io::Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?,
+        })
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if self.signal.recv().await.is_some() {
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
@@ -0,0 +1,139 @@
+// src/webhooks.rs
+//
+// Delivery of inventory events from the webhook outbox. A background task polls for due
+// events and POSTs each one to every configured URL, signed with HMAC-SHA256 of the body.
+// Failed deliveries are retried with exponential backoff; an event that still fails after
+// `max_attempts` is dead-lettered: logged, and kept in the outbox with `failed_at` set.
+use crate::config::WebhookConfig;
+use crate::tables::{OutboxEvent, WebhookOutboxTable};
+use crate::utils::logging::log_database_error;
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use sha2::Sha256;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const EVENT_ID_HEADER: &str = "x-webhook-id";
+pub const EVENT_TYPE_HEADER: &str = "x-webhook-event";
+
+/// How often the outbox is checked for due events
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Events claimed per poll
+const CLAIM_BATCH_SIZE: i64 = 10;
+/// Backoff after the first failed attempt, doubling up to the cap
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+/// How often, and after how long, delivered events are purged
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+const DELIVERED_RETENTION: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// `sha256=<hex>` HMAC of `body` under `secret`
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Delay before the retry that follows attempt number `attempts`
+fn retry_delay(attempts: u32) -> Duration {
+    INITIAL_RETRY_DELAY
+        .checked_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+}
+
+/// Start delivering outbox events to the configured webhooks
+pub fn spawn_delivery(table: WebhookOutboxTable, config: WebhookConfig) -> anyhow::Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .build()?;
+
+    info!("Delivering inventory webhooks to {} endpoint(s)", config.urls.len());
+
+    tokio::spawn(async move {
+        let mut poll = tokio::time::interval(POLL_INTERVAL);
+        let mut cleanup = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = poll.tick() => deliver_due(&table, &client, &config).await,
+                _ = cleanup.tick() => match table.delete_delivered(DELIVERED_RETENTION).await {
+                    Ok(0) => {}
+                    Ok(deleted) => info!("Deleted {} delivered webhook events", deleted),
+                    Err(e) => log_database_error("delete delivered webhook events", &e),
+                },
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Deliver every due event, until the outbox has nothing left to send right now
+async fn deliver_due(table: &WebhookOutboxTable, client: &reqwest::Client, config: &WebhookConfig) {
+    loop {
+        let events = match table.claim_due(CLAIM_BATCH_SIZE).await {
+            Ok(events) => events,
+            Err(e) => {
+                log_database_error("claim webhook events", &e);
+                return;
+            }
+        };
+
+        let claimed = events.len() as i64;
+        for event in events {
+            deliver(table, client, config, event).await;
+        }
+
+        if claimed < CLAIM_BATCH_SIZE {
+            return;
+        }
+    }
+}
+
+/// Send one event to every URL that has not accepted it yet and record the outcome
+async fn deliver(table: &WebhookOutboxTable, client: &reqwest::Client, config: &WebhookConfig, event: OutboxEvent) {
+    let signature = config.secret.as_deref().map(|secret| signature(secret, event.payload.as_bytes()));
+    let pending: Vec<&String> = config.urls.iter().filter(|url| !event.delivered_to.contains(url)).collect();
+    let mut delivered_to = event.delivered_to.clone();
+    let mut errors = Vec::new();
+
+    for url in pending {
+        let mut request = client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(EVENT_ID_HEADER, event.event_id.to_string())
+            .header(EVENT_TYPE_HEADER, &event.event_type)
+            .body(event.payload.clone());
+        if let Some(signature) = &signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        match request.send().await.and_then(|response| response.error_for_status()) {
+            Ok(_) => delivered_to.push(url.to_string()),
+            Err(e) => errors.push(format!("{}: {}", url, e)),
+        }
+    }
+
+    let attempts = event.attempts as u32 + 1;
+    let result = if errors.is_empty() {
+        table.mark_delivered(event.event_id).await
+    } else {
+        let error = errors.join("; ");
+        let retry_in = (attempts < config.max_attempts).then(|| retry_delay(attempts));
+        match retry_in {
+            Some(delay) => warn!(
+                "Webhook event {} failed (attempt {}/{}), retrying in {:?}: {}",
+                event.event_id, attempts, config.max_attempts, delay, error
+            ),
+            None => error!(
+                "Webhook event {} dead-lettered after {} attempts: {}; payload: {}",
+                event.event_id, attempts, error, event.payload
+            ),
+        }
+        table.mark_failed(event.event_id, &delivered_to, &error, retry_in).await
+    };
+
+    if let Err(e) = result {
+        log_database_error("record webhook delivery", &e);
+    }
+}
@@ -0,0 +1,380 @@
+// tests/database.rs
+//
+// Behavior that only shows against Postgres itself. Needs a database:
+// TEST_DATABASE_URL=postgres://... cargo test -- --ignored
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use onechilli_dev_api::config::DatabaseConfig;
+use onechilli_dev_api::database::Database;
+use onechilli_dev_api::tables::{
+    AuditLogTable, BulkWriteOptions, ConsumeInventoryRequest, CreateGoodRequest, CreatePurchaseOrderLine, CreatePurchaseOrderRequest,
+    CreateReservationRequest, GoodsConflictMode, GoodsSearchParams, IdempotencyKey, InventorySearchParams,
+    NewAuditEntry, PurchaseOrderStatus, ReceivePurchaseOrderRequest, UpdateGoodRequest, UpdateInventoryRequest,
+};
+use onechilli_dev_api::utils::response::{format_database_error, unique_violation};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+/// Counts the statements sqlx logs
+#[derive(Clone, Default)]
+struct StatementCounter(Arc<AtomicUsize>);
+
+impl StatementCounter {
+    fn take(&self) -> usize {
+        self.0.swap(0, Ordering::SeqCst)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for StatementCounter {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() == "sqlx::query" {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+fn config(database_url: String) -> DatabaseConfig {
+    DatabaseConfig {
+        database_url,
+        read_replica_url: None,
+        max_connections: 1,
+        run_migrations: true,
+        connect_max_attempts: 1,
+        connect_max_wait_secs: 0,
+        acquire_timeout_secs: 5,
+        idle_timeout_secs: 60,
+        max_lifetime_secs: 60,
+        statement_timeout_ms: 0,
+        goods_cache_enabled: false,
+        goods_cache_capacity: 0,
+        goods_cache_ttl_secs: 0,
+    }
+}
+
+/// A good with `items` inventory rows; returns its goods_id
+async fn seed(database: &Database, material_code: &str, items: i32) -> i32 {
+    let goods_id: i32 = sqlx::query_scalar(
+        "INSERT INTO goods (material_code, goods_name, price, volumn_l, mass_g) VALUES ($1, 'Statement count', 1, 1, 1) RETURNING goods_id",
+    )
+    .bind(material_code)
+    .fetch_one(&database.pool)
+    .await
+    .unwrap();
+
+    sqlx::query("INSERT INTO inventory (goods_id, quantity) SELECT $1, 10 FROM generate_series(1, $2)")
+        .bind(goods_id)
+        .bind(items)
+        .execute(&database.pool)
+        .await
+        .unwrap();
+    goods_id
+}
+
+async fn clean_up(database: &Database, goods_id: i32) {
+    sqlx::query("DELETE FROM stock_movements WHERE item_id IN (SELECT item_id FROM inventory WHERE goods_id = $1)")
+        .bind(goods_id)
+        .execute(&database.pool)
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM inventory WHERE goods_id = $1").bind(goods_id).execute(&database.pool).await.unwrap();
+    sqlx::query("DELETE FROM goods WHERE goods_id = $1").bind(goods_id).execute(&database.pool).await.unwrap();
+}
+
+/// Statements run by a filter-based update of every item of a good with `items` rows
+async fn statements_for_update(database: &Database, counter: &StatementCounter, items: i32) -> usize {
+    let material_code = format!("STMT-COUNT-{}-{}", items, std::process::id());
+    let goods_id = seed(database, &material_code, items).await;
+
+    let mut params = InventorySearchParams::new();
+    params.goods_params.material_code = Some(material_code);
+    let request = UpdateInventoryRequest {
+        material_code: None,
+        goods_name: Some("Statement count, renamed".to_string()),
+        description: None,
+        price: None,
+        volumn_l: None,
+        mass_g: None,
+        mass_base: None,
+        volumn_base: None,
+        quantity: Some(7),
+        expired_date: None,
+        lot_number: None,
+    };
+
+    counter.take();
+    let updated = database
+        .inventory_table
+        .update(params, request, BulkWriteOptions { max_affected: i64::from(items), dry_run: false })
+        .await
+        .unwrap();
+    let statements = counter.take();
+
+    assert_eq!(updated.len(), items as usize);
+    assert!(updated.iter().all(|item| item.quantity == 7 && item.goods_name == "Statement count, renamed"));
+
+    clean_up(database, goods_id).await;
+    statements
+}
+
+async fn database() -> Database {
+    database_with_webhook_events(false).await
+}
+
+async fn database_with_webhook_events(webhook_events: bool) -> Database {
+    let database_url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL");
+    Database::new(config(database_url), webhook_events, false).await.unwrap()
+}
+
+/// Filter-based inventory updates run a fixed number of statements however many rows they match
+#[tokio::test]
+#[ignore = "needs a Postgres database in TEST_DATABASE_URL"]
+async fn filter_based_update_runs_a_bounded_number_of_statements() {
+    let counter = StatementCounter::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(counter.clone()));
+    let database = database().await;
+
+    let few = statements_for_update(&database, &counter, 5).await;
+    let many = statements_for_update(&database, &counter, 200).await;
+
+    assert_eq!(few, many, "statement count grew with the number of matched rows");
+    assert!(many <= 12, "expected a handful of statements, got {}", many);
+}
+
+/// A duplicate that slips past the existence check is reported with its field and value
+#[tokio::test]
+#[ignore = "needs a Postgres database in TEST_DATABASE_URL"]
+async fn duplicate_material_code_reports_the_field_and_value() {
+    let database = database().await;
+    let material_code = format!("DUP-{}", std::process::id());
+    let goods_id = seed(&database, &material_code, 0).await;
+
+    let error = sqlx::query("INSERT INTO goods (material_code, goods_name, price, volumn_l, mass_g) VALUES ($1, 'Duplicate', 1, 1, 1)")
+        .bind(&material_code)
+        .execute(&database.pool)
+        .await
+        .unwrap_err();
+    clean_up(&database, goods_id).await;
+
+    let sqlx::Error::Database(db_err) = &error else {
+        panic!("expected a database error, got {:?}", error);
+    };
+    let violation = unique_violation(db_err.as_ref()).expect("unique violation");
+    assert_eq!(violation.field, "material_code");
+    assert_eq!(violation.value.as_deref(), Some(material_code.as_str()));
+    assert_eq!(format_database_error(&error, "create goods"), format!("material_code '{}' already exists", material_code));
+}
+
+fn goods_named(material_code: &str, goods_name: &str) -> CreateGoodRequest {
+    CreateGoodRequest {
+        material_code: material_code.to_string(),
+        goods_name: goods_name.to_string(),
+        description: None,
+        price: 1.into(),
+        volumn_l: 1.into(),
+        mass_g: 1.into(),
+        mass_base: None,
+        volumn_base: None,
+        reorder_point: None,
+        category_id: None,
+        barcode: None,
+        tags: None,
+        is_active: None,
+    }
+}
+
+/// `%`, `'` and SQL words in a goods name are matched literally by the ILIKE search and update
+#[tokio::test]
+#[ignore = "needs a Postgres database in TEST_DATABASE_URL"]
+async fn names_with_quotes_percent_signs_and_sql_words_round_trip() {
+    let database = database().await;
+    let run = std::process::id();
+    let mut goods_ids = Vec::new();
+
+    // A `50%` pattern left unescaped would match this one too
+    let decoy = database.goods_table.insert(goods_named(&format!("RT-{}-0", run), &format!("500 offcuts {}", run)), GoodsConflictMode::Error).await.unwrap();
+    goods_ids.push(decoy.good.goods_id);
+
+    for (index, goods_name) in ["Baker's Selection", "50% off", "Update Sauce; DROP TABLE goods"].into_iter().enumerate() {
+        let goods_name = format!("{} {}", goods_name, run);
+        let saved = database.goods_table.insert(goods_named(&format!("RT-{}-{}", run, index + 1), &goods_name), GoodsConflictMode::Error).await.unwrap();
+        let goods_id = saved.good.goods_id;
+        goods_ids.push(goods_id);
+        assert_eq!(saved.good.goods_name, goods_name);
+
+        let mut params = GoodsSearchParams::new();
+        params.goods_name = Some(goods_name.clone());
+        let found = database.goods_table.search(params.clone()).await.unwrap();
+        assert_eq!(found.iter().map(|good| good.goods_id).collect::<Vec<_>>(), [goods_id], "{}", goods_name);
+
+        let renamed = format!("{} update", goods_name);
+        let request = UpdateGoodRequest { goods_name: Some(renamed.clone()), ..Default::default() };
+        let updated = database.goods_table.update(params, request, BulkWriteOptions { max_affected: 1, dry_run: false }).await.unwrap();
+        assert_eq!(updated.iter().map(|good| (good.goods_id, good.goods_name.as_str())).collect::<Vec<_>>(), [(goods_id, renamed.as_str())]);
+
+        let request = UpdateGoodRequest { goods_name: Some(goods_name.clone()), ..Default::default() };
+        let updated = database.goods_table.update_by_id(goods_id, request).await.unwrap().expect("good");
+        assert_eq!(updated.goods_name, goods_name);
+    }
+
+    for goods_id in goods_ids {
+        clean_up(&database, goods_id).await;
+    }
+}
+
+/// The same Idempotency-Key from two callers is two claims, and a claim left in progress
+/// past `stale_after` is taken over by the retry
+#[tokio::test]
+#[ignore = "needs a Postgres database in TEST_DATABASE_URL"]
+async fn idempotency_keys_are_per_caller_and_stale_claims_are_taken_over() {
+    let database = database().await;
+    let table = &database.idempotency_table;
+    let key_for = |caller: &str| IdempotencyKey {
+        key: format!("retry-{}", std::process::id()),
+        request_path: "/inventory".to_string(),
+        caller: caller.to_string(),
+    };
+    let (shop, warehouse) = (key_for("label:shop"), key_for("label:warehouse"));
+    let ttl = Duration::from_secs(60);
+
+    assert!(table.claim(&shop, "hash-a", ttl, ttl).await.unwrap().is_none());
+    table.complete(&shop, 201, "{}").await.unwrap();
+    assert!(table.claim(&warehouse, "hash-b", ttl, ttl).await.unwrap().is_none(), "another caller's key was shared");
+
+    // The warehouse claim never completed; within stale_after it is in progress, after it free
+    let in_progress = table.claim(&warehouse, "hash-b", ttl, ttl).await.unwrap().expect("in-progress claim");
+    assert_eq!(in_progress.status_code, None);
+    assert!(table.claim(&warehouse, "hash-b", ttl, Duration::ZERO).await.unwrap().is_none());
+
+    let stored = table.claim(&shop, "hash-a", ttl, Duration::ZERO).await.unwrap().expect("stored response");
+    assert_eq!((stored.status_code, stored.response_body.as_deref()), (Some(201), Some("{}")));
+
+    table.release(&shop).await.unwrap();
+    table.release(&warehouse).await.unwrap();
+}
+
+/// Type, old and new quantity of the outbox events for one good, oldest first
+async fn outbox_events(database: &Database, goods_id: i32) -> Vec<(String, Option<i32>, Option<i32>)> {
+    sqlx::query_as(
+        r#"
+        SELECT event_type, (payload->>'old_quantity')::int4, (payload->>'new_quantity')::int4
+        FROM webhook_outbox
+        WHERE (payload->>'goods_id')::int4 = $1
+        ORDER BY event_id
+        "#,
+    )
+    .bind(goods_id)
+    .fetch_all(&database.pool)
+    .await
+    .unwrap()
+}
+
+/// Consuming, committing a reservation and receiving a purchase order each queue a webhook event
+#[tokio::test]
+#[ignore = "needs a Postgres database in TEST_DATABASE_URL"]
+async fn stock_changes_outside_the_inventory_endpoints_reach_the_outbox() {
+    let database = database_with_webhook_events(true).await;
+    let goods_id = seed(&database, &format!("OUTBOX-{}", std::process::id()), 1).await;
+    let item_id: i32 = sqlx::query_scalar("SELECT item_id FROM inventory WHERE goods_id = $1")
+        .bind(goods_id)
+        .fetch_one(&database.pool)
+        .await
+        .unwrap();
+
+    let consume = ConsumeInventoryRequest { goods_id: Some(goods_id), material_code: None, quantity: 4, delete_empty: None };
+    database.inventory_table.consume(&consume).await.unwrap();
+
+    let reservation = CreateReservationRequest { item_id, quantity: 2, reference: None, expires_at: None };
+    let reservation = database.reservations_table.create(&reservation).await.unwrap();
+    database.reservations_table.commit(reservation.reservation_id).await.unwrap();
+
+    let order = CreatePurchaseOrderRequest {
+        reference: None,
+        status: None,
+        lines: vec![CreatePurchaseOrderLine { goods_id, quantity: 5 }],
+    };
+    let order = database.purchase_orders_table.create(&order, PurchaseOrderStatus::Ordered).await.unwrap();
+    database.purchase_orders_table.receive(order.purchase_order_id, &ReceivePurchaseOrderRequest::default()).await.unwrap().expect("order");
+
+    let events = outbox_events(&database, goods_id).await;
+
+    for statement in [
+        "DELETE FROM webhook_outbox WHERE (payload->>'goods_id')::int4 = $1",
+        "DELETE FROM purchase_order_lines WHERE goods_id = $1",
+    ] {
+        sqlx::query(statement).bind(goods_id).execute(&database.pool).await.unwrap();
+    }
+    sqlx::query("DELETE FROM purchase_orders WHERE purchase_order_id = $1")
+        .bind(order.purchase_order_id)
+        .execute(&database.pool)
+        .await
+        .unwrap();
+    clean_up(&database, goods_id).await;
+
+    assert_eq!(
+        events,
+        [
+            ("inventory.adjusted".to_string(), Some(10), Some(6)),
+            ("inventory.adjusted".to_string(), Some(6), Some(4)),
+            ("inventory.updated".to_string(), Some(4), Some(9)),
+        ]
+    );
+}
+
+/// Audit rows of the requests whose id is `request_id`: target table and affected ids
+async fn audit_rows(database: &Database, request_id: &str) -> Vec<(String, Vec<i32>)> {
+    sqlx::query_as("SELECT target_table, affected_ids FROM audit_log WHERE request_id = $1 ORDER BY audit_id")
+        .bind(request_id)
+        .fetch_all(&database.pool)
+        .await
+        .unwrap()
+}
+
+/// Goods and inventory changes write the pending audit entry on their own transaction; a
+/// change that fails leaves none, and so does one made outside an audited request
+#[tokio::test]
+#[ignore = "needs a Postgres database in TEST_DATABASE_URL"]
+async fn audit_entries_are_written_with_the_change() {
+    let database = database().await;
+    let goods_id = seed(&database, &format!("AUDIT-{}", std::process::id()), 1).await;
+    let item_id: i32 = sqlx::query_scalar("SELECT item_id FROM inventory WHERE goods_id = $1")
+        .bind(goods_id)
+        .fetch_one(&database.pool)
+        .await
+        .unwrap();
+
+    let request_id = format!("audit-test-{}", std::process::id());
+    let entry = |method: &str, target_table: &str| NewAuditEntry {
+        api_key_label: None,
+        client_ip: None,
+        role: "admin".to_string(),
+        request_id: Some(request_id.clone()),
+        method: method.to_string(),
+        path: "/test".to_string(),
+        target_table: target_table.to_string(),
+        request_body: None,
+    };
+
+    let (adjusted, recorded) =
+        AuditLogTable::with_pending_entry(entry("POST", "inventory"), database.inventory_table.adjust(item_id, 3, "audit test")).await;
+    assert!(adjusted.is_ok() && recorded);
+
+    let (refused, recorded) =
+        AuditLogTable::with_pending_entry(entry("POST", "inventory"), database.inventory_table.adjust(item_id, -1000, "audit test")).await;
+    assert!(refused.is_err() && !recorded);
+
+    let rename = UpdateGoodRequest { goods_name: Some("Audited".to_string()), ..Default::default() };
+    let (updated, recorded) =
+        AuditLogTable::with_pending_entry(entry("PATCH", "goods"), database.goods_table.update_by_id(goods_id, rename.clone())).await;
+    assert!(updated.unwrap().is_some() && recorded);
+
+    database.goods_table.update_by_id(goods_id, rename).await.unwrap();
+
+    let rows = audit_rows(&database, &request_id).await;
+
+    sqlx::query("DELETE FROM audit_log WHERE request_id = $1").bind(&request_id).execute(&database.pool).await.unwrap();
+    clean_up(&database, goods_id).await;
+
+    assert_eq!(rows, [("inventory".to_string(), vec![item_id]), ("goods".to_string(), vec![goods_id])]);
+}
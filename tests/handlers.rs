@@ -0,0 +1,501 @@
+// tests/handlers.rs
+//
+// The router served from the in-memory goods and inventory of the `testing` feature
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Method, Request, StatusCode};
+use axum::Router;
+use flate2::read::GzDecoder;
+use onechilli_dev_api::config::{AppConfig, DatabaseConfig};
+use onechilli_dev_api::server::Server;
+use onechilli_dev_api::testing::InMemoryStore;
+use serde_json::{json, Value};
+use std::io::Read;
+use tower::ServiceExt;
+
+fn config() -> AppConfig {
+    AppConfig {
+        database: DatabaseConfig {
+            // Only parsed; the lazy pool never connects in these tests
+            database_url: "postgres://test@localhost/test".to_string(),
+            read_replica_url: None,
+            max_connections: 1,
+            run_migrations: false,
+            connect_max_attempts: 1,
+            connect_max_wait_secs: 0,
+            acquire_timeout_secs: 1,
+            idle_timeout_secs: 60,
+            max_lifetime_secs: 60,
+            statement_timeout_ms: 0,
+            goods_cache_enabled: false,
+            goods_cache_capacity: 0,
+            goods_cache_ttl_secs: 0,
+        },
+        server: Default::default(),
+        auth: Default::default(),
+        webhooks: Default::default(),
+        expired_purge: Default::default(),
+        stock_snapshot: Default::default(),
+        alerts: Default::default(),
+        inventory: Default::default(),
+        validation: Default::default(),
+    }
+}
+
+fn router() -> Router {
+    router_with(config())
+}
+
+fn router_with(config: AppConfig) -> Router {
+    let state = InMemoryStore::new().app_state(config).expect("lazy pool for the untouched tables");
+    Server::create_router(state)
+}
+
+/// A router that requires one of a read, a write and an admin key
+fn router_with_api_keys() -> Router {
+    let mut config = config();
+    config.auth = serde_json::from_value(json!({
+        "api_keys": [
+            { "key": "read-key", "role": "read" },
+            { "key": "write-key", "role": "write" },
+            { "key": "admin-key", "role": "admin" }
+        ]
+    }))
+    .unwrap();
+    router_with(config)
+}
+
+async fn send(router: &Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    send_as(router, None, method, uri, body).await
+}
+
+/// `send` with `api_key` in the `X-Api-Key` header
+async fn send_as(router: &Router, api_key: Option<&str>, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(api_key) = api_key {
+        request = request.header("x-api-key", api_key);
+    }
+    let request = match body {
+        Some(body) => request.header(header::CONTENT_TYPE, "application/json").body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn good(material_code: &str, goods_name: &str) -> Value {
+    json!({
+        "material_code": material_code,
+        "goods_name": goods_name,
+        "price": "4.50",
+        "volumn_l": "0.5",
+        "mass_g": "250"
+    })
+}
+
+#[tokio::test]
+async fn lists_created_goods() {
+    let router = router();
+    for (material_code, goods_name) in [("CH-100", "Chili flakes"), ("CH-200", "Chili oil")] {
+        let (status, _) = send(&router, Method::POST, "/goods", Some(good(material_code, goods_name))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, body) = send(&router, Method::GET, "/goods?goods_name=oil", None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], true);
+    let goods = body["data"].as_array().expect("list of goods");
+    assert_eq!(goods.len(), 1);
+    assert_eq!(goods[0]["material_code"], "CH-200");
+}
+
+#[tokio::test]
+async fn missing_inventory_item_is_not_found() {
+    let router = router();
+
+    let (status, body) = send(&router, Method::GET, "/inventory/42", None).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["success"], false);
+    assert_eq!(body["error"]["code"], "NOT_FOUND");
+}
+
+#[tokio::test]
+async fn duplicate_material_code_conflicts_with_on_conflict_error() {
+    let router = router();
+    let (status, body) = send(&router, Method::POST, "/goods", Some(good("CH-100", "Chili flakes"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["created"], true);
+
+    let (status, body) = send(&router, Method::POST, "/goods?on_conflict=error", Some(good("CH-100", "Chili paste"))).await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "CONFLICT");
+}
+
+#[tokio::test]
+async fn patch_with_stale_version_conflicts_and_changes_nothing() {
+    let router = router();
+    let (_, body) = send(&router, Method::POST, "/goods", Some(good("CH-100", "Chili flakes"))).await;
+    let goods_id = body["data"]["goods_id"].as_i64().expect("goods_id");
+    let version = body["data"]["version"].as_i64().expect("version");
+
+    let uri = format!("/goods/{}", goods_id);
+    let (status, _) = send(&router, Method::PATCH, &uri, Some(json!({ "goods_name": "Chili oil", "expected_version": version }))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(&router, Method::PATCH, &uri, Some(json!({ "goods_name": "Chili paste", "expected_version": version }))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "CONFLICT");
+
+    let (_, body) = send(&router, Method::GET, &format!("/goods?goods_id={}", goods_id), None).await;
+    assert_eq!(body["data"][0]["goods_name"], "Chili oil");
+}
+
+#[tokio::test]
+async fn read_key_can_get_but_not_delete() {
+    let router = router_with_api_keys();
+
+    let (status, _) = send_as(&router, Some("read-key"), Method::GET, "/goods?goods_name=chili", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send_as(&router, Some("read-key"), Method::DELETE, "/goods?material_code=CH-100", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "FORBIDDEN");
+}
+
+#[tokio::test]
+async fn missing_or_unknown_api_key_is_unauthorized() {
+    let router = router_with_api_keys();
+
+    for api_key in [None, Some("wrong-key")] {
+        let (status, body) = send_as(&router, api_key, Method::GET, "/goods?goods_name=chili", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"]["code"], "UNAUTHORIZED");
+    }
+}
+
+#[tokio::test]
+async fn wildcard_delete_needs_an_admin_key() {
+    let router = router_with_api_keys();
+    let (status, _) = send_as(&router, Some("write-key"), Method::POST, "/goods", Some(good("CH-100", "Chili flakes"))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send_as(&router, Some("write-key"), Method::DELETE, "/goods?material_code=*&confirm=true", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "FORBIDDEN");
+
+    let (status, _) = send_as(&router, Some("admin-key"), Method::DELETE, "/goods?material_code=*&confirm=true", None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn wildcard_with_another_filter_is_rejected_and_deletes_nothing() {
+    let router = router();
+    let item_id = item_of_five(&router).await;
+
+    let (status, body) = send(&router, Method::DELETE, "/inventory?goods_name=*&expired=true&confirm=true", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "VALIDATION_ERROR");
+
+    let (status, _) = send(&router, Method::GET, &format!("/inventory/{}", item_id), None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn client_request_id_round_trips_into_the_error_body() {
+    let router = router();
+    let request = Request::builder().uri("/inventory/42").header("x-request-id", "client-id-7").body(Body::empty()).unwrap();
+
+    let response = router.oneshot(request).await.unwrap();
+
+    assert_eq!(response.headers()["x-request-id"], "client-id-7");
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert_eq!(body["meta"]["request_id"], "client-id-7");
+}
+
+#[tokio::test]
+async fn request_id_is_generated_when_missing_or_invalid() {
+    let router = router();
+
+    for client_id in [None, Some("not a valid id")] {
+        let mut request = Request::builder().uri("/inventory/42");
+        if let Some(client_id) = client_id {
+            request = request.header("x-request-id", client_id);
+        }
+        let response = router.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+
+        let request_id = response.headers()["x-request-id"].to_str().unwrap().to_string();
+        assert!(uuid::Uuid::parse_str(&request_id).is_ok(), "{}", request_id);
+        let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["meta"]["request_id"], request_id.as_str());
+    }
+}
+
+#[tokio::test]
+async fn filter_based_writes_matching_nothing_are_not_found() {
+    let router = router();
+    let (status, _) = send(&router, Method::POST, "/goods", Some(good("CH-100", "Chili flakes"))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let rename = json!({ "goods_name": "Chili paste" });
+    let requests = [
+        (Method::PUT, "/goods?material_code=XX-999", Some(rename.clone())),
+        (Method::DELETE, "/goods?material_code=XX-999", None),
+        (Method::PUT, "/inventory?item_id=42", Some(rename)),
+        (Method::DELETE, "/inventory?item_id=42", None),
+    ];
+
+    for (method, uri, body) in requests {
+        let (status, body) = send(&router, method.clone(), uri, body).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, uri);
+        assert_eq!(body["error"]["code"], "NOT_FOUND", "{} {}", method, uri);
+    }
+}
+
+#[tokio::test]
+async fn filter_based_writes_with_bad_filters_are_validation_errors() {
+    let router = router();
+
+    for (method, uri) in [(Method::DELETE, "/goods"), (Method::DELETE, "/inventory?item_id=abc"), (Method::PUT, "/goods?min_price=100&max_price=10")] {
+        let body = (method == Method::PUT).then(|| json!({ "goods_name": "Chili paste" }));
+        let (status, body) = send(&router, method.clone(), uri, body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{} {}", method, uri);
+        assert_eq!(body["error"]["code"], "VALIDATION_ERROR", "{} {}", method, uri);
+    }
+}
+
+#[tokio::test]
+async fn large_inventory_listing_is_gzipped_and_decodes_to_the_same_items() {
+    let router = router();
+    let (status, _) = send(&router, Method::POST, "/goods", Some(good("CH-100", "Chili flakes"))).await;
+    assert_eq!(status, StatusCode::OK);
+    for lot in 0..40 {
+        let item = json!({ "material_code": "CH-100", "quantity": 10, "lot_number": format!("LOT-{}", lot) });
+        let (status, _) = send(&router, Method::POST, "/inventory", Some(item)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (_, plain) = send(&router, Method::GET, "/inventory?material_code=CH-100&per_page=100", None).await;
+
+    let request = Request::builder()
+        .uri("/inventory?material_code=CH-100&per_page=100")
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .body(Body::empty())
+        .unwrap();
+    let response = router.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    let compressed = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let mut decoded = String::new();
+    GzDecoder::new(&compressed[..]).read_to_string(&mut decoded).unwrap();
+    assert!(compressed.len() < decoded.len());
+
+    let decoded: Value = serde_json::from_str(&decoded).unwrap();
+    assert_eq!(decoded["data"].as_array().map(Vec::len), Some(40));
+    assert_eq!(decoded["data"], plain["data"]);
+}
+
+#[tokio::test]
+async fn streamed_export_is_gzipped_as_a_whole() {
+    let router = router();
+    for (material_code, goods_name) in [("CH-100", "Chili flakes"), ("CH-200", "Chili oil")] {
+        let (status, _) = send(&router, Method::POST, "/goods", Some(good(material_code, goods_name))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let request = Request::builder().uri("/goods/export.ndjson?material_code=CH").header(header::ACCEPT_ENCODING, "gzip").body(Body::empty()).unwrap();
+    let response = router.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    let compressed = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let mut decoded = String::new();
+    GzDecoder::new(&compressed[..]).read_to_string(&mut decoded).unwrap();
+    let material_codes: Vec<Value> = decoded.lines().map(|line| serde_json::from_str::<Value>(line).unwrap()["material_code"].clone()).collect();
+    assert_eq!(material_codes, [json!("CH-100"), json!("CH-200")]);
+}
+
+#[tokio::test]
+async fn underscore_in_material_code_matches_only_itself() {
+    let router = router();
+    for (material_code, goods_name) in [("CH_100", "Chili flakes"), ("CHX100", "Chili oil")] {
+        let (status, _) = send(&router, Method::POST, "/goods", Some(good(material_code, goods_name))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    for uri in ["/goods?material_code=CH_100", "/goods?material_code=CH_100&literal=true"] {
+        let (status, body) = send(&router, Method::GET, uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let material_codes: Vec<&Value> = body["data"].as_array().unwrap().iter().map(|good| &good["material_code"]).collect();
+        assert_eq!(material_codes, [&json!("CH_100")], "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn unknown_path_is_a_json_404_listing_the_routes() {
+    let router = router();
+
+    let (status, body) = send(&router, Method::GET, "/no-such-route", None).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "NOT_FOUND");
+    let routes = &body["error"]["details"][0]["available_routes"];
+    assert!(routes.as_array().unwrap().contains(&json!("/goods")), "{}", routes);
+}
+
+#[tokio::test]
+async fn unsupported_method_is_a_json_405() {
+    let router = router();
+
+    let (status, body) = send(&router, Method::PATCH, "/goods", None).await;
+
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(body["error"]["code"], "METHOD_NOT_ALLOWED");
+}
+
+#[tokio::test]
+async fn repeated_and_empty_query_params_keep_their_meaning() {
+    let router = router();
+    for (material_code, goods_name) in [("CH-100", "Chili flakes"), ("CH-200", "Chili oil")] {
+        let (status, _) = send(&router, Method::POST, "/goods", Some(good(material_code, goods_name))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // The last of a repeated key wins
+    let (status, body) = send(&router, Method::GET, "/goods?goods_name=flakes&goods_name=oil", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().map(Vec::len), Some(1));
+    assert_eq!(body["data"][0]["material_code"], "CH-200");
+
+    // An empty value is sent through and rejected, not dropped
+    let (status, body) = send(&router, Method::GET, "/goods?goods_name=", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "VALIDATION_ERROR");
+}
+
+#[tokio::test]
+async fn names_with_quotes_percent_signs_and_sql_words_round_trip() {
+    let router = router();
+    // Would match a `50%` pattern that treated the percent sign as a wildcard
+    let (status, _) = send(&router, Method::POST, "/goods", Some(good("RT-000", "500 offcuts"))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let names = [("RT-100", "Baker's Selection", "Baker%27s%20Selection"), ("RT-200", "50% off", "50%25%20off"), ("RT-300", "Update Sauce", "Update%20Sauce")];
+    for (material_code, goods_name, encoded) in names {
+        let (status, body) = send(&router, Method::POST, "/goods", Some(good(material_code, goods_name))).await;
+        assert_eq!(status, StatusCode::OK, "{}", goods_name);
+        assert_eq!(body["data"]["goods_name"], goods_name);
+        let goods_id = body["data"]["goods_id"].as_i64().expect("goods_id");
+
+        let (status, body) = send(&router, Method::GET, &format!("/goods?goods_name={}", encoded), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", goods_name);
+        let found: Vec<&Value> = body["data"].as_array().unwrap().iter().map(|good| &good["goods_id"]).collect();
+        assert_eq!(found, [&json!(goods_id)], "{}", goods_name);
+
+        let renamed = format!("{} update", goods_name);
+        let (status, body) = send(&router, Method::PUT, &format!("/goods?goods_name={}", encoded), Some(json!({ "goods_name": renamed }))).await;
+        assert_eq!(status, StatusCode::OK, "{}", goods_name);
+        assert_eq!(body["data"][0]["goods_name"], renamed.as_str());
+
+        let (status, body) = send(&router, Method::PATCH, &format!("/goods/{}", goods_id), Some(json!({ "goods_name": goods_name }))).await;
+        assert_eq!(status, StatusCode::OK, "{}", goods_name);
+        assert_eq!(body["data"]["goods_name"], goods_name);
+    }
+}
+
+/// A router whose inventory may go below zero when `allow_negative_stock` is set
+fn router_with_negative_stock(allow_negative_stock: bool) -> Router {
+    let mut config = config();
+    config.inventory.allow_negative_stock = allow_negative_stock;
+    router_with(config)
+}
+
+/// Goods CH-100 with one inventory item of 5; returns its item_id
+async fn item_of_five(router: &Router) -> i64 {
+    let (status, _) = send(router, Method::POST, "/goods", Some(good("CH-100", "Chili flakes"))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(router, Method::POST, "/inventory", Some(json!({ "material_code": "CH-100", "quantity": 5 }))).await;
+    assert_eq!(status, StatusCode::OK);
+    body["data"]["item_id"].as_i64().expect("item_id")
+}
+
+#[tokio::test]
+async fn decrement_below_zero_is_refused_by_default() {
+    let router = router_with_negative_stock(false);
+    let item_id = item_of_five(&router).await;
+
+    let (status, body) = send(&router, Method::POST, &format!("/inventory/{}/decrement", item_id), Some(json!({ "quantity": 8 }))).await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "CONFLICT");
+}
+
+#[tokio::test]
+async fn negative_stock_when_allowed_is_found_with_max_quantity_minus_one() {
+    let router = router_with_negative_stock(true);
+    let item_id = item_of_five(&router).await;
+
+    let (status, _) = send(&router, Method::POST, &format!("/inventory/{}/decrement", item_id), Some(json!({ "quantity": 8 }))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(&router, Method::GET, "/inventory?max_quantity=-1", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let items = body["data"].as_array().expect("list of items");
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["item_id"], item_id);
+    assert_eq!(items[0]["quantity"], -3);
+}
+
+#[tokio::test]
+async fn cursor_pages_walk_every_good_once() {
+    let router = router();
+    for material_code in ["CH-100", "CH-200", "CH-300"] {
+        let (status, _) = send(&router, Method::POST, "/goods", Some(good(material_code, "Chili"))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let mut seen = Vec::new();
+    let mut uri = "/goods?goods_name=chili&per_page=2".to_string();
+    loop {
+        let (status, body) = send(&router, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        seen.extend(body["data"].as_array().unwrap().iter().map(|good| good["material_code"].as_str().unwrap().to_string()));
+        match body["meta"]["pagination"]["next_cursor"].as_str() {
+            Some(cursor) => uri = format!("/goods?goods_name=chili&per_page=2&cursor={}", cursor),
+            None => break,
+        }
+    }
+
+    assert_eq!(seen, ["CH-100", "CH-200", "CH-300"]);
+}
+
+#[tokio::test]
+async fn tampered_cursor_is_a_validation_error() {
+    let router = router();
+
+    let (status, body) = send(&router, Method::GET, "/goods?goods_name=chili&cursor=bm90LWEtY3Vyc29y", None).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "VALIDATION_ERROR");
+}
+
+#[tokio::test]
+async fn misspelled_filter_is_rejected_with_a_suggestion_unless_lenient() {
+    let router = router();
+    let (status, _) = send(&router, Method::POST, "/goods", Some(good("CH-200", "Chili oil"))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(&router, Method::GET, "/goods?goods_nam=oil", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "VALIDATION_ERROR");
+    assert!(body["error"]["message"].as_str().unwrap().contains("'goods_nam' (did you mean goods_name?)"), "{}", body);
+
+    let (status, body) = send(&router, Method::GET, "/goods?goods_name=oil&colour=red&lenient=true", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().map(Vec::len), Some(1));
+}